@@ -0,0 +1,39 @@
+//!
+//! Time to extract one function's subgraph from files of growing size.
+//! Extraction only visits the subgraph's own nodes and edges, so the time per
+//! extraction should stay flat as the file grows.
+//!
+//! Run with `cargo bench --bench create_subgraph`.
+//!
+use std::time::Instant;
+use tree_graph::ASTGraph;
+use tree_sitter::Parser;
+
+const ROUNDS: u32 = 200;
+
+fn source_with_functions(count: usize) -> String {
+    (0..count)
+        .map(|i| format!("int f{}(int x) {{\n    if (x > {}) {{ return x * 2; }}\n    return f{}(x + 1);\n}}\n", i, i, i))
+        .collect()
+}
+
+fn main() {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+    println!("{:>10} {:>10} {:>16}", "functions", "nodes", "per extraction");
+    for count in [10, 100, 1_000, 10_000] {
+        let source = source_with_functions(count);
+        let tree = parser.parse(&source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source);
+        ast_graph.build_from_tree(&tree);
+
+        let function = ast_graph.children(ast_graph.root().unwrap())[count / 2];
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            std::hint::black_box(ast_graph.extract_subgraph_from(function));
+        }
+        let elapsed = start.elapsed() / ROUNDS;
+        println!("{:>10} {:>10} {:>16?}", count, ast_graph.graph().node_count(), elapsed);
+    }
+}
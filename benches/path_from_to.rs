@@ -0,0 +1,58 @@
+//!
+//! Time of `path_from_to` (no heuristic) against `path_from_to_with` and the
+//! byte-distance heuristic, from the root to the deepest leaf of files with
+//! growing nesting. Both find the same path; the guided search should pull
+//! ahead as the files get deeper and wider.
+//!
+//! Run with `cargo bench --bench path_from_to`.
+//!
+use std::time::Instant;
+use tree_graph::ASTGraph;
+use tree_graph::paths::Heuristic;
+use tree_sitter::Parser;
+
+const ROUNDS: u32 = 50;
+
+fn nested_source(depth: usize) -> String {
+    let mut source = String::new();
+    for i in 0..depth {
+        source.push_str(&format!("int f{}(int x) {{ return x + {}; }}\n", i, i));
+    }
+    source.push_str("int main(int x) {\n");
+    for i in 0..depth {
+        source.push_str(&format!("if (x > {}) {{ x = f{}(x);\n", i, i));
+    }
+    source.push_str(&"}\n".repeat(depth));
+    source.push_str("return x;\n}\n");
+    source
+}
+
+fn main() {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+    println!("{:>8} {:>10} {:>14} {:>14}", "depth", "nodes", "zero", "byte distance");
+    for depth in [10, 50, 200, 500] {
+        let source = nested_source(depth);
+        let tree = parser.parse(&source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source);
+        ast_graph.build_from_tree(&tree);
+
+        let root = ast_graph.root().unwrap();
+        let leaf = ast_graph.deepest_paths(1)[0].leaf;
+        let heuristic = Heuristic::byte_distance(&ast_graph);
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            std::hint::black_box(ast_graph.path_from_to(root, leaf));
+        }
+        let zero = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            std::hint::black_box(ast_graph.path_from_to_with(root, leaf, &heuristic));
+        }
+        let guided = start.elapsed() / ROUNDS;
+        println!("{:>8} {:>10} {:>14?} {:>14?}", depth, ast_graph.graph().node_count(), zero, guided);
+    }
+}
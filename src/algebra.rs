@@ -0,0 +1,136 @@
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::{HashMap, HashSet};
+
+use crate::{ASTGraph, ids};
+use crate::ids::StableId;
+
+///
+/// What makes a node of one graph "the same" as a node of another.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Identity {
+    /// the tree-sitter id: graphs cut from (or built from) the same parse,
+    /// such as analysis slices of one file
+    TreeSitterId,
+    /// kind and byte range: graphs built from the same source text, even
+    /// from different parses
+    Extent,
+    /// position in the tree (`StableId`): graphs of the same shape, e.g.
+    /// one file's graph before and after a change that kept its structure
+    Stable,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+enum NodeKey {
+    Id(usize),
+    Extent(u16, usize, usize),
+    Stable(StableId),
+}
+
+impl ASTGraph {
+    fn node_keys(&self, identity: Identity) -> HashMap<NodeIndex, NodeKey> {
+        match identity {
+            Identity::TreeSitterId => self.graph.node_indices().map(|node| (node, NodeKey::Id(self.graph[node].id))).collect(),
+            Identity::Extent => self.graph.node_indices().map(|node| {
+                let gnode = &self.graph[node];
+                (node, NodeKey::Extent(gnode.kind_id, gnode.range.start_byte, gnode.range.end_byte))
+            }).collect(),
+            Identity::Stable => self.stable_ids().into_iter().map(|(node, id)| (node, NodeKey::Stable(id))).collect(),
+        }
+    }
+
+    ///
+    /// The graph restricted to `nodes` and the edges between them. Leaving out
+    /// inner nodes splits the tree into several roots.
+    ///
+    pub fn induced(&self, nodes: &HashSet<NodeIndex>) -> ASTGraph {
+        let nodes: HashSet<NodeIndex> = nodes.iter().copied().filter(|&node| self.graph.node_weight(node).is_some()).collect();
+        self.create_subgraph(&nodes)
+    }
+
+    ///
+    /// The nodes of this graph that `other` has as well (under `identity`),
+    /// with the edges between them. Node data and source come from `self`.
+    ///
+    pub fn intersection(&self, other: &ASTGraph, identity: Identity) -> ASTGraph {
+        let other_keys: HashSet<NodeKey> = other.node_keys(identity).into_values().collect();
+        let nodes: HashSet<NodeIndex> = self.node_keys(identity).into_iter()
+            .filter(|(_, key)| other_keys.contains(key))
+            .map(|(node, _)| node)
+            .collect();
+        self.create_subgraph(&nodes)
+    }
+
+    ///
+    /// All nodes of both graphs, nodes present in both (under `identity`)
+    /// merged, with node data and source from `self`. Edges of `other` are
+    /// added where they don't give a node a second parent, so the result
+    /// stays a forest.
+    ///
+    pub fn union(&self, other: &ASTGraph, identity: Identity) -> ASTGraph {
+        let mut digraph = DiGraph::new();
+        let mut node_map = HashMap::new();
+        let mut by_key: HashMap<NodeKey, NodeIndex> = HashMap::new();
+        let mut from_self = HashMap::new();
+        let mut from_other = HashMap::new();
+
+        let self_keys = self.node_keys(identity);
+        for node in self.graph.node_indices() {
+            let new_node = digraph.add_node(self.graph[node]);
+            node_map.insert(new_node, self.graph[node].id);
+            by_key.insert(self_keys[&node].clone(), new_node);
+            from_self.insert(node, new_node);
+        }
+        for edge in self.graph.edge_references() {
+            digraph.add_edge(from_self[&edge.source()], from_self[&edge.target()], ());
+        }
+
+        let other_keys = other.node_keys(identity);
+        let mut other_nodes: Vec<NodeIndex> = other.graph.node_indices().collect();
+        other_nodes.sort();
+        for node in other_nodes {
+            let key = &other_keys[&node];
+            let new_node = match by_key.get(key) {
+                Some(&existing) => existing,
+                None => {
+                    let new_node = digraph.add_node(other.graph[node]);
+                    node_map.insert(new_node, other.graph[node].id);
+                    by_key.insert(key.clone(), new_node);
+                    new_node
+                },
+            };
+            from_other.insert(node, new_node);
+        }
+        for edge in other.graph.edge_references() {
+            let (source, target) = (from_other[&edge.source()], from_other[&edge.target()]);
+            if digraph.neighbors_directed(target, Direction::Incoming).next().is_some() {
+                continue;
+            }
+            // target is a root here; it must not be above source either
+            let mut ancestor = Some(source);
+            while let Some(current) = ancestor {
+                if current == target {
+                    break;
+                }
+                ancestor = digraph.neighbors_directed(current, Direction::Incoming).next();
+            }
+            if ancestor.is_none() {
+                digraph.add_edge(source, target, ());
+            }
+        }
+
+        ASTGraph {
+            graph: digraph,
+            node_map: node_map,
+            source: self.source.clone(),
+            source_start: self.source_start,
+            title: self.title.clone(),
+            metadata: self.metadata.clone(),
+            labels: self.labels.clone(),
+            graph_id: ids::next_graph_id(),
+            leaf_text: self.leaf_text.as_ref().map(|leaf_text| leaf_text.remap(&from_self)),
+        }
+    }
+}
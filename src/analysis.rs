@@ -0,0 +1,227 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+use tree_sitter::Language;
+
+use crate::ASTGraph;
+
+pub mod duplicates;
+pub mod signature;
+pub mod taint;
+
+///
+/// Every named kind id `language` calls `kind`. Grammars may have several
+/// symbols with the same name (aliases), and graphs store whichever one the
+/// parser produced.
+///
+pub fn kind_ids(language: &Language, kind: &str) -> HashSet<u16> {
+    (0..language.node_kind_count() as u16)
+        .filter(|&id| language.node_kind_is_named(id) && language.node_kind_for_id(id) == Some(kind))
+        .collect()
+}
+
+///
+/// Kind names and ids of one grammar: the `Language` itself, or a table
+/// saved from it (see `cached`) when the grammar isn't linked.
+///
+pub trait KindLookup {
+    ///
+    /// Every kind id called `name` that can appear in a graph.
+    ///
+    fn ids_named(&self, name: &str) -> HashSet<u16>;
+
+    fn name_of(&self, kind_id: u16) -> Option<&str>;
+}
+
+impl KindLookup for Language {
+    fn ids_named(&self, name: &str) -> HashSet<u16> {
+        kind_ids(self, name)
+    }
+
+    fn name_of(&self, kind_id: u16) -> Option<&str> {
+        self.node_kind_for_id(kind_id)
+    }
+}
+
+///
+/// The nearest proper ancestor of `node` whose kind is in `scope_kinds`, or
+/// `None` when the node sits at file level.
+///
+pub fn enclosing_scope(graph: &ASTGraph, node: NodeIndex, scope_kinds: &HashSet<u16>) -> Option<NodeIndex> {
+    let mut current = graph.parent(node);
+    while let Some(ancestor) = current {
+        if scope_kinds.contains(&graph.graph[ancestor].kind_id) {
+            return Some(ancestor);
+        }
+        current = graph.parent(ancestor);
+    }
+    None
+}
+
+///
+/// Name of the function a call node invokes: the callee itself when it is a
+/// plain or qualified name, otherwise the last name inside it (the method of
+/// `obj.method()`). Calls through other expressions have no name, nor do
+/// calls whose source the graph doesn't have.
+///
+pub fn callee_name<'g>(graph: &'g ASTGraph, call: NodeIndex, kinds: &LanguageKinds) -> Option<&'g str> {
+    let callee = graph.children(call).into_iter()
+        .find(|&child| kinds.is_statement(graph.graph[child].kind_id))?;
+    if kinds.function_names.contains(&graph.graph[callee].kind_id) {
+        return graph.try_node_source(callee).ok();
+    }
+    graph.children(callee).into_iter().rev()
+        .find(|&child| kinds.function_names.contains(&graph.graph[child].kind_id))
+        .and_then(|child| graph.try_node_source(child).ok())
+}
+
+pub(crate) fn contains(graph: &ASTGraph, outer: NodeIndex, inner: NodeIndex) -> bool {
+    let outer = &graph.graph[outer].range;
+    let inner = &graph.graph[inner].range;
+    outer.start_byte <= inner.start_byte && inner.end_byte <= outer.end_byte
+}
+
+fn kinds_named(language: &Language, kinds: &[&str]) -> HashSet<u16> {
+    kinds.iter().flat_map(|kind| kind_ids(language, kind)).collect()
+}
+
+///
+/// The kind ids the syntactic analyses below rely on, looked up by name so
+/// one table covers the C++ and Fortran grammars (names a grammar lacks
+/// simply match nothing).
+///
+#[derive(Debug,Clone)]
+pub struct LanguageKinds {
+    pub identifiers: HashSet<u16>,
+    /// kinds whose identifier children all declare variables (`int a, b;`)
+    pub declarations: HashSet<u16>,
+    /// kinds whose first identifier child declares a variable (`int a = b;`)
+    pub initializers: HashSet<u16>,
+    pub scopes: HashSet<u16>,
+    /// kinds whose children are executed in sequence
+    pub blocks: HashSet<u16>,
+    pub jumps: HashSet<u16>,
+    /// kinds control can enter from elsewhere, ending an unreachable run
+    pub entries: HashSet<u16>,
+    pub comments: HashSet<u16>,
+    pub functions: HashSet<u16>,
+    /// the part of a function naming it and listing its parameters
+    pub function_headers: HashSet<u16>,
+    pub function_names: HashSet<u16>,
+    pub parameter_lists: HashSet<u16>,
+    pub calls: HashSet<u16>,
+    pub includes: HashSet<u16>,
+    named: HashSet<u16>,
+}
+
+impl LanguageKinds {
+    pub fn new(language: &Language) -> Self {
+        LanguageKinds {
+            identifiers: kinds_named(language, &["identifier"]),
+            declarations: kinds_named(language, &["declaration", "variable_declaration"]),
+            initializers: kinds_named(language, &["init_declarator"]),
+            scopes: kinds_named(language, &["function_definition", "lambda_expression", "subroutine", "function", "program", "module"]),
+            blocks: kinds_named(language, &["compound_statement", "case_statement", "subroutine", "function", "program",
+                                            "do_loop_statement", "if_statement", "elseif_clause", "else_clause"]),
+            jumps: kinds_named(language, &["return_statement", "break_statement", "continue_statement", "goto_statement",
+                                           "throw_statement", "stop_statement", "exit_statement", "cycle_statement"]),
+            entries: kinds_named(language, &["labeled_statement", "case_statement", "else_clause", "elseif_clause",
+                                             "contains_statement", "internal_procedures"]),
+            comments: kinds_named(language, &["comment"]),
+            functions: kinds_named(language, &["function_definition", "subroutine", "function"]),
+            function_headers: kinds_named(language, &["function_declarator", "subroutine_statement", "function_statement"]),
+            function_names: kinds_named(language, &["identifier", "qualified_identifier", "field_identifier", "destructor_name",
+                                                    "operator_name", "name"]),
+            parameter_lists: kinds_named(language, &["parameter_list", "parameters"]),
+            calls: kinds_named(language, &["call_expression", "subroutine_call", "call_statement"]),
+            includes: kinds_named(language, &["preproc_include", "include_statement"]),
+            named: (0..language.node_kind_count() as u16)
+                .filter(|&id| language.node_kind_is_named(id) && !language.node_kind_for_id(id).is_some_and(|kind| kind.starts_with("end_")))
+                .collect(),
+        }
+    }
+
+    fn is_statement(&self, kind_id: u16) -> bool {
+        self.named.contains(&kind_id) && !self.comments.contains(&kind_id)
+    }
+}
+
+///
+/// Identifier nodes declaring variables in `graph`.
+///
+fn declared_identifiers(graph: &ASTGraph, kinds: &LanguageKinds) -> HashSet<NodeIndex> {
+    let mut declared = HashSet::new();
+    for node in graph.graph.node_indices() {
+        let kind_id = graph.graph[node].kind_id;
+        let all = kinds.declarations.contains(&kind_id);
+        if !all && !kinds.initializers.contains(&kind_id) {
+            continue;
+        }
+        let identifiers = graph.children(node).into_iter()
+            .filter(|&child| kinds.identifiers.contains(&graph.graph[child].kind_id));
+        if all {
+            declared.extend(identifiers);
+        } else {
+            declared.extend(identifiers.take(1));
+        }
+    }
+    declared
+}
+
+///
+/// Declarations of local variables that are never referenced again.
+///
+/// This works on names rather than a scope graph: a variable counts as used
+/// if its name appears as an identifier anywhere else in the same scope
+/// (function, subroutine, ...), so shadowed variables in nested blocks can
+/// hide each other's uses, and a variable that is only assigned counts as
+/// used. Parameters are not reported, nor are declarations whose name isn't
+/// in the graph's source.
+///
+pub fn unused_variables(subgraph: &ASTGraph, kinds: &LanguageKinds) -> Vec<NodeIndex> {
+    let declared = declared_identifiers(subgraph, kinds);
+    let mut used: HashSet<(Option<NodeIndex>, &str)> = HashSet::new();
+    for node in subgraph.graph.node_indices() {
+        if kinds.identifiers.contains(&subgraph.graph[node].kind_id) && !declared.contains(&node) {
+            if let Ok(name) = subgraph.try_node_source(node) {
+                used.insert((enclosing_scope(subgraph, node, &kinds.scopes), name));
+            }
+        }
+    }
+
+    let mut unused: Vec<NodeIndex> = declared.into_iter()
+        .filter(|&node| match subgraph.try_node_source(node) {
+            Ok(name) => !used.contains(&(enclosing_scope(subgraph, node, &kinds.scopes), name)),
+            Err(_) => false,
+        })
+        .collect();
+    unused.sort_by_key(|&node| subgraph.graph[node].range.start_byte);
+    unused
+}
+
+///
+/// Statements that follow a `return`, `break`, `goto`, `stop`, ... in the
+/// same block, up to the next label, `case` or `else` through which control
+/// can enter again. Only the statements themselves are returned, not their
+/// descendants.
+///
+/// The check is purely syntactic: jumps nested in conditionals and loops
+/// that never terminate are not followed.
+///
+pub fn unreachable_statements(subgraph: &ASTGraph, kinds: &LanguageKinds) -> Vec<NodeIndex> {
+    let mut unreachable = Vec::new();
+    for block in subgraph.graph.node_indices().filter(|&node| kinds.blocks.contains(&subgraph.graph[node].kind_id)) {
+        let mut dead = false;
+        for child in subgraph.children(block) {
+            let kind_id = subgraph.graph[child].kind_id;
+            if kinds.entries.contains(&kind_id) {
+                dead = false;
+            } else if dead && kinds.is_statement(kind_id) {
+                unreachable.push(child);
+            } else if kinds.jumps.contains(&kind_id) {
+                dead = true;
+            }
+        }
+    }
+    unreachable.sort_by_key(|&node| subgraph.graph[node].range.start_byte);
+    unreachable
+}
@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::forest::Forest;
+
+///
+/// A subtree occurring more than once across the graphs of a forest.
+/// `bytes` is the size of one occurrence, `text` the source of the first one
+/// (empty when the graphs had no source).
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct DuplicatedSubtree {
+    pub forest_node: u32,
+    pub kind_id: u16,
+    pub nodes: usize,
+    pub bytes: usize,
+    pub occurrences: usize,
+    pub files: Vec<String>,
+    pub text: String,
+}
+
+impl DuplicatedSubtree {
+    ///
+    /// Bytes taken by all occurrences together.
+    ///
+    pub fn total_bytes(&self) -> usize {
+        self.bytes * self.occurrences
+    }
+
+    ///
+    /// Bytes factoring it out into one copy would save.
+    ///
+    pub fn redundant_bytes(&self) -> usize {
+        self.bytes * (self.occurrences - 1)
+    }
+}
+
+///
+/// The subtrees of at least `min_nodes` nodes that occur more than once in
+/// `forest`, most redundant bytes first. Only maximal duplicates are
+/// reported: a subtree that is only ever repeated as part of a larger
+/// repeated subtree is left to that one.
+///
+pub fn duplicated_subtrees(forest: &Forest, min_nodes: usize) -> Vec<DuplicatedSubtree> {
+    let count = forest.node_count();
+    let names: Vec<&str> = forest.names().collect();
+
+    // forest nodes are created children first, so ids order them bottom-up
+    let mut sizes = vec![1usize; count];
+    for id in 0..count {
+        let node = forest.node(id as u32).unwrap();
+        sizes[id] += node.children.iter().map(|&(child, _)| sizes[child as usize]).sum::<usize>();
+    }
+
+    let mut occurrences = vec![0usize; count];
+    let mut inherited = vec![0usize; count];
+    for name in names.iter() {
+        for &(root, _) in forest.roots(name).unwrap() {
+            occurrences[root as usize] += 1;
+        }
+    }
+    for id in (0..count).rev() {
+        for &(child, _) in forest.node(id as u32).unwrap().children.iter() {
+            occurrences[child as usize] += occurrences[id];
+            if occurrences[id] > 1 {
+                inherited[child as usize] += occurrences[id];
+            }
+        }
+    }
+
+    let reported: HashSet<u32> = (0..count)
+        .filter(|&id| occurrences[id] > 1 && occurrences[id] > inherited[id] && sizes[id] >= min_nodes)
+        .map(|id| id as u32)
+        .collect();
+    let mut duplicates: Vec<DuplicatedSubtree> = Vec::with_capacity(reported.len());
+    let mut positions = HashMap::with_capacity(reported.len());
+    for &id in reported.iter() {
+        let node = forest.node(id).unwrap();
+        positions.insert(id, duplicates.len());
+        duplicates.push(DuplicatedSubtree {
+            forest_node: id,
+            kind_id: node.kind_id,
+            nodes: sizes[id as usize],
+            bytes: node.extent.bytes,
+            occurrences: occurrences[id as usize],
+            files: Vec::new(),
+            text: String::new(),
+        });
+    }
+
+    // which files hold each reported subtree, and the text of its first occurrence
+    for name in names.iter() {
+        let source = forest.source(name).unwrap_or("");
+        let base = forest.source_start(name).unwrap_or_default().byte;
+        let mut seen = HashSet::new();
+        let mut stack: Vec<(u32, usize)> = forest.roots(name).unwrap().iter().rev().map(|&(root, start)| (root, start.bytes)).collect();
+        while let Some((id, start_byte)) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            let node = forest.node(id).unwrap();
+            if let Some(&position) = positions.get(&id) {
+                let duplicate = &mut duplicates[position];
+                if duplicate.files.is_empty() {
+                    duplicate.text = start_byte.checked_sub(base).and_then(|start| source.get(start..start + node.extent.bytes)).unwrap_or("").to_string();
+                }
+                duplicate.files.push(name.to_string());
+            }
+            stack.extend(node.children.iter().rev().map(|&(child, start)| (child, start_byte + start.bytes)));
+        }
+    }
+
+    duplicates.sort_by(|a, b| b.redundant_bytes().cmp(&a.redundant_bytes()).then(a.forest_node.cmp(&b.forest_node)));
+    duplicates
+}
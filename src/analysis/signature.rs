@@ -0,0 +1,198 @@
+use petgraph::graph::NodeIndex;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::ASTGraph;
+use crate::analysis::LanguageKinds;
+use crate::error::GraphError;
+use crate::hashing::Fnv64;
+
+const SECTION: u64 = u64::MAX;
+
+///
+/// A function's name and the hash of its signature (see `signature_hash`).
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub hash: u64,
+    pub node: NodeIndex,
+}
+
+///
+/// Function names whose signatures differ between two snapshots, each list
+/// sorted by name.
+///
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SignatureChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl SignatureChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn first_descendant(graph: &ASTGraph, node: NodeIndex, kinds: &HashSet<u16>) -> Option<NodeIndex> {
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        if current != node && kinds.contains(&graph.graph[current].kind_id) {
+            return Some(current);
+        }
+        stack.extend(graph.children(current).into_iter().rev());
+    }
+    None
+}
+
+// kinds in preorder plus the text of every leaf whose kind isn't in `anonymous`
+fn hash_tokens(graph: &ASTGraph, node: NodeIndex, anonymous: &HashSet<u16>, hasher: &mut Fnv64) -> Result<(), GraphError> {
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        let kind_id = graph.graph[current].kind_id;
+        hasher.write_u64(kind_id as u64);
+        let children = graph.children(current);
+        if children.is_empty() && !anonymous.contains(&kind_id) {
+            hasher.write(graph.try_node_source(current)?.as_bytes());
+            hasher.write_u64(SECTION);
+        }
+        stack.extend(children.into_iter().rev());
+    }
+    Ok(())
+}
+
+///
+/// Hash of the tokens of the subtree at `node`: kinds plus the text of every
+/// leaf, so it changes with any edit except whitespace. Fails if the graph
+/// has no source for the leaves.
+///
+pub(crate) fn token_hash(graph: &ASTGraph, node: NodeIndex) -> Result<u64, GraphError> {
+    let mut hasher = Fnv64::new();
+    hash_tokens(graph, node, &HashSet::new(), &mut hasher)?;
+    Ok(hasher.finish())
+}
+
+///
+/// Signature of the function at `function`: its name, everything written
+/// before the name (return type, specifiers), and everything after it in the
+/// header (parameters, qualifiers, result clause). Parameter names are left
+/// out, so renaming a parameter keeps the hash. `None` if the function has
+/// no recognizable header, an error if the graph has no source for it.
+///
+pub fn function_signature(graph: &ASTGraph, function: NodeIndex, kinds: &LanguageKinds) -> Result<Option<FunctionSignature>, GraphError> {
+    let header = match first_descendant(graph, function, &kinds.function_headers) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let header_children = graph.children(header);
+    let name = match header_children.iter().find(|&&child| kinds.function_names.contains(&graph.graph[child].kind_id)) {
+        Some(&name) => name,
+        None => return Ok(None),
+    };
+    let name_text = graph.try_node_source(name)?;
+    let no_names = HashSet::new();
+
+    let mut hasher = Fnv64::new();
+    hasher.write(name_text.as_bytes());
+    hasher.write_u64(SECTION);
+
+    // the path down from the function to its name; whatever precedes it on each level is the return type
+    let mut path = vec![name];
+    while let Some(parent) = graph.parent(*path.last().unwrap()) {
+        if parent == function {
+            break;
+        }
+        path.push(parent);
+    }
+    let mut parent = function;
+    for step in path.into_iter().rev() {
+        for child in graph.children(parent).into_iter().take_while(|&child| child != step) {
+            if !kinds.comments.contains(&graph.graph[child].kind_id) {
+                hash_tokens(graph, child, &no_names, &mut hasher)?;
+            }
+        }
+        parent = step;
+    }
+    hasher.write_u64(SECTION);
+
+    for child in header_children.into_iter().skip_while(|&child| child != name).skip(1) {
+        let kind_id = graph.graph[child].kind_id;
+        if kinds.parameter_lists.contains(&kind_id) {
+            hash_tokens(graph, child, &kinds.identifiers, &mut hasher)?;
+        } else if !kinds.comments.contains(&kind_id) {
+            hash_tokens(graph, child, &no_names, &mut hasher)?;
+        }
+    }
+
+    Ok(Some(FunctionSignature {
+        name: name_text.to_string(),
+        hash: hasher.finish(),
+        node: function,
+    }))
+}
+
+///
+/// Hash of the signature of the function rooting `function_subgraph` (as
+/// returned by `extract_subgraph_from`, which keeps the full source), or
+/// `None` if the root has no recognizable header.
+///
+pub fn signature_hash(function_subgraph: &ASTGraph, kinds: &LanguageKinds) -> Result<Option<u64>, GraphError> {
+    let root = match function_subgraph.root() {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    Ok(function_signature(function_subgraph, root, kinds)?.map(|signature| signature.hash))
+}
+
+///
+/// Signatures of every function in `graph`, nested ones included, in source
+/// order.
+///
+pub fn function_signatures(graph: &ASTGraph, kinds: &LanguageKinds) -> Result<Vec<FunctionSignature>, GraphError> {
+    let mut signatures = Vec::new();
+    for node in graph.graph.node_indices().filter(|&node| kinds.functions.contains(&graph.graph[node].kind_id)) {
+        signatures.extend(function_signature(graph, node, kinds)?);
+    }
+    signatures.sort_by_key(|signature| graph.graph[signature.node].range.start_byte);
+    Ok(signatures)
+}
+
+fn signatures_by_name(snapshot: &[ASTGraph], kinds: &LanguageKinds) -> Result<BTreeMap<String, Vec<u64>>, GraphError> {
+    let mut by_name: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for graph in snapshot.iter() {
+        for signature in function_signatures(graph, kinds)? {
+            by_name.entry(signature.name).or_default().push(signature.hash);
+        }
+    }
+    for hashes in by_name.values_mut() {
+        hashes.sort();
+    }
+    Ok(by_name)
+}
+
+///
+/// Compare the functions of two snapshots, each given as the graphs of all
+/// its files. Functions are matched by name; overloads are compared as a
+/// group, so adding an overload of an existing function reports it as
+/// changed.
+///
+pub fn compare_signatures(old: &[ASTGraph], new: &[ASTGraph], kinds: &LanguageKinds) -> Result<SignatureChanges, GraphError> {
+    let old_signatures = signatures_by_name(old, kinds)?;
+    let new_signatures = signatures_by_name(new, kinds)?;
+    let mut changes = SignatureChanges::default();
+
+    for (name, hashes) in old_signatures.iter() {
+        match new_signatures.get(name) {
+            None => changes.removed.push(name.clone()),
+            Some(new_hashes) if new_hashes != hashes => changes.changed.push(name.clone()),
+            Some(_) => {},
+        }
+    }
+    changes.added = new_signatures.keys()
+        .filter(|name| !old_signatures.contains_key(*name))
+        .cloned()
+        .collect();
+
+    Ok(changes)
+}
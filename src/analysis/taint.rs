@@ -0,0 +1,206 @@
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+use tree_sitter::{Language, Query};
+
+use crate::ASTGraph;
+use crate::analysis::{contains, enclosing_scope, kind_ids};
+use crate::error::GraphError;
+use crate::search::Reparsed;
+
+///
+/// Configuration of a taint analysis, all in terms of tree-sitter queries
+/// for the graph's language:
+///
+/// * sources: expressions producing tainted values (`@target`, or the first capture)
+/// * sinks: places tainted values must not reach (`@target`, or the first capture)
+/// * propagators: assignments and initializations, with the value captured as
+///   `@from` and the variable it is stored in as `@to`
+///
+/// Variables are tracked by name within their scope, the nearest enclosing
+/// node with a kind added through `add_scope_kind` (typically function
+/// definitions), so the analysis is intraprocedural and flow-insensitive: a
+/// variable assigned a tainted value anywhere in a function is tainted
+/// throughout it.
+///
+pub struct TaintSpec {
+    language: Language,
+    identifier_kinds: HashSet<u16>,
+    scope_kinds: HashSet<u16>,
+    sources: Vec<Query>,
+    sinks: Vec<Query>,
+    propagators: Vec<Query>,
+}
+
+///
+/// A path from a source to a sink. `path` starts at the source, alternates
+/// between the variables the value was stored in and their uses, and ends
+/// at the sink.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct TaintFlow {
+    pub source: NodeIndex,
+    pub sink: NodeIndex,
+    pub path: Vec<NodeIndex>,
+}
+
+impl TaintSpec {
+    ///
+    /// An empty spec; nodes named `identifier` in `language` count as
+    /// variable uses.
+    ///
+    pub fn new(language: Language) -> Self {
+        let identifier_kinds = kind_ids(&language, "identifier");
+        TaintSpec {
+            language: language,
+            identifier_kinds: identifier_kinds,
+            scope_kinds: HashSet::new(),
+            sources: Vec::new(),
+            sinks: Vec::new(),
+            propagators: Vec::new(),
+        }
+    }
+
+    pub fn add_identifier_kind(&mut self, kind_id: u16) -> &mut Self {
+        self.identifier_kinds.insert(kind_id);
+        self
+    }
+
+    pub fn add_scope_kind(&mut self, kind_id: u16) -> &mut Self {
+        self.scope_kinds.insert(kind_id);
+        self
+    }
+
+    pub fn add_source(&mut self, query: &str) -> Result<&mut Self, GraphError> {
+        let query = self.compile(query, &[])?;
+        self.sources.push(query);
+        Ok(self)
+    }
+
+    pub fn add_sink(&mut self, query: &str) -> Result<&mut Self, GraphError> {
+        let query = self.compile(query, &[])?;
+        self.sinks.push(query);
+        Ok(self)
+    }
+
+    pub fn add_propagator(&mut self, query: &str) -> Result<&mut Self, GraphError> {
+        let query = self.compile(query, &["from", "to"])?;
+        self.propagators.push(query);
+        Ok(self)
+    }
+
+    fn compile(&self, source: &str, required: &[&str]) -> Result<Query, GraphError> {
+        let query = Query::new(&self.language, source).map_err(|err| GraphError::Query(err.to_string()))?;
+        if query.capture_names().is_empty() {
+            return Err(GraphError::Query("query has no captures".to_string()));
+        }
+        for name in required {
+            if query.capture_index_for_name(name).is_none() {
+                return Err(GraphError::Query(format!("query has no @{} capture", name)));
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn targets(reparsed: &Reparsed, queries: &[Query]) -> Result<Vec<NodeIndex>, GraphError> {
+    let mut nodes = Vec::new();
+    for query in queries.iter() {
+        let target = query.capture_index_for_name("target").unwrap_or(0);
+        for captures in reparsed.matches(query)? {
+            nodes.extend(captures.iter().filter(|(index, _)| *index == target).map(|(_, node)| *node));
+        }
+    }
+    nodes.sort_by_key(|node| node.index());
+    nodes.dedup();
+    Ok(nodes)
+}
+
+type Variable = (Option<NodeIndex>, String);
+
+struct State<'g> {
+    graph: &'g ASTGraph,
+    sources: Vec<NodeIndex>,
+    uses: Vec<(NodeIndex, Variable)>,
+    // witness path of every tainted variable, ending at its definition
+    tainted: HashMap<Variable, Vec<NodeIndex>>,
+}
+
+impl<'g> State<'g> {
+    ///
+    /// Witness path for the first tainted value inside `node`: a source, or a
+    /// use of a tainted variable.
+    ///
+    fn taint_within(&self, node: NodeIndex) -> Option<Vec<NodeIndex>> {
+        if let Some(source) = self.sources.iter().find(|&&source| contains(self.graph, node, source)) {
+            return Some(vec![*source]);
+        }
+        self.uses.iter()
+            .filter(|(used, _)| contains(self.graph, node, *used))
+            .find_map(|(used, variable)| self.tainted.get(variable).map(|path| {
+                let mut path = path.clone();
+                path.push(*used);
+                path
+            }))
+    }
+}
+
+///
+/// Every flow of a source's value into a sink within `graph`, in sink order.
+/// Each sink is reported once, with one witness path.
+///
+pub fn find_flows(graph: &ASTGraph, spec: &TaintSpec) -> Result<Vec<TaintFlow>, GraphError> {
+    let reparsed = Reparsed::new(graph, &spec.language)?;
+    let variable = |node: NodeIndex| -> Result<Variable, GraphError> {
+        Ok((enclosing_scope(graph, node, &spec.scope_kinds), graph.try_node_source(node)?.to_string()))
+    };
+
+    let mut assignments = Vec::new();
+    for query in spec.propagators.iter() {
+        let from = query.capture_index_for_name("from").unwrap();
+        let to = query.capture_index_for_name("to").unwrap();
+        for captures in reparsed.matches(query)? {
+            let value = captures.iter().find(|(index, _)| *index == from).map(|(_, node)| *node);
+            let target = captures.iter().find(|(index, _)| *index == to).map(|(_, node)| *node);
+            if let (Some(value), Some(target)) = (value, target) {
+                assignments.push((value, target, variable(target)?));
+            }
+        }
+    }
+
+    let mut state = State {
+        graph: graph,
+        sources: targets(&reparsed, &spec.sources)?,
+        uses: graph.graph.node_indices()
+            .filter(|&node| spec.identifier_kinds.contains(&graph.graph[node].kind_id))
+            .map(|node| Ok((node, variable(node)?)))
+            .collect::<Result<Vec<(NodeIndex, Variable)>, GraphError>>()?,
+        tainted: HashMap::new(),
+    };
+
+    // flow-insensitive, so iterate until no assignment taints a new variable
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (value, target, target_variable) in assignments.iter() {
+            if state.tainted.contains_key(target_variable) {
+                continue;
+            }
+            if let Some(mut path) = state.taint_within(*value) {
+                path.push(*target);
+                state.tainted.insert(target_variable.clone(), path);
+                changed = true;
+            }
+        }
+    }
+
+    let mut flows = Vec::new();
+    for sink in targets(&reparsed, &spec.sinks)? {
+        if let Some(mut path) = state.taint_within(sink) {
+            path.push(sink);
+            flows.push(TaintFlow { source: path[0], sink: sink, path: path });
+        }
+    }
+    flows.sort_by_key(|flow| graph.graph[flow.sink].range.start_byte);
+
+    Ok(flows)
+}
@@ -0,0 +1,117 @@
+use petgraph::graph::{DiGraph, NodeIndex};
+use std::collections::HashMap;
+use tree_sitter::Tree;
+
+use crate::ASTGraph;
+use crate::geometry::{GNode, GRange};
+
+///
+/// Reusable buffers for building graphs in bulk: the nodes of a tree are
+/// first collected into a flat arena with one cursor walk, then the graph is
+/// allocated at its final size and filled in one go, instead of growing node
+/// by node through recursive `traverse_and_build`. Keeping one `TreeArena`
+/// per worker avoids reallocating the buffers for every file of a corpus.
+///
+/// The result is the same graph `build_from_tree` produces, node and edge
+/// indices included.
+///
+#[derive(Debug,Default)]
+pub struct TreeArena {
+    nodes: Vec<GNode>,
+    edges: Vec<(u32, u32)>,
+    // preorder positions of the open ancestors during the walk
+    ancestors: Vec<u32>,
+}
+
+impl TreeArena {
+    pub fn new() -> Self {
+        TreeArena::default()
+    }
+
+    ///
+    /// Nodes the buffers can hold without growing.
+    ///
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    fn collect(&mut self, tree: &Tree) {
+        self.nodes.clear();
+        self.edges.clear();
+        self.ancestors.clear();
+
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            let position = self.nodes.len() as u32;
+            self.nodes.push(GNode {
+                id: node.id(),
+                kind_id: node.kind_id(),
+                range: GRange::from(node.range()),
+                is_named: node.is_named(),
+                source_hash: 0,
+            });
+            if let Some(&parent) = self.ancestors.last() {
+                self.edges.push((parent, position));
+            }
+
+            if cursor.goto_first_child() {
+                self.ancestors.push(position);
+                continue;
+            }
+            loop {
+                if cursor.goto_next_sibling() {
+                    break;
+                }
+                if !cursor.goto_parent() {
+                    return;
+                }
+                self.ancestors.pop();
+            }
+        }
+    }
+
+    ///
+    /// Graph of `tree` over `source`.
+    ///
+    pub fn build(&mut self, tree: &Tree, source: String) -> ASTGraph {
+        let mut ast_graph = ASTGraph::new(source);
+        self.build_into(&mut ast_graph, tree);
+        ast_graph
+    }
+
+    ///
+    /// Append the nodes of `tree` to `ast_graph`, like `build_from_tree`.
+    ///
+    pub fn build_into(&mut self, ast_graph: &mut ASTGraph, tree: &Tree) {
+        self.collect(tree);
+
+        let offset = ast_graph.graph.node_count();
+        if offset == 0 {
+            ast_graph.graph = DiGraph::with_capacity(self.nodes.len(), self.edges.len());
+            ast_graph.node_map = HashMap::with_capacity(self.nodes.len());
+        } else {
+            ast_graph.graph.reserve_nodes(self.nodes.len());
+            ast_graph.graph.reserve_edges(self.edges.len());
+            ast_graph.node_map.reserve(self.nodes.len());
+        }
+
+        for gnode in self.nodes.iter() {
+            let index = ast_graph.graph.add_node(*gnode);
+            ast_graph.node_map.insert(index, gnode.id);
+        }
+        for &(parent, child) in self.edges.iter() {
+            ast_graph.graph.add_edge(NodeIndex::new(offset + parent as usize), NodeIndex::new(offset + child as usize), ());
+        }
+        ast_graph.record_provenance(tree);
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// `build_from_tree` through a one-off `TreeArena`.
+    ///
+    pub fn build_from_tree_in_bulk(&mut self, tree: &Tree) {
+        TreeArena::new().build_into(self, tree);
+    }
+}
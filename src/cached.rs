@@ -0,0 +1,109 @@
+//!
+//! Graphs read back from caches by tools that don't link the grammar they
+//! were built with. The graph is stored together with the names of the kinds
+//! it uses, so kind-name and structure queries still work.
+//!
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use tree_sitter::Language;
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::analysis::KindLookup;
+use crate::error::GraphError;
+use crate::project::archive::KindNames;
+use crate::source::SourceStart;
+
+#[derive(Serialize,Deserialize)]
+struct StoredCachedGraph {
+    kind_names: Vec<(u16, String)>,
+    source: Option<String>,
+    source_start: SourceStart,
+    graph: SerializableGraph,
+}
+
+///
+/// A graph with its kind-name table.
+///
+pub struct CachedGraph {
+    graph: ASTGraph,
+    kinds: KindNames,
+}
+
+impl CachedGraph {
+    ///
+    /// Pair `graph` with the names `language` gives its kinds.
+    ///
+    pub fn new(graph: ASTGraph, language: &Language) -> Self {
+        let kinds = KindNames::for_graphs(language, [&graph]);
+        CachedGraph { graph: graph, kinds: kinds }
+    }
+
+    pub fn graph(&self) -> &ASTGraph {
+        &self.graph
+    }
+
+    pub fn kinds(&self) -> &KindNames {
+        &self.kinds
+    }
+
+    pub fn into_graph(self) -> ASTGraph {
+        self.graph
+    }
+
+    pub fn kind_name(&self, node: NodeIndex) -> Option<&str> {
+        self.graph.get(node).and_then(|gnode| self.kinds.get(gnode.kind_id))
+    }
+
+    ///
+    /// Nodes whose kind is called `name`, in index order. An error when no
+    /// kind of the graph has that name.
+    ///
+    pub fn nodes_of_kind(&self, name: &str) -> Result<Vec<NodeIndex>, GraphError> {
+        let ids = self.kinds.ids_named(name);
+        if ids.is_empty() {
+            return Err(GraphError::Query(format!("unknown node kind `{}`", name)));
+        }
+        Ok(self.graph.nodes().filter(|(_, gnode)| ids.contains(&gnode.kind_id)).map(|(node, _)| node).collect())
+    }
+
+    ///
+    /// `ASTGraph::find_paths_matching` with the saved kind names.
+    ///
+    pub fn find_paths_matching(&self, pattern: &[&str]) -> Result<Vec<Vec<NodeIndex>>, GraphError> {
+        self.graph.find_paths_matching(&self.kinds, pattern)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), GraphError> {
+        let stored = StoredCachedGraph {
+            kind_names: self.kinds.to_pairs(),
+            source: self.graph.source().map(|source| source.to_string()),
+            source_start: self.graph.source_start(),
+            graph: self.graph.to_serializable(),
+        };
+        bincode::serialize_into(writer, &stored)?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> Result<CachedGraph, GraphError> {
+        let stored: StoredCachedGraph = bincode::deserialize_from(reader)?;
+        let mut graph = ASTGraph::from_serializable(stored.graph);
+        if let Some(source) = stored.source {
+            graph.set_source_at(source, stored.source_start);
+        }
+        Ok(CachedGraph { graph: graph, kinds: KindNames::from_pairs(&stored.kind_names) })
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<CachedGraph, GraphError> {
+        CachedGraph::read_from(BufReader::new(File::open(path)?))
+    }
+}
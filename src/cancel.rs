@@ -0,0 +1,97 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tree_sitter::{Language, Parser, Tree};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::limits::BuildLimits;
+use crate::project::ProjectGraph;
+
+///
+/// Lets another thread stop long work (building, extraction, ingestion),
+/// e.g. an editor abandoning a build the user's next keystroke made stale.
+/// Cancelled operations return `GraphError::Cancelled`. Clones share the
+/// same state; work checks the token periodically, not continuously.
+///
+#[derive(Clone,Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    callback: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    ///
+    /// A token cancelled when `flag` is set, for hosts that already have one.
+    ///
+    pub fn from_flag(flag: Arc<AtomicBool>) -> Self {
+        CancelToken { flag: flag, callback: None }
+    }
+
+    ///
+    /// A token asking `callback` whether to stop.
+    ///
+    pub fn from_fn<F: Fn() -> bool + Send + Sync + 'static>(callback: F) -> Self {
+        CancelToken { flag: Arc::new(AtomicBool::new(false)), callback: Some(Arc::new(callback)) }
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed) || self.callback.as_ref().is_some_and(|callback| callback())
+    }
+
+    pub(crate) fn check(&self) -> Result<(), GraphError> {
+        if self.is_cancelled() {
+            Err(GraphError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// `build_from_tree` on a new graph over `source`, stopping when `cancel`
+    /// is cancelled.
+    ///
+    pub fn build_cancellable(tree: &Tree, source: String, cancel: &CancelToken) -> Result<ASTGraph, GraphError> {
+        ASTGraph::build_checked(tree, source, &BuildLimits::default(), Some(cancel))
+    }
+}
+
+impl ProjectGraph {
+    ///
+    /// Parse `files` (path, source) with `language` and add their graphs,
+    /// checking `cancel` between files and while building. Files added before
+    /// a cancellation stay in the project. Returns how many were added.
+    ///
+    pub fn ingest<I: IntoIterator<Item = (String, String)>>(&mut self, files: I, language: &Language, cancel: &CancelToken)
+        -> Result<usize, GraphError> {
+        let mut parser = Parser::new();
+        parser.set_language(language).map_err(|err| GraphError::Query(err.to_string()))?;
+        let mut added = 0;
+        for (path, source) in files {
+            cancel.check()?;
+            let tree = parser.parse(&source, None)
+                .ok_or_else(|| GraphError::Query(format!("parsing {} failed", path)))?;
+            let mut graph = ASTGraph::build_cancellable(&tree, source, cancel)?;
+            graph.set_title(path.clone());
+            self.insert(&path, graph);
+            added += 1;
+        }
+        Ok(added)
+    }
+}
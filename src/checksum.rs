@@ -0,0 +1,67 @@
+use petgraph::graph::NodeIndex;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::hashing::Fnv64;
+
+///
+/// The short hash `record_source_hashes` stores: FNV-1a folded to 32 bits,
+/// never 0 (which marks nodes without a recorded hash).
+///
+pub fn source_hash(text: &str) -> u32 {
+    let mut hasher = Fnv64::new();
+    hasher.write(text.as_bytes());
+    let hash = hasher.finish();
+    match (hash ^ (hash >> 32)) as u32 {
+        0 => 1,
+        folded => folded,
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Store the hash of every node's source slice in the node, so whoever
+    /// reads the graph back later can tell whether a source still matches it
+    /// before trusting its ranges. Costs no memory: the hash fits in what was
+    /// padding of `GNode`.
+    ///
+    pub fn record_source_hashes(&mut self) {
+        for node in self.graph.node_indices() {
+            let range = self.graph[node].range;
+            let hash = self.range_text(range.start_byte, range.end_byte).map(source_hash).unwrap_or(0);
+            self.graph[node].source_hash = hash;
+        }
+    }
+
+    pub fn clear_source_hashes(&mut self) {
+        for gnode in self.graph.node_weights_mut() {
+            gnode.source_hash = 0;
+        }
+    }
+
+    pub fn has_source_hashes(&self) -> bool {
+        self.graph.node_weights().any(|gnode| gnode.source_hash != 0)
+    }
+
+    ///
+    /// Nodes with a recorded hash that `source` doesn't match, because the
+    /// text changed or the range runs past its end, in index order.
+    ///
+    pub fn stale_nodes(&self, source: &str) -> Vec<NodeIndex> {
+        self.nodes()
+            .filter(|(_, gnode)| gnode.source_hash != 0)
+            .filter(|(_, gnode)| source.get(gnode.range.start_byte..gnode.range.end_byte).map(source_hash) != Some(gnode.source_hash))
+            .map(|(node, _)| node)
+            .collect()
+    }
+
+    ///
+    /// `stale_nodes` as a check: an error naming the first stale node.
+    ///
+    pub fn check_source(&self, source: &str) -> Result<(), GraphError> {
+        match self.stale_nodes(source).first() {
+            Some(node) => Err(GraphError::InvalidNode(format!("source of node {} no longer matches the graph", node.index()))),
+            None => Ok(()),
+        }
+    }
+}
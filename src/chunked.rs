@@ -0,0 +1,173 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+use crate::geometry::GRange;
+use crate::source::SourceStart;
+
+///
+/// Chunked graph files: every outermost subtree whose kind is in the split set
+/// (typically function definitions) is stored as an independent bincode chunk,
+/// followed by an index of all chunks so a reader can seek straight to the
+/// ones it needs.
+///
+/// Layout: `MAGIC`, chunks..., index (bincode `Vec<ChunkEntry>`),
+/// index offset (u64 little endian), `MAGIC`.
+///
+pub const MAGIC: &[u8; 4] = b"TGCK";
+
+const TRAILER_LEN: u64 = 8 + 4;
+
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct ChunkEntry {
+    /// tree-sitter id of the chunk's root node
+    pub root_id: usize,
+    pub kind_id: u16,
+    pub range: GRange,
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Serialize,Deserialize)]
+struct Chunk {
+    source: Option<String>,
+    source_start: SourceStart,
+    graph: SerializableGraph,
+}
+
+///
+/// Roots of the outermost subtrees with a kind in `kinds`, in source order.
+///
+fn top_level_matches(graph: &ASTGraph, kinds: &HashSet<u16>) -> Vec<NodeIndex> {
+    let mut matches = Vec::new();
+    let mut stack: Vec<NodeIndex> = graph.roots().into_iter().rev().collect();
+
+    while let Some(node) = stack.pop() {
+        if kinds.contains(&graph.graph[node].kind_id) {
+            matches.push(node);
+            continue;
+        }
+        for child in graph.children(node).into_iter().rev() {
+            stack.push(child);
+        }
+    }
+
+    matches
+}
+
+///
+/// Write `graph` in the chunked format, one chunk per top-level match of
+/// `kinds`. Returns the index that was written.
+///
+pub fn write_chunked<W: Write>(graph: &ASTGraph, kinds: &HashSet<u16>, mut writer: W) -> Result<Vec<ChunkEntry>, GraphError> {
+    let mut entries = Vec::new();
+    let mut offset = MAGIC.len() as u64;
+    writer.write_all(MAGIC)?;
+
+    for root in top_level_matches(graph, kinds) {
+        let gnode = &graph.graph[root];
+        let subgraph = graph.extract_subgraph_from(root);
+        let chunk = Chunk {
+            source: graph.range_text(gnode.range.start_byte, gnode.range.end_byte).map(|text| text.to_string()),
+            source_start: SourceStart::of(&gnode.range),
+            graph: subgraph.to_serializable(),
+        };
+        let bytes = bincode::serialize(&chunk)?;
+        writer.write_all(&bytes)?;
+
+        entries.push(ChunkEntry {
+            root_id: gnode.id,
+            kind_id: gnode.kind_id,
+            range: gnode.range,
+            offset: offset,
+            length: bytes.len() as u64,
+        });
+        offset += bytes.len() as u64;
+    }
+
+    let index = bincode::serialize(&entries)?;
+    writer.write_all(&index)?;
+    writer.write_all(&offset.to_le_bytes())?;
+    writer.write_all(MAGIC)?;
+    writer.flush()?;
+
+    Ok(entries)
+}
+
+///
+/// Random access to the chunks of a chunked graph file. Only the index is
+/// read up front; `load` reads and decodes a single chunk.
+///
+pub struct ChunkedReader<R> {
+    reader: R,
+    entries: Vec<ChunkEntry>,
+}
+
+impl<R: Read + Seek> ChunkedReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, GraphError> {
+        let mut magic = [0u8; 4];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GraphError::Encoding("not a chunked graph file".to_string()));
+        }
+
+        let end = reader.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+        let mut offset_bytes = [0u8; 8];
+        reader.read_exact(&mut offset_bytes)?;
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GraphError::Encoding("chunked graph file is truncated".to_string()));
+        }
+
+        let index_offset = u64::from_le_bytes(offset_bytes);
+        if index_offset > end {
+            return Err(GraphError::Encoding("chunk index offset out of bounds".to_string()));
+        }
+        reader.seek(SeekFrom::Start(index_offset))?;
+        let mut index = vec![0u8; (end - index_offset) as usize];
+        reader.read_exact(&mut index)?;
+        let entries: Vec<ChunkEntry> = bincode::deserialize(&index)?;
+
+        Ok(ChunkedReader { reader, entries })
+    }
+
+    pub fn entries(&self) -> &[ChunkEntry] {
+        &self.entries
+    }
+
+    ///
+    /// Load the chunk at position `index` in `entries()`; the returned graph
+    /// carries the chunk's own source text, as `extract_subgraphs` does.
+    ///
+    pub fn load(&mut self, index: usize) -> Result<ASTGraph, GraphError> {
+        let entry = self.entries.get(index)
+            .ok_or_else(|| GraphError::Encoding(format!("no chunk {}", index)))?
+            .clone();
+
+        self.reader.seek(SeekFrom::Start(entry.offset))?;
+        let mut bytes = vec![0u8; entry.length as usize];
+        self.reader.read_exact(&mut bytes)?;
+        let chunk: Chunk = bincode::deserialize(&bytes)?;
+
+        let mut graph = ASTGraph::try_from_serializable(chunk.graph)?;
+        if let Some(source) = chunk.source {
+            graph.set_source_at(source, chunk.source_start);
+        }
+        Ok(graph)
+    }
+
+    ///
+    /// Load every chunk whose root has the given kind.
+    ///
+    pub fn load_kind(&mut self, kind_id: u16) -> Result<Vec<ASTGraph>, GraphError> {
+        let positions: Vec<usize> = self.entries.iter().enumerate()
+            .filter(|(_, entry)| entry.kind_id == kind_id)
+            .map(|(position, _)| position)
+            .collect();
+        positions.into_iter().map(|position| self.load(position)).collect()
+    }
+}
@@ -0,0 +1,130 @@
+use petgraph::graph::{DiGraph, NodeIndex};
+use std::collections::{HashMap, HashSet};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::ids;
+
+impl ASTGraph {
+    ///
+    /// Release the spare capacity of the graph, its maps and the source, for
+    /// graphs kept resident long after they were built or pruned. Node
+    /// indices (and `AstNodeId`s) stay valid.
+    ///
+    pub fn compact(&mut self) {
+        let node_count = self.graph.node_count();
+        self.node_map.retain(|node, _| node.index() < node_count);
+        self.graph.shrink_to_fit();
+        self.node_map.shrink_to_fit();
+        if let Some(source) = self.source.as_mut() {
+            source.shrink_to_fit();
+        }
+        if let Some(leaf_text) = self.leaf_text.as_mut() {
+            leaf_text.shrink_to_fit();
+        }
+    }
+
+    ///
+    /// Renumber the nodes in preorder (roots in order, children in source
+    /// order), as `build_from_tree` numbers them, and compact the graph.
+    /// Returns the old index of every node mapped to its new one; indices
+    /// held from before are invalid afterwards, and `AstNodeId`s from before
+    /// no longer resolve.
+    ///
+    pub fn reindex(&mut self) -> HashMap<NodeIndex, NodeIndex> {
+        let mut order = Vec::with_capacity(self.graph.node_count());
+        let mut stack: Vec<NodeIndex> = self.roots().into_iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(self.children(node).into_iter().rev());
+        }
+
+        let mapping: HashMap<NodeIndex, NodeIndex> = order.iter().enumerate()
+            .map(|(position, &node)| (node, NodeIndex::new(position)))
+            .collect();
+        let mut graph = DiGraph::with_capacity(order.len(), order.len().saturating_sub(1));
+        let mut node_map = HashMap::with_capacity(order.len());
+        for &node in order.iter() {
+            let index = graph.add_node(self.graph[node]);
+            node_map.insert(index, self.graph[node].id);
+        }
+        for &node in order.iter() {
+            if let Some(parent) = self.parent(node) {
+                graph.add_edge(mapping[&parent], mapping[&node], ());
+            }
+        }
+
+        self.graph = graph;
+        self.node_map = node_map;
+        self.leaf_text = self.leaf_text.as_ref().map(|leaf_text| leaf_text.remap(&mapping));
+        self.graph_id = ids::next_graph_id();
+        self.compact();
+        mapping
+    }
+}
+
+impl ASTGraph {
+    // remove `nodes` the way petgraph does (the last node takes each freed
+    // index), keeping node_map and leaf text in step; returns the original
+    // index of every surviving node that moved, mapped to its new index
+    fn remove_nodes(&mut self, nodes: &HashSet<NodeIndex>) -> HashMap<NodeIndex, NodeIndex> {
+        if !nodes.is_empty() {
+            // indices change meaning, so AstNodeIds issued before must not resolve
+            self.graph_id = ids::next_graph_id();
+        }
+        let mut removals: Vec<NodeIndex> = nodes.iter().copied().collect();
+        removals.sort_by(|a, b| b.cmp(a));
+
+        // current index -> original index, for nodes that moved
+        let mut original: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        for node in removals {
+            let last = NodeIndex::new(self.graph.node_count() - 1);
+            self.graph.remove_node(node);
+            self.node_map.remove(&node);
+            if let Some(leaf_text) = self.leaf_text.as_mut() {
+                leaf_text.remove(node);
+            }
+            original.remove(&node);
+            if last != node {
+                if let Some(id) = self.node_map.remove(&last) {
+                    self.node_map.insert(node, id);
+                }
+                if let Some(leaf_text) = self.leaf_text.as_mut() {
+                    leaf_text.move_node(last, node);
+                }
+                let from = original.remove(&last).unwrap_or(last);
+                original.insert(node, from);
+            }
+        }
+        original.into_iter().map(|(current, from)| (from, current)).collect()
+    }
+
+    ///
+    /// Delete `node` and everything below it (pruned comments, say). Like
+    /// petgraph's own removal this moves the last nodes into the freed
+    /// indices: the returned map gives the new index of every node that
+    /// moved, all other indices stay valid. `AstNodeId`s taken before the
+    /// removal no longer resolve, since they can't tell which nodes moved.
+    ///
+    pub fn remove_subtree(&mut self, node: NodeIndex) -> Result<HashMap<NodeIndex, NodeIndex>, GraphError> {
+        if self.graph.node_weight(node).is_none() {
+            return Err(GraphError::InvalidNode(format!("no node {}", node.index())));
+        }
+        let subtree = self.collect_subgraph_nodes(node);
+        Ok(self.remove_nodes(&subtree))
+    }
+
+    ///
+    /// Make `node` the root by deleting everything outside its subtree. The
+    /// source is kept whole, so ranges stay valid. Returns the moved indices
+    /// as `remove_subtree` does.
+    ///
+    pub fn reroot(&mut self, node: NodeIndex) -> Result<HashMap<NodeIndex, NodeIndex>, GraphError> {
+        if self.graph.node_weight(node).is_none() {
+            return Err(GraphError::InvalidNode(format!("no node {}", node.index())));
+        }
+        let subtree = self.collect_subgraph_nodes(node);
+        let outside: HashSet<NodeIndex> = self.graph.node_indices().filter(|other| !subtree.contains(other)).collect();
+        Ok(self.remove_nodes(&outside))
+    }
+}
@@ -0,0 +1,65 @@
+use petgraph::adj::List;
+use petgraph::graph::NodeIndex;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::stable_graph::StableDiGraph;
+
+use crate::ASTGraph;
+use crate::geometry::GNode;
+
+impl ASTGraph {
+    ///
+    /// The tree as a `DiGraphMap` keyed by this graph's node indices (node
+    /// data stays in the `ASTGraph`, look it up with `graph[node]`).
+    ///
+    pub fn to_graphmap(&self) -> DiGraphMap<NodeIndex, ()> {
+        let mut map = DiGraphMap::with_capacity(self.graph.node_count(), self.graph.edge_count());
+        for node in self.graph.node_indices() {
+            map.add_node(node);
+        }
+        for (parent, child) in self.edges() {
+            map.add_edge(parent, child, ());
+        }
+        map
+    }
+
+    ///
+    /// The tree as a petgraph adjacency list; node `n` of the list is node
+    /// `NodeIndex::new(n)` of this graph.
+    ///
+    pub fn to_adjacency_list(&self) -> List<()> {
+        let mut list = List::with_capacity(self.graph.node_count());
+        for _ in self.graph.node_indices() {
+            list.add_node();
+        }
+        for (parent, child) in self.edges() {
+            list.add_edge(parent.index() as u32, child.index() as u32, ());
+        }
+        list
+    }
+
+    ///
+    /// The tree as a `StableDiGraph`, whose indices survive node removal,
+    /// with the same indices and node data.
+    ///
+    pub fn to_stable_graph(&self) -> StableDiGraph<GNode, ()> {
+        StableDiGraph::from(self.graph.clone())
+    }
+}
+
+impl From<&ASTGraph> for DiGraphMap<NodeIndex, ()> {
+    fn from(graph: &ASTGraph) -> Self {
+        graph.to_graphmap()
+    }
+}
+
+impl From<&ASTGraph> for List<()> {
+    fn from(graph: &ASTGraph) -> Self {
+        graph.to_adjacency_list()
+    }
+}
+
+impl From<&ASTGraph> for StableDiGraph<GNode, ()> {
+    fn from(graph: &ASTGraph) -> Self {
+        graph.to_stable_graph()
+    }
+}
@@ -0,0 +1,131 @@
+use petgraph::graph::NodeIndex;
+
+use crate::ASTGraph;
+use crate::geometry::GNode;
+
+// one level of the path: the node's siblings in source order and its place
+struct Level {
+    siblings: Vec<NodeIndex>,
+    position: usize,
+}
+
+///
+/// Cursor over an `ASTGraph` with the moves and semantics of tree-sitter's
+/// `TreeCursor`, so walks written for parse trees also run over graphs read
+/// back from disk: children come in source order, anonymous nodes included,
+/// and the cursor never leaves the subtree of the node it started on (from
+/// `walk`, the roots are siblings).
+///
+pub struct AstCursor<'g> {
+    graph: &'g ASTGraph,
+    path: Vec<Level>,
+}
+
+impl<'g> AstCursor<'g> {
+    fn new(graph: &'g ASTGraph, siblings: Vec<NodeIndex>, position: usize) -> Self {
+        AstCursor { graph: graph, path: vec![Level { siblings: siblings, position: position }] }
+    }
+
+    fn level(&self) -> &Level {
+        self.path.last().expect("cursor path is never empty")
+    }
+
+    pub fn node(&self) -> NodeIndex {
+        let level = self.level();
+        level.siblings[level.position]
+    }
+
+    pub fn gnode(&self) -> &'g GNode {
+        &self.graph.graph[self.node()]
+    }
+
+    ///
+    /// Depth below the node the cursor started on.
+    ///
+    pub fn depth(&self) -> u32 {
+        (self.path.len() - 1) as u32
+    }
+
+    pub fn goto_first_child(&mut self) -> bool {
+        let children = self.graph.children(self.node());
+        if children.is_empty() {
+            return false;
+        }
+        self.path.push(Level { siblings: children, position: 0 });
+        true
+    }
+
+    pub fn goto_last_child(&mut self) -> bool {
+        let children = self.graph.children(self.node());
+        if children.is_empty() {
+            return false;
+        }
+        let position = children.len() - 1;
+        self.path.push(Level { siblings: children, position: position });
+        true
+    }
+
+    ///
+    /// Move to the first child ending after `byte`, returning its position
+    /// among the children.
+    ///
+    pub fn goto_first_child_for_byte(&mut self, byte: usize) -> Option<usize> {
+        let children = self.graph.children(self.node());
+        let position = children.iter().position(|&child| self.graph.graph[child].range.end_byte > byte)?;
+        self.path.push(Level { siblings: children, position: position });
+        Some(position)
+    }
+
+    pub fn goto_next_sibling(&mut self) -> bool {
+        let level = self.path.last_mut().expect("cursor path is never empty");
+        if level.position + 1 >= level.siblings.len() {
+            return false;
+        }
+        level.position += 1;
+        true
+    }
+
+    pub fn goto_previous_sibling(&mut self) -> bool {
+        let level = self.path.last_mut().expect("cursor path is never empty");
+        if level.position == 0 {
+            return false;
+        }
+        level.position -= 1;
+        true
+    }
+
+    pub fn goto_parent(&mut self) -> bool {
+        if self.path.len() == 1 {
+            return false;
+        }
+        self.path.pop();
+        true
+    }
+
+    ///
+    /// Start over from `node`, which becomes the new top of the cursor.
+    ///
+    pub fn reset(&mut self, node: NodeIndex) {
+        self.path = vec![Level { siblings: vec![node], position: 0 }];
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Cursor on the first root, `None` for an empty graph.
+    ///
+    pub fn walk(&self) -> Option<AstCursor<'_>> {
+        let roots = self.roots();
+        if roots.is_empty() {
+            return None;
+        }
+        Some(AstCursor::new(self, roots, 0))
+    }
+
+    ///
+    /// Cursor confined to the subtree of `node`, like `Node::walk`.
+    ///
+    pub fn walk_from(&self, node: NodeIndex) -> AstCursor<'_> {
+        AstCursor::new(self, vec![node], 0)
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::labels::LabelMap;
+use crate::manifest::{content_hash, Manifest};
+use crate::sample::SampleRng;
+use crate::store::{decode_graph, encode_graph};
+
+///
+/// Write each graph to its own file in `dir` (created if needed), in the
+/// encoding `DirectoryStore` uses, so title, metadata, labels and source all
+/// travel with it. The manifest lists the files with their labels plus the
+/// label ids of the whole dataset; the `manifest` feature saves it in `dir`.
+///
+pub fn write_dataset<P: AsRef<Path>>(graphs: &[ASTGraph], dir: P) -> Result<Manifest, GraphError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let mut manifest = Manifest::new();
+    manifest.label_map = LabelMap::from_graphs(graphs);
+    for (position, graph) in graphs.iter().enumerate() {
+        let file = manifest.file_name_for(graph, position, "tgb");
+        let (_, bytes) = encode_graph(graph)?;
+        manifest.write_artifact(dir, file, graph, &bytes)?;
+    }
+    #[cfg(feature="manifest")]
+    manifest.save(dir)?;
+    Ok(manifest)
+}
+
+///
+/// Read back the graphs of a dataset written by `write_dataset`, in
+/// manifest order, failing on a file that no longer has its recorded hash.
+///
+pub fn read_dataset<P: AsRef<Path>>(dir: P, manifest: &Manifest) -> Result<Vec<ASTGraph>, GraphError> {
+    let dir = dir.as_ref();
+    manifest.entries.iter()
+        .map(|entry| {
+            let bytes = fs::read(dir.join(&entry.file))?;
+            if content_hash(&bytes) != entry.hash {
+                return Err(GraphError::Encoding(format!("{} does not match its manifest entry", entry.file)));
+            }
+            decode_graph(&bytes)
+        })
+        .collect()
+}
+
+///
+/// What `split` keeps balanced across the partitions: each stratum is split
+/// in the given ratios on its own.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub enum Stratify {
+    None,
+    /// graphs with the same set of labels form a stratum
+    Labels,
+    /// the first label starting with the prefix, e.g. `"lang:"` for language
+    /// tags; graphs without one form a stratum of their own
+    LabelPrefix(String),
+    /// the value of a metadata key
+    Metadata(String),
+}
+
+impl Stratify {
+    fn key(&self, graph: &ASTGraph) -> String {
+        match self {
+            Stratify::None => String::new(),
+            Stratify::Labels => {
+                let mut labels: Vec<&str> = graph.labels().iter().map(|label| label.as_str()).collect();
+                labels.sort_unstable();
+                labels.join("\n")
+            },
+            Stratify::LabelPrefix(prefix) => graph.labels().iter()
+                .find(|label| label.starts_with(prefix.as_str()))
+                .cloned()
+                .unwrap_or_default(),
+            Stratify::Metadata(key) => graph.metadata_value(key).unwrap_or("").to_string(),
+        }
+    }
+}
+
+///
+/// Partition `graphs` into `ratios.len()` parts (train/validation/test, or
+/// folds) of about the given proportions, as sorted indices into `graphs`.
+/// Ratios need not sum to one; they are normalized. The shuffle is seeded,
+/// so the same graphs, ratios and seed always give the same partition.
+///
+/// Panics if `ratios` is empty, has a negative entry or sums to zero.
+///
+pub fn split(graphs: &[ASTGraph], ratios: &[f64], stratify_by: &Stratify, seed: u64) -> Vec<Vec<usize>> {
+    let total: f64 = ratios.iter().sum();
+    assert!(!ratios.is_empty() && ratios.iter().all(|&ratio| ratio >= 0.0) && total > 0.0,
+            "split ratios must be non-negative and not all zero");
+
+    let mut strata: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, graph) in graphs.iter().enumerate() {
+        strata.entry(stratify_by.key(graph)).or_default().push(index);
+    }
+
+    let mut rng = SampleRng::new(seed);
+    let mut parts = vec![Vec::new(); ratios.len()];
+    for members in strata.values_mut() {
+        for last in (1..members.len()).rev() {
+            let other = rng.below(last + 1);
+            members.swap(last, other);
+        }
+        // rounding the cumulative bounds hands out every member exactly once
+        let mut cumulative = 0.0;
+        let mut start = 0;
+        for (part, ratio) in ratios.iter().enumerate() {
+            cumulative += ratio / total;
+            let end = if part + 1 == ratios.len() { members.len() } else { (cumulative * members.len() as f64).round() as usize };
+            parts[part].extend_from_slice(&members[start..end.max(start)]);
+            start = end.max(start);
+        }
+    }
+    for part in parts.iter_mut() {
+        part.sort_unstable();
+    }
+    parts
+}
+
+///
+/// Two graphs of a similarity dataset, by index, and whether they count as
+/// similar.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct GraphPair {
+    pub first: usize,
+    pub second: usize,
+    pub similar: bool,
+}
+
+///
+/// Similar and dissimilar pairs of `graphs` for contrastive training. Clones
+/// are the positives: every pair whose `positives_by_hash` values are equal
+/// (`ASTGraph::structural_hash` pairs graphs differing only in names,
+/// literals and layout). Then `negative_ratio` times as many pairs with
+/// different hashes are drawn as negatives, seeded like `split`, or all of
+/// them if there are fewer. Positives come first; both runs are sorted, and
+/// `first < second` in every pair.
+///
+pub fn pairs<F: Fn(&ASTGraph) -> u64>(graphs: &[ASTGraph], positives_by_hash: F, negative_ratio: f64, seed: u64) -> Vec<GraphPair> {
+    let hashes: Vec<u64> = graphs.iter().map(positives_by_hash).collect();
+    let mut groups: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    for (index, &hash) in hashes.iter().enumerate() {
+        groups.entry(hash).or_default().push(index);
+    }
+
+    let mut result = Vec::new();
+    for members in groups.values() {
+        for (position, &first) in members.iter().enumerate() {
+            for &second in members[position + 1..].iter() {
+                result.push(GraphPair { first: first, second: second, similar: true });
+            }
+        }
+    }
+    result.sort_unstable_by_key(|pair| (pair.first, pair.second));
+
+    let positives = result.len();
+    let available = graphs.len() * graphs.len().saturating_sub(1) / 2 - positives;
+    let wanted = ((positives as f64 * negative_ratio.max(0.0)).round() as usize).min(available);
+    let mut rng = SampleRng::new(seed);
+    let mut negatives: Vec<(usize, usize)> = Vec::with_capacity(wanted);
+    if wanted * 2 > available {
+        // most of the candidates are needed: shuffle them all and take a prefix
+        for first in 0..graphs.len() {
+            for second in first + 1..graphs.len() {
+                if hashes[first] != hashes[second] {
+                    negatives.push((first, second));
+                }
+            }
+        }
+        for last in (1..negatives.len()).rev() {
+            let other = rng.below(last + 1);
+            negatives.swap(last, other);
+        }
+        negatives.truncate(wanted);
+    } else {
+        // at least half of the draws hit an unused candidate
+        let mut taken = HashSet::with_capacity(wanted);
+        while negatives.len() < wanted {
+            let (a, b) = (rng.below(graphs.len()), rng.below(graphs.len()));
+            let pair = (a.min(b), a.max(b));
+            if a != b && hashes[a] != hashes[b] && taken.insert(pair) {
+                negatives.push(pair);
+            }
+        }
+    }
+    negatives.sort_unstable();
+    result.extend(negatives.into_iter().map(|(first, second)| GraphPair { first: first, second: second, similar: false }));
+    result
+}
@@ -0,0 +1,195 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::geometry::{GNode, GPoint, GRange};
+use crate::hashing::{layout_hashes, Fnv64};
+
+///
+/// One step of a patch, in preorder over the new graph. `Node` is a node that
+/// has to be shipped, followed by the ops of its `child_count` children;
+/// `Reuse` stands for a whole subtree copied from the old graph (found by its
+/// layout hash) and moved to a new start position.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub enum PatchOp {
+    Node { node: GNode, child_count: u32 },
+    Reuse { hash: u64, start_byte: usize, start_point: GPoint },
+}
+
+///
+/// Delta from one version of a graph to the next. Unchanged subtrees are
+/// referenced by hash, so its size is proportional to what changed.
+/// Reused nodes keep their tree-sitter ids from the old graph.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct GraphPatch {
+    /// hash over the roots of the graph the patch applies to
+    pub base_hash: u64,
+    pub root_count: u32,
+    pub ops: Vec<PatchOp>,
+}
+
+impl GraphPatch {
+    pub fn reused_subtrees(&self) -> usize {
+        self.ops.iter().filter(|op| matches!(op, PatchOp::Reuse { .. })).count()
+    }
+
+    pub fn added_nodes(&self) -> usize {
+        self.ops.iter().filter(|op| matches!(op, PatchOp::Node { .. })).count()
+    }
+}
+
+fn base_hash(graph: &ASTGraph, hashes: &HashMap<NodeIndex, u64>) -> u64 {
+    let mut hasher = Fnv64::new();
+    for root in graph.roots() {
+        hasher.write_u64(hashes[&root]);
+    }
+    hasher.finish()
+}
+
+///
+/// Compute the patch turning `old` into `new`.
+///
+pub fn diff(old: &ASTGraph, new: &ASTGraph) -> GraphPatch {
+    let old_hashes = layout_hashes(old);
+    let new_hashes = layout_hashes(new);
+    let available: HashSet<u64> = old_hashes.values().cloned().collect();
+
+    let mut ops = Vec::new();
+    let roots = new.roots();
+    let mut stack: Vec<NodeIndex> = roots.iter().rev().cloned().collect();
+
+    while let Some(node) = stack.pop() {
+        let gnode = new.graph[node];
+        let hash = new_hashes[&node];
+        if available.contains(&hash) {
+            ops.push(PatchOp::Reuse { hash: hash, start_byte: gnode.range.start_byte, start_point: gnode.range.start_point });
+            continue;
+        }
+        let children = new.children(node);
+        ops.push(PatchOp::Node { node: gnode, child_count: children.len() as u32 });
+        for child in children.into_iter().rev() {
+            stack.push(child);
+        }
+    }
+
+    GraphPatch {
+        base_hash: base_hash(old, &old_hashes),
+        root_count: roots.len() as u32,
+        ops: ops,
+    }
+}
+
+///
+/// `diff` encoded with bincode.
+///
+pub fn diff_serialize(old: &ASTGraph, new: &ASTGraph) -> Result<Vec<u8>, GraphError> {
+    Ok(bincode::serialize(&diff(old, new))?)
+}
+
+///
+/// Rebuild the new graph from `old` and a patch produced by `diff_serialize`.
+/// The result has no source attached.
+///
+pub fn apply_patch(old: &ASTGraph, patch: &[u8]) -> Result<ASTGraph, GraphError> {
+    let patch: GraphPatch = bincode::deserialize(patch)?;
+    apply(old, &patch)
+}
+
+pub fn apply(old: &ASTGraph, patch: &GraphPatch) -> Result<ASTGraph, GraphError> {
+    let old_hashes = layout_hashes(old);
+    if base_hash(old, &old_hashes) != patch.base_hash {
+        return Err(GraphError::Encoding("patch was computed against a different graph".to_string()));
+    }
+    let mut by_hash: HashMap<u64, NodeIndex> = HashMap::with_capacity(old_hashes.len());
+    for (node, hash) in old_hashes.into_iter() {
+        by_hash.entry(hash).or_insert(node);
+    }
+
+    let mut target = ASTGraph::new("".to_string());
+    target.source = None;
+    target.set_title(old.title());
+    target.metadata = old.metadata.clone();
+    target.labels = old.labels.clone();
+    let mut position = 0;
+    for _ in 0..patch.root_count {
+        apply_op(old, &by_hash, patch, &mut position, None, &mut target)?;
+    }
+    if position != patch.ops.len() {
+        return Err(GraphError::Encoding("patch has trailing operations".to_string()));
+    }
+
+    Ok(target)
+}
+
+fn apply_op(old: &ASTGraph, by_hash: &HashMap<u64, NodeIndex>, patch: &GraphPatch, position: &mut usize,
+            parent: Option<NodeIndex>, target: &mut ASTGraph) -> Result<(), GraphError> {
+    let op = patch.ops.get(*position)
+        .ok_or_else(|| GraphError::Encoding("patch ended early".to_string()))?;
+    *position += 1;
+
+    match op {
+        PatchOp::Node { node, child_count } => {
+            let index = add_to(target, *node, parent);
+            for _ in 0..*child_count {
+                apply_op(old, by_hash, patch, position, Some(index), target)?;
+            }
+        },
+        PatchOp::Reuse { hash, start_byte, start_point } => {
+            let old_root = *by_hash.get(hash)
+                .ok_or_else(|| GraphError::Encoding(format!("subtree {:016x} is not in the base graph", hash)))?;
+            copy_shifted(old, old_root, *start_byte, *start_point, parent, target);
+        },
+    }
+
+    Ok(())
+}
+
+fn add_to(target: &mut ASTGraph, gnode: GNode, parent: Option<NodeIndex>) -> NodeIndex {
+    let index = target.graph.add_node(gnode);
+    target.node_map.insert(index, gnode.id);
+    if let Some(parent) = parent {
+        target.add_edge(parent, index);
+    }
+    index
+}
+
+fn shift_point(point: GPoint, anchor: GPoint, new_anchor: GPoint) -> GPoint {
+    let row = (point.row + new_anchor.row).wrapping_sub(anchor.row);
+    let column = if point.row == anchor.row {
+        (point.column + new_anchor.column).wrapping_sub(anchor.column)
+    } else {
+        point.column
+    };
+    GPoint { row: row, column: column }
+}
+
+fn copy_shifted(old: &ASTGraph, old_root: NodeIndex, start_byte: usize, start_point: GPoint,
+                parent: Option<NodeIndex>, target: &mut ASTGraph) {
+    let anchor = old.graph[old_root].range;
+    let mut stack = vec![(old_root, parent)];
+
+    while let Some((node, new_parent)) = stack.pop() {
+        let gnode = old.graph[node];
+        let range = gnode.range;
+        let shifted = GNode {
+            id: gnode.id,
+            kind_id: gnode.kind_id,
+            is_named: gnode.is_named,
+            source_hash: gnode.source_hash,
+            range: GRange {
+                start_byte: (range.start_byte + start_byte).wrapping_sub(anchor.start_byte),
+                end_byte: (range.end_byte + start_byte).wrapping_sub(anchor.start_byte),
+                start_point: shift_point(range.start_point, anchor.start_point, start_point),
+                end_point: shift_point(range.end_point, anchor.start_point, start_point),
+            },
+        };
+        let index = add_to(target, shifted, new_parent);
+        for child in old.children(node).into_iter().rev() {
+            stack.push((child, Some(index)));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use petgraph::graph::NodeIndex;
+use tree_sitter::InputEdit;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::geometry::GPoint;
+
+///
+/// Position reached after writing `text` starting at `start`.
+///
+pub fn advance_point(start: GPoint, text: &str) -> GPoint {
+    match text.rfind('\n') {
+        Some(last_newline) => GPoint {
+            row: start.row + text.matches('\n').count(),
+            column: text.len() - last_newline - 1,
+        },
+        None => GPoint { row: start.row, column: start.column + text.len() },
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Source with the text of `node` replaced by `new_text`, plus the matching
+    /// edit for `Tree::edit` so the old tree can be reused for an incremental
+    /// reparse. The graph itself is left untouched. Fails if the graph has
+    /// no source, or only part of the file's (an extracted subgraph).
+    ///
+    pub fn replace_node_source(&self, node: NodeIndex, new_text: &str) -> Result<(String, InputEdit), GraphError> {
+        let old_text = self.try_node_source(node)?;
+        if self.source_start.byte != 0 {
+            return Err(GraphError::SourceUnavailable(format!("graph `{}` only has the source from byte {} on, not the whole file", self.title, self.source_start.byte)));
+        }
+        let source = self.source_text();
+        let range = self.graph[node].range;
+
+        let mut patched = String::with_capacity(source.len() - old_text.len() + new_text.len());
+        patched.push_str(&source[..range.start_byte]);
+        patched.push_str(new_text);
+        patched.push_str(&source[range.end_byte..]);
+
+        let edit = InputEdit {
+            start_byte: range.start_byte,
+            old_end_byte: range.end_byte,
+            new_end_byte: range.start_byte + new_text.len(),
+            start_position: range.start_point.into(),
+            old_end_position: range.end_point.into(),
+            new_end_position: advance_point(range.start_point, new_text).into(),
+        };
+
+        Ok((patched, edit))
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::io;
+
+use crate::limits::Limit;
+
+///
+/// Errors returned by the fallible parts of the crate (I/O, import/decoding).
+///
+#[derive(Debug)]
+pub enum GraphError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    Encoding(String),
+    Storage(String),
+    InvalidNode(String),
+    Query(String),
+    LimitExceeded(Limit),
+    Cancelled,
+    SourceUnavailable(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::Io(err) => write!(f, "I/O error: {}", err),
+            GraphError::Parse { line, message } => write!(f, "parse error on line {}: {}", line, message),
+            GraphError::Encoding(message) => write!(f, "encoding error: {}", message),
+            GraphError::Storage(message) => write!(f, "storage error: {}", message),
+            GraphError::InvalidNode(message) => write!(f, "invalid node: {}", message),
+            GraphError::Query(message) => write!(f, "query error: {}", message),
+            GraphError::LimitExceeded(limit) => write!(f, "limit exceeded: {}", limit),
+            GraphError::Cancelled => write!(f, "cancelled"),
+            GraphError::SourceUnavailable(message) => write!(f, "source unavailable: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GraphError {
+    fn from(err: io::Error) -> GraphError {
+        GraphError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for GraphError {
+    fn from(err: bincode::Error) -> GraphError {
+        match *err {
+            bincode::ErrorKind::Io(io_err) => GraphError::Io(io_err),
+            other => GraphError::Encoding(other.to_string()),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use petgraph::graph::NodeIndex;
+
+use crate::ASTGraph;
+
+pub const ELLIPSIS: &str = "…";
+
+///
+/// Where `excerpt` cuts text that doesn't fit.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Ellipsis {
+    /// keep the beginning: `int main() { retu…`
+    Tail,
+    /// keep both ends: `int main() {…n 0; }`
+    Middle,
+    /// keep whole children of the node from the start, then the closing
+    /// token if it is short: `{ a(); b(); … }`; falls back to `Tail` when not
+    /// even the first child fits
+    Statements,
+}
+
+// largest char boundary of `text` not above `index`
+fn floor_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+// smallest char boundary of `text` not below `index`
+fn ceil_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+// control characters other than line breaks and tabs become spaces
+fn display_safe(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() && c != '\n' && c != '\t' { ' ' } else { c }).collect()
+}
+
+impl ASTGraph {
+    ///
+    /// Source of `node` cut to at most `max_bytes` bytes (ellipsis included),
+    /// never splitting a character, and with control characters other than
+    /// newlines and tabs replaced by spaces. Returns an empty string when the
+    /// graph has no source for the node.
+    ///
+    pub fn excerpt(&self, node: NodeIndex, max_bytes: usize, strategy: Ellipsis) -> String {
+        let range = self.graph[node].range;
+        let text = match self.range_text(range.start_byte, range.end_byte) {
+            Some(text) => text,
+            None => return String::new(),
+        };
+        if text.len() <= max_bytes {
+            return display_safe(text);
+        }
+        if max_bytes < ELLIPSIS.len() {
+            return display_safe(&text[..floor_boundary(text, max_bytes)]);
+        }
+        let budget = max_bytes - ELLIPSIS.len();
+
+        let cut = match strategy {
+            Ellipsis::Tail => None,
+            Ellipsis::Middle => {
+                let head = floor_boundary(text, budget - budget / 2);
+                let tail = ceil_boundary(text, text.len() - budget / 2);
+                Some(format!("{}{}{}", &text[..head], ELLIPSIS, &text[tail..]))
+            },
+            Ellipsis::Statements => self.statement_excerpt(node, text, budget),
+        };
+        let excerpt = cut.unwrap_or_else(|| format!("{}{}", &text[..floor_boundary(text, budget)], ELLIPSIS));
+        display_safe(&excerpt)
+    }
+
+    fn statement_excerpt(&self, node: NodeIndex, text: &str, budget: usize) -> Option<String> {
+        let start = self.graph[node].range.start_byte;
+        let children = self.children(node);
+        let closing = children.last()
+            .filter(|&&last| self.graph.neighbors(last).next().is_none())
+            .map(|&last| &text[self.graph[last].range.start_byte - start..])
+            .filter(|closing| closing.len() <= 3)
+            .unwrap_or("");
+        // a space on either side of the ellipsis when there is a closing token
+        let separators = if closing.is_empty() { 0 } else { 2 };
+
+        let mut end = None;
+        for child in children.iter().take(children.len().saturating_sub(if closing.is_empty() { 0 } else { 1 })) {
+            let child_end = self.graph[*child].range.end_byte - start;
+            if child_end + separators + closing.len() > budget {
+                break;
+            }
+            end = Some(child_end);
+        }
+
+        end.map(|end| if closing.is_empty() {
+            format!("{}{}", &text[..end], ELLIPSIS)
+        } else {
+            format!("{} {} {}", &text[..end], ELLIPSIS, closing)
+        })
+    }
+}
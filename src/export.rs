@@ -0,0 +1,353 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::excerpt::Ellipsis;
+use crate::geometry::GNode;
+use crate::kinds::KindTable;
+use crate::manifest::Manifest;
+
+///
+/// Options shared by the textual exporters (DOT, Mermaid, PlantUML).
+///
+/// `max_depth` collapses everything deeper than the cutoff into a single
+/// "… (N nodes)" placeholder per subtree, which keeps graphs of real files
+/// small enough to actually render.
+///
+/// `leaf_text` adds the text of leaves to their labels, eliding the middle
+/// of any longer than the given number of bytes, so a giant literal doesn't
+/// swamp the output.
+///
+/// `kinds` labels nodes with kind names instead of kind ids.
+///
+#[derive(Debug,Clone,Default)]
+pub struct ExportOptions {
+    pub max_depth: Option<usize>,
+    pub leaf_text: Option<usize>,
+    pub kinds: Option<Arc<KindTable>>,
+}
+
+impl ExportOptions {
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        ExportOptions { max_depth: Some(max_depth), leaf_text: None, kinds: None }
+    }
+
+    pub fn with_leaf_text(mut self, max_bytes: usize) -> Self {
+        self.leaf_text = Some(max_bytes);
+        self
+    }
+
+    pub fn with_kind_names(mut self, kinds: Arc<KindTable>) -> Self {
+        self.kinds = Some(kinds);
+        self
+    }
+}
+
+///
+/// Format-independent view of what gets rendered: node ids with labels,
+/// and the edges between them.
+///
+struct View {
+    nodes: Vec<(String, String, Option<GNode>)>,
+    edges: Vec<(String, String)>,
+}
+
+fn node_label(gnode: &GNode, kinds: Option<&KindTable>) -> String {
+    match kinds.and_then(|kinds| kinds.name(gnode.kind_id)) {
+        Some(name) => name.to_string(),
+        None => format!("{}", gnode.kind_id),
+    }
+}
+
+// kind and elided text of a leaf; just the kind when there is no text
+fn leaf_label(graph: &ASTGraph, node: NodeIndex, max_bytes: usize, kinds: Option<&KindTable>) -> String {
+    let text = match graph.stored_leaf_text().and_then(|leaf_text| leaf_text.get(node)) {
+        Some(text) if text.len() <= max_bytes => text.to_string(),
+        _ => graph.excerpt(node, max_bytes, Ellipsis::Middle),
+    };
+    if text.is_empty() {
+        node_label(&graph.graph[node], kinds)
+    } else {
+        // one line per node, as import::from_dot expects
+        format!("{}: {}", node_label(&graph.graph[node], kinds), text.replace('\n', " "))
+    }
+}
+
+fn node_id(node: NodeIndex) -> String {
+    format!("n{}", node.index())
+}
+
+fn build_view(graph: &ASTGraph, options: &ExportOptions) -> View {
+    let mut view = View { nodes: Vec::new(), edges: Vec::new() };
+    let kinds = options.kinds.as_deref();
+    let mut stack: Vec<(NodeIndex, usize)> = graph.roots().into_iter().rev().map(|r| (r, 0)).collect();
+
+    while let Some((node, depth)) = stack.pop() {
+        let children = graph.children(node);
+        let label = match options.leaf_text {
+            Some(max_bytes) if children.is_empty() => leaf_label(graph, node, max_bytes, kinds),
+            _ => node_label(&graph.graph[node], kinds),
+        };
+        view.nodes.push((node_id(node), label, Some(graph.graph[node])));
+
+        for &child in children.iter() {
+            view.edges.push((node_id(node), node_id(child)));
+        }
+
+        match options.max_depth {
+            Some(max_depth) if depth >= max_depth => {
+                // drop the edges we just added and summarize the subtree instead
+                view.edges.truncate(view.edges.len() - children.len());
+                let hidden = graph.subtree_size(node) - 1;
+                if hidden > 0 {
+                    let placeholder = format!("p{}", node.index());
+                    view.nodes.push((placeholder.clone(), format!("… ({} nodes)", hidden), None));
+                    view.edges.push((node_id(node), placeholder));
+                }
+            },
+            _ => {
+                for &child in children.iter().rev() {
+                    stack.push((child, depth + 1));
+                }
+            }
+        }
+    }
+
+    view
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// PlantUML has no escape for quotes inside quoted names
+fn escape_plantuml(label: &str) -> String {
+    label.replace('"', "'")
+}
+
+fn escape_mermaid(label: &str) -> String {
+    label.replace('"', "#quot;")
+}
+
+///
+/// Render the graph in Graphviz DOT format.
+///
+/// Besides the `label`, every node carries `kind_id`, `ts_id` (the tree-sitter
+/// id) and `range` ("start_byte,end_byte,start_row,start_column,end_row,end_column")
+/// attributes, anonymous nodes `named=false` and nodes with a recorded
+/// source hash `source_hash`, which is what `import::from_dot` reads back.
+///
+pub fn to_dot(graph: &ASTGraph, options: &ExportOptions) -> String {
+    let view = build_view(graph, options);
+    let mut out = String::new();
+
+    writeln!(out, "digraph \"{}\" {{", escape_dot(&graph.title())).unwrap();
+    for (id, label, gnode) in view.nodes.iter() {
+        match gnode {
+            Some(gnode) => {
+                let r = &gnode.range;
+                let mut extra = String::new();
+                if !gnode.is_named {
+                    extra.push_str(", named=false");
+                }
+                if gnode.source_hash != 0 {
+                    write!(extra, ", source_hash={}", gnode.source_hash).unwrap();
+                }
+                writeln!(out, "    {} [label=\"{}\", kind_id={}, ts_id={}, range=\"{},{},{},{},{},{}\"{}];",
+                    id, escape_dot(label), gnode.kind_id, gnode.id,
+                    r.start_byte, r.end_byte, r.start_point.row, r.start_point.column, r.end_point.row, r.end_point.column,
+                    extra).unwrap();
+            },
+            None => {
+                writeln!(out, "    {} [label=\"{}\"];", id, escape_dot(label)).unwrap();
+            }
+        }
+    }
+    for (source, target) in view.edges.iter() {
+        writeln!(out, "    {} -> {};", source, target).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+///
+/// Render the graph as a Mermaid flowchart.
+///
+pub fn to_mermaid(graph: &ASTGraph, options: &ExportOptions) -> String {
+    let view = build_view(graph, options);
+    let mut out = String::new();
+
+    writeln!(out, "graph TD").unwrap();
+    for (id, label, _) in view.nodes.iter() {
+        writeln!(out, "    {}[\"{}\"]", id, escape_mermaid(label)).unwrap();
+    }
+    for (source, target) in view.edges.iter() {
+        writeln!(out, "    {} --> {}", source, target).unwrap();
+    }
+
+    out
+}
+
+///
+/// Render the graph as a PlantUML diagram, one rectangle per node.
+///
+pub fn to_plantuml(graph: &ASTGraph, options: &ExportOptions) -> String {
+    let view = build_view(graph, options);
+    let mut out = String::new();
+
+    writeln!(out, "@startuml").unwrap();
+    if !graph.title().is_empty() {
+        writeln!(out, "title {}", graph.title()).unwrap();
+    }
+    for (id, label, _) in view.nodes.iter() {
+        writeln!(out, "rectangle \"{}\" as {}", escape_plantuml(label), id).unwrap();
+    }
+    for (source, target) in view.edges.iter() {
+        writeln!(out, "{} --> {}", source, target).unwrap();
+    }
+    writeln!(out, "@enduml").unwrap();
+
+    out
+}
+
+///
+/// Render two graphs side by side: nodes paired in `matching` (indices into
+/// `a` and `b` respectively) are linked by dashed edges, nodes only in `a`
+/// are colored as removed and nodes only in `b` as added.
+///
+pub fn diff_dot(a: &ASTGraph, b: &ASTGraph, matching: &[(NodeIndex, NodeIndex)]) -> String {
+    let matched_a: HashSet<NodeIndex> = matching.iter().map(|&(x, _)| x).collect();
+    let matched_b: HashSet<NodeIndex> = matching.iter().map(|&(_, y)| y).collect();
+    let mut out = String::new();
+
+    writeln!(out, "digraph \"diff\" {{").unwrap();
+    write_diff_cluster(&mut out, "a", a, &matched_a, "#f4cccc");
+    write_diff_cluster(&mut out, "b", b, &matched_b, "#d9ead3");
+    for &(x, y) in matching.iter() {
+        writeln!(out, "    a_{} -> b_{} [style=dashed, color=gray, arrowhead=none, constraint=false];", node_id(x), node_id(y)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+fn write_diff_cluster(out: &mut String, prefix: &str, graph: &ASTGraph, matched: &HashSet<NodeIndex>, unmatched_color: &str) {
+    writeln!(out, "    subgraph cluster_{} {{", prefix).unwrap();
+    writeln!(out, "        label=\"{}\";", escape_dot(&graph.title())).unwrap();
+    for node in graph.graph.node_indices() {
+        let label = escape_dot(&node_label(&graph.graph[node], None));
+        if matched.contains(&node) {
+            writeln!(out, "        {}_{} [label=\"{}\"];", prefix, node_id(node), label).unwrap();
+        } else {
+            writeln!(out, "        {}_{} [label=\"{}\", style=filled, fillcolor=\"{}\"];", prefix, node_id(node), label, unmatched_color).unwrap();
+        }
+    }
+    for node in graph.graph.node_indices() {
+        for child in graph.children(node) {
+            writeln!(out, "        {}_{} -> {}_{};", prefix, node_id(node), prefix, node_id(child)).unwrap();
+        }
+    }
+    writeln!(out, "    }}").unwrap();
+}
+
+///
+/// File name for a graph titled `title`: characters other than ASCII
+/// letters, digits, `-`, `_` and `.` become `_`, leading dots are dropped
+/// (no hidden files, no `..`) and the result is cut to 100 bytes. Empty if
+/// nothing is left.
+///
+pub fn sanitize_file_name(title: &str) -> String {
+    let name: String = title.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    let mut name = name.trim_start_matches('.').to_string();
+    name.truncate(MAX_FILE_NAME_BYTES);
+    name
+}
+
+const MAX_FILE_NAME_BYTES: usize = 100;
+
+///
+/// Write every graph to its own DOT file in `dir` (created if needed),
+/// named after its title. Untitled graphs are named `graph_<n>` by their
+/// position and repeated names get a `_<n>` suffix, so no file is
+/// overwritten. Returns the manifest of what was written, in the order of
+/// `subgraphs`, which the `manifest` feature also saves in `dir`.
+///
+pub fn write_all_dot<P: AsRef<Path>>(subgraphs: &[ASTGraph], dir: P, options: &ExportOptions) -> Result<Manifest, GraphError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let mut manifest = Manifest::new();
+    for (position, graph) in subgraphs.iter().enumerate() {
+        let file = manifest.file_name_for(graph, position, "dot");
+        manifest.write_artifact(dir, file, graph, to_dot(graph, options).as_bytes())?;
+    }
+    #[cfg(feature="manifest")]
+    manifest.save(dir)?;
+    Ok(manifest)
+}
+
+///
+/// Vocabulary used by `to_turtle`: every node is a `tg:Node` with `tg:kindId`,
+/// `tg:treeSitterId`, byte/point range properties, a `tg:childIndex` within
+/// its parent and `tg:child` links to its children.
+///
+pub const ONTOLOGY: &str = "https://github.com/jcarlson23/tree-graph/ontology#";
+
+#[derive(Debug,Clone)]
+pub struct RdfOptions {
+    /// IRI prefix for the node resources, e.g. `https://example.org/repo/main.cpp#`
+    pub namespace: String,
+}
+
+impl Default for RdfOptions {
+    fn default() -> Self {
+        RdfOptions { namespace: "urn:tree-graph:".to_string() }
+    }
+}
+
+///
+/// Render the graph as RDF in Turtle syntax.
+///
+pub fn to_turtle(graph: &ASTGraph, options: &RdfOptions) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "@prefix tg: <{}> .", ONTOLOGY).unwrap();
+    writeln!(out, "@prefix node: <{}> .", options.namespace).unwrap();
+    writeln!(out).unwrap();
+
+    for node in graph.graph.node_indices() {
+        let gnode = &graph.graph[node];
+        let r = &gnode.range;
+        writeln!(out, "node:{} a tg:Node ;", node_id(node)).unwrap();
+        writeln!(out, "    tg:kindId {} ;", gnode.kind_id).unwrap();
+        writeln!(out, "    tg:treeSitterId {} ;", gnode.id).unwrap();
+        writeln!(out, "    tg:startByte {} ;", r.start_byte).unwrap();
+        writeln!(out, "    tg:endByte {} ;", r.end_byte).unwrap();
+        writeln!(out, "    tg:startRow {} ;", r.start_point.row).unwrap();
+        writeln!(out, "    tg:startColumn {} ;", r.start_point.column).unwrap();
+        writeln!(out, "    tg:endRow {} ;", r.end_point.row).unwrap();
+        write!(out, "    tg:endColumn {}", r.end_point.column).unwrap();
+
+        if let Some(parent) = graph.parent(node) {
+            let position = graph.children(parent).iter().position(|&c| c == node).unwrap();
+            write!(out, " ;\n    tg:childIndex {}", position).unwrap();
+        }
+
+        let children = graph.children(node);
+        if !children.is_empty() {
+            let objects: Vec<String> = children.iter().map(|&c| format!("node:{}", node_id(c))).collect();
+            write!(out, " ;\n    tg:child {}", objects.join(", ")).unwrap();
+        }
+        writeln!(out, " .").unwrap();
+    }
+
+    out
+}
@@ -0,0 +1,166 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+
+///
+/// Per-node featurizer for embedding and GNN pipelines. `width` is the number
+/// of values `features` returns for every node.
+///
+pub trait FeatureExtractor {
+    fn name(&self) -> &str;
+
+    fn width(&self) -> usize;
+
+    fn features(&self, graph: &ASTGraph, node: NodeIndex) -> Vec<f32>;
+}
+
+///
+/// One-hot encoding of the node kind over a fixed list of kind ids; kinds
+/// not in the list get all zeros.
+///
+#[derive(Debug,Clone)]
+pub struct KindOneHot {
+    pub kinds: Vec<u16>,
+}
+
+impl FeatureExtractor for KindOneHot {
+    fn name(&self) -> &str {
+        "kind"
+    }
+
+    fn width(&self) -> usize {
+        self.kinds.len()
+    }
+
+    fn features(&self, graph: &ASTGraph, node: NodeIndex) -> Vec<f32> {
+        let kind_id = graph.graph[node].kind_id;
+        self.kinds.iter().map(|&kind| if kind == kind_id { 1.0 } else { 0.0 }).collect()
+    }
+}
+
+///
+/// Depth below the root, number of children and byte length of the node.
+///
+#[derive(Debug,Clone,Copy,Default)]
+pub struct Shape;
+
+impl FeatureExtractor for Shape {
+    fn name(&self) -> &str {
+        "shape"
+    }
+
+    fn width(&self) -> usize {
+        3
+    }
+
+    fn features(&self, graph: &ASTGraph, node: NodeIndex) -> Vec<f32> {
+        let mut depth = 0;
+        let mut current = node;
+        while let Some(parent) = graph.parent(current) {
+            depth += 1;
+            current = parent;
+        }
+        let range = graph.graph[node].range;
+        vec![depth as f32, graph.graph.neighbors(node).count() as f32, (range.end_byte - range.start_byte) as f32]
+    }
+}
+
+///
+/// Features of every node of a graph: row `i` belongs to the node with
+/// index `i`, columns are the extractors' outputs side by side (`columns`
+/// names each block and its width).
+///
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct FeatureMatrix {
+    pub rows: usize,
+    pub width: usize,
+    pub columns: Vec<(String, usize)>,
+    pub values: Vec<f32>,
+}
+
+impl FeatureMatrix {
+    pub fn row(&self, node: NodeIndex) -> Option<&[f32]> {
+        if node.index() >= self.rows {
+            return None;
+        }
+        Some(&self.values[node.index() * self.width..(node.index() + 1) * self.width])
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), GraphError> {
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> Result<FeatureMatrix, GraphError> {
+        let matrix: FeatureMatrix = bincode::deserialize_from(reader)?;
+        if matrix.values.len() != matrix.rows * matrix.width {
+            return Err(GraphError::Encoding(format!("{} values for a {}x{} feature matrix", matrix.values.len(), matrix.rows, matrix.width)));
+        }
+        Ok(matrix)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<FeatureMatrix, GraphError> {
+        FeatureMatrix::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+///
+/// Registered extractors, applied together in one pass over the nodes.
+///
+#[derive(Default)]
+pub struct FeatureSet {
+    extractors: Vec<Box<dyn FeatureExtractor>>,
+}
+
+impl FeatureSet {
+    pub fn new() -> Self {
+        FeatureSet::default()
+    }
+
+    pub fn add<E: FeatureExtractor + 'static>(mut self, extractor: E) -> Self {
+        self.extractors.push(Box::new(extractor));
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.extractors.iter().map(|extractor| extractor.width()).sum()
+    }
+
+    ///
+    /// Feature matrix of `graph`. Fails if an extractor returns a different
+    /// number of values than its `width`.
+    ///
+    pub fn extract(&self, graph: &ASTGraph) -> Result<FeatureMatrix, GraphError> {
+        let width = self.width();
+        let rows = graph.graph.node_count();
+        let mut values = Vec::with_capacity(rows * width);
+        for node in graph.graph.node_indices() {
+            for extractor in self.extractors.iter() {
+                let features = extractor.features(graph, node);
+                if features.len() != extractor.width() {
+                    return Err(GraphError::InvalidNode(format!("extractor {} returned {} features for node {} instead of {}",
+                                                               extractor.name(), features.len(), node.index(), extractor.width())));
+                }
+                values.extend(features);
+            }
+        }
+        Ok(FeatureMatrix {
+            rows: rows,
+            width: width,
+            columns: self.extractors.iter().map(|extractor| (extractor.name().to_string(), extractor.width())).collect(),
+            values: values,
+        })
+    }
+}
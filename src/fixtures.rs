@@ -0,0 +1,196 @@
+//!
+//! Representative sources per language with stats that hold for their
+//! graphs, so downstream crates can write integration tests against stable
+//! inputs (enable the `test-corpus` feature). The C++ and Fortran fixtures
+//! parse with the grammars this crate depends on; others come as source for
+//! crates that bring their own grammar.
+//!
+use std::collections::HashSet;
+use tree_sitter::{Language, Parser};
+
+use crate::ASTGraph;
+use crate::analysis::LanguageKinds;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum FixtureLanguage {
+    Cpp,
+    Fortran,
+    Python,
+}
+
+impl FixtureLanguage {
+    ///
+    /// The grammar, for the languages this crate depends on.
+    ///
+    pub fn grammar(&self) -> Option<Language> {
+        match self {
+            FixtureLanguage::Cpp => Some(tree_sitter_cpp::language()),
+            FixtureLanguage::Fortran => Some(tree_sitter_fortran::language()),
+            FixtureLanguage::Python => None,
+        }
+    }
+}
+
+///
+/// What a fixture's graph is expected to contain: source lines, and the
+/// functions and calls `LanguageKinds` finds in it.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct FixtureStats {
+    pub lines: usize,
+    pub functions: usize,
+    pub calls: usize,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub language: FixtureLanguage,
+    pub source: &'static str,
+    pub stats: FixtureStats,
+}
+
+impl Fixture {
+    ///
+    /// The fixture's graph, titled with its name; `None` without a grammar.
+    ///
+    pub fn graph(&self) -> Option<ASTGraph> {
+        let language = self.language.grammar()?;
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        let tree = parser.parse(self.source, None)?;
+        let mut graph = ASTGraph::new(self.source.to_string());
+        graph.build_from_tree(&tree);
+        graph.set_title(self.name.to_string());
+        Some(graph)
+    }
+
+    ///
+    /// The stats measured on the fixture's graph, to compare with `stats`.
+    ///
+    pub fn measure(&self) -> Option<FixtureStats> {
+        let kinds = LanguageKinds::new(&self.language.grammar()?);
+        let graph = self.graph()?;
+        let count = |kind_ids: &HashSet<u16>| graph.nodes().filter(|(_, gnode)| kind_ids.contains(&gnode.kind_id)).count();
+        Some(FixtureStats { lines: self.source.lines().count(), functions: count(&kinds.functions), calls: count(&kinds.calls) })
+    }
+}
+
+const CPP_FILE_READER: &str = r#"#include <fstream>
+#include <iostream>
+#include <string>
+
+void readFile(const std::string& filePath) {
+    std::ifstream file(filePath);
+
+    if (!file.is_open()) {
+        std::cerr << "Error reading file: Could not open the file." << std::endl;
+        return;
+    }
+
+    std::string line;
+    while (std::getline(file, line)) {
+        std::cout << line << std::endl;
+    }
+
+    file.close();
+}
+
+int main() {
+    std::string filePath;
+
+    // Prompt the user for the file path
+    std::cout << "Enter the file path: ";
+    std::getline(std::cin, filePath);
+
+    readFile(filePath);
+
+    return 0;
+}
+"#;
+
+const CPP_SHAPES: &str = r#"#include <cmath>
+
+class Circle {
+public:
+    explicit Circle(double radius) : radius_(radius) {}
+    double area() const { return M_PI * radius_ * radius_; }
+private:
+    double radius_;
+};
+
+double total_area(const Circle* circles, int count) {
+    double total = 0.0;
+    for (int i = 0; i < count; ++i) {
+        total += circles[i].area();
+    }
+    return total;
+}
+"#;
+
+const FORTRAN_REPORT: &str = r#"program main
+  implicit none
+  integer :: n
+  n = 10
+  call report(n)
+  call report(n + 1)
+contains
+  subroutine report(value)
+    integer, intent(in) :: value
+    print *, value
+  end subroutine report
+end program main
+"#;
+
+const PYTHON_READ_LINES: &str = r#"def read_lines(path):
+    with open(path) as handle:
+        return [line.rstrip() for line in handle]
+
+
+def main():
+    for line in read_lines("input.txt"):
+        print(line)
+
+
+if __name__ == "__main__":
+    main()
+"#;
+
+const FIXTURES: [Fixture; 4] = [
+    Fixture {
+        name: "cpp/file_reader",
+        language: FixtureLanguage::Cpp,
+        source: CPP_FILE_READER,
+        stats: FixtureStats { lines: 31, functions: 2, calls: 5 },
+    },
+    Fixture {
+        name: "cpp/shapes",
+        language: FixtureLanguage::Cpp,
+        source: CPP_SHAPES,
+        stats: FixtureStats { lines: 17, functions: 3, calls: 1 },
+    },
+    Fixture {
+        name: "fortran/report",
+        language: FixtureLanguage::Fortran,
+        source: FORTRAN_REPORT,
+        stats: FixtureStats { lines: 12, functions: 1, calls: 2 },
+    },
+    Fixture {
+        name: "python/read_lines",
+        language: FixtureLanguage::Python,
+        source: PYTHON_READ_LINES,
+        stats: FixtureStats { lines: 12, functions: 2, calls: 5 },
+    },
+];
+
+pub fn fixtures() -> &'static [Fixture] {
+    &FIXTURES
+}
+
+pub fn fixtures_for(language: FixtureLanguage) -> impl Iterator<Item = &'static Fixture> {
+    FIXTURES.iter().filter(move |fixture| fixture.language == language)
+}
+
+pub fn fixture(name: &str) -> Option<&'static Fixture> {
+    FIXTURES.iter().find(|fixture| fixture.name == name)
+}
@@ -0,0 +1,261 @@
+use petgraph::graph::NodeIndex;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::ASTGraph;
+use crate::geometry::{GNode, GPoint, GRange};
+use crate::hashing::Fnv64;
+use crate::interner::Interner;
+use crate::source::SourceStart;
+
+///
+/// A position relative to an anchor point: bytes and rows are offsets, the
+/// column is one only while on the anchor's row (as `delta` shifts nodes).
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub struct RelativePoint {
+    pub bytes: usize,
+    pub rows: usize,
+    pub column: usize,
+}
+
+impl RelativePoint {
+    fn between(anchor_byte: usize, anchor: GPoint, byte: usize, point: GPoint) -> Self {
+        RelativePoint {
+            bytes: byte - anchor_byte,
+            rows: point.row - anchor.row,
+            column: if point.row == anchor.row { point.column - anchor.column } else { point.column },
+        }
+    }
+
+    fn apply(&self, anchor_byte: usize, anchor: GPoint) -> (usize, GPoint) {
+        let column = if self.rows == 0 { anchor.column + self.column } else { self.column };
+        (anchor_byte + self.bytes, GPoint { row: anchor.row + self.rows, column: column })
+    }
+}
+
+///
+/// One hash-consed subtree: everything about it that doesn't depend on
+/// where it sits, so every occurrence of the same code shares it.
+///
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct ForestNode {
+    pub kind_id: u16,
+    pub is_named: bool,
+    /// end of the node relative to its start
+    pub extent: RelativePoint,
+    /// children in source order, each with its start relative to this node's
+    pub children: Vec<(u32, RelativePoint)>,
+    /// interned text of leaves (when the graph had its source)
+    pub text: Option<u32>,
+}
+
+struct ForestTree {
+    title: String,
+    roots: Vec<(u32, RelativePoint)>,
+    source: Option<String>,
+    source_start: SourceStart,
+    node_count: usize,
+}
+
+///
+/// Many graphs stored with identical subtrees (same kinds, shape and leaf
+/// text) kept once, for project-scale corpora full of repeated includes and
+/// boilerplate. Graphs come back out with `get`; tree-sitter ids are not
+/// kept, so rebuilt nodes are numbered in preorder instead.
+///
+#[derive(Default)]
+pub struct Forest {
+    nodes: Vec<ForestNode>,
+    buckets: HashMap<u64, Vec<u32>>,
+    texts: Interner,
+    trees: BTreeMap<String, ForestTree>,
+}
+
+fn hash_node(node: &ForestNode) -> u64 {
+    let mut hasher = Fnv64::new();
+    hasher.write(&node.kind_id.to_le_bytes());
+    hasher.write(&[node.is_named as u8]);
+    for value in [node.extent.bytes, node.extent.rows, node.extent.column] {
+        hasher.write_u64(value as u64);
+    }
+    for (child, start) in node.children.iter() {
+        hasher.write_u64(*child as u64);
+        for value in [start.bytes, start.rows, start.column] {
+            hasher.write_u64(value as u64);
+        }
+    }
+    hasher.write_u64(node.text.map(|text| text as u64 + 1).unwrap_or(0));
+    hasher.finish()
+}
+
+impl Forest {
+    pub fn new() -> Self {
+        Forest::default()
+    }
+
+    fn intern_node(&mut self, node: ForestNode) -> u32 {
+        let hash = hash_node(&node);
+        let bucket = self.buckets.entry(hash).or_default();
+        if let Some(&id) = bucket.iter().find(|&&id| self.nodes[id as usize] == node) {
+            return id;
+        }
+        let id = self.nodes.len() as u32;
+        self.nodes.push(node);
+        bucket.push(id);
+        id
+    }
+
+    ///
+    /// Add (or replace) the graph stored under `name`, sharing every subtree
+    /// already in the forest. Returns how many new forest nodes it took.
+    /// Subtrees only a replaced graph used stay in the forest.
+    ///
+    pub fn insert(&mut self, name: &str, graph: &ASTGraph) -> usize {
+        let before = self.nodes.len();
+        let mut ids: HashMap<NodeIndex, u32> = HashMap::with_capacity(graph.graph.node_count());
+        // children before parents
+        let mut stack: Vec<(NodeIndex, bool)> = graph.roots().into_iter().rev().map(|root| (root, false)).collect();
+        while let Some((node, expanded)) = stack.pop() {
+            let children = graph.children(node);
+            if !expanded {
+                stack.push((node, true));
+                stack.extend(children.into_iter().rev().map(|child| (child, false)));
+                continue;
+            }
+            let range = graph.graph[node].range;
+            let text = if children.is_empty() {
+                graph.range_text(range.start_byte, range.end_byte).map(|text| self.texts.intern(text))
+            } else {
+                None
+            };
+            let forest_node = ForestNode {
+                kind_id: graph.graph[node].kind_id,
+                is_named: graph.graph[node].is_named,
+                extent: RelativePoint::between(range.start_byte, range.start_point, range.end_byte, range.end_point),
+                children: children.iter()
+                    .map(|child| {
+                        let start = graph.graph[*child].range;
+                        (ids[child], RelativePoint::between(range.start_byte, range.start_point, start.start_byte, start.start_point))
+                    })
+                    .collect(),
+                text: text,
+            };
+            let id = self.intern_node(forest_node);
+            ids.insert(node, id);
+        }
+
+        let origin = GPoint { row: 0, column: 0 };
+        let roots = graph.roots().into_iter()
+            .map(|root| {
+                let range = graph.graph[root].range;
+                (ids[&root], RelativePoint::between(0, origin, range.start_byte, range.start_point))
+            })
+            .collect();
+        self.trees.insert(name.to_string(), ForestTree {
+            title: graph.title(),
+            roots: roots,
+            source: graph.source().map(|source| source.to_string()),
+            source_start: graph.source_start(),
+            node_count: graph.graph.node_count(),
+        });
+        self.nodes.len() - before
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.trees.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.trees.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    ///
+    /// Distinct subtrees stored.
+    ///
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    ///
+    /// Nodes of all graphs together, as if stored separately.
+    ///
+    pub fn expanded_node_count(&self) -> usize {
+        self.trees.values().map(|tree| tree.node_count).sum()
+    }
+
+    pub fn node(&self, id: u32) -> Option<&ForestNode> {
+        self.nodes.get(id as usize)
+    }
+
+    pub fn text(&self, id: u32) -> Option<&str> {
+        self.texts.get(id)
+    }
+
+    ///
+    /// Source of the graph stored under `name`, if it had one.
+    ///
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.trees.get(name).and_then(|tree| tree.source.as_deref())
+    }
+
+    ///
+    /// Where the source of the graph stored under `name` starts in its file.
+    ///
+    pub fn source_start(&self, name: &str) -> Option<SourceStart> {
+        self.trees.get(name).map(|tree| tree.source_start)
+    }
+
+    ///
+    /// Roots of the graph stored under `name`, with their starts.
+    ///
+    pub fn roots(&self, name: &str) -> Option<&[(u32, RelativePoint)]> {
+        self.trees.get(name).map(|tree| tree.roots.as_slice())
+    }
+
+    ///
+    /// Rebuild the graph stored under `name`.
+    ///
+    pub fn get(&self, name: &str) -> Option<ASTGraph> {
+        let tree = self.trees.get(name)?;
+        let mut graph = ASTGraph::new(String::new());
+        if let Some(source) = tree.source.clone() {
+            graph.set_source_at(source, tree.source_start);
+        }
+        graph.set_title(tree.title.clone());
+        let origin = GPoint { row: 0, column: 0 };
+        let mut stack: Vec<(u32, usize, GPoint, Option<NodeIndex>)> = tree.roots.iter().rev()
+            .map(|&(root, start)| {
+                let (byte, point) = start.apply(0, origin);
+                (root, byte, point, None)
+            })
+            .collect();
+        while let Some((id, start_byte, start_point, parent)) = stack.pop() {
+            let forest_node = &self.nodes[id as usize];
+            let (end_byte, end_point) = forest_node.extent.apply(start_byte, start_point);
+            let gnode = GNode {
+                id: graph.graph.node_count(),
+                kind_id: forest_node.kind_id,
+                range: GRange { start_byte: start_byte, end_byte: end_byte, start_point: start_point, end_point: end_point },
+                is_named: forest_node.is_named,
+                source_hash: 0,
+            };
+            let node = graph.add_gnode(gnode);
+            if let Some(parent) = parent {
+                graph.graph.add_edge(parent, node, ());
+            }
+            for &(child, start) in forest_node.children.iter().rev() {
+                let (child_byte, child_point) = start.apply(start_byte, start_point);
+                stack.push((child, child_byte, child_point, Some(node)));
+            }
+        }
+        Some(graph)
+    }
+}
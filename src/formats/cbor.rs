@@ -0,0 +1,67 @@
+use ciborium::tag::Required;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+use crate::geometry::{Edge, GNode, GRange};
+
+///
+/// CBOR tag wrapped around every node range. The number is taken from the
+/// first-come-first-served space and is not IANA registered; decoders that
+/// don't know it can ignore the tag and read the plain map underneath.
+///
+pub const RANGE_TAG: u64 = 52_250;
+
+#[derive(Serialize,Deserialize)]
+struct CborNode {
+    id: usize,
+    kind_id: u16,
+    range: Required<GRange, RANGE_TAG>,
+    is_named: bool,
+    source_hash: u32,
+}
+
+#[derive(Serialize,Deserialize)]
+struct CborGraph {
+    title: String,
+    metadata: BTreeMap<String, String>,
+    labels: Vec<String>,
+    nodes: Vec<CborNode>,
+    edges: Vec<Edge>,
+}
+
+impl ASTGraph {
+    pub fn to_cbor(&self) -> Result<Vec<u8>, GraphError> {
+        let serializable_graph = self.to_serializable();
+        let cbor_graph = CborGraph {
+            title: serializable_graph.title,
+            metadata: serializable_graph.metadata,
+            labels: serializable_graph.labels,
+            nodes: serializable_graph.nodes.iter()
+                .map(|n| CborNode { id: n.id, kind_id: n.kind_id, range: Required(n.range), is_named: n.is_named, source_hash: n.source_hash })
+                .collect(),
+            edges: serializable_graph.edges,
+        };
+
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(&cbor_graph, &mut buffer)
+            .map_err(|err| GraphError::Encoding(err.to_string()))?;
+        Ok(buffer)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<ASTGraph, GraphError> {
+        let cbor_graph: CborGraph = ciborium::de::from_reader(bytes)
+            .map_err(|err| GraphError::Encoding(err.to_string()))?;
+        let serializable_graph = SerializableGraph {
+            title: cbor_graph.title,
+            metadata: cbor_graph.metadata,
+            labels: cbor_graph.labels,
+            nodes: cbor_graph.nodes.into_iter()
+                .map(|n| GNode { id: n.id, kind_id: n.kind_id, range: n.range.0, is_named: n.is_named, source_hash: n.source_hash })
+                .collect(),
+            edges: cbor_graph.edges,
+        };
+        ASTGraph::try_from_serializable(serializable_graph)
+    }
+}
@@ -0,0 +1,123 @@
+//!
+//! FlatBuffers writer for `schema/tree_graph.fbs`. Only the writing side lives
+//! here -- readers (Python, C++, ...) use `flatc`-generated code and access the
+//! buffer in place. The two structs below are laid out byte-for-byte the way
+//! `flatc` lays out the schema structs, so keep them in sync.
+//!
+
+use flatbuffers::{FlatBufferBuilder, Push, PushAlignment};
+
+use crate::ASTGraph;
+
+pub const FILE_IDENTIFIER: &str = "TGFB";
+
+const NODE_SIZE: usize = 64;
+const EDGE_SIZE: usize = 8;
+
+// vtable slots of the `Graph` table fields, in declaration order
+const GRAPH_TITLE: u16 = 4;
+const GRAPH_NODES: u16 = 6;
+const GRAPH_EDGES: u16 = 8;
+const GRAPH_METADATA: u16 = 10;
+const GRAPH_LABELS: u16 = 12;
+// and of the `Entry` table fields
+const ENTRY_KEY: u16 = 4;
+const ENTRY_VALUE: u16 = 6;
+
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+struct FbNode([u8; NODE_SIZE]);
+
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+struct FbEdge([u8; EDGE_SIZE]);
+
+impl Push for FbNode {
+    type Output = FbNode;
+
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        dst.copy_from_slice(&self.0);
+    }
+
+    fn alignment() -> PushAlignment {
+        PushAlignment::new(8)
+    }
+}
+
+impl Push for FbEdge {
+    type Output = FbEdge;
+
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        dst.copy_from_slice(&self.0);
+    }
+
+    fn alignment() -> PushAlignment {
+        PushAlignment::new(4)
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Write the graph as a finished FlatBuffer (file identifier "TGFB").
+    /// Panics if the graph has more than `u32::MAX` nodes.
+    ///
+    pub fn to_flatbuffer(&self) -> Vec<u8> {
+        let serializable_graph = self.to_serializable();
+
+        let nodes: Vec<FbNode> = serializable_graph.nodes.iter().map(|n| {
+            let mut bytes = [0u8; NODE_SIZE];
+            let fields = [
+                n.id,
+                n.range.start_byte,
+                n.range.end_byte,
+                n.range.start_point.row,
+                n.range.start_point.column,
+                n.range.end_point.row,
+                n.range.end_point.column,
+            ];
+            for (i, value) in fields.iter().enumerate() {
+                bytes[i * 8..(i + 1) * 8].copy_from_slice(&(*value as u64).to_le_bytes());
+            }
+            bytes[56..58].copy_from_slice(&n.kind_id.to_le_bytes());
+            bytes[58] = n.is_named as u8;
+            bytes[60..64].copy_from_slice(&n.source_hash.to_le_bytes());
+            FbNode(bytes)
+        }).collect();
+
+        let edges: Vec<FbEdge> = serializable_graph.edges.iter().map(|e| {
+            let source = u32::try_from(e.source.index()).expect("graph too large for the flatbuffer layout");
+            let target = u32::try_from(e.target.index()).expect("graph too large for the flatbuffer layout");
+            let mut bytes = [0u8; EDGE_SIZE];
+            bytes[0..4].copy_from_slice(&source.to_le_bytes());
+            bytes[4..8].copy_from_slice(&target.to_le_bytes());
+            FbEdge(bytes)
+        }).collect();
+
+        let mut builder = FlatBufferBuilder::with_capacity(nodes.len() * NODE_SIZE + edges.len() * EDGE_SIZE + 64);
+        let title = builder.create_string(&serializable_graph.title);
+        let nodes = builder.create_vector(&nodes);
+        let edges = builder.create_vector(&edges);
+        let entries: Vec<_> = serializable_graph.metadata.iter().map(|(key, value)| {
+            let key = builder.create_string(key);
+            let value = builder.create_string(value);
+            let entry = builder.start_table();
+            builder.push_slot_always(ENTRY_KEY, key);
+            builder.push_slot_always(ENTRY_VALUE, value);
+            builder.end_table(entry)
+        }).collect();
+        let metadata = builder.create_vector(&entries);
+        let labels: Vec<_> = serializable_graph.labels.iter().map(|label| builder.create_string(label)).collect();
+        let labels = builder.create_vector(&labels);
+
+        let table = builder.start_table();
+        builder.push_slot_always(GRAPH_TITLE, title);
+        builder.push_slot_always(GRAPH_NODES, nodes);
+        builder.push_slot_always(GRAPH_EDGES, edges);
+        builder.push_slot_always(GRAPH_METADATA, metadata);
+        builder.push_slot_always(GRAPH_LABELS, labels);
+        let root = builder.end_table(table);
+
+        builder.finish(root, Some(FILE_IDENTIFIER));
+        builder.finished_data().to_vec()
+    }
+}
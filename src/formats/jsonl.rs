@@ -0,0 +1,108 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::geometry::{GNode, GRange};
+
+///
+/// One line of the JSON Lines stream. A stream is a `header` line followed by
+/// `node` lines in index order and then `edge` lines between those indices.
+///
+#[derive(Serialize,Deserialize,Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JsonlRecord {
+    Header {
+        title: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        labels: Vec<String>,
+    },
+    Node {
+        index: usize,
+        id: usize,
+        kind_id: u16,
+        range: GRange,
+        // streams written before the flag existed read as all named
+        #[serde(default = "named_by_default")]
+        is_named: bool,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        source_hash: u32,
+    },
+    Edge { source: usize, target: usize },
+}
+
+fn named_by_default() -> bool {
+    true
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl ASTGraph {
+    ///
+    /// Write the graph one record per line without building a serialized copy
+    /// in memory first.
+    ///
+    pub fn stream_jsonl<W: Write>(&self, mut writer: W) -> Result<(), GraphError> {
+        let mut write_record = |record: &JsonlRecord| -> Result<(), GraphError> {
+            serde_json::to_writer(&mut writer, record).map_err(|err| GraphError::Encoding(err.to_string()))?;
+            writer.write_all(b"\n")?;
+            Ok(())
+        };
+
+        write_record(&JsonlRecord::Header { title: self.title(), labels: self.labels().to_vec() })?;
+        for node in self.graph.node_indices() {
+            let gnode = &self.graph[node];
+            write_record(&JsonlRecord::Node { index: node.index(), id: gnode.id, kind_id: gnode.kind_id, range: gnode.range, is_named: gnode.is_named, source_hash: gnode.source_hash })?;
+        }
+        for edge in self.graph.edge_indices() {
+            let (source, target) = self.graph.edge_endpoints(edge).unwrap();
+            write_record(&JsonlRecord::Edge { source: source.index(), target: target.index() })?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Rebuild a graph from a JSON Lines stream, adding nodes and edges as the
+    /// lines are read. The resulting graph has no source attached.
+    ///
+    pub fn read_jsonl<R: BufRead>(reader: R) -> Result<ASTGraph, GraphError> {
+        let mut ast_graph = ASTGraph::new("".to_string());
+        ast_graph.source = None;
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line_number = number + 1;
+            let record: JsonlRecord = serde_json::from_str(&line)
+                .map_err(|err| GraphError::Parse { line: line_number, message: err.to_string() })?;
+
+            match record {
+                JsonlRecord::Header { title, labels } => {
+                    ast_graph.set_title(title);
+                    ast_graph.set_labels(labels);
+                },
+                JsonlRecord::Node { index, id, kind_id, range, is_named, source_hash } => {
+                    if index != ast_graph.graph.node_count() {
+                        return Err(GraphError::Parse { line: line_number, message: format!("node {} out of order", index) });
+                    }
+                    let node_index = ast_graph.graph.add_node(GNode { id: id, kind_id: kind_id, range: range, is_named: is_named, source_hash: source_hash });
+                    ast_graph.node_map.insert(node_index, id);
+                },
+                JsonlRecord::Edge { source, target } => {
+                    let node_count = ast_graph.graph.node_count();
+                    if source >= node_count || target >= node_count {
+                        return Err(GraphError::Parse { line: line_number, message: format!("edge {} -> {} references a missing node", source, target) });
+                    }
+                    ast_graph.add_edge(NodeIndex::new(source), NodeIndex::new(target));
+                },
+            }
+        }
+
+        Ok(ast_graph)
+    }
+}
@@ -0,0 +1,19 @@
+//!
+//! Alternative on-disk/wire encodings of `SerializableGraph`, each behind its
+//! own feature so the default build only pulls in bincode.
+//!
+
+#[cfg(feature="cbor")]
+pub mod cbor;
+
+#[cfg(feature="flatbuffers")]
+pub mod flatbuffers;
+
+#[cfg(feature="jsonl")]
+pub mod jsonl;
+
+#[cfg(feature="msgpack")]
+pub mod msgpack;
+
+#[cfg(feature="protobuf")]
+pub mod protobuf;
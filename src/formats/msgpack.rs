@@ -0,0 +1,19 @@
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+
+impl ASTGraph {
+    ///
+    /// Encode as MessagePack. Structs are written as maps keyed by field
+    /// name so non-Rust readers (e.g. `@msgpack/msgpack`) get plain objects.
+    ///
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, GraphError> {
+        rmp_serde::to_vec_named(&self.to_serializable())
+            .map_err(|err| GraphError::Encoding(err.to_string()))
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<ASTGraph, GraphError> {
+        let serializable_graph: SerializableGraph = rmp_serde::from_slice(bytes)
+            .map_err(|err| GraphError::Encoding(err.to_string()))?;
+        ASTGraph::try_from_serializable(serializable_graph)
+    }
+}
@@ -0,0 +1,143 @@
+//!
+//! Protobuf encoding matching `proto/tree_graph.proto`. The message types are
+//! written out with prost derives rather than generated, so building the crate
+//! doesn't need `protoc`; keep the two in sync when either changes.
+//!
+
+use petgraph::graph::NodeIndex;
+use prost::Message;
+use std::collections::BTreeMap;
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+use crate::geometry::{Edge, GNode, GPoint, GRange};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Point {
+    #[prost(uint64, tag = "1")]
+    pub row: u64,
+    #[prost(uint64, tag = "2")]
+    pub column: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Range {
+    #[prost(uint64, tag = "1")]
+    pub start_byte: u64,
+    #[prost(uint64, tag = "2")]
+    pub end_byte: u64,
+    #[prost(message, optional, tag = "3")]
+    pub start_point: Option<Point>,
+    #[prost(message, optional, tag = "4")]
+    pub end_point: Option<Point>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Node {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(uint32, tag = "2")]
+    pub kind_id: u32,
+    #[prost(message, optional, tag = "3")]
+    pub range: Option<Range>,
+    #[prost(bool, tag = "4")]
+    pub is_named: bool,
+    #[prost(uint32, tag = "5")]
+    pub source_hash: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ProtoEdge {
+    #[prost(uint64, tag = "1")]
+    pub source: u64,
+    #[prost(uint64, tag = "2")]
+    pub target: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Graph {
+    #[prost(string, tag = "1")]
+    pub title: String,
+    #[prost(message, repeated, tag = "2")]
+    pub nodes: Vec<Node>,
+    #[prost(message, repeated, tag = "3")]
+    pub edges: Vec<ProtoEdge>,
+    #[prost(btree_map = "string, string", tag = "4")]
+    pub metadata: BTreeMap<String, String>,
+    #[prost(string, repeated, tag = "5")]
+    pub labels: Vec<String>,
+}
+
+impl From<GPoint> for Point {
+    fn from(point: GPoint) -> Point {
+        Point { row: point.row as u64, column: point.column as u64 }
+    }
+}
+
+impl From<Point> for GPoint {
+    fn from(point: Point) -> GPoint {
+        GPoint { row: point.row as usize, column: point.column as usize }
+    }
+}
+
+impl From<GRange> for Range {
+    fn from(range: GRange) -> Range {
+        Range {
+            start_byte: range.start_byte as u64,
+            end_byte: range.end_byte as u64,
+            start_point: Some(range.start_point.into()),
+            end_point: Some(range.end_point.into()),
+        }
+    }
+}
+
+impl From<Range> for GRange {
+    fn from(range: Range) -> GRange {
+        GRange {
+            start_byte: range.start_byte as usize,
+            end_byte: range.end_byte as usize,
+            start_point: range.start_point.unwrap_or_default().into(),
+            end_point: range.end_point.unwrap_or_default().into(),
+        }
+    }
+}
+
+impl ASTGraph {
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        let serializable_graph = self.to_serializable();
+        let graph = Graph {
+            title: serializable_graph.title,
+            nodes: serializable_graph.nodes.iter()
+                .map(|n| Node { id: n.id as u64, kind_id: n.kind_id as u32, range: Some(n.range.into()), is_named: n.is_named, source_hash: n.source_hash })
+                .collect(),
+            edges: serializable_graph.edges.iter()
+                .map(|e| ProtoEdge { source: e.source.index() as u64, target: e.target.index() as u64 })
+                .collect(),
+            metadata: serializable_graph.metadata,
+            labels: serializable_graph.labels,
+        };
+        graph.encode_to_vec()
+    }
+
+    pub fn from_protobuf(bytes: &[u8]) -> Result<ASTGraph, GraphError> {
+        let graph = Graph::decode(bytes).map_err(|err| GraphError::Encoding(err.to_string()))?;
+
+        let mut nodes = Vec::with_capacity(graph.nodes.len());
+        for node in graph.nodes {
+            let kind_id = u16::try_from(node.kind_id)
+                .map_err(|_| GraphError::Encoding(format!("kind_id {} out of range", node.kind_id)))?;
+            nodes.push(GNode { id: node.id as usize, kind_id: kind_id, range: node.range.unwrap_or_default().into(), is_named: node.is_named, source_hash: node.source_hash });
+        }
+
+        let node_count = nodes.len() as u64;
+        let mut edges = Vec::with_capacity(graph.edges.len());
+        for edge in graph.edges {
+            if edge.source >= node_count || edge.target >= node_count {
+                return Err(GraphError::Encoding(format!("edge {} -> {} references a missing node", edge.source, edge.target)));
+            }
+            edges.push(Edge { source: NodeIndex::new(edge.source as usize), target: NodeIndex::new(edge.target as usize) });
+        }
+
+        Ok(ASTGraph::from_serializable(SerializableGraph { title: graph.title, metadata: graph.metadata, labels: graph.labels, nodes, edges }))
+    }
+}
@@ -26,6 +26,15 @@ impl From<tree_sitter::Point> for GPoint {
    }
 }
 
+impl From<GPoint> for tree_sitter::Point {
+   fn from(point:GPoint) -> tree_sitter::Point {
+       tree_sitter::Point {
+           row: point.row,
+           column: point.column,
+       }
+   }
+}
+
 impl From<tree_sitter::Range> for GRange {
    fn from(value:tree_sitter::Range) -> GRange {
        GRange {
@@ -37,11 +46,15 @@ impl From<tree_sitter::Range> for GRange {
    }
 }
 
-#[derive(Serialize,Deserialize,Debug,Copy,Clone)]
+#[derive(Serialize,Deserialize,Debug,Copy,Clone,PartialEq)]
 pub struct GNode { // G-Node to differentiate from a Treesitter node
     pub id: usize,
     pub kind_id: u16,
     pub range: GRange,
+    // false for anonymous nodes (punctuation, keywords)
+    pub is_named: bool,
+    // short hash of the node's source text, 0 when not recorded (see checksum)
+    pub source_hash: u32,
 }
 
 // #[cfg(feature="informational")]
@@ -75,3 +88,38 @@ where
     let index = usize::deserialize(d)?;
     Ok(NodeIndex::new(index))
 }
+
+///
+/// Byte offset -> (row, column) lookup for a source text, with columns in
+/// bytes as tree-sitter counts them.
+///
+#[derive(Debug,Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (offset, byte) in source.bytes().enumerate() {
+            if byte == b'\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+        LineIndex { line_starts }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Byte offset where `row` starts, if the source has that many lines.
+    pub fn line_start(&self, row: usize) -> Option<usize> {
+        self.line_starts.get(row).cloned()
+    }
+
+    pub fn point(&self, byte: usize) -> GPoint {
+        let row = self.line_starts.partition_point(|&start| start <= byte) - 1;
+        GPoint { row: row, column: byte - self.line_starts[row] }
+    }
+}
@@ -0,0 +1,236 @@
+//!
+//! A `GraphService` as the tonic gRPC service of
+//! `proto/graph_service.proto`, for running the crate as a sidecar. As in
+//! `formats::protobuf` the messages and the server glue are written out
+//! rather than generated, so building doesn't need `protoc`; keep them in
+//! sync with the proto file.
+//!
+//! ```ignore
+//! let server = GraphGrpcServer::new(GraphService::new(LanguageRegistry::with_builtin()));
+//! tonic::transport::Server::builder().add_service(server).serve(addr).await?;
+//! ```
+//!
+use prost::Message;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use tonic::codegen::{empty_body, http, Body, BoxFuture, Service, StdError};
+use tonic::{Code, Status};
+
+use crate::error::GraphError;
+use crate::formats::protobuf::Range;
+use crate::service::{self, GraphService};
+
+pub const SERVICE_NAME: &str = "tree_graph.GraphService";
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NodeInfo {
+    #[prost(uint64, tag = "1")]
+    pub index: u64,
+    #[prost(string, tag = "2")]
+    pub kind: String,
+    #[prost(uint32, tag = "3")]
+    pub kind_id: u32,
+    #[prost(message, optional, tag = "4")]
+    pub range: Option<Range>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BuildGraphRequest {
+    #[prost(string, tag = "1")]
+    pub uri: String,
+    #[prost(string, tag = "2")]
+    pub language: String,
+    #[prost(string, tag = "3")]
+    pub source: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BuildGraphResponse {
+    #[prost(uint64, tag = "1")]
+    pub nodes: u64,
+    #[prost(uint64, tag = "2")]
+    pub edges: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExtractSubgraphsRequest {
+    #[prost(string, tag = "1")]
+    pub uri: String,
+    #[prost(string, tag = "2")]
+    pub kind: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Subgraph {
+    #[prost(message, optional, tag = "1")]
+    pub root: Option<NodeInfo>,
+    #[prost(uint64, tag = "2")]
+    pub nodes: u64,
+    #[prost(string, tag = "3")]
+    pub source: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExtractSubgraphsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub subgraphs: Vec<Subgraph>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct QueryRequest {
+    #[prost(string, tag = "1")]
+    pub uri: String,
+    #[prost(string, tag = "2")]
+    pub kind: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct QueryResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub nodes: Vec<NodeInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GetSubtreeSourceRequest {
+    #[prost(string, tag = "1")]
+    pub uri: String,
+    #[prost(uint64, tag = "2")]
+    pub node: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GetSubtreeSourceResponse {
+    #[prost(string, tag = "1")]
+    pub source: String,
+}
+
+impl From<service::NodeInfo> for NodeInfo {
+    fn from(node: service::NodeInfo) -> NodeInfo {
+        NodeInfo { index: node.index as u64, kind: node.kind, kind_id: node.kind_id as u32, range: Some(node.range.into()) }
+    }
+}
+
+fn status(err: GraphError) -> Status {
+    let code = match err {
+        GraphError::Query(_) | GraphError::InvalidNode(_) | GraphError::Parse { .. } => Code::InvalidArgument,
+        GraphError::LimitExceeded(_) => Code::ResourceExhausted,
+        GraphError::Cancelled => Code::Cancelled,
+        GraphError::SourceUnavailable(_) => Code::FailedPrecondition,
+        _ => Code::Internal,
+    };
+    Status::new(code, err.to_string())
+}
+
+///
+/// The gRPC server. Clones share one `GraphService`; builds take it
+/// exclusively, the other calls run concurrently.
+///
+#[derive(Clone)]
+pub struct GraphGrpcServer {
+    service: Arc<RwLock<GraphService>>,
+}
+
+impl GraphGrpcServer {
+    pub fn new(service: GraphService) -> Self {
+        GraphGrpcServer { service: Arc::new(RwLock::new(service)) }
+    }
+
+    pub fn service(&self) -> Arc<RwLock<GraphService>> {
+        self.service.clone()
+    }
+
+    pub fn build_graph(&self, request: BuildGraphRequest) -> Result<BuildGraphResponse, Status> {
+        let mut service = self.service.write().map_err(|_| Status::internal("graph service lock poisoned"))?;
+        let summary = service.build(&request.uri, &request.language, request.source).map_err(status)?;
+        Ok(BuildGraphResponse { nodes: summary.nodes as u64, edges: summary.edges as u64 })
+    }
+
+    pub fn extract_subgraphs(&self, request: ExtractSubgraphsRequest) -> Result<ExtractSubgraphsResponse, Status> {
+        let service = self.service.read().map_err(|_| Status::internal("graph service lock poisoned"))?;
+        let subgraphs = service.extract(&request.uri, &request.kind).map_err(status)?;
+        Ok(ExtractSubgraphsResponse {
+            subgraphs: subgraphs.into_iter()
+                .map(|subgraph| Subgraph { root: Some(subgraph.root.into()), nodes: subgraph.nodes as u64, source: subgraph.source })
+                .collect(),
+        })
+    }
+
+    pub fn query(&self, request: QueryRequest) -> Result<QueryResponse, Status> {
+        let service = self.service.read().map_err(|_| Status::internal("graph service lock poisoned"))?;
+        let nodes = service.query(&request.uri, &request.kind).map_err(status)?;
+        Ok(QueryResponse { nodes: nodes.into_iter().map(NodeInfo::from).collect() })
+    }
+
+    pub fn get_subtree_source(&self, request: GetSubtreeSourceRequest) -> Result<GetSubtreeSourceResponse, Status> {
+        let service = self.service.read().map_err(|_| Status::internal("graph service lock poisoned"))?;
+        let source = service.subtree_source(&request.uri, request.node as usize).map_err(status)?;
+        Ok(GetSubtreeSourceResponse { source: source })
+    }
+}
+
+// one RPC: the handler above it dispatches to
+struct Unary<Req, Resp> {
+    server: GraphGrpcServer,
+    handler: fn(&GraphGrpcServer, Req) -> Result<Resp, Status>,
+}
+
+impl<Req: Send + 'static, Resp: Send + 'static> tonic::server::UnaryService<Req> for Unary<Req, Resp> {
+    type Response = Resp;
+    type Future = BoxFuture<tonic::Response<Resp>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Req>) -> Self::Future {
+        let response = (self.handler)(&self.server, request.into_inner()).map(tonic::Response::new);
+        Box::pin(std::future::ready(response))
+    }
+}
+
+impl GraphGrpcServer {
+    fn unary<Req, Resp, B>(&self, handler: fn(&GraphGrpcServer, Req) -> Result<Resp, Status>, request: http::Request<B>)
+        -> BoxFuture<http::Response<tonic::body::BoxBody>, std::convert::Infallible>
+    where
+        Req: Message + Default + Send + 'static,
+        Resp: Message + Send + 'static,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        let unary = Unary { server: self.clone(), handler: handler };
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+            Ok(grpc.unary(unary, request).await)
+        })
+    }
+}
+
+impl<B> Service<http::Request<B>> for GraphGrpcServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        match request.uri().path() {
+            "/tree_graph.GraphService/BuildGraph" => self.unary(GraphGrpcServer::build_graph, request),
+            "/tree_graph.GraphService/ExtractSubgraphs" => self.unary(GraphGrpcServer::extract_subgraphs, request),
+            "/tree_graph.GraphService/Query" => self.unary(GraphGrpcServer::query, request),
+            "/tree_graph.GraphService/GetSubtreeSource" => self.unary(GraphGrpcServer::get_subtree_source, request),
+            _ => Box::pin(async move {
+                let mut response = http::Response::new(empty_body());
+                let headers = response.headers_mut();
+                headers.insert(Status::GRPC_STATUS, (Code::Unimplemented as i32).into());
+                headers.insert(http::header::CONTENT_TYPE, tonic::metadata::GRPC_CONTENT_TYPE);
+                Ok(response)
+            }),
+        }
+    }
+}
+
+impl tonic::server::NamedService for GraphGrpcServer {
+    const NAME: &'static str = SERVICE_NAME;
+}
@@ -0,0 +1,159 @@
+use petgraph::graph::NodeIndex;
+use petgraph::visit::DfsPostOrder;
+use std::collections::HashMap;
+
+use crate::ASTGraph;
+use crate::geometry::{GNode, GRange};
+
+///
+/// 64-bit FNV-1a. Hashes computed here get persisted (patches, stores), so
+/// they can't come from `DefaultHasher`, whose output may change between
+/// Rust releases.
+///
+#[derive(Debug,Clone,Copy)]
+pub struct Fnv64(u64);
+
+impl Fnv64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    pub fn new() -> Self {
+        Fnv64(Self::OFFSET)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Fnv64::new()
+    }
+}
+
+///
+/// Hash of every subtree computed bottom-up by `combine`, which receives the
+/// node and its children (in source order) paired with their hashes.
+///
+fn subtree_hashes_with<F>(graph: &ASTGraph, combine: F) -> HashMap<NodeIndex, u64>
+where
+    F: Fn(&GNode, &[(&GNode, u64)]) -> u64
+{
+    let mut hashes = HashMap::with_capacity(graph.graph.node_count());
+
+    for root in graph.roots() {
+        let mut postorder = DfsPostOrder::new(&graph.graph, root);
+        while let Some(node) = postorder.next(&graph.graph) {
+            let children: Vec<(&GNode, u64)> = graph.children(node).iter()
+                .map(|c| (&graph.graph[*c], hashes[c]))
+                .collect();
+            hashes.insert(node, combine(&graph.graph[node], &children));
+        }
+    }
+
+    hashes
+}
+
+///
+/// Hash of each subtree's shape: kinds and child order only, ignoring where
+/// in the source the subtree sits. Equal hashes mean (barring collisions)
+/// isomorphic ordered trees with the same kinds.
+///
+pub fn structural_hashes(graph: &ASTGraph) -> HashMap<NodeIndex, u64> {
+    subtree_hashes_with(graph, |gnode, children| {
+        let mut hasher = Fnv64::new();
+        hasher.write_u64(gnode.kind_id as u64);
+        hasher.write_u64(children.len() as u64);
+        for (_, child_hash) in children {
+            hasher.write_u64(*child_hash);
+        }
+        hasher.finish()
+    })
+}
+
+///
+/// Like `structural_hashes`, but also covering every node's extent relative to
+/// the subtree root (byte offsets, row offsets, and columns). Two subtrees with
+/// equal layout hashes differ only by a translation of the root position, so
+/// one can be rebuilt from the other by shifting its ranges.
+///
+pub fn layout_hashes(graph: &ASTGraph) -> HashMap<NodeIndex, u64> {
+    subtree_hashes_with(graph, |gnode, children| {
+        let range = &gnode.range;
+        let mut hasher = Fnv64::new();
+        hasher.write_u64(gnode.kind_id as u64);
+        hasher.write_u64(range.end_byte.wrapping_sub(range.start_byte) as u64);
+        hasher.write_u64(range.end_point.row.wrapping_sub(range.start_point.row) as u64);
+        hasher.write_u64(relative_column(range.start_point.row, range.start_point.column, range.end_point.row, range.end_point.column) as u64);
+        hasher.write_u64(children.len() as u64);
+        for (child, child_hash) in children {
+            let child_range = &child.range;
+            hasher.write_u64(child_range.start_byte.wrapping_sub(range.start_byte) as u64);
+            hasher.write_u64(child_range.start_point.row.wrapping_sub(range.start_point.row) as u64);
+            hasher.write_u64(relative_column(range.start_point.row, range.start_point.column, child_range.start_point.row, child_range.start_point.column) as u64);
+            hasher.write_u64(*child_hash);
+        }
+        hasher.finish()
+    })
+}
+
+// columns on the anchor's own row move with it; on later rows they don't
+fn relative_column(anchor_row: usize, anchor_column: usize, row: usize, column: usize) -> usize {
+    if row == anchor_row {
+        column.wrapping_sub(anchor_column)
+    } else {
+        column
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Hash over the structural hashes of all roots: equal for graphs with the
+    /// same ordered trees of kinds, whatever their ranges or node order.
+    ///
+    pub fn structural_hash(&self) -> u64 {
+        let hashes = structural_hashes(self);
+        let mut hasher = Fnv64::new();
+        for root in self.roots() {
+            hasher.write_u64(hashes[&root]);
+        }
+        hasher.finish()
+    }
+
+    // preorder of (kind, range, child count) from every root in turn
+    fn canonical_sequence(&self) -> Vec<(u16, GRange, usize)> {
+        let mut sequence = Vec::with_capacity(self.graph.node_count());
+        let mut stack: Vec<NodeIndex> = self.roots().into_iter().rev().collect();
+
+        while let Some(node) = stack.pop() {
+            let children = self.children(node);
+            sequence.push((self.graph[node].kind_id, self.graph[node].range, children.len()));
+            stack.extend(children.into_iter().rev());
+        }
+
+        sequence
+    }
+
+    ///
+    /// True if both graphs hold the same trees -- same kinds, ranges and child
+    /// order -- regardless of the order their nodes were inserted in.
+    /// Tree-sitter ids are ignored since they differ between parses.
+    ///
+    pub fn structurally_equal(&self, other: &ASTGraph) -> bool {
+        self.graph.node_count() == other.graph.node_count()
+            && self.graph.edge_count() == other.graph.edge_count()
+            && self.canonical_sequence() == other.canonical_sequence()
+    }
+}
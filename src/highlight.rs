@@ -0,0 +1,107 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+use tree_sitter::Language;
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+
+///
+/// Highlight classes recognized by default, in the dotted form used by
+/// `highlights.scm` files. A capture like `@function.method` falls back to
+/// the longest listed prefix (`function`).
+///
+pub const HIGHLIGHT_NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "constant.builtin",
+    "constructor",
+    "function",
+    "function.builtin",
+    "keyword",
+    "label",
+    "number",
+    "operator",
+    "property",
+    "punctuation",
+    "punctuation.bracket",
+    "punctuation.delimiter",
+    "string",
+    "string.escape",
+    "type",
+    "type.builtin",
+    "variable",
+    "variable.builtin",
+    "variable.parameter",
+];
+
+///
+/// A `tree-sitter-highlight` configuration for one language together with the
+/// class names it was configured with.
+///
+pub struct Highlighting {
+    config: HighlightConfiguration,
+    names: Vec<String>,
+}
+
+impl Highlighting {
+    ///
+    /// Configuration from the grammar's highlights query (e.g.
+    /// `tree_sitter_cpp::HIGHLIGHT_QUERY`), using `HIGHLIGHT_NAMES`.
+    ///
+    pub fn new(language: Language, name: &str, highlights_query: &str) -> Result<Self, GraphError> {
+        Highlighting::with_names(language, name, highlights_query, HIGHLIGHT_NAMES)
+    }
+
+    pub fn with_names(language: Language, name: &str, highlights_query: &str, names: &[&str]) -> Result<Self, GraphError> {
+        let mut config = HighlightConfiguration::new(language, name, highlights_query, "", "")
+            .map_err(|err| GraphError::Query(err.to_string()))?;
+        config.configure(names);
+        Ok(Highlighting { config, names: names.iter().map(|name| name.to_string()).collect() })
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Highlight class of every leaf the highlighter tags, e.g. `keyword` for
+    /// `return` or `function` for the name in a call. Leaves without a class
+    /// (most punctuation, with the usual queries) are absent from the map.
+    ///
+    pub fn highlight_leaves<'h>(&self, highlighting: &'h Highlighting) -> Result<HashMap<NodeIndex, &'h str>, GraphError> {
+        let mut leaves: Vec<NodeIndex> = self.graph.node_indices()
+            .filter(|&node| self.graph.neighbors(node).next().is_none())
+            .collect();
+        leaves.sort_by_key(|&node| (self.graph[node].range.start_byte, self.graph[node].range.end_byte));
+
+        let mut classes = HashMap::new();
+        let mut highlighter = Highlighter::new();
+        let events = highlighter.highlight(&highlighting.config, self.try_source()?.as_bytes(), None, |_| None)
+            .map_err(|err| GraphError::Query(err.to_string()))?;
+        let mut active: Vec<usize> = Vec::new();
+
+        for event in events {
+            match event.map_err(|err| GraphError::Query(err.to_string()))? {
+                HighlightEvent::HighlightStart(highlight) => active.push(highlight.0),
+                HighlightEvent::HighlightEnd => { active.pop(); },
+                HighlightEvent::Source { start, end } => {
+                    let (start, end) = (self.source_start.byte + start, self.source_start.byte + end);
+                    let class = match active.last() {
+                        Some(&class) => highlighting.names[class].as_str(),
+                        None => continue,
+                    };
+                    let first = leaves.partition_point(|&node| self.graph[node].range.end_byte <= start);
+                    for &leaf in leaves[first..].iter().take_while(|&&node| self.graph[node].range.start_byte < end) {
+                        classes.entry(leaf).or_insert(class);
+                    }
+                },
+            }
+        }
+
+        Ok(classes)
+    }
+}
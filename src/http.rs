@@ -0,0 +1,185 @@
+//!
+//! A `ProjectGraph` served read-only over HTTP, for quick internal
+//! dashboards. Routing doesn't depend on any HTTP stack: `respond` maps a
+//! method and request target to a response, `serve` answers requests on a
+//! std `TcpListener` one at a time, and with the `axum` feature `router`
+//! mounts the same routes in an axum application.
+//!
+//! Routes (file paths percent-encoded where needed):
+//!
+//! - `GET /files`: every file with its title and node/edge counts (JSON)
+//! - `GET /files/<path>/nodes`: the file's nodes in index order (JSON)
+//! - `GET /files/<path>/edges`: its (parent, child) index pairs (JSON)
+//! - `GET /files/<path>/graph.dot`, `/graph.mmd`: DOT and Mermaid renderings,
+//!   taking `max_depth` and `leaf_text` query parameters as `ExportOptions`
+//!
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::export::{self, ExportOptions};
+use crate::project::ProjectGraph;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn json(value: Value) -> Self {
+        HttpResponse { status: 200, content_type: "application/json", body: value.to_string() }
+    }
+
+    fn text(content_type: &'static str, body: String) -> Self {
+        HttpResponse { status: 200, content_type: content_type, body: body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        HttpResponse { status: status, content_type: "text/plain; charset=utf-8", body: format!("{}\n", message) }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn export_options(query: &str) -> Result<ExportOptions, HttpResponse> {
+    let mut options = ExportOptions::default();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let parsed = || value.parse::<usize>().map_err(|_| HttpResponse::error(400, &format!("`{}` is not a number", value)));
+        match name {
+            "max_depth" => options.max_depth = Some(parsed()?),
+            "leaf_text" => options.leaf_text = Some(parsed()?),
+            _ => {},
+        }
+    }
+    Ok(options)
+}
+
+fn file_summary(path: &str, graph: &ASTGraph) -> Value {
+    json!({ "path": path, "title": graph.title(), "nodes": graph.graph().node_count(), "edges": graph.edge_count() })
+}
+
+fn nodes_json(graph: &ASTGraph) -> Value {
+    Value::Array(graph.nodes()
+        .map(|(node, gnode)| json!({ "index": node.index(), "id": gnode.id, "kind_id": gnode.kind_id, "range": gnode.range }))
+        .collect())
+}
+
+fn edges_json(graph: &ASTGraph) -> Value {
+    Value::Array(graph.edges().map(|(parent, child)| json!([parent.index(), child.index()])).collect())
+}
+
+///
+/// The response to `method target`, `target` being the path and query of
+/// the request line.
+///
+pub fn respond(project: &ProjectGraph, method: &str, target: &str) -> HttpResponse {
+    if method != "GET" {
+        return HttpResponse::error(405, "only GET is supported");
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path == "/files" {
+        return HttpResponse::json(Value::Array(project.files().map(|(path, graph)| file_summary(path, graph)).collect()));
+    }
+    let rest = match path.strip_prefix("/files/") {
+        Some(rest) => rest,
+        None => return HttpResponse::error(404, "no such route"),
+    };
+    let (file, route) = match rest.rsplit_once('/') {
+        Some(split) => split,
+        None => return HttpResponse::error(404, "no such route"),
+    };
+    let graph = match percent_decode(file).and_then(|file| project.get(&file)) {
+        Some(graph) => graph,
+        None => return HttpResponse::error(404, "no such file"),
+    };
+
+    match route {
+        "nodes" => HttpResponse::json(nodes_json(graph)),
+        "edges" => HttpResponse::json(edges_json(graph)),
+        "graph.dot" | "graph.mmd" => match export_options(query) {
+            Ok(options) if route == "graph.dot" => HttpResponse::text("text/vnd.graphviz; charset=utf-8", export::to_dot(graph, &options)),
+            Ok(options) => HttpResponse::text("text/plain; charset=utf-8", export::to_mermaid(graph, &options)),
+            Err(response) => response,
+        },
+        _ => HttpResponse::error(404, "no such route"),
+    }
+}
+
+///
+/// Answer HTTP/1.1 requests on `listener`, one connection at a time and
+/// one request per connection, until accepting fails.
+///
+pub fn serve(project: &ProjectGraph, listener: &TcpListener) -> Result<(), GraphError> {
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // skip the headers; no route takes a body
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let response = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => respond(project, method, target),
+            _ => HttpResponse::error(400, "malformed request line"),
+        };
+        write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status, response.reason(), response.content_type, response.body.len())?;
+        stream.write_all(response.body.as_bytes())?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+///
+/// The routes as an axum router (all of them, as its fallback), so they can
+/// be nested under a prefix of a larger application.
+///
+#[cfg(feature="axum")]
+pub fn router(project: std::sync::Arc<ProjectGraph>) -> axum::Router {
+    use axum::http::{header, Method, StatusCode, Uri};
+    use axum::response::IntoResponse;
+
+    axum::Router::new().fallback(move |method: Method, uri: Uri| {
+        let project = project.clone();
+        async move {
+            let target = uri.path_and_query().map(|target| target.as_str()).unwrap_or("/");
+            let response = respond(&project, method.as_str(), target);
+            let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, [(header::CONTENT_TYPE, response.content_type)], response.body).into_response()
+        }
+    })
+}
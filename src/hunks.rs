@@ -0,0 +1,177 @@
+//!
+//! Unified diffs mapped onto graphs: which nodes and functions a change
+//! touches, for "which functions changed" reports on a pull request. The
+//! graph is that of the new version of the file.
+//!
+use petgraph::graph::NodeIndex;
+use std::collections::BTreeSet;
+
+use crate::ASTGraph;
+use crate::analysis::LanguageKinds;
+use crate::error::GraphError;
+use crate::window::last_row;
+
+///
+/// One hunk of a unified diff. Rows are 0-based rows of the new file;
+/// `changed_rows` are its added lines, and the rows on either side of lines
+/// deleted without replacement.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct Hunk {
+    /// the file of the hunk, from the `+++` line (without `b/`)
+    pub path: Option<String>,
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub changed_rows: Vec<usize>,
+}
+
+fn parse_error(line: usize, message: &str) -> GraphError {
+    GraphError::Parse { line: line, message: message.to_string() }
+}
+
+// `start,count` or `start` (count 1) after the `-`/`+`
+fn parse_span(text: &str, line: usize) -> Result<(usize, usize), GraphError> {
+    let (start, count) = match text.split_once(',') {
+        Some((start, count)) => (start, count),
+        None => (text, "1"),
+    };
+    let start = start.parse().map_err(|_| parse_error(line, "bad line number in hunk header"))?;
+    let count = count.parse().map_err(|_| parse_error(line, "bad line count in hunk header"))?;
+    Ok((start, count))
+}
+
+///
+/// The hunks of a unified diff (`git diff`, `diff -u`), of one or more files.
+///
+pub fn parse_hunks(unified_diff: &str) -> Result<Vec<Hunk>, GraphError> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut path: Option<String> = None;
+    // old and new lines still to come in the current hunk, the new row, and
+    // whether lines were deleted at that row with nothing added in their place
+    let mut remaining = (0, 0);
+    let mut row = 0;
+    let mut deleted = false;
+
+    for (index, text) in unified_diff.lines().enumerate() {
+        let line = index + 1;
+        if remaining != (0, 0) {
+            let hunk = hunks.last_mut().unwrap();
+            match text.chars().next() {
+                Some('+') if remaining.1 > 0 => {
+                    hunk.changed_rows.push(row);
+                    row += 1;
+                    remaining.1 -= 1;
+                    deleted = false;
+                }
+                Some('-') if remaining.0 > 0 => {
+                    remaining.0 -= 1;
+                    deleted = true;
+                }
+                Some(' ') | None if remaining.0 > 0 && remaining.1 > 0 => {
+                    if deleted {
+                        hunk.changed_rows.extend(row.checked_sub(1));
+                        hunk.changed_rows.push(row);
+                        deleted = false;
+                    }
+                    row += 1;
+                    remaining = (remaining.0 - 1, remaining.1 - 1);
+                }
+                Some('\\') => {}
+                _ => return Err(parse_error(line, "hunk ends before its line counts")),
+            }
+            // a deletion at the end of the hunk touches the rows around it
+            if remaining == (0, 0) && deleted {
+                hunk.changed_rows.extend(row.checked_sub(1));
+                hunk.changed_rows.push(row);
+                deleted = false;
+            }
+            continue;
+        }
+
+        if let Some(target) = text.strip_prefix("+++ ") {
+            let target = target.split('\t').next().unwrap_or(target).trim();
+            path = (target != "/dev/null").then(|| target.strip_prefix("b/").unwrap_or(target).to_string());
+        } else if let Some(header) = text.strip_prefix("@@ ") {
+            let spans = header.split(" @@").next().unwrap_or("");
+            let mut parts = spans.split_whitespace();
+            let old = parts.next().and_then(|part| part.strip_prefix('-')).ok_or_else(|| parse_error(line, "hunk header without old range"))?;
+            let new = parts.next().and_then(|part| part.strip_prefix('+')).ok_or_else(|| parse_error(line, "hunk header without new range"))?;
+            let (old_start, old_lines) = parse_span(old, line)?;
+            let (new_start, new_lines) = parse_span(new, line)?;
+            // an empty new range starts after the given line, others on it
+            row = if new_lines == 0 { new_start } else { new_start.saturating_sub(1) };
+            remaining = (old_lines, new_lines);
+            hunks.push(Hunk {
+                path: path.clone(),
+                old_start: old_start,
+                old_lines: old_lines,
+                new_start: new_start,
+                new_lines: new_lines,
+                changed_rows: Vec::new(),
+            });
+        }
+    }
+    if remaining != (0, 0) {
+        return Err(parse_error(unified_diff.lines().count(), "diff ends inside a hunk"));
+    }
+    for hunk in hunks.iter_mut() {
+        hunk.changed_rows.sort();
+        hunk.changed_rows.dedup();
+    }
+    Ok(hunks)
+}
+
+// changed rows of all hunks as inclusive runs of consecutive rows
+fn changed_runs(hunks: &[Hunk]) -> Vec<(usize, usize)> {
+    let rows: BTreeSet<usize> = hunks.iter().flat_map(|hunk| hunk.changed_rows.iter().copied()).collect();
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for row in rows {
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == row => run.1 = row,
+            _ => runs.push((row, row)),
+        }
+    }
+    runs
+}
+
+impl ASTGraph {
+    ///
+    /// The smallest nodes covering the changes of `unified_diff` (the diff
+    /// producing this graph's source): one per run of changed rows, minus
+    /// those inside another, sorted.
+    ///
+    pub fn affected_nodes_from_diff(&self, unified_diff: &str) -> Result<Vec<NodeIndex>, GraphError> {
+        let covering: BTreeSet<NodeIndex> = changed_runs(&parse_hunks(unified_diff)?).into_iter()
+            .filter_map(|(start, end)| self.covering_node_for_lines(start, end))
+            .collect();
+        Ok(covering.iter().copied()
+            .filter(|&node| {
+                let mut ancestor = self.parent(node);
+                while let Some(current) = ancestor {
+                    if covering.contains(&current) {
+                        return false;
+                    }
+                    ancestor = self.parent(current);
+                }
+                true
+            })
+            .collect())
+    }
+
+    ///
+    /// Functions (`kinds.functions`) with a changed row of `unified_diff`,
+    /// sorted.
+    ///
+    pub fn affected_functions_from_diff(&self, unified_diff: &str, kinds: &LanguageKinds) -> Result<Vec<NodeIndex>, GraphError> {
+        let runs = changed_runs(&parse_hunks(unified_diff)?);
+        Ok(self.graph.node_indices()
+            .filter(|&node| kinds.functions.contains(&self.graph[node].kind_id))
+            .filter(|&node| {
+                let range = &self.graph[node].range;
+                runs.iter().any(|&(start, end)| range.start_point.row <= end && last_row(range) >= start)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,222 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::geometry::GNode;
+use tree_sitter::{Node, Tree};
+
+static NEXT_GRAPH_ID: AtomicU64 = AtomicU64::new(1);
+
+pub(crate) fn next_graph_id() -> u64 {
+    NEXT_GRAPH_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+///
+/// Node handle tied to the graph that issued it. A bare `NodeIndex` from one
+/// graph happily indexes into any other (e.g. parent-graph indices used on a
+/// subgraph); an `AstNodeId` is rejected by every graph but its own.
+///
+/// Graph ids are unique per process and not persisted; a deserialized or
+/// cloned-then-rebuilt graph hands out new ids. Clones share their original's
+/// id, since indices stay valid in a clone.
+///
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct AstNodeId {
+    graph_id: u64,
+    index: NodeIndex,
+}
+
+impl AstNodeId {
+    pub fn graph_id(&self) -> u64 {
+        self.graph_id
+    }
+
+    ///
+    /// The raw index, for use with petgraph APIs on the issuing graph.
+    ///
+    pub fn index(&self) -> NodeIndex {
+        self.index
+    }
+}
+
+impl ASTGraph {
+    pub fn graph_id(&self) -> u64 {
+        self.graph_id
+    }
+
+    ///
+    /// Typed handle for `index`, or `None` if the index is out of bounds.
+    ///
+    pub fn id_of(&self, index: NodeIndex) -> Option<AstNodeId> {
+        if index.index() < self.graph.node_count() {
+            Some(AstNodeId { graph_id: self.graph_id, index: index })
+        } else {
+            None
+        }
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = AstNodeId> + '_ {
+        self.graph.node_indices().map(move |index| AstNodeId { graph_id: self.graph_id, index: index })
+    }
+
+    ///
+    /// Check that `id` was issued by this graph and still refers to a node.
+    ///
+    pub fn resolve(&self, id: AstNodeId) -> Result<NodeIndex, GraphError> {
+        if id.graph_id != self.graph_id {
+            return Err(GraphError::InvalidNode(format!("node {} belongs to graph {}, not graph {}", id.index.index(), id.graph_id, self.graph_id)));
+        }
+        if id.index.index() >= self.graph.node_count() {
+            return Err(GraphError::InvalidNode(format!("node {} is out of bounds", id.index.index())));
+        }
+        Ok(id.index)
+    }
+
+    pub fn node(&self, id: AstNodeId) -> Result<&GNode, GraphError> {
+        let index = self.resolve(id)?;
+        Ok(&self.graph[index])
+    }
+
+    pub fn node_source(&self, id: AstNodeId) -> Result<&str, GraphError> {
+        let index = self.resolve(id)?;
+        self.try_node_source(index)
+    }
+
+    pub fn subgraph_at(&self, id: AstNodeId) -> Result<ASTGraph, GraphError> {
+        let index = self.resolve(id)?;
+        Ok(self.extract_subgraph_from(index))
+    }
+}
+
+///
+/// Node identifier that survives rebuilds, serialization and reparsing of the
+/// same source: the position of the root among the graph's roots followed by
+/// the child position (in source order) at every step down to the node.
+/// Written as `0/2/1`.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct StableId(pub Vec<u32>);
+
+impl StableId {
+    pub fn depth(&self) -> usize {
+        self.0.len().saturating_sub(1)
+    }
+
+    pub fn parent(&self) -> Option<StableId> {
+        if self.0.len() > 1 {
+            Some(StableId(self.0[..self.0.len() - 1].to_vec()))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_ancestor_of(&self, other: &StableId) -> bool {
+        self.0.len() < other.0.len() && other.0.starts_with(&self.0)
+    }
+}
+
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", parts.join("/"))
+    }
+}
+
+impl FromStr for StableId {
+    type Err = GraphError;
+
+    fn from_str(s: &str) -> Result<StableId, GraphError> {
+        s.split('/')
+            .map(|part| part.parse::<u32>().map_err(|_| GraphError::InvalidNode(format!("malformed stable id `{}`", s))))
+            .collect::<Result<Vec<u32>, GraphError>>()
+            .map(StableId)
+    }
+}
+
+impl ASTGraph {
+    pub fn stable_id(&self, node: NodeIndex) -> StableId {
+        let mut path = Vec::new();
+        let mut current = node;
+        while let Some(parent) = self.parent(current) {
+            let position = self.children(parent).iter().position(|&c| c == current).unwrap();
+            path.push(position as u32);
+            current = parent;
+        }
+        let root_position = self.roots().iter().position(|&r| r == current).unwrap_or(0);
+        path.push(root_position as u32);
+        path.reverse();
+        StableId(path)
+    }
+
+    ///
+    /// Stable ids of all nodes in one top-down pass, cheaper than calling
+    /// `stable_id` per node.
+    ///
+    pub fn stable_ids(&self) -> HashMap<NodeIndex, StableId> {
+        let mut ids = HashMap::with_capacity(self.graph.node_count());
+        let mut stack: Vec<(NodeIndex, Vec<u32>)> = self.roots().into_iter().enumerate()
+            .map(|(position, root)| (root, vec![position as u32]))
+            .collect();
+
+        while let Some((node, path)) = stack.pop() {
+            for (position, child) in self.children(node).into_iter().enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(position as u32);
+                stack.push((child, child_path));
+            }
+            ids.insert(node, StableId(path));
+        }
+
+        ids
+    }
+
+    pub fn resolve_stable_id(&self, id: &StableId) -> Option<NodeIndex> {
+        let (&root_position, steps) = id.0.split_first()?;
+        let mut current = *self.roots().get(root_position as usize)?;
+        for &position in steps {
+            current = *self.children(current).get(position as usize)?;
+        }
+        Some(current)
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// The node of `tree` that `index` was built from, found through its range
+    /// and checked by tree-sitter id. `None` if `tree` isn't the tree the graph
+    /// was built from (ids differ between parses, including after an edit).
+    ///
+    pub fn resolve_ts_node<'t>(&self, index: NodeIndex, tree: &'t Tree) -> Option<Node<'t>> {
+        let gnode = self.graph.node_weight(index)?;
+        let mut candidate = tree.root_node().descendant_for_byte_range(gnode.range.start_byte, gnode.range.end_byte)?;
+        // nodes sharing a range nest; the smallest one comes back first
+        loop {
+            if candidate.id() == gnode.id {
+                return Some(candidate);
+            }
+            candidate = candidate.parent()
+                .filter(|parent| parent.start_byte() == gnode.range.start_byte && parent.end_byte() == gnode.range.end_byte)?;
+        }
+    }
+
+    ///
+    /// Graph index of a node of the tree the graph was built from. This scans
+    /// the graph; use `ts_id_index` for many lookups.
+    ///
+    pub fn graph_index_for(&self, node: &Node) -> Option<NodeIndex> {
+        let id = node.id();
+        self.node_map.iter().find(|(_, ts_id)| **ts_id == id).map(|(&index, _)| index)
+    }
+
+    ///
+    /// Map from tree-sitter id to graph index.
+    ///
+    pub fn ts_id_index(&self) -> HashMap<usize, NodeIndex> {
+        self.node_map.iter().map(|(&index, &ts_id)| (ts_id, index)).collect()
+    }
+}
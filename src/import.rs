@@ -0,0 +1,226 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+use crate::geometry::{Edge, GNode, GPoint, GRange};
+
+///
+/// Read a graph back from DOT.
+///
+/// Files written by `export::to_dot` round-trip exactly: nodes are rebuilt from
+/// their `kind_id`, `ts_id`, `range` and (when set) `named` and
+/// `source_hash` attributes. For DOT from elsewhere, a node without `kind_id` falls back to
+/// a numeric `label` as its kind (with an empty range); anything else --
+/// including the "… (N nodes)" placeholders of a collapsed export -- is
+/// skipped along with its edges. The resulting graph has no source attached.
+///
+pub fn from_dot(input: &str) -> Result<ASTGraph, GraphError> {
+    let mut nodes = Vec::new();
+    let mut positions: HashMap<String, NodeIndex> = HashMap::new();
+    let mut pending_edges: Vec<(String, String)> = Vec::new();
+    let mut title = String::new();
+
+    for (number, raw) in input.lines().enumerate() {
+        let line_number = number + 1;
+        let line = raw.trim().trim_end_matches(';').trim();
+        if line.is_empty() || line.starts_with('}') || line.starts_with('{') || line.starts_with("//") || line.starts_with('#') {
+            continue;
+        }
+        if line.ends_with('{') {
+            // graph header (subgraph/cluster headers carry no nodes of their own)
+            if line.starts_with("digraph") || line.starts_with("graph") || line.starts_with("strict") {
+                title = parse_graph_title(line);
+            }
+            continue;
+        }
+
+        let (head, attributes) = match line.find('[') {
+            Some(open) => {
+                let close = line.rfind(']').filter(|&close| close > open)
+                    .ok_or_else(|| parse_error(line_number, "unterminated attribute list"))?;
+                (line[..open].trim(), parse_attributes(&line[open + 1..close], line_number)?)
+            },
+            None => (line, HashMap::new()),
+        };
+
+        if head.contains("->") {
+            let endpoints: Vec<String> = head.split("->").map(|id| unquote(id.trim())).collect();
+            for pair in endpoints.windows(2) {
+                pending_edges.push((pair[0].clone(), pair[1].clone()));
+            }
+        } else if head == "node" || head == "edge" || head == "graph" || head.contains('=') {
+            continue;
+        } else if let Some(gnode) = parse_node(&attributes, line_number)? {
+            positions.insert(unquote(head), NodeIndex::new(nodes.len()));
+            nodes.push(gnode);
+        }
+    }
+
+    let edges = pending_edges.iter()
+        .filter_map(|(source, target)| {
+            match (positions.get(source), positions.get(target)) {
+                (Some(&source), Some(&target)) => Some(Edge { source: source, target: target }),
+                _ => None,
+            }
+        }).collect();
+
+    Ok(ASTGraph::from_serializable(SerializableGraph { title: title, nodes: nodes, edges: edges, ..Default::default() }))
+}
+
+fn parse_error(line: usize, message: &str) -> GraphError {
+    GraphError::Parse { line: line, message: message.to_string() }
+}
+
+fn unquote(id: &str) -> String {
+    let id = id.trim();
+    if id.len() >= 2 && id.starts_with('"') && id.ends_with('"') {
+        id[1..id.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        id.to_string()
+    }
+}
+
+fn parse_graph_title(line: &str) -> String {
+    match (line.find('"'), line.rfind('"')) {
+        (Some(open), Some(close)) if close > open => unquote(&line[open..=close]),
+        _ => "".to_string(),
+    }
+}
+
+fn parse_attributes(list: &str, line_number: usize) -> Result<HashMap<String, String>, GraphError> {
+    let mut attributes = HashMap::new();
+    let mut chars = list.chars().peekable();
+
+    loop {
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == ',' || c == ';' {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if chars.next() != Some('=') {
+            return Err(parse_error(line_number, &format!("attribute `{}` has no value", key)));
+        }
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut closed = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some(escaped) = chars.next() {
+                            if escaped != '"' && escaped != '\\' {
+                                value.push('\\');
+                            }
+                            value.push(escaped);
+                        }
+                    },
+                    '"' => {
+                        closed = true;
+                        break;
+                    },
+                    _ => value.push(c),
+                }
+            }
+            if !closed {
+                return Err(parse_error(line_number, &format!("unterminated string for attribute `{}`", key)));
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == ';' || c.is_whitespace() {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+
+        attributes.insert(key, value);
+    }
+
+    Ok(attributes)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str, line_number: usize) -> Result<T, GraphError> {
+    value.trim().parse::<T>().map_err(|_| parse_error(line_number, &format!("invalid {} `{}`", name, value)))
+}
+
+fn parse_node(attributes: &HashMap<String, String>, line_number: usize) -> Result<Option<GNode>, GraphError> {
+    let kind_id = match attributes.get("kind_id") {
+        Some(kind_id) => parse_number::<u16>(kind_id, "kind_id", line_number)?,
+        None => match attributes.get("label").and_then(|label| label.parse::<u16>().ok()) {
+            Some(kind_id) => kind_id,
+            None => return Ok(None),
+        },
+    };
+
+    let id = match attributes.get("ts_id") {
+        Some(id) => parse_number::<usize>(id, "ts_id", line_number)?,
+        None => 0,
+    };
+
+    let range = match attributes.get("range") {
+        Some(range) => {
+            let parts = range.split(',')
+                .map(|part| parse_number::<usize>(part, "range", line_number))
+                .collect::<Result<Vec<usize>, GraphError>>()?;
+            if parts.len() != 6 {
+                return Err(parse_error(line_number, &format!("range `{}` needs 6 components", range)));
+            }
+            GRange {
+                start_byte: parts[0],
+                end_byte: parts[1],
+                start_point: GPoint { row: parts[2], column: parts[3] },
+                end_point: GPoint { row: parts[4], column: parts[5] },
+            }
+        },
+        None => GRange {
+            start_byte: 0,
+            end_byte: 0,
+            start_point: GPoint { row: 0, column: 0 },
+            end_point: GPoint { row: 0, column: 0 },
+        },
+    };
+
+    let is_named = match attributes.get("named").map(|named| named.as_str()) {
+        Some("false") => false,
+        Some("true") | None => true,
+        Some(named) => return Err(parse_error(line_number, &format!("invalid named `{}`", named))),
+    };
+
+    let source_hash = match attributes.get("source_hash") {
+        Some(hash) => parse_number::<u32>(hash, "source_hash", line_number)?,
+        None => 0,
+    };
+
+    Ok(Some(GNode { id: id, kind_id: kind_id, range: range, is_named: is_named, source_hash: source_hash }))
+}
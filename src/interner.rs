@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+///
+/// String interner (string <-> u32): every distinct string is stored once
+/// and referred to by its position. Shared by the graphs of a project for
+/// kind names and paths, and used for stored leaf text. Serializes as the
+/// plain list of strings.
+///
+#[derive(Debug,Clone,Default,PartialEq,Serialize,Deserialize)]
+#[serde(from="Vec<String>", into="Vec<String>")]
+pub struct Interner {
+    strings: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> u32 {
+        if let Some(&id) = self.index.get(text) {
+            return id;
+        }
+        let id = self.strings.len() as u32;
+        self.strings.push(text.to_string());
+        self.index.insert(text.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.strings.get(id as usize).map(|text| text.as_str())
+    }
+
+    ///
+    /// Id of an already interned string.
+    ///
+    pub fn id_of(&self, text: &str) -> Option<u32> {
+        self.index.get(text).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.strings.shrink_to_fit();
+        self.index.shrink_to_fit();
+    }
+}
+
+impl From<Vec<String>> for Interner {
+    fn from(strings: Vec<String>) -> Self {
+        let mut interner = Interner::new();
+        for text in strings.iter() {
+            interner.intern(text);
+        }
+        interner
+    }
+}
+
+impl From<Interner> for Vec<String> {
+    fn from(interner: Interner) -> Self {
+        interner.strings
+    }
+}
@@ -0,0 +1,253 @@
+use petgraph::graph::{EdgeIndices, NodeIndex, NodeIndices};
+use std::collections::VecDeque;
+
+use crate::ASTGraph;
+use crate::geometry::{GNode, GRange};
+
+///
+/// Nodes of a graph with their data, in index order; see `ASTGraph::nodes`.
+///
+pub struct Nodes<'g> {
+    graph: &'g ASTGraph,
+    indices: NodeIndices,
+}
+
+impl<'g> Iterator for Nodes<'g> {
+    type Item = (NodeIndex, &'g GNode);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().map(|node| (node, &self.graph.graph[node]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Nodes<'_> {}
+
+///
+/// (parent, child) pairs of a graph in insertion order; see
+/// `ASTGraph::edges`.
+///
+pub struct Edges<'g> {
+    graph: &'g ASTGraph,
+    indices: EdgeIndices,
+}
+
+impl Iterator for Edges<'_> {
+    type Item = (NodeIndex, NodeIndex);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.indices.next().and_then(|edge| self.graph.graph.edge_endpoints(edge))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Edges<'_> {}
+
+///
+/// Handle on a subtree of a graph, without copying it: its root, extent and
+/// source text (`None` if the graph's source doesn't hold it; see
+/// `try_node_source`). `to_graph` materializes it when needed.
+///
+#[derive(Debug,Clone,Copy)]
+pub struct SubtreeRef<'g> {
+    graph: &'g ASTGraph,
+    pub root: NodeIndex,
+    pub kind_id: u16,
+    pub range: GRange,
+    pub source: Option<&'g str>,
+}
+
+impl<'g> SubtreeRef<'g> {
+    pub fn graph(&self) -> &'g ASTGraph {
+        self.graph
+    }
+
+    pub fn children(&self) -> Vec<NodeIndex> {
+        self.graph.children(self.root)
+    }
+
+    pub fn to_graph(&self) -> ASTGraph {
+        self.graph.extract_subgraph_from(self.root)
+    }
+}
+
+///
+/// Subtrees rooted at nodes of one kind, in index order; see
+/// `ASTGraph::subtrees_of_kind`.
+///
+pub struct SubtreesOfKind<'g> {
+    graph: &'g ASTGraph,
+    kind_id: u16,
+    indices: NodeIndices,
+}
+
+impl<'g> Iterator for SubtreesOfKind<'g> {
+    type Item = SubtreeRef<'g>;
+
+    fn next(&mut self) -> Option<SubtreeRef<'g>> {
+        let graph = self.graph;
+        let kind_id = self.kind_id;
+        self.indices.find(|&node| graph.graph[node].kind_id == kind_id).map(|node| SubtreeRef {
+            graph: graph,
+            root: node,
+            kind_id: kind_id,
+            range: graph.graph[node].range,
+            source: graph.try_node_source(node).ok(),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.indices.size_hint().1)
+    }
+}
+
+///
+/// Named nodes below a node, in preorder; see `ASTGraph::named_descendants`.
+///
+pub struct NamedDescendants<'g> {
+    graph: &'g ASTGraph,
+    stack: Vec<NodeIndex>,
+}
+
+impl Iterator for NamedDescendants<'_> {
+    type Item = NodeIndex;
+
+    fn next(&mut self) -> Option<NodeIndex> {
+        while let Some(node) = self.stack.pop() {
+            self.stack.extend(self.graph.children(node).into_iter().rev());
+            if self.graph.graph[node].is_named {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+///
+/// Nodes at most a given depth below a node, in preorder; see
+/// `ASTGraph::descendants_within`.
+///
+pub struct DescendantsWithin<'g> {
+    graph: &'g ASTGraph,
+    stack: Vec<(NodeIndex, usize)>,
+    max_depth: usize,
+}
+
+impl Iterator for DescendantsWithin<'_> {
+    type Item = NodeIndex;
+
+    fn next(&mut self) -> Option<NodeIndex> {
+        let (node, depth) = self.stack.pop()?;
+        if depth < self.max_depth {
+            self.stack.extend(self.graph.children(node).into_iter().rev().map(|child| (child, depth + 1)));
+        }
+        Some(node)
+    }
+}
+
+///
+/// The first nodes of a breadth-first walk; see `ASTGraph::bfs_limited`.
+///
+pub struct BfsLimited<'g> {
+    graph: &'g ASTGraph,
+    queue: VecDeque<NodeIndex>,
+    remaining: usize,
+}
+
+impl Iterator for BfsLimited<'_> {
+    type Item = NodeIndex;
+
+    fn next(&mut self) -> Option<NodeIndex> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.queue.pop_front()?;
+        self.remaining -= 1;
+        // nodes queued past `remaining` would never be yielded
+        let room = self.remaining.saturating_sub(self.queue.len());
+        self.queue.extend(self.graph.children(node).into_iter().take(room));
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining.min(self.queue.len()), Some(self.remaining))
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Nodes with their data, in index order.
+    ///
+    pub fn nodes(&self) -> Nodes<'_> {
+        Nodes { graph: self, indices: self.graph.node_indices() }
+    }
+
+    ///
+    /// (parent, child) pairs, in insertion order.
+    ///
+    pub fn edges(&self) -> Edges<'_> {
+        Edges { graph: self, indices: self.graph.edge_indices() }
+    }
+
+    ///
+    /// Lazily, every subtree whose root has kind `kind_id` (nested ones
+    /// included), e.g. to walk all functions without extracting them.
+    ///
+    pub fn subtrees_of_kind(&self, kind_id: u16) -> SubtreesOfKind<'_> {
+        SubtreesOfKind { graph: self, kind_id: kind_id, indices: self.graph.node_indices() }
+    }
+
+    ///
+    /// The named children of `node` in source order, skipping punctuation
+    /// and keyword tokens.
+    ///
+    pub fn named_children(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.children(node).into_iter().filter(move |&child| self.graph[child].is_named)
+    }
+
+    ///
+    /// The named nodes strictly below `node`, in preorder. Anonymous nodes
+    /// are skipped but their descendants are not.
+    ///
+    pub fn named_descendants(&self, node: NodeIndex) -> NamedDescendants<'_> {
+        let stack = self.children(node).into_iter().rev().collect();
+        NamedDescendants { graph: self, stack: stack }
+    }
+
+    ///
+    /// The nodes strictly below `node` and at most `max_depth` levels down
+    /// (children are at depth 1), in preorder, never looking deeper: a
+    /// bounded walk over pathologically deep generated code.
+    ///
+    pub fn descendants_within(&self, node: NodeIndex, max_depth: usize) -> DescendantsWithin<'_> {
+        let stack = if max_depth == 0 { Vec::new() } else { self.children(node).into_iter().rev().map(|child| (child, 1)).collect() };
+        DescendantsWithin { graph: self, stack: stack, max_depth: max_depth }
+    }
+
+    ///
+    /// `start` and its descendants breadth-first, children in source order,
+    /// stopping after `max_nodes` nodes: a bounded walk over pathologically
+    /// wide generated code.
+    ///
+    pub fn bfs_limited(&self, start: NodeIndex, max_nodes: usize) -> BfsLimited<'_> {
+        BfsLimited { graph: self, queue: VecDeque::from([start]), remaining: max_nodes }
+    }
+}
+
+///
+/// `for (node, gnode) in &ast_graph` walks `nodes()`.
+///
+impl<'g> IntoIterator for &'g ASTGraph {
+    type Item = (NodeIndex, &'g GNode);
+    type IntoIter = Nodes<'g>;
+
+    fn into_iter(self) -> Nodes<'g> {
+        self.nodes()
+    }
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use tree_sitter::Language;
+
+use crate::analysis::KindLookup;
+use crate::error::GraphError;
+
+///
+/// What a grammar says about one node kind.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct KindInfo {
+    pub name: String,
+    /// Named kinds are rules of the grammar; anonymous ones are the
+    /// literal tokens (punctuation, keywords).
+    pub named: bool,
+    /// Hidden kinds (`_expression`) never appear in parse trees.
+    pub visible: bool,
+}
+
+///
+/// Every kind of a grammar by kind id, saved next to graphs so exporters and
+/// readers of serialized graphs can name kinds without the grammar.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq)]
+pub struct KindTable {
+    kinds: Vec<KindInfo>,
+}
+
+impl KindTable {
+    pub fn from_language(language: &Language) -> Self {
+        let kinds = (0..language.node_kind_count() as u16)
+            .map(|kind_id| KindInfo {
+                name: language.node_kind_for_id(kind_id).unwrap_or("").to_string(),
+                named: language.node_kind_is_named(kind_id),
+                visible: language.node_kind_is_visible(kind_id),
+            })
+            .collect();
+        KindTable { kinds: kinds }
+    }
+
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn get(&self, kind_id: u16) -> Option<&KindInfo> {
+        self.kinds.get(kind_id as usize)
+    }
+
+    pub fn name(&self, kind_id: u16) -> Option<&str> {
+        self.get(kind_id).map(|kind| kind.name.as_str())
+    }
+
+    pub fn is_named(&self, kind_id: u16) -> Option<bool> {
+        self.get(kind_id).map(|kind| kind.named)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &KindInfo)> {
+        self.kinds.iter().enumerate().map(|(kind_id, kind)| (kind_id as u16, kind))
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), GraphError> {
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> Result<KindTable, GraphError> {
+        Ok(bincode::deserialize_from(reader)?)
+    }
+}
+
+///
+/// Like the `Language` lookup, names resolve to named kinds only.
+///
+impl KindLookup for KindTable {
+    fn ids_named(&self, name: &str) -> HashSet<u16> {
+        self.iter().filter(|(_, kind)| kind.named && kind.name == name).map(|(kind_id, _)| kind_id).collect()
+    }
+
+    fn name_of(&self, kind_id: u16) -> Option<&str> {
+        self.name(kind_id)
+    }
+}
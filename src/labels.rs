@@ -0,0 +1,104 @@
+use serde::{Serialize,Deserialize};
+use std::collections::BTreeMap;
+
+use crate::ASTGraph;
+
+impl ASTGraph {
+    ///
+    /// Labels of the graph for supervised learning ("bug", "lang:cpp", ...),
+    /// in the order added. Carried over to subgraphs and stored with the
+    /// graph, like the metadata.
+    ///
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn has_label(&self, label: &str) -> bool {
+        self.labels.iter().any(|existing| existing == label)
+    }
+
+    ///
+    /// Add `label` unless the graph already has it; returns whether it was
+    /// added.
+    ///
+    pub fn add_label(&mut self, label: &str) -> bool {
+        if self.has_label(label) {
+            return false;
+        }
+        self.labels.push(label.to_string());
+        true
+    }
+
+    pub fn remove_label(&mut self, label: &str) -> bool {
+        let before = self.labels.len();
+        self.labels.retain(|existing| existing != label);
+        self.labels.len() != before
+    }
+
+    pub fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels.clear();
+        for label in labels.iter() {
+            self.add_label(label);
+        }
+    }
+}
+
+///
+/// Dense ids for the labels of a dataset, in label order, so models can
+/// use class indices while the graphs keep readable names.
+///
+#[derive(Debug,Clone,Default,PartialEq,Serialize,Deserialize)]
+pub struct LabelMap {
+    ids: BTreeMap<String, u32>,
+}
+
+impl LabelMap {
+    pub fn new() -> Self {
+        LabelMap::default()
+    }
+
+    ///
+    /// Every label used by `graphs`, numbered in sorted order.
+    ///
+    pub fn from_graphs(graphs: &[ASTGraph]) -> Self {
+        let mut names: Vec<&str> = graphs.iter().flat_map(|graph| graph.labels().iter().map(|label| label.as_str())).collect();
+        names.sort_unstable();
+        names.dedup();
+        LabelMap { ids: names.into_iter().enumerate().map(|(id, name)| (name.to_string(), id as u32)).collect() }
+    }
+
+    ///
+    /// Id of `label`, numbering it after the existing ones if it is new.
+    ///
+    pub fn insert(&mut self, label: &str) -> u32 {
+        let next = self.ids.len() as u32;
+        *self.ids.entry(label.to_string()).or_insert(next)
+    }
+
+    pub fn id(&self, label: &str) -> Option<u32> {
+        self.ids.get(label).copied()
+    }
+
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.ids.iter().find(|(_, &existing)| existing == id).map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.ids.iter().map(|(name, &id)| (name.as_str(), id))
+    }
+
+    ///
+    /// Ids of the labels of `graph` this map knows, in the graph's order.
+    ///
+    pub fn ids_of(&self, graph: &ASTGraph) -> Vec<u32> {
+        graph.labels().iter().filter_map(|label| self.id(label)).collect()
+    }
+}
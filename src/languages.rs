@@ -0,0 +1,154 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+use std::path::Path;
+use tree_sitter::Language;
+
+use crate::ASTGraph;
+use crate::analysis::kind_ids;
+
+///
+/// Which kinds of a grammar are functions, classes and methods. Functions
+/// whose nearest enclosing definition is a class count as methods, so one
+/// kind (Python's `function_definition`, say) covers both.
+///
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ExtractionProfile {
+    pub functions: HashSet<u16>,
+    pub classes: HashSet<u16>,
+    /// kinds that are always methods (Java's `method_declaration`)
+    pub methods: HashSet<u16>,
+}
+
+///
+/// Definitions found by `ExtractionProfile::extract`, each in preorder.
+///
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Extraction {
+    pub functions: Vec<NodeIndex>,
+    pub classes: Vec<NodeIndex>,
+    pub methods: Vec<NodeIndex>,
+}
+
+impl ExtractionProfile {
+    pub fn new(language: &Language, functions: &[&str], classes: &[&str], methods: &[&str]) -> Self {
+        let named = |names: &[&str]| -> HashSet<u16> { names.iter().flat_map(|name| kind_ids(language, name)).collect() };
+        ExtractionProfile { functions: named(functions), classes: named(classes), methods: named(methods) }
+    }
+
+    fn is_definition(&self, kind_id: u16) -> bool {
+        self.functions.contains(&kind_id) || self.classes.contains(&kind_id) || self.methods.contains(&kind_id)
+    }
+
+    pub fn extract(&self, graph: &ASTGraph) -> Extraction {
+        let mut extraction = Extraction::default();
+        let mut stack: Vec<NodeIndex> = graph.roots().into_iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            let kind_id = graph[node].kind_id;
+            if self.classes.contains(&kind_id) {
+                extraction.classes.push(node);
+            } else if self.methods.contains(&kind_id) {
+                extraction.methods.push(node);
+            } else if self.functions.contains(&kind_id) {
+                let mut ancestor = graph.parent(node);
+                while let Some(current) = ancestor {
+                    if self.is_definition(graph[current].kind_id) {
+                        break;
+                    }
+                    ancestor = graph.parent(current);
+                }
+                match ancestor {
+                    Some(current) if self.classes.contains(&graph[current].kind_id) => extraction.methods.push(node),
+                    _ => extraction.functions.push(node),
+                }
+            }
+            let mut children = graph.children(node);
+            children.reverse();
+            stack.extend(children);
+        }
+        extraction
+    }
+}
+
+///
+/// A grammar known to a `LanguageRegistry`, with the file extensions it
+/// handles and its extraction profile.
+///
+#[derive(Debug,Clone)]
+pub struct LanguageEntry {
+    pub name: &'static str,
+    pub language: Language,
+    pub extensions: &'static [&'static str],
+    pub profile: ExtractionProfile,
+}
+
+impl LanguageEntry {
+    pub fn new(name: &'static str, language: Language, extensions: &'static [&'static str],
+               functions: &[&str], classes: &[&str], methods: &[&str]) -> Self {
+        let profile = ExtractionProfile::new(&language, functions, classes, methods);
+        LanguageEntry { name: name, language: language, extensions: extensions, profile: profile }
+    }
+}
+
+///
+/// Grammars by name and file extension. `with_builtin` has C++ and Fortran
+/// plus the grammars enabled by the `lang-python`, `lang-rust` and
+/// `lang-java` features.
+///
+#[derive(Debug,Clone,Default)]
+pub struct LanguageRegistry {
+    entries: Vec<LanguageEntry>,
+}
+
+impl LanguageRegistry {
+    pub fn new() -> Self {
+        LanguageRegistry::default()
+    }
+
+    pub fn with_builtin() -> Self {
+        let mut registry = LanguageRegistry::new();
+        registry.register(LanguageEntry::new("cpp", tree_sitter_cpp::language(),
+            &["cpp", "cc", "cxx", "hpp", "hh", "h"],
+            &["function_definition"], &["class_specifier", "struct_specifier"], &[]));
+        registry.register(LanguageEntry::new("fortran", tree_sitter_fortran::language(),
+            &["f90", "f95", "f03", "f08", "f", "for"],
+            &["subroutine", "function"], &["derived_type_definition"], &[]));
+        #[cfg(feature="lang-python")]
+        registry.register(LanguageEntry::new("python", tree_sitter_python::LANGUAGE.into(),
+            &["py", "pyi"],
+            &["function_definition"], &["class_definition"], &[]));
+        #[cfg(feature="lang-rust")]
+        registry.register(LanguageEntry::new("rust", tree_sitter_rust::LANGUAGE.into(),
+            &["rs"],
+            &["function_item"], &["struct_item", "enum_item", "trait_item", "impl_item"], &[]));
+        #[cfg(feature="lang-java")]
+        registry.register(LanguageEntry::new("java", tree_sitter_java::LANGUAGE.into(),
+            &["java"],
+            &[], &["class_declaration", "interface_declaration", "enum_declaration", "record_declaration"],
+            &["method_declaration", "constructor_declaration"]));
+        registry
+    }
+
+    ///
+    /// Add a grammar, replacing any entry of the same name.
+    ///
+    pub fn register(&mut self, entry: LanguageEntry) {
+        self.entries.retain(|existing| existing.name != entry.name);
+        self.entries.push(entry);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LanguageEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|entry| entry.name)
+    }
+
+    pub fn for_extension(&self, extension: &str) -> Option<&LanguageEntry> {
+        self.entries.iter().find(|entry| entry.extensions.iter().any(|known| known.eq_ignore_ascii_case(extension)))
+    }
+
+    pub fn for_path<P: AsRef<Path>>(&self, path: P) -> Option<&LanguageEntry> {
+        self.for_extension(path.as_ref().extension()?.to_str()?)
+    }
+}
@@ -0,0 +1,131 @@
+use petgraph::graph::{DiGraph, NodeIndex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{ASTGraph, ids};
+use crate::error::GraphError;
+use crate::geometry::GNode;
+
+///
+/// Node of the left-child/right-sibling encoding: `left` is the first
+/// child, `right` the next sibling (positions in `LcrsTree::nodes`).
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Copy,PartialEq)]
+pub struct LcrsNode {
+    pub node: GNode,
+    pub left: Option<u32>,
+    pub right: Option<u32>,
+}
+
+///
+/// Binary-tree encoding of a graph, nodes in preorder (so the root, if any,
+/// is at 0). The roots of a forest are chained as right siblings.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq)]
+pub struct LcrsTree {
+    pub nodes: Vec<LcrsNode>,
+}
+
+impl LcrsTree {
+    pub fn root(&self) -> Option<u32> {
+        if self.nodes.is_empty() { None } else { Some(0) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Left-child/right-sibling encoding of the graph, children in source
+    /// order.
+    ///
+    pub fn to_lcrs(&self) -> LcrsTree {
+        let mut nodes: Vec<LcrsNode> = Vec::with_capacity(self.graph.node_count());
+        // sibling lists, and a preorder stack of (list, offset, position of
+        // the node whose first child it is, position of its previous sibling)
+        let mut lists = vec![self.roots()];
+        let mut stack: Vec<(usize, usize, Option<usize>, Option<usize>)> = Vec::new();
+        if !lists[0].is_empty() {
+            stack.push((0, 0, None, None));
+        }
+        while let Some((list, offset, parent, previous)) = stack.pop() {
+            let node = lists[list][offset];
+            let position = nodes.len();
+            nodes.push(LcrsNode { node: self.graph[node], left: None, right: None });
+            if let Some(parent) = parent {
+                nodes[parent].left = Some(position as u32);
+            }
+            if let Some(previous) = previous {
+                nodes[previous].right = Some(position as u32);
+            }
+
+            // the next sibling comes after this node's subtree
+            if offset + 1 < lists[list].len() {
+                stack.push((list, offset + 1, None, Some(position)));
+            }
+            let children = self.children(node);
+            if !children.is_empty() {
+                lists.push(children);
+                stack.push((lists.len() - 1, 0, Some(position), None));
+            }
+        }
+        LcrsTree { nodes }
+    }
+
+    ///
+    /// Rebuild a graph from its left-child/right-sibling encoding. Fails
+    /// unless every link points forward (as in preorder) to a node no other
+    /// link points to.
+    ///
+    pub fn from_lcrs(tree: &LcrsTree, source: String) -> Result<ASTGraph, GraphError> {
+        let mut referenced = vec![false; tree.nodes.len()];
+        for (position, lcrs_node) in tree.nodes.iter().enumerate() {
+            for link in [lcrs_node.left, lcrs_node.right].into_iter().flatten() {
+                let target = link as usize;
+                if target <= position || target >= tree.nodes.len() || referenced[target] {
+                    return Err(GraphError::Encoding(format!("invalid link from node {} to {}", position, link)));
+                }
+                referenced[target] = true;
+            }
+        }
+
+        let mut digraph = DiGraph::new();
+        let mut node_map = HashMap::new();
+        for lcrs_node in tree.nodes.iter() {
+            let index = digraph.add_node(lcrs_node.node);
+            node_map.insert(index, lcrs_node.node.id);
+        }
+        // walk each sibling chain, linking its members to their parent
+        let mut stack: Vec<(u32, Option<u32>)> = tree.root().map(|root| (root, None)).into_iter().collect();
+        while let Some((position, parent)) = stack.pop() {
+            let mut current = Some(position);
+            while let Some(member) = current {
+                if let Some(parent) = parent {
+                    digraph.add_edge(NodeIndex::new(parent as usize), NodeIndex::new(member as usize), ());
+                }
+                let lcrs_node = &tree.nodes[member as usize];
+                if let Some(left) = lcrs_node.left {
+                    stack.push((left, Some(member)));
+                }
+                current = lcrs_node.right;
+            }
+        }
+        Ok(ASTGraph {
+            graph: digraph,
+            node_map: node_map,
+            source: Some(source.into()),
+            source_start: Default::default(),
+            title: "".to_string(),
+            metadata: Default::default(),
+            labels: Vec::new(),
+            graph_id: ids::next_graph_id(),
+            leaf_text: None,
+        })
+    }
+}
@@ -1,9 +1,14 @@
-use petgraph::algo::astar;
 use petgraph::graph::{DiGraph, NodeIndex};
-use petgraph::visit::{EdgeRef, Bfs, Dfs, Reversed};
+use petgraph::visit::{Bfs, Dfs, Reversed};
+use petgraph::Direction;
 use tree_sitter::{Node, Tree};
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::Index;
+use std::path::Path;
 use serde::{Deserialize, Serialize};
 use bincode::{serialize_into, deserialize_from};
 use fixedbitset::FixedBitSet;
@@ -11,6 +16,79 @@ use fixedbitset::FixedBitSet;
 pub mod geometry;
 use geometry::{GNode,GRange,Edge};
 
+pub mod algebra;
+pub mod analysis;
+pub mod arena;
+pub mod cached;
+pub mod cancel;
+pub mod checksum;
+pub mod chunked;
+pub mod compact;
+pub mod convert;
+pub mod cursor;
+pub mod dataset;
+pub mod delta;
+pub mod edit;
+pub mod error;
+pub mod excerpt;
+use error::GraphError;
+
+pub mod export;
+pub mod features;
+#[cfg(feature="test-corpus")]
+pub mod fixtures;
+pub mod forest;
+pub mod formats;
+#[cfg(feature="grpc")]
+pub mod grpc;
+pub mod hashing;
+#[cfg(feature="highlight")]
+pub mod highlight;
+#[cfg(feature="http")]
+pub mod http;
+pub mod hunks;
+pub mod ids;
+pub mod lcrs;
+pub mod limits;
+pub mod overlay;
+pub mod page;
+#[cfg(feature="parallel")]
+pub mod parallel;
+pub mod owned;
+pub mod paths;
+pub mod payload;
+pub mod prelude;
+pub mod project;
+pub mod provenance;
+pub mod redact;
+pub mod remap;
+pub mod rewrite;
+pub mod rules;
+pub mod sample;
+pub mod search;
+#[cfg(feature="server")]
+pub mod server;
+pub mod service;
+pub mod source;
+pub mod stats;
+pub mod store;
+pub mod succinct;
+pub mod summary;
+pub mod text;
+pub mod tokens;
+pub mod visit;
+pub mod window;
+pub mod import;
+pub mod kinds;
+pub mod interner;
+pub mod labels;
+pub mod iter;
+pub mod languages;
+pub mod manifest;
+#[cfg(feature="mmap")]
+pub mod mapped;
+pub mod minimize;
+
 // Import the test module
 #[cfg(test)]
 mod tests;
@@ -21,23 +99,37 @@ use petgraph::dot::{Dot, Config};
 
 ///
 /// Serializable graph -- as PetGraph doesn't provide a direct means
-/// to do this.
+/// to do this. Carries the title, metadata and labels along with the
+/// structure, so every format built on it keeps them.
 /// 
-#[derive(Serialize,Deserialize)]
+#[derive(Serialize,Deserialize,Default)]
 pub struct SerializableGraph {
+    pub title: String,
+    pub metadata: BTreeMap<String,String>,
+    pub labels: Vec<String>,
     pub nodes: Vec<GNode>,
     pub edges: Vec<Edge>,
 }
 
+///
+/// Version of the layout `write_to` writes and `read_from` accepts.
+///
+pub const FORMAT_VERSION: u32 = 3;
+
 ///
 /// AST Graph
 /// 
 #[derive(Debug,Clone)]
 pub struct ASTGraph {
-    pub graph: DiGraph<GNode,()>,
+    graph: DiGraph<GNode,()>, // read through graph(), changed only through the methods below
     node_map: HashMap<NodeIndex,usize>,
-    source: String,
+    source: Option<source::SourceBuffer>, // absent for deserialized graphs, see source()
+    source_start: source::SourceStart, // where source begins in the file, see set_source_at
     title: String, // title of the graph
+    metadata: BTreeMap<String,String>, // provenance: commit, path, parser version, ...
+    labels: Vec<String>, // dataset tags, see add_label
+    graph_id: u64, // tags AstNodeIds handed out by this graph
+    leaf_text: Option<text::LeafText>, // interned leaf text, see store_leaf_text
 }
 
 impl ASTGraph {
@@ -45,8 +137,13 @@ impl ASTGraph {
         ASTGraph {
             graph: DiGraph::new(),
             node_map: HashMap::new(),
-            source: source_code,
+            source: Some(source_code.into()),
+            source_start: Default::default(),
             title: "".to_string(),
+            metadata: BTreeMap::new(),
+            labels: Vec::new(),
+            graph_id: ids::next_graph_id(),
+            leaf_text: None,
         }
     }
 
@@ -58,6 +155,28 @@ impl ASTGraph {
         self.title = new_title;
     }
 
+    ///
+    /// Key/value provenance of the graph (commit hash, file path, parser
+    /// version, pipeline tags). Saved with the title by every format, and
+    /// carried over to subgraphs; building from a tree adds the `provenance`
+    /// keys.
+    ///
+    pub fn metadata(&self) -> &BTreeMap<String,String> {
+        &self.metadata
+    }
+
+    pub fn metadata_value(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|value| value.as_str())
+    }
+
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> Option<String> {
+        self.metadata.insert(key.to_string(), value.to_string())
+    }
+
+    pub fn remove_metadata(&mut self, key: &str) -> Option<String> {
+        self.metadata.remove(key)
+    }
+
     pub fn name(&self) -> String {
         // naming scheme to come up with unique names for graphs and subgraphs
         let root_index:NodeIndex = 1.into();
@@ -69,6 +188,56 @@ impl ASTGraph {
         self.node_map.len()
     }
 
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    ///
+    /// The underlying petgraph graph, read-only: use it with petgraph's
+    /// algorithms. Changes go through `add_node`/`add_gnode`, `add_edge(s)`,
+    /// `remove_subtree` and `reroot`, which keep the graph's own maps in step.
+    ///
+    pub fn graph(&self) -> &DiGraph<GNode,()> {
+        &self.graph
+    }
+
+    ///
+    /// Nodes without a parent -- a graph built from a single tree has exactly one.
+    ///
+    pub fn roots(&self) -> Vec<NodeIndex> {
+        self.graph.externals(Direction::Incoming).collect()
+    }
+
+    pub fn root(&self) -> Option<NodeIndex> {
+        self.roots().into_iter().next()
+    }
+
+    pub fn parent(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.graph.neighbors_directed(node, Direction::Incoming).next()
+    }
+
+    ///
+    /// Children of a node in source order (petgraph yields neighbors in
+    /// reverse insertion order, and subgraphs are built from unordered sets).
+    ///
+    pub fn children(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut children: Vec<NodeIndex> = self.graph.neighbors_directed(node, Direction::Outgoing).collect();
+        children.sort_by_key(|&c| (self.graph[c].range.start_byte, self.graph[c].range.end_byte, c.index()));
+        children
+    }
+
+    ///
+    /// Number of nodes in the subtree rooted at `node`, including `node` itself.
+    ///
+    pub fn subtree_size(&self, node: NodeIndex) -> usize {
+        let mut count = 0;
+        let mut bfs = Bfs::new(&self.graph, node);
+        while bfs.next(&self.graph).is_some() {
+            count += 1;
+        }
+        count
+    }
+
     pub fn add_node(&mut self, tree_node: Node ) -> NodeIndex {
         let id = tree_node.id();
         let kind_id = tree_node.kind_id();
@@ -77,29 +246,64 @@ impl ASTGraph {
             id: id,
             kind_id: kind_id,
             range: range,
+            is_named: tree_node.is_named(),
+            source_hash: 0,
         };
-        let node_index = self.graph.add_node( new_node );
-        self.node_map.insert(node_index, id );
+        self.add_gnode(new_node)
+    }
+
+    ///
+    /// Add a node from its data alone, e.g. when building a graph from
+    /// another representation.
+    ///
+    pub fn add_gnode(&mut self, gnode: GNode) -> NodeIndex {
+        let node_index = self.graph.add_node(gnode);
+        self.node_map.insert(node_index, gnode.id);
         node_index
     }
 
+    ///
+    /// Data of `node`, or `None` if the graph has no such node; `graph[node]`
+    /// is the panicking form.
+    ///
+    pub fn get(&self, node: NodeIndex) -> Option<&GNode> {
+        self.graph.node_weight(node)
+    }
+
     pub fn get_node(&self, id:NodeIndex) -> Option<usize> {
         self.node_map.get(&id).cloned()
     }
 
+    ///
+    /// Source text of a node. Panics, saying why, when the graph has no
+    /// source or the node's range isn't in it; see `try_node_source`.
+    ///
     pub fn get_node_source(&self, id:NodeIndex) -> &str {
-        let graph_node = self.graph[id];
-        let slice = &self.source[graph_node.range.start_byte..graph_node.range.end_byte];
-        slice
+        match self.try_node_source(id) {
+            Ok(slice) => slice,
+            Err(err) => panic!("{}", err),
+        }
     }
 
     pub fn add_edge(&mut self, parent: NodeIndex, child: NodeIndex) {
         self.graph.add_edge(parent, child, ());
     }
 
+    ///
+    /// Add many (parent, child) edges at once, reserving room for all of them
+    /// up front.
+    ///
+    pub fn add_edges(&mut self, edges: &[(NodeIndex, NodeIndex)]) {
+        self.graph.reserve_edges(edges.len());
+        for &(parent, child) in edges {
+            self.graph.add_edge(parent, child, ());
+        }
+    }
+
     pub fn build_from_tree(&mut self, tree: &Tree) {
         let root_node = tree.root_node();
         self.traverse_and_build(root_node, None);
+        self.record_provenance(tree);
     }
 
     pub fn traverse_and_build(&mut self, tree_node:Node, parent: Option<NodeIndex>) {
@@ -117,20 +321,64 @@ impl ASTGraph {
     }
 
     pub fn extract_subgraphs(&self, kinds_to_split_on:HashSet<u16>) -> Vec<ASTGraph> {
-        let mut subgraphs = Vec::new();
-
-        for node in self.graph.node_indices() {
-            if kinds_to_split_on.contains( &self.graph[node].kind_id ) {
-                let node_range = &self.graph[node].range;
-                let split_source = &self.source[node_range.start_byte..node_range.end_byte];
-                let subgraph_nodes = self.collect_subgraph_nodes(node);
-                let mut subgraph = self.create_subgraph(&subgraph_nodes);
-                subgraph.source = split_source.to_string();
-                subgraphs.push(subgraph);
+        self.split_subgraphs(&kinds_to_split_on, None).unwrap_or_default()
+    }
+
+    ///
+    /// `extract_subgraphs`, checking `cancel` between subgraphs.
+    ///
+    pub fn extract_subgraphs_cancellable(&self, kinds_to_split_on:HashSet<u16>, cancel: &cancel::CancelToken) -> Result<Vec<ASTGraph>, GraphError> {
+        self.split_subgraphs(&kinds_to_split_on, Some(cancel)).ok_or(GraphError::Cancelled)
+    }
+
+    // None once `cancel` is cancelled
+    fn split_subgraphs(&self, kinds_to_split_on: &HashSet<u16>, cancel: Option<&cancel::CancelToken>) -> Option<Vec<ASTGraph>> {
+        let matches: Vec<NodeIndex> = self.graph.node_indices()
+            .filter(|&node| kinds_to_split_on.contains( &self.graph[node].kind_id ))
+            .collect();
+        let mut members = self.match_members(&matches);
+
+        let mut subgraphs = Vec::with_capacity(matches.len());
+        for (&node, subgraph_nodes) in matches.iter().zip(members.drain(..)) {
+            if cancel.is_some_and(|cancel| cancel.is_cancelled()) {
+                return None;
             }
+            let node_range = &self.graph[node].range;
+            let mut subgraph = self.create_subgraph(&subgraph_nodes);
+            subgraph.source = self.range_text(node_range.start_byte, node_range.end_byte).map(|split_source| split_source.into());
+            subgraph.source_start = source::SourceStart::of(node_range);
+            subgraphs.push(subgraph);
         }
 
-        subgraphs
+        Some(subgraphs)
+    }
+
+    ///
+    /// The subtree nodes of each of `matches`, found in one traversal: every
+    /// node goes to all matches above it, so nested matches don't walk their
+    /// shared descendants again.
+    ///
+    fn match_members(&self, matches: &[NodeIndex]) -> Vec<HashSet<NodeIndex>> {
+        let positions: HashMap<NodeIndex, usize> = matches.iter().enumerate().map(|(position, &node)| (node, position)).collect();
+        let mut members = vec![HashSet::new(); matches.len()];
+        // (match position, depth of the match) of the matches above the current node
+        let mut active: Vec<(usize, usize)> = Vec::new();
+        let mut stack: Vec<(NodeIndex, usize)> = self.roots().into_iter().map(|root| (root, 0)).collect();
+
+        while let Some((node, depth)) = stack.pop() {
+            while active.last().is_some_and(|&(_, active_depth)| active_depth >= depth) {
+                active.pop();
+            }
+            if let Some(&position) = positions.get(&node) {
+                active.push((position, depth));
+            }
+            for &(position, _) in active.iter() {
+                members[position].insert(node);
+            }
+            stack.extend(self.graph.neighbors(node).map(|child| (child, depth + 1)));
+        }
+
+        members
     }
 
     ///
@@ -166,20 +414,28 @@ impl ASTGraph {
             original_mapping.insert(new_node,original_id );
         }
 
-        for edge in self.graph.edge_references() {
-            let source = edge.source();
-            let target = edge.target();
-            if subgraph_nodes.contains(&source) && subgraph_nodes.contains(&target) {
-                digraph.add_edge(node_map[&source], node_map[&target], ());
+        // only the edges leaving subgraph nodes, not every edge of the graph
+        let mut edges: Vec<(NodeIndex, NodeIndex)> = Vec::with_capacity(subgraph_nodes.len());
+        for &node in subgraph_nodes {
+            for child in self.graph.neighbors(node) {
+                if let Some(&new_child) = node_map.get(&child) {
+                    edges.push((node_map[&node], new_child));
+                }
             }
         }
 
-        let subgraph = ASTGraph {
+        let mut subgraph = ASTGraph {
             graph: digraph,
             node_map: original_mapping,
             source: self.source.clone(),
-            title: "".to_string()
+            source_start: self.source_start,
+            title: "".to_string(),
+            metadata: self.metadata.clone(),
+            labels: self.labels.clone(),
+            graph_id: ids::next_graph_id(),
+            leaf_text: self.leaf_text.as_ref().map(|leaf_text| leaf_text.remap(&node_map)),
         };
+        subgraph.add_edges(&edges);
 
         subgraph
     }
@@ -194,7 +450,7 @@ impl ASTGraph {
                     target: target,
                 }
             }).collect();
-        SerializableGraph { nodes, edges }
+        SerializableGraph { title: self.title.clone(), metadata: self.metadata.clone(), labels: self.labels.clone(), nodes, edges }
     }
 
     pub fn from_serializable(serializable_graph: SerializableGraph) -> Self {
@@ -217,10 +473,61 @@ impl ASTGraph {
         ASTGraph {
             graph,
             node_map,
-            source: "".to_string(), // Update according to your needs
-            title: "".to_string(),
+            source: None,
+            source_start: Default::default(),
+            title: serializable_graph.title,
+            metadata: serializable_graph.metadata,
+            labels: serializable_graph.labels,
+            graph_id: ids::next_graph_id(),
+            leaf_text: None,
         }
     }
+
+    ///
+    /// `from_serializable` for graphs decoded from bytes we didn't write: an
+    /// edge naming a node the graph doesn't have is an encoding error rather
+    /// than a panic.
+    ///
+    pub(crate) fn try_from_serializable(serializable_graph: SerializableGraph) -> Result<Self, GraphError> {
+        let node_count = serializable_graph.nodes.len();
+        if let Some(edge) = serializable_graph.edges.iter().find(|edge| edge.source.index() >= node_count || edge.target.index() >= node_count) {
+            return Err(GraphError::Encoding(format!("edge {} -> {} references a missing node", edge.source.index(), edge.target.index())));
+        }
+        Ok(ASTGraph::from_serializable(serializable_graph))
+    }
+
+    ///
+    /// Serialize (bincode) into any writer -- a file, socket, compression
+    /// wrapper or in-memory buffer. The header holds `FORMAT_VERSION`, the
+    /// title, the metadata (provenance included) and the labels, ahead of
+    /// the structure.
+    ///
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), GraphError> {
+        serialize_into(&mut writer, &FORMAT_VERSION)?;
+        serialize_into(&mut writer, &self.to_serializable())?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> Result<ASTGraph, GraphError> {
+        let version: u32 = deserialize_from(&mut reader)?;
+        if version != FORMAT_VERSION {
+            return Err(GraphError::Encoding(format!("unsupported graph file version {}", version)));
+        }
+        let serializable_graph: SerializableGraph = deserialize_from(reader)?;
+        ASTGraph::try_from_serializable(serializable_graph)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<ASTGraph, GraphError> {
+        ASTGraph::read_from(BufReader::new(File::open(path)?))
+    }
+
     /// 
     /// Iterators
     ///
@@ -239,20 +546,8 @@ impl ASTGraph {
 
 
     pub fn path_from_to(&self, start_node: NodeIndex, goal: NodeIndex) -> Option<Vec<NodeIndex>> {
-        // use the A* algorithm to get the short path from start -> goal
-        let result = astar(
-            &self.graph,
-            start_node,
-            |finish| finish == goal,
-            |_| 1, // edge cost (uniform cost here)
-            |_| 0, // heuristic cost
-        );
-
-        if let Some((_, path)) = result {
-            Some(path)
-        } else {
-            None
-        }
+        // A* without a heuristic; see path_from_to_with for a guided search
+        self.path_from_to_with(start_node, goal, &paths::Heuristic::Zero)
     }
 
     #[cfg(feature="informational")]
@@ -273,3 +568,15 @@ impl ASTGraph {
     }
 
 }
+
+///
+/// `ast_graph[node]` reads a node's data. There is deliberately no
+/// `IndexMut`: changing a node's id in place would leave `node_map` stale.
+///
+impl Index<NodeIndex> for ASTGraph {
+    type Output = GNode;
+
+    fn index(&self, node: NodeIndex) -> &GNode {
+        &self.graph[node]
+    }
+}
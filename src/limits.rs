@@ -0,0 +1,128 @@
+use petgraph::graph::NodeIndex;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tree_sitter::Tree;
+
+use crate::ASTGraph;
+use crate::cancel::CancelToken;
+use crate::error::GraphError;
+
+// nodes built between two looks at the clock
+const CLOCK_INTERVAL: usize = 1024;
+
+///
+/// Bounds on building a graph, for services ingesting arbitrary uploaded
+/// files: going over one fails with `GraphError::LimitExceeded` instead of
+/// running the process out of memory or time. `None` means unbounded.
+///
+#[derive(Debug,Clone,Copy,Default,PartialEq)]
+pub struct BuildLimits {
+    pub max_nodes: Option<usize>,
+    pub max_bytes: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+///
+/// The limit a build went over.
+///
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Limit {
+    Nodes(usize),
+    Bytes(usize),
+    Timeout(Duration),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Nodes(max) => write!(f, "more than {} nodes", max),
+            Limit::Bytes(max) => write!(f, "source over {} bytes", max),
+            Limit::Timeout(timeout) => write!(f, "build reached its {:?} timeout", timeout),
+        }
+    }
+}
+
+impl BuildLimits {
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    ///
+    /// Check the size of a source before spending time parsing it.
+    ///
+    pub fn check_source(&self, source: &str) -> Result<(), GraphError> {
+        match self.max_bytes {
+            Some(max_bytes) if source.len() > max_bytes => Err(GraphError::LimitExceeded(Limit::Bytes(max_bytes))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// `build_from_tree` on a new graph over `source`, within `limits`.
+    ///
+    pub fn build_with_limits(tree: &Tree, source: String, limits: &BuildLimits) -> Result<ASTGraph, GraphError> {
+        ASTGraph::build_checked(tree, source, limits, None)
+    }
+
+    // build within `limits`, looking at the clock and `cancel` every
+    // CLOCK_INTERVAL nodes
+    pub(crate) fn build_checked(tree: &Tree, source: String, limits: &BuildLimits, cancel: Option<&CancelToken>)
+        -> Result<ASTGraph, GraphError> {
+        limits.check_source(&source)?;
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+        let started = Instant::now();
+        let mut graph = ASTGraph::new(source);
+
+        // preorder like build_from_tree, with the open ancestors on a stack
+        let mut ancestors: Vec<NodeIndex> = Vec::new();
+        let mut cursor = tree.walk();
+        'walk: loop {
+            let count = graph.graph.node_count();
+            if limits.max_nodes.is_some_and(|max_nodes| count >= max_nodes) {
+                return Err(GraphError::LimitExceeded(Limit::Nodes(limits.max_nodes.unwrap())));
+            }
+            if count % CLOCK_INTERVAL == 0 {
+                if let Some(timeout) = limits.timeout {
+                    if started.elapsed() >= timeout {
+                        return Err(GraphError::LimitExceeded(Limit::Timeout(timeout)));
+                    }
+                }
+                if let Some(cancel) = cancel {
+                    cancel.check()?;
+                }
+            }
+
+            let node = graph.add_node(cursor.node());
+            if let Some(&parent) = ancestors.last() {
+                graph.add_edge(parent, node);
+            }
+            if cursor.goto_first_child() {
+                ancestors.push(node);
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+                ancestors.pop();
+            }
+        }
+        graph.record_provenance(tree);
+        Ok(graph)
+    }
+}
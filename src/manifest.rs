@@ -0,0 +1,158 @@
+use serde::{Serialize,Deserialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::export::sanitize_file_name;
+use crate::geometry::GRange;
+use crate::hashing::Fnv64;
+use crate::labels::LabelMap;
+
+///
+/// Name of the manifest written next to a batch of artifacts.
+///
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+///
+/// One artifact of a batch: its file name within the batch directory, the
+/// title, labels, range of the root and size of the graph it holds, and the
+/// FNV-1a hash (16 hex digits) of the bytes written, for validation.
+///
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct ManifestEntry {
+    pub file: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    pub range: Option<GRange>,
+    pub hash: String,
+    pub node_count: usize,
+}
+
+///
+/// What a batch writer (`export::write_all_dot`, the dataset writers) put
+/// in a directory, in the order written. With the `manifest` feature it is
+/// also saved there as `manifest.json`, for downstream jobs to discover and
+/// check the artifacts. Dataset writers also record the ids of the labels.
+///
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub entries: Vec<ManifestEntry>,
+    #[serde(default, skip_serializing_if = "LabelMap::is_empty")]
+    pub label_map: LabelMap,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest { version: MANIFEST_VERSION, entries: Vec::new(), label_map: LabelMap::new() }
+    }
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hasher = Fnv64::new();
+    hasher.write(bytes);
+    format!("{:016x}", hasher.finish())
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Manifest::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, file: &str) -> Option<&ManifestEntry> {
+        self.entries.iter().find(|entry| entry.file == file)
+    }
+
+    ///
+    /// File name for the graph at `position` of a batch: its sanitized
+    /// title, or `graph_<position>` when it has none, with a `_<n>` suffix
+    /// if an earlier entry already took the name.
+    ///
+    pub(crate) fn file_name_for(&self, graph: &ASTGraph, position: usize, extension: &str) -> String {
+        let mut stem = sanitize_file_name(&graph.title());
+        if stem.is_empty() {
+            stem = format!("graph_{}", position);
+        }
+        let used: HashSet<&str> = self.entries.iter().map(|entry| entry.file.as_str()).collect();
+        let mut file = format!("{}.{}", stem, extension);
+        let mut copy = 2;
+        while used.contains(file.as_str()) || file == MANIFEST_FILE {
+            file = format!("{}_{}.{}", stem, copy, extension);
+            copy += 1;
+        }
+        file
+    }
+
+    ///
+    /// Write `bytes` (the encoding of `graph`) to `dir/file` and record it.
+    ///
+    pub(crate) fn write_artifact(&mut self, dir: &Path, file: String, graph: &ASTGraph, bytes: &[u8]) -> Result<(), GraphError> {
+        fs::write(dir.join(&file), bytes)?;
+        self.entries.push(ManifestEntry {
+            file: file,
+            title: graph.title(),
+            labels: graph.labels().to_vec(),
+            range: graph.root().map(|root| graph.graph[root].range),
+            hash: content_hash(bytes),
+            node_count: graph.graph.node_count(),
+        });
+        Ok(())
+    }
+
+    ///
+    /// Entries whose file in `dir` is missing or no longer has the recorded
+    /// hash.
+    ///
+    pub fn verify<P: AsRef<Path>>(&self, dir: P) -> Vec<&ManifestEntry> {
+        self.entries.iter()
+            .filter(|entry| match fs::read(dir.as_ref().join(&entry.file)) {
+                Ok(bytes) => content_hash(&bytes) != entry.hash,
+                Err(_) => true,
+            })
+            .collect()
+    }
+
+    #[cfg(feature="manifest")]
+    pub fn to_json(&self) -> Result<String, GraphError> {
+        serde_json::to_string_pretty(self).map_err(|err| GraphError::Encoding(err.to_string()))
+    }
+
+    #[cfg(feature="manifest")]
+    pub fn from_json(json: &str) -> Result<Manifest, GraphError> {
+        let manifest: Manifest = serde_json::from_str(json).map_err(|err| GraphError::Encoding(err.to_string()))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(GraphError::Encoding(format!("unsupported manifest version {}", manifest.version)));
+        }
+        Ok(manifest)
+    }
+
+    ///
+    /// Save as `manifest.json` in `dir`.
+    ///
+    #[cfg(feature="manifest")]
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<(), GraphError> {
+        fs::write(dir.as_ref().join(MANIFEST_FILE), self.to_json()?)?;
+        Ok(())
+    }
+
+    ///
+    /// Read the `manifest.json` of a batch directory.
+    ///
+    #[cfg(feature="manifest")]
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Manifest, GraphError> {
+        Manifest::from_json(&fs::read_to_string(dir.as_ref().join(MANIFEST_FILE))?)
+    }
+}
@@ -0,0 +1,67 @@
+use memmap2::Mmap;
+use petgraph::graph::NodeIndex;
+use std::fs::File;
+use std::path::Path;
+use tree_sitter::{Language, Parser};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+
+///
+/// An `ASTGraph` over a memory-mapped source file, for sources too large to
+/// copy into a `String` (generated Fortran of hundreds of MB). The file is
+/// parsed straight from the map and node text borrows from it.
+///
+/// The inner graph carries no source of its own: read node text through
+/// `get_node_source` here, or `into_owned` for the methods that need the
+/// source in the graph. As with any mapping, the file must not change while
+/// it is mapped.
+///
+pub struct MappedAstGraph {
+    graph: ASTGraph,
+    map: Mmap,
+}
+
+impl MappedAstGraph {
+    pub fn open<P: AsRef<Path>>(path: P, language: &Language) -> Result<Self, GraphError> {
+        let file = File::open(path.as_ref())?;
+        // SAFETY: the map is read-only; the caller keeps the file unchanged
+        // while it is mapped (see the type's documentation)
+        let map = unsafe { Mmap::map(&file)? };
+        std::str::from_utf8(&map).map_err(|err| GraphError::Encoding(format!("source is not UTF-8: {}", err)))?;
+
+        let mut parser = Parser::new();
+        parser.set_language(language).map_err(|err| GraphError::Query(err.to_string()))?;
+        let tree = parser.parse(&map[..], None)
+            .ok_or_else(|| GraphError::Query("parsing the source failed".to_string()))?;
+
+        let mut graph = ASTGraph::new(String::new());
+        graph.build_from_tree(&tree);
+        graph.source = None;
+        graph.set_title(path.as_ref().display().to_string());
+        Ok(MappedAstGraph { graph, map })
+    }
+
+    pub fn graph(&self) -> &ASTGraph {
+        &self.graph
+    }
+
+    pub fn source(&self) -> &str {
+        // SAFETY: checked to be UTF-8 in `open`, and the map is read-only
+        unsafe { std::str::from_utf8_unchecked(&self.map) }
+    }
+
+    pub fn get_node_source(&self, node: NodeIndex) -> &str {
+        let range = self.graph[node].range;
+        &self.source()[range.start_byte..range.end_byte]
+    }
+
+    ///
+    /// The graph with the source copied into it.
+    ///
+    pub fn into_owned(self) -> ASTGraph {
+        let mut graph = self.graph;
+        graph.set_source(self.source().to_string());
+        graph
+    }
+}
@@ -0,0 +1,68 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+
+use crate::ASTGraph;
+
+impl ASTGraph {
+    // `kept` without the subtrees of `roots`
+    fn without_subtrees(&self, kept: &HashSet<NodeIndex>, roots: &[NodeIndex]) -> HashSet<NodeIndex> {
+        let mut remaining = kept.clone();
+        for &root in roots {
+            for node in self.collect_subgraph_nodes(root) {
+                remaining.remove(&node);
+            }
+        }
+        remaining
+    }
+
+    ///
+    /// Shrink the graph to a small one on which `still_fails` holds, for
+    /// turning a crash on a real-world file into a test case. Subtrees are
+    /// removed level by level (hierarchical delta debugging): at each depth
+    /// ever smaller groups of the nodes there are dropped as long as the
+    /// property survives. Returns a copy of the graph if it does not hold to
+    /// begin with; chain with `redacted` to share the result.
+    ///
+    pub fn minimize_for_repro<F: FnMut(&ASTGraph) -> bool>(&self, mut still_fails: F) -> ASTGraph {
+        if !still_fails(self) {
+            return self.clone();
+        }
+        let mut kept: HashSet<NodeIndex> = self.graph.node_indices().collect();
+        let mut level: Vec<NodeIndex> = self.roots();
+
+        while !level.is_empty() {
+            let mut candidates = level.clone();
+            let mut chunk = candidates.len().div_ceil(2).max(1);
+            loop {
+                let mut reduced = false;
+                let mut start = 0;
+                while start < candidates.len() {
+                    let end = (start + chunk).min(candidates.len());
+                    let remaining = self.without_subtrees(&kept, &candidates[start..end]);
+                    if still_fails(&self.create_subgraph(&remaining)) {
+                        kept = remaining;
+                        candidates.drain(start..end);
+                        reduced = true;
+                    } else {
+                        start = end;
+                    }
+                }
+                if chunk == 1 && !reduced {
+                    break;
+                }
+                if !reduced {
+                    chunk = chunk.div_ceil(2);
+                }
+                if candidates.is_empty() {
+                    break;
+                }
+            }
+            level = candidates.iter().flat_map(|&node| self.graph.neighbors(node)).collect();
+            level.sort();
+        }
+
+        let mut minimized = self.create_subgraph(&kept);
+        minimized.set_title(self.title.clone());
+        minimized
+    }
+}
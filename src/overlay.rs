@@ -0,0 +1,585 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::{Read, Write};
+
+use crate::ASTGraph;
+use crate::analysis::{callee_name, LanguageKinds};
+use crate::analysis::signature::function_signatures;
+use crate::error::GraphError;
+use crate::ids::StableId;
+
+// bounded repetitions are unrolled into the automaton, so keep them small
+const MAX_REPEAT: usize = 64;
+
+///
+/// Label of an overlay edge. `Child` and `Sibling` come from the tree,
+/// `Call` and `Flow` from analyses.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub enum EdgeKind {
+    /// parent to child
+    Child,
+    /// node to its next sibling
+    Sibling,
+    /// call to the definition of the called function
+    Call,
+    /// data flow between two nodes
+    Flow,
+}
+
+impl EdgeKind {
+    fn from_name(name: &str) -> Option<EdgeKind> {
+        match name {
+            "Child" => Some(EdgeKind::Child),
+            "Sibling" => Some(EdgeKind::Sibling),
+            "Call" => Some(EdgeKind::Call),
+            "Flow" => Some(EdgeKind::Flow),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Labeled edges over the nodes of a graph: the tree's own edges plus
+/// whatever semantic edges are added on top.
+///
+#[derive(Debug,Clone)]
+pub struct Overlay<'g> {
+    graph: &'g ASTGraph,
+    edges: HashMap<NodeIndex, Vec<(EdgeKind, NodeIndex)>>,
+}
+
+impl<'g> Overlay<'g> {
+    ///
+    /// Overlay with the `Child` and `Sibling` edges of `graph`.
+    ///
+    pub fn new(graph: &'g ASTGraph) -> Self {
+        let mut overlay = Overlay { graph: graph, edges: HashMap::new() };
+        for node in graph.graph.node_indices() {
+            let children = graph.children(node);
+            for &child in children.iter() {
+                overlay.add_edge(node, child, EdgeKind::Child);
+            }
+            for pair in children.windows(2) {
+                overlay.add_edge(pair[0], pair[1], EdgeKind::Sibling);
+            }
+        }
+        overlay
+    }
+
+    pub fn graph(&self) -> &'g ASTGraph {
+        self.graph
+    }
+
+    ///
+    /// Add an edge of `kind`. Edges of different kinds between the same two
+    /// nodes coexist (a call that is also a flow); adding the same edge
+    /// twice keeps one.
+    ///
+    pub fn add_edge(&mut self, from: NodeIndex, to: NodeIndex, kind: EdgeKind) {
+        let edges = self.edges.entry(from).or_default();
+        if !edges.contains(&(kind, to)) {
+            edges.push((kind, to));
+        }
+    }
+
+    ///
+    /// Drop every edge of one layer, e.g. to recompute `Call` edges after an
+    /// edit without touching the tree layers.
+    ///
+    pub fn remove_layer(&mut self, kind: EdgeKind) {
+        for edges in self.edges.values_mut() {
+            edges.retain(|&(edge_kind, _)| edge_kind != kind);
+        }
+        self.edges.retain(|_, edges| !edges.is_empty());
+    }
+
+    ///
+    /// Add a `Call` edge from every call to the functions of the graph with
+    /// the called name (resolved by name only). Fails if the graph has no
+    /// source to read the names from.
+    ///
+    pub fn add_calls(&mut self, kinds: &LanguageKinds) -> Result<(), GraphError> {
+        let mut definitions: HashMap<String, Vec<NodeIndex>> = HashMap::new();
+        for signature in function_signatures(self.graph, kinds)? {
+            definitions.entry(signature.name).or_default().push(signature.node);
+        }
+        let graph = self.graph;
+        for call in graph.graph.node_indices().filter(|&node| kinds.calls.contains(&graph.graph[node].kind_id)) {
+            if let Some(targets) = callee_name(graph, call, kinds).and_then(|name| definitions.get(name)) {
+                for &target in targets {
+                    self.add_edge(call, target, EdgeKind::Call);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn edges(&self, node: NodeIndex) -> &[(EdgeKind, NodeIndex)] {
+        self.edges.get(&node).map(|edges| edges.as_slice()).unwrap_or(&[])
+    }
+
+    ///
+    /// Targets of the edges of one layer leaving `node`, in insertion order.
+    ///
+    pub fn edges_of_kind(&self, node: NodeIndex, kind: EdgeKind) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.edges(node).iter().filter(move |&&(edge_kind, _)| edge_kind == kind).map(|&(_, target)| target)
+    }
+
+    ///
+    /// (from, to) pairs of one layer, sorted.
+    ///
+    pub fn layer(&self, kind: EdgeKind) -> Vec<(NodeIndex, NodeIndex)> {
+        let mut pairs: Vec<(NodeIndex, NodeIndex)> = self.edges.iter()
+            .flat_map(|(&from, edges)| edges.iter().filter(move |&&(edge_kind, _)| edge_kind == kind).map(move |&(_, to)| (from, to)))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    ///
+    /// Depth-first walk of one layer from `start`, each node once, following
+    /// edges in insertion order. Walking `Child` visits exactly the subtree
+    /// whatever other layers were added.
+    ///
+    pub fn walk_layer_from(&self, start: NodeIndex, kind: EdgeKind) -> Vec<NodeIndex> {
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        self.walk(start, kind, &mut visited, &mut order);
+        order
+    }
+
+    ///
+    /// Depth-first walk of the whole of one layer: from every node without
+    /// an incoming edge of the layer (roots, for `Child`), then from whatever
+    /// is left in cycles, in index order. Nodes without edges of the layer
+    /// are left out, except the roots of the `Child` layer.
+    ///
+    pub fn walk_layer(&self, kind: EdgeKind) -> Vec<NodeIndex> {
+        let layer = self.layer(kind);
+        let targets: HashSet<NodeIndex> = layer.iter().map(|&(_, to)| to).collect();
+        let mut starts: Vec<NodeIndex> = match kind {
+            EdgeKind::Child => self.graph.roots(),
+            _ => layer.iter().map(|&(from, _)| from).filter(|from| !targets.contains(from)).collect(),
+        };
+        starts.sort();
+        starts.dedup();
+        starts.extend(layer.iter().map(|&(from, _)| from));
+
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        for start in starts {
+            self.walk(start, kind, &mut visited, &mut order);
+        }
+        order
+    }
+
+    fn walk(&self, start: NodeIndex, kind: EdgeKind, visited: &mut HashSet<NodeIndex>, order: &mut Vec<NodeIndex>) {
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            order.push(node);
+            let next: Vec<NodeIndex> = self.edges_of_kind(node, kind).collect();
+            stack.extend(next.into_iter().rev());
+        }
+    }
+
+    ///
+    /// Nodes reachable from `start` along a walk whose labels spell a word of
+    /// `path`, sorted by index.
+    ///
+    pub fn query_from(&self, start: NodeIndex, path: &PathExpr) -> Vec<NodeIndex> {
+        let automaton = &path.automaton;
+        let mut seen: HashSet<(NodeIndex, usize)> = HashSet::new();
+        let mut queue: VecDeque<(NodeIndex, usize)> = VecDeque::new();
+        let mut ends = BTreeSet::new();
+        for state in automaton.closure(automaton.start) {
+            if seen.insert((start, state)) {
+                queue.push_back((start, state));
+            }
+        }
+        while let Some((node, state)) = queue.pop_front() {
+            if state == automaton.accept {
+                ends.insert(node);
+            }
+            for &(label, next_state) in automaton.labeled[state].iter() {
+                for &(kind, next_node) in self.edges(node) {
+                    if kind != label {
+                        continue;
+                    }
+                    for closed in automaton.closure(next_state) {
+                        if seen.insert((next_node, closed)) {
+                            queue.push_back((next_node, closed));
+                        }
+                    }
+                }
+            }
+        }
+        ends.into_iter().collect()
+    }
+
+    ///
+    /// All (start, end) pairs connected by a walk matching `path`, e.g.
+    /// `Child* Call Child{0,3}`: a node, a call somewhere below it, and the
+    /// top three levels of the called function.
+    ///
+    pub fn query(&self, path: &PathExpr) -> Vec<(NodeIndex, NodeIndex)> {
+        let mut nodes: Vec<NodeIndex> = self.graph.graph.node_indices().collect();
+        nodes.sort();
+        nodes.into_iter()
+            .flat_map(|start| self.query_from(start, path).into_iter().map(move |end| (start, end)))
+            .collect()
+    }
+}
+
+///
+/// Overlay edges detached from the graph they were computed on: endpoints
+/// are stable ids, so the edges can be saved, shipped and laid over any
+/// build of the same source (`view`, `Overlay::combine`), including graphs
+/// the analysis that made them never saw.
+///
+/// `graph_hash` is the structural hash of the graph the edges were made for;
+/// when set, attaching to a graph with another hash is refused.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq)]
+pub struct DetachedOverlay {
+    pub graph_hash: Option<u64>,
+    edges: Vec<(StableId, EdgeKind, StableId)>,
+}
+
+impl DetachedOverlay {
+    pub fn new() -> Self {
+        DetachedOverlay::default()
+    }
+
+    ///
+    /// An empty overlay bound to `graph`'s structural hash.
+    ///
+    pub fn for_graph(graph: &ASTGraph) -> Self {
+        DetachedOverlay { graph_hash: Some(graph.structural_hash()), edges: Vec::new() }
+    }
+
+    pub fn add_edge(&mut self, from: StableId, to: StableId, kind: EdgeKind) {
+        let edge = (from, kind, to);
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    pub fn edges(&self) -> &[(StableId, EdgeKind, StableId)] {
+        &self.edges
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    ///
+    /// Add the edges of `other`, which must be for the same graph.
+    ///
+    pub fn combine(&mut self, other: &DetachedOverlay) -> Result<(), GraphError> {
+        match (self.graph_hash, other.graph_hash) {
+            (Some(ours), Some(theirs)) if ours != theirs => {
+                return Err(GraphError::Query("overlays were made for different graphs".to_string()));
+            },
+            (None, theirs) => self.graph_hash = theirs,
+            _ => {},
+        }
+        for (from, kind, to) in other.edges.iter() {
+            self.add_edge(from.clone(), to.clone(), *kind);
+        }
+        Ok(())
+    }
+
+    ///
+    /// The tree of `graph` (as `Overlay::new`) with these edges on top.
+    ///
+    pub fn view<'g>(&self, graph: &'g ASTGraph) -> Result<Overlay<'g>, GraphError> {
+        let mut overlay = Overlay::new(graph);
+        overlay.combine(self)?;
+        Ok(overlay)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), GraphError> {
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> Result<DetachedOverlay, GraphError> {
+        Ok(bincode::deserialize_from(reader)?)
+    }
+}
+
+impl<'g> Overlay<'g> {
+    ///
+    /// The edges of the `kinds` layers, keyed by stable id, e.g. just `Call`
+    /// and `Flow` to ship what an analysis added without the tree.
+    ///
+    pub fn detach(&self, kinds: &[EdgeKind]) -> DetachedOverlay {
+        let ids = self.graph.stable_ids();
+        let mut detached = DetachedOverlay::for_graph(self.graph);
+        let mut froms: Vec<&NodeIndex> = self.edges.keys().collect();
+        froms.sort();
+        for &from in froms {
+            for &(kind, to) in self.edges(from).iter().filter(|(kind, _)| kinds.contains(kind)) {
+                detached.add_edge(ids[&from].clone(), ids[&to].clone(), kind);
+            }
+        }
+        detached
+    }
+
+    ///
+    /// Add the edges of `detached`. Nothing is added if the overlay was made
+    /// for another graph or names a node this graph doesn't have.
+    ///
+    pub fn combine(&mut self, detached: &DetachedOverlay) -> Result<(), GraphError> {
+        if let Some(hash) = detached.graph_hash {
+            if hash != self.graph.structural_hash() {
+                return Err(GraphError::Query("overlay was made for a different graph".to_string()));
+            }
+        }
+        let graph = self.graph;
+        let resolve = |id: &StableId| graph.resolve_stable_id(id)
+            .ok_or_else(|| GraphError::InvalidNode(format!("no node {} in the graph", id)));
+        let edges = detached.edges.iter()
+            .map(|(from, kind, to)| Ok((resolve(from)?, *kind, resolve(to)?)))
+            .collect::<Result<Vec<(NodeIndex, EdgeKind, NodeIndex)>, GraphError>>()?;
+        for (from, kind, to) in edges {
+            self.add_edge(from, to, kind);
+        }
+        Ok(())
+    }
+}
+
+enum Expr {
+    Label(EdgeKind),
+    Seq(Vec<Expr>),
+    Alt(Vec<Expr>),
+    Repeat(Box<Expr>, usize, Option<usize>),
+}
+
+// Thompson automaton: `labeled` and `epsilon` transitions per state
+#[derive(Debug,Clone,Default)]
+struct Automaton {
+    labeled: Vec<Vec<(EdgeKind, usize)>>,
+    epsilon: Vec<Vec<usize>>,
+    start: usize,
+    accept: usize,
+}
+
+impl Automaton {
+    fn state(&mut self) -> usize {
+        self.labeled.push(Vec::new());
+        self.epsilon.push(Vec::new());
+        self.labeled.len() - 1
+    }
+
+    // states for `expr` as a (entry, exit) pair
+    fn build(&mut self, expr: &Expr) -> (usize, usize) {
+        match expr {
+            Expr::Label(kind) => {
+                let (entry, exit) = (self.state(), self.state());
+                self.labeled[entry].push((*kind, exit));
+                (entry, exit)
+            },
+            Expr::Seq(parts) => {
+                let entry = self.state();
+                let mut exit = entry;
+                for part in parts {
+                    let (part_entry, part_exit) = self.build(part);
+                    self.epsilon[exit].push(part_entry);
+                    exit = part_exit;
+                }
+                (entry, exit)
+            },
+            Expr::Alt(options) => {
+                let (entry, exit) = (self.state(), self.state());
+                for option in options {
+                    let (option_entry, option_exit) = self.build(option);
+                    self.epsilon[entry].push(option_entry);
+                    self.epsilon[option_exit].push(exit);
+                }
+                (entry, exit)
+            },
+            Expr::Repeat(inner, min, max) => {
+                let entry = self.state();
+                let mut exit = entry;
+                for _ in 0..*min {
+                    let (copy_entry, copy_exit) = self.build(inner);
+                    self.epsilon[exit].push(copy_entry);
+                    exit = copy_exit;
+                }
+                match max {
+                    None => {
+                        let (loop_entry, loop_exit) = self.build(inner);
+                        self.epsilon[exit].push(loop_entry);
+                        self.epsilon[loop_exit].push(exit);
+                    },
+                    Some(max) => {
+                        let end = self.state();
+                        self.epsilon[exit].push(end);
+                        for _ in *min..*max {
+                            let (copy_entry, copy_exit) = self.build(inner);
+                            self.epsilon[exit].push(copy_entry);
+                            self.epsilon[copy_exit].push(end);
+                            exit = copy_exit;
+                        }
+                        exit = end;
+                    },
+                }
+                (entry, exit)
+            },
+        }
+    }
+
+    fn closure(&self, state: usize) -> Vec<usize> {
+        let mut closure = vec![state];
+        let mut seen: HashSet<usize> = closure.iter().copied().collect();
+        let mut position = 0;
+        while position < closure.len() {
+            for &next in self.epsilon[closure[position]].iter() {
+                if seen.insert(next) {
+                    closure.push(next);
+                }
+            }
+            position += 1;
+        }
+        closure
+    }
+}
+
+///
+/// Regular expression over edge labels: labels (`Child`, `Sibling`,
+/// `Call`, `Flow`) in sequence, `|` for alternatives, parentheses, and the
+/// postfix repetitions `*`, `+`, `?`, `{n}`, `{m,n}` and `{m,}`.
+///
+#[derive(Debug,Clone)]
+pub struct PathExpr {
+    automaton: Automaton,
+}
+
+impl PathExpr {
+    pub fn parse(expression: &str) -> Result<PathExpr, GraphError> {
+        let tokens = tokenize(expression)?;
+        let mut position = 0;
+        let expr = parse_alternatives(&tokens, &mut position)?;
+        if position < tokens.len() {
+            return Err(GraphError::Query(format!("unexpected `{}` in path expression", tokens[position])));
+        }
+        let mut automaton = Automaton::default();
+        let (start, accept) = automaton.build(&expr);
+        automaton.start = start;
+        automaton.accept = accept;
+        Ok(PathExpr { automaton })
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<String>, GraphError> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_alphabetic() {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric()) {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+        } else if c == '{' {
+            let mut bound = String::new();
+            for c in chars.by_ref() {
+                bound.push(c);
+                if c == '}' {
+                    break;
+                }
+            }
+            if !bound.ends_with('}') {
+                return Err(GraphError::Query("unclosed `{` in path expression".to_string()));
+            }
+            tokens.push(bound);
+        } else if "()|*+?".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else {
+            return Err(GraphError::Query(format!("unexpected `{}` in path expression", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_alternatives(tokens: &[String], position: &mut usize) -> Result<Expr, GraphError> {
+    let mut options = vec![parse_sequence(tokens, position)?];
+    while tokens.get(*position).map(|token| token.as_str()) == Some("|") {
+        *position += 1;
+        options.push(parse_sequence(tokens, position)?);
+    }
+    Ok(if options.len() == 1 { options.pop().unwrap() } else { Expr::Alt(options) })
+}
+
+fn parse_sequence(tokens: &[String], position: &mut usize) -> Result<Expr, GraphError> {
+    let mut parts = Vec::new();
+    while let Some(token) = tokens.get(*position) {
+        let atom = match token.as_str() {
+            "|" | ")" => break,
+            "(" => {
+                *position += 1;
+                let inner = parse_alternatives(tokens, position)?;
+                if tokens.get(*position).map(|token| token.as_str()) != Some(")") {
+                    return Err(GraphError::Query("unclosed `(` in path expression".to_string()));
+                }
+                *position += 1;
+                inner
+            },
+            name => {
+                let kind = EdgeKind::from_name(name)
+                    .ok_or_else(|| GraphError::Query(format!("unknown edge kind `{}`", name)))?;
+                *position += 1;
+                Expr::Label(kind)
+            },
+        };
+        parts.push(parse_repetitions(atom, tokens, position)?);
+    }
+    if parts.is_empty() {
+        return Err(GraphError::Query("empty path expression".to_string()));
+    }
+    Ok(if parts.len() == 1 { parts.pop().unwrap() } else { Expr::Seq(parts) })
+}
+
+fn parse_repetitions(mut atom: Expr, tokens: &[String], position: &mut usize) -> Result<Expr, GraphError> {
+    while let Some(token) = tokens.get(*position) {
+        let (min, max) = match token.as_str() {
+            "*" => (0, None),
+            "+" => (1, None),
+            "?" => (0, Some(1)),
+            bound if bound.starts_with('{') => parse_bound(bound)?,
+            _ => break,
+        };
+        *position += 1;
+        atom = Expr::Repeat(Box::new(atom), min, max);
+    }
+    Ok(atom)
+}
+
+fn parse_bound(bound: &str) -> Result<(usize, Option<usize>), GraphError> {
+    let invalid = || GraphError::Query(format!("invalid repetition `{}` in path expression", bound));
+    let inner = &bound[1..bound.len() - 1];
+    let number = |text: &str| text.trim().parse::<usize>().map_err(|_| invalid());
+    let (min, max) = match inner.split_once(',') {
+        None => (number(inner)?, Some(number(inner)?)),
+        Some((min, max)) if max.trim().is_empty() => (number(min)?, None),
+        Some((min, max)) => (number(min)?, Some(number(max)?)),
+    };
+    if max.is_some_and(|max| max < min) || max.unwrap_or(min) > MAX_REPEAT {
+        return Err(invalid());
+    }
+    Ok((min, max))
+}
@@ -0,0 +1,90 @@
+use std::ops::Deref;
+use tree_sitter::{InputEdit, Language, Parser, Tree, TreeCursor};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+
+///
+/// An `ASTGraph` together with the `tree_sitter::Tree` it was built from and
+/// the parser that produced it, for code that needs live tree access (node
+/// kinds by name, cursors, queries) next to the graph algorithms.
+///
+/// Dereferences to the graph for reading. There is deliberately no mutable
+/// access to the graph, since it must stay in step with the tree; changes go
+/// through `edit`, which reparses incrementally and rebuilds the graph.
+///
+pub struct OwnedAstGraph {
+    graph: ASTGraph,
+    tree: Tree,
+    parser: Parser,
+    language: Language,
+}
+
+impl OwnedAstGraph {
+    pub fn parse(language: &Language, source: String) -> Result<Self, GraphError> {
+        let mut parser = Parser::new();
+        parser.set_language(language).map_err(|err| GraphError::Query(err.to_string()))?;
+        let tree = parser.parse(&source, None)
+            .ok_or_else(|| GraphError::Query("parsing the source failed".to_string()))?;
+
+        let mut graph = ASTGraph::new(source);
+        graph.build_from_tree(&tree);
+        Ok(OwnedAstGraph { graph, tree, parser, language: language.clone() })
+    }
+
+    pub fn graph(&self) -> &ASTGraph {
+        &self.graph
+    }
+
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    pub fn language(&self) -> &Language {
+        &self.language
+    }
+
+    ///
+    /// Cursor over the live tree, starting at its root.
+    ///
+    pub fn walk(&self) -> TreeCursor {
+        self.tree.walk()
+    }
+
+    ///
+    /// Replace the source with `new_source`, which must be the old source with
+    /// `edit` applied (as returned by `replace_node_source`). The tree is
+    /// reparsed incrementally and the graph rebuilt from it; the graph keeps
+    /// its title and metadata.
+    ///
+    pub fn edit(&mut self, edit: &InputEdit, new_source: String) -> Result<(), GraphError> {
+        self.tree.edit(edit);
+        let tree = self.parser.parse(&new_source, Some(&self.tree))
+            .ok_or_else(|| GraphError::Query("parsing the source failed".to_string()))?;
+
+        let mut graph = ASTGraph::new(new_source);
+        graph.set_title(self.graph.title());
+        graph.metadata = self.graph.metadata.clone();
+        graph.build_from_tree(&tree);
+
+        self.tree = tree;
+        self.graph = graph;
+        Ok(())
+    }
+
+    pub fn into_parts(self) -> (ASTGraph, Tree) {
+        (self.graph, self.tree)
+    }
+
+    pub fn into_graph(self) -> ASTGraph {
+        self.graph
+    }
+}
+
+impl Deref for OwnedAstGraph {
+    type Target = ASTGraph;
+
+    fn deref(&self) -> &ASTGraph {
+        &self.graph
+    }
+}
@@ -0,0 +1,91 @@
+use petgraph::graph::NodeIndex;
+use std::cmp::Ordering;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+
+///
+/// Order of the nodes `nodes_page` pages through.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub enum NodeOrder {
+    /// Index order; pages are sliced without sorting anything.
+    #[default]
+    Index,
+    /// Source order: by start byte, enclosing nodes before what they enclose.
+    Position,
+    /// Grouped by kind id, in index order within a kind.
+    Kind,
+}
+
+///
+/// One page of a longer list, with where it starts and how long the whole
+/// list is so a UI can show "41–60 of 12,345" and ask for the next one.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub offset: usize,
+    pub total: usize,
+}
+
+impl<T> Page<T> {
+    pub fn has_more(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+
+    ///
+    /// The offset to ask for next, `None` after the last page.
+    ///
+    pub fn next_offset(&self) -> Option<usize> {
+        if self.has_more() { Some(self.offset + self.items.len()) } else { None }
+    }
+}
+
+impl ASTGraph {
+    fn compare_nodes(&self, a: NodeIndex, b: NodeIndex, order: NodeOrder) -> Ordering {
+        let (ga, gb) = (&self.graph[a], &self.graph[b]);
+        match order {
+            NodeOrder::Index => a.cmp(&b),
+            NodeOrder::Position => (ga.range.start_byte, gb.range.end_byte, a).cmp(&(gb.range.start_byte, ga.range.end_byte, b)),
+            NodeOrder::Kind => (ga.kind_id, a).cmp(&(gb.kind_id, b)),
+        }
+    }
+
+    ///
+    /// The nodes `offset..offset + limit` of the graph in `order`. Only the
+    /// first `offset + limit` nodes are ever sorted, so early pages of huge
+    /// graphs stay cheap.
+    ///
+    pub fn nodes_page(&self, offset: usize, limit: usize, order: NodeOrder) -> Page<NodeIndex> {
+        let total = self.graph.node_count();
+        let end = offset.saturating_add(limit).min(total);
+        let items = if offset >= end {
+            Vec::new()
+        } else if order == NodeOrder::Index {
+            (offset..end).map(NodeIndex::new).collect()
+        } else {
+            let mut nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+            if end < total {
+                nodes.select_nth_unstable_by(end, |&a, &b| self.compare_nodes(a, b, order));
+                nodes.truncate(end);
+            }
+            nodes.sort_unstable_by(|&a, &b| self.compare_nodes(a, b, order));
+            nodes.split_off(offset)
+        };
+        Page { items: items, offset: offset, total: total }
+    }
+
+    ///
+    /// The children `offset..offset + limit` of `node`, in source order.
+    ///
+    pub fn children_page(&self, node: NodeIndex, offset: usize, limit: usize) -> Result<Page<NodeIndex>, GraphError> {
+        if self.graph.node_weight(node).is_none() {
+            return Err(GraphError::InvalidNode(format!("no node {}", node.index())));
+        }
+        let children = self.children(node);
+        let total = children.len();
+        let items = children.into_iter().skip(offset).take(limit).collect();
+        Ok(Page { items: items, offset: offset, total: total })
+    }
+}
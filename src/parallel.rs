@@ -0,0 +1,111 @@
+//!
+//! Parallel variants of the costlier operations (feature `parallel`), run on
+//! rayon. `Parallelism` says which pool they use, so hosts that manage their
+//! own threads can hand theirs in instead of having the crate spawn more.
+//!
+use petgraph::graph::NodeIndex;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tree_sitter::{Language, Parser};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::project::ProjectGraph;
+use crate::source::SourceStart;
+
+///
+/// Where parallel work runs: rayon's global pool (the default), a pool of
+/// `threads` threads built for the call, or a pool supplied by the host.
+///
+#[derive(Debug,Clone,Default)]
+pub struct Parallelism {
+    threads: Option<usize>,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl Parallelism {
+    pub fn new() -> Self {
+        Parallelism::default()
+    }
+
+    pub fn with_threads(threads: usize) -> Self {
+        Parallelism { threads: Some(threads), pool: None }
+    }
+
+    pub fn with_pool(pool: Arc<ThreadPool>) -> Self {
+        Parallelism { threads: None, pool: Some(pool) }
+    }
+
+    ///
+    /// Run `work` in the configured pool.
+    ///
+    pub fn install<R: Send, F: FnOnce() -> R + Send>(&self, work: F) -> Result<R, GraphError> {
+        if let Some(pool) = self.pool.as_ref() {
+            return Ok(pool.install(work));
+        }
+        match self.threads {
+            Some(threads) => {
+                let pool = ThreadPoolBuilder::new().num_threads(threads).build()
+                    .map_err(|err| GraphError::Query(format!("building a thread pool failed: {}", err)))?;
+                Ok(pool.install(work))
+            },
+            None => Ok(work()),
+        }
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// `extract_subgraphs`, building the subgraphs in parallel.
+    ///
+    pub fn extract_subgraphs_parallel(&self, kinds_to_split_on: &HashSet<u16>, parallelism: &Parallelism) -> Result<Vec<ASTGraph>, GraphError> {
+        let matches: Vec<NodeIndex> = self.graph.node_indices()
+            .filter(|&node| kinds_to_split_on.contains(&self.graph[node].kind_id))
+            .collect();
+        let members = self.match_members(&matches);
+        parallelism.install(|| {
+            matches.par_iter().zip(members.par_iter()).map(|(&node, subgraph_nodes)| {
+                let range = &self.graph[node].range;
+                let mut subgraph = self.create_subgraph(subgraph_nodes);
+                subgraph.source = self.range_text(range.start_byte, range.end_byte).map(|text| text.into());
+                subgraph.source_start = SourceStart::of(range);
+                subgraph
+            }).collect()
+        })
+    }
+}
+
+impl ProjectGraph {
+    ///
+    /// Parse `files` (path, source) with `language` in parallel and add their
+    /// graphs. Returns how many were added.
+    ///
+    pub fn ingest_parallel(&mut self, files: Vec<(String, String)>, language: &Language, parallelism: &Parallelism) -> Result<usize, GraphError> {
+        let graphs: Result<Vec<(String, ASTGraph)>, GraphError> = parallelism.install(|| {
+            files.into_par_iter().map_init(
+                || {
+                    let mut parser = Parser::new();
+                    parser.set_language(language).map(|_| parser).map_err(|err| GraphError::Query(err.to_string()))
+                },
+                |parser, (path, source)| {
+                    let parser = parser.as_mut().map_err(|err| GraphError::Query(err.to_string()))?;
+                    let tree = parser.parse(&source, None)
+                        .ok_or_else(|| GraphError::Query(format!("parsing {} failed", path)))?;
+                    let mut graph = ASTGraph::new(source);
+                    graph.build_from_tree(&tree);
+                    graph.set_title(path.clone());
+                    Ok((path, graph))
+                },
+            ).collect()
+        })?;
+
+        let graphs = graphs?;
+        let added = graphs.len();
+        for (path, graph) in graphs {
+            self.insert(&path, graph);
+        }
+        Ok(added)
+    }
+}
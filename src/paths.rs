@@ -0,0 +1,277 @@
+use petgraph::algo::astar;
+use petgraph::graph::NodeIndex;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::ASTGraph;
+use crate::geometry::GNode;
+
+///
+/// Cost of moving along a tree edge, computed from the parent's and the
+/// child's data when a search needs it (there is no weighted copy of the
+/// graph).
+///
+#[derive(Debug,Clone,Copy)]
+pub enum EdgeCost {
+    /// every edge costs 1, as in `path_from_to`
+    Uniform,
+    /// bytes between the parent's and the child's start
+    ByteDistance,
+    /// rows between the parent's and the child's start
+    LineDistance,
+    /// any other function of (parent, child)
+    Custom(fn(&GNode, &GNode) -> u64),
+}
+
+impl EdgeCost {
+    pub fn cost(&self, parent: &GNode, child: &GNode) -> u64 {
+        match self {
+            EdgeCost::Uniform => 1,
+            EdgeCost::ByteDistance => child.range.start_byte.abs_diff(parent.range.start_byte) as u64,
+            EdgeCost::LineDistance => child.range.start_point.row.abs_diff(parent.range.start_point.row) as u64,
+            EdgeCost::Custom(cost) => cost(parent, child),
+        }
+    }
+}
+
+///
+/// How shortest-path searches treat the tree: edge costs, and whether edges
+/// can be walked upwards as well (undirected), which connects any two nodes
+/// of a tree, not only ancestors to descendants.
+///
+#[derive(Debug,Clone,Copy)]
+pub struct PathOptions {
+    pub cost: EdgeCost,
+    pub undirected: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        PathOptions { cost: EdgeCost::Uniform, undirected: false }
+    }
+}
+
+impl PathOptions {
+    pub fn with_cost(mut self, cost: EdgeCost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn undirected(mut self) -> Self {
+        self.undirected = true;
+        self
+    }
+}
+
+///
+/// Estimate of the remaining cost to the goal for `path_from_to_with`, which
+/// counts edges. `Zero` is plain Dijkstra; `ByteDistance` divides the
+/// start-byte distance to the goal by the largest start-byte gap of any edge,
+/// so it never overestimates and stays admissible.
+///
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Heuristic {
+    Zero,
+    ByteDistance { bytes_per_edge: u64 },
+}
+
+impl Heuristic {
+    ///
+    /// Byte-distance heuristic scaled for `graph` (one pass over its edges;
+    /// build it once and reuse it across queries).
+    ///
+    pub fn byte_distance(graph: &ASTGraph) -> Self {
+        let bytes_per_edge = graph.graph.edge_indices()
+            .map(|edge| {
+                let (parent, child) = graph.graph.edge_endpoints(edge).unwrap();
+                EdgeCost::ByteDistance.cost(&graph.graph[parent], &graph.graph[child])
+            })
+            .max()
+            .unwrap_or(0);
+        Heuristic::ByteDistance { bytes_per_edge: bytes_per_edge.max(1) }
+    }
+
+    pub fn estimate(&self, node: &GNode, goal: &GNode) -> u64 {
+        match self {
+            Heuristic::Zero => 0,
+            Heuristic::ByteDistance { bytes_per_edge } => {
+                (goal.range.start_byte.abs_diff(node.range.start_byte) as u64).div_ceil(*bytes_per_edge)
+            }
+        }
+    }
+}
+
+///
+/// Tree distances (edges on the undirected path) between every pair of a set
+/// of nodes, in the order the nodes were given; `None` for nodes of
+/// different roots.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct DistanceMatrix {
+    nodes: Vec<NodeIndex>,
+    distances: Vec<Option<usize>>,
+}
+
+impl DistanceMatrix {
+    pub fn nodes(&self) -> &[NodeIndex] {
+        &self.nodes
+    }
+
+    ///
+    /// Distance between the `i`-th and `j`-th nodes.
+    ///
+    pub fn get(&self, i: usize, j: usize) -> Option<usize> {
+        self.distances[i * self.nodes.len() + j]
+    }
+
+    ///
+    /// Distance between two nodes of the matrix, by node.
+    ///
+    pub fn between(&self, a: NodeIndex, b: NodeIndex) -> Option<usize> {
+        let i = self.nodes.iter().position(|&node| node == a)?;
+        let j = self.nodes.iter().position(|&node| node == b)?;
+        self.get(i, j)
+    }
+}
+
+impl ASTGraph {
+    // (neighbor, cost of the step) under `options`
+    pub(crate) fn weighted_neighbors(&self, node: NodeIndex, options: &PathOptions) -> Vec<(NodeIndex, u64)> {
+        let gnode = &self.graph[node];
+        let mut neighbors: Vec<(NodeIndex, u64)> = self.graph.neighbors(node)
+            .map(|child| (child, options.cost.cost(gnode, &self.graph[child])))
+            .collect();
+        if options.undirected {
+            if let Some(parent) = self.parent(node) {
+                neighbors.push((parent, options.cost.cost(&self.graph[parent], gnode)));
+            }
+        }
+        neighbors
+    }
+
+    // Dijkstra from `start`, stopping once `done` holds; returns costs and
+    // predecessors of the nodes settled so far
+    pub(crate) fn dijkstra<F: FnMut(NodeIndex) -> bool>(&self, start: NodeIndex, options: &PathOptions, mut done: F)
+        -> (HashMap<NodeIndex, u64>, HashMap<NodeIndex, NodeIndex>) {
+        let mut costs: HashMap<NodeIndex, u64> = HashMap::new();
+        let mut settled: HashMap<NodeIndex, u64> = HashMap::new();
+        let mut previous: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        costs.insert(start, 0);
+        heap.push(Reverse((0, start)));
+
+        while let Some(Reverse((cost, node))) = heap.pop() {
+            if settled.contains_key(&node) {
+                continue;
+            }
+            settled.insert(node, cost);
+            if done(node) {
+                break;
+            }
+            for (next, step) in self.weighted_neighbors(node, options) {
+                let next_cost = cost + step;
+                if costs.get(&next).is_none_or(|&known| next_cost < known) {
+                    costs.insert(next, next_cost);
+                    previous.insert(next, node);
+                    heap.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        (settled, previous)
+    }
+
+    pub(crate) fn trace_path(previous: &HashMap<NodeIndex, NodeIndex>, start: NodeIndex, goal: NodeIndex) -> Vec<NodeIndex> {
+        let mut path = vec![goal];
+        while *path.last().unwrap() != start {
+            path.push(previous[path.last().unwrap()]);
+        }
+        path.reverse();
+        path
+    }
+
+    ///
+    /// Cheapest path from `start` to `goal` with its cost, under the costs
+    /// and directionality of `options`.
+    ///
+    pub fn shortest_path(&self, start: NodeIndex, goal: NodeIndex, options: &PathOptions) -> Option<(u64, Vec<NodeIndex>)> {
+        self.graph.node_weight(start)?;
+        let (settled, previous) = self.dijkstra(start, options, |node| node == goal);
+        let cost = *settled.get(&goal)?;
+        Some((cost, ASTGraph::trace_path(&previous, start, goal)))
+    }
+
+    ///
+    /// `path_from_to` guided by `heuristic`; the path found is the same, but
+    /// on deep trees `Heuristic::byte_distance` expands far fewer nodes.
+    ///
+    pub fn path_from_to_with(&self, start: NodeIndex, goal: NodeIndex, heuristic: &Heuristic) -> Option<Vec<NodeIndex>> {
+        let target = self.graph.node_weight(goal)?;
+        astar(
+            &self.graph,
+            start,
+            |finish| finish == goal,
+            |_| 1,
+            |node| heuristic.estimate(&self.graph[node], target),
+        ).map(|(_, path)| path)
+    }
+
+    ///
+    /// `path_from_to` for many goals at once: one search from `start` that
+    /// stops when every goal is reached. Paths come back in the order of
+    /// `goals`, `None` where a goal is unreachable.
+    ///
+    pub fn paths_from_to_many(&self, start: NodeIndex, goals: &[NodeIndex]) -> Vec<Option<Vec<NodeIndex>>> {
+        if self.graph.node_weight(start).is_none() {
+            return vec![None; goals.len()];
+        }
+        let mut remaining: HashSet<NodeIndex> = goals.iter().copied().collect();
+        let (settled, previous) = self.dijkstra(start, &PathOptions::default(), |node| {
+            remaining.remove(&node);
+            remaining.is_empty()
+        });
+        goals.iter()
+            .map(|goal| settled.contains_key(goal).then(|| ASTGraph::trace_path(&previous, start, *goal)))
+            .collect()
+    }
+
+
+    ///
+    /// Distance matrix of `nodes` (typically the nodes of one subgraph), from
+    /// their ancestor chains: the distance of a pair is the steps of both up
+    /// to their lowest common ancestor, with no path search per pair.
+    ///
+    pub fn pairwise_distances(&self, nodes: &[NodeIndex]) -> DistanceMatrix {
+        // every node's ancestors (itself included) with the steps up to them
+        let chains: Vec<HashMap<NodeIndex, usize>> = nodes.iter().map(|&node| {
+            let mut chain = HashMap::new();
+            let mut current = Some(node);
+            let mut steps = 0;
+            while let Some(ancestor) = current {
+                chain.insert(ancestor, steps);
+                current = self.parent(ancestor);
+                steps += 1;
+            }
+            chain
+        }).collect();
+
+        let count = nodes.len();
+        let mut distances = vec![None; count * count];
+        for i in 0..count {
+            distances[i * count + i] = Some(0);
+            for j in i + 1..count {
+                let mut current = Some(nodes[j]);
+                let mut steps = 0;
+                while let Some(ancestor) = current {
+                    if let Some(up) = chains[i].get(&ancestor) {
+                        distances[i * count + j] = Some(up + steps);
+                        distances[j * count + i] = Some(up + steps);
+                        break;
+                    }
+                    current = self.parent(ancestor);
+                    steps += 1;
+                }
+            }
+        }
+        DistanceMatrix { nodes: nodes.to_vec(), distances }
+    }
+}
@@ -0,0 +1,91 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+use std::ops::Deref;
+use tree_sitter::{Node, Tree};
+
+use crate::ASTGraph;
+
+///
+/// An `ASTGraph` carrying a user payload per node (precomputed hashes,
+/// normalized text, ...), built once by a mapper called with every
+/// tree-sitter node and the source.
+///
+/// Dereferences to the graph for reading; like `OwnedAstGraph` there is no
+/// mutable access to the graph, so payloads always match its nodes.
+///
+#[derive(Debug,Clone)]
+pub struct ASTGraphWith<T> {
+    graph: ASTGraph,
+    payloads: Vec<T>,
+}
+
+impl<T> ASTGraphWith<T> {
+    pub fn build<F: FnMut(Node, &str) -> T>(tree: &Tree, source: String, mut mapper: F) -> Self {
+        let mut graph = ASTGraph::new(source);
+        graph.build_from_tree(tree);
+
+        // same preorder as build_from_tree, so the n-th payload is node n's
+        let mut payloads = Vec::with_capacity(graph.graph.node_count());
+        let mut cursor = tree.walk();
+        'walk: loop {
+            payloads.push(mapper(cursor.node(), graph.source_text()));
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+            }
+        }
+
+        ASTGraphWith { graph, payloads }
+    }
+
+    pub fn graph(&self) -> &ASTGraph {
+        &self.graph
+    }
+
+    pub fn payload(&self, node: NodeIndex) -> Option<&T> {
+        self.payloads.get(node.index())
+    }
+
+    pub fn payload_mut(&mut self, node: NodeIndex) -> Option<&mut T> {
+        self.payloads.get_mut(node.index())
+    }
+
+    ///
+    /// Nodes with their payloads, in index order.
+    ///
+    pub fn payloads(&self) -> impl Iterator<Item = (NodeIndex, &T)> + '_ {
+        self.payloads.iter().enumerate().map(|(index, payload)| (NodeIndex::new(index), payload))
+    }
+
+    pub fn into_parts(self) -> (ASTGraph, Vec<T>) {
+        (self.graph, self.payloads)
+    }
+}
+
+impl<T: Clone> ASTGraphWith<T> {
+    ///
+    /// `extract_subgraph_from` keeping the payloads of the extracted nodes.
+    ///
+    pub fn extract_subgraph_from(&self, new_root: NodeIndex) -> ASTGraphWith<T> {
+        let subgraph = self.graph.extract_subgraph_from(new_root);
+        let by_id: HashMap<usize, NodeIndex> = self.graph.collect_subgraph_nodes(new_root).into_iter()
+            .map(|node| (self.graph.graph[node].id, node))
+            .collect();
+        let payloads = subgraph.graph.node_indices()
+            .map(|node| self.payloads[by_id[&subgraph.graph[node].id].index()].clone())
+            .collect();
+        ASTGraphWith { graph: subgraph, payloads }
+    }
+}
+
+impl<T> Deref for ASTGraphWith<T> {
+    type Target = ASTGraph;
+
+    fn deref(&self) -> &ASTGraph {
+        &self.graph
+    }
+}
@@ -0,0 +1,22 @@
+//!
+//! The types most programs need, in one import:
+//!
+//! ```ignore
+//! use tree_graph::prelude::*;
+//! ```
+//!
+//! Includes the petgraph and tree-sitter types the basic API takes and
+//! returns, so simple users don't need those crates as direct dependencies.
+//!
+
+pub use crate::ASTGraph;
+pub use crate::arena::TreeArena;
+pub use crate::error::GraphError;
+pub use crate::features::FeatureExtractor;
+pub use crate::geometry::{GNode, GPoint, GRange};
+pub use crate::ids::{AstNodeId, StableId};
+pub use crate::owned::OwnedAstGraph;
+pub use crate::store::{GraphBackend, GraphStore};
+
+pub use petgraph::graph::NodeIndex;
+pub use tree_sitter::{Language, Parser, Tree};
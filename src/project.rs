@@ -0,0 +1,307 @@
+use petgraph::graph::NodeIndex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use crate::ASTGraph;
+use crate::analysis::{callee_name, enclosing_scope, LanguageKinds};
+use crate::analysis::signature::{function_signature, token_hash};
+use crate::delta;
+use crate::error::GraphError;
+
+pub mod archive;
+
+///
+/// The graphs of all files of a project (or of one snapshot of it), keyed by
+/// path. All files are expected to be in the language `kinds` was built for.
+/// The analyses read function and include names from the sources, so they
+/// fail with `GraphError::SourceUnavailable` on files without one.
+///
+pub struct ProjectGraph {
+    kinds: LanguageKinds,
+    files: BTreeMap<String, ASTGraph>,
+}
+
+///
+/// A function of a project, located by file and node.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct FunctionRef {
+    pub file: String,
+    pub name: String,
+    pub node: NodeIndex,
+}
+
+///
+/// A file present in both snapshots whose graph changed, with the size of the
+/// structural delta between the two versions (see `delta::diff`).
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct FileDiff {
+    pub path: String,
+    pub added_nodes: usize,
+    pub reused_subtrees: usize,
+}
+
+///
+/// A function whose code is unchanged but which now lives in other files.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct MovedFunction {
+    pub name: String,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+///
+/// Everything that changed between two snapshots of a project. Functions are
+/// identified by name (overloads as a group) and calls by (caller, callee)
+/// names; all lists are sorted.
+///
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ProjectDiff {
+    pub added_files: Vec<String>,
+    pub removed_files: Vec<String>,
+    pub modified_files: Vec<FileDiff>,
+    pub added_functions: Vec<String>,
+    pub removed_functions: Vec<String>,
+    pub modified_functions: Vec<String>,
+    pub moved_functions: Vec<MovedFunction>,
+    pub added_calls: Vec<(String, String)>,
+    pub removed_calls: Vec<(String, String)>,
+}
+
+impl ProjectDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == ProjectDiff::default()
+    }
+}
+
+impl ProjectGraph {
+    pub fn new(kinds: LanguageKinds) -> Self {
+        ProjectGraph { kinds: kinds, files: BTreeMap::new() }
+    }
+
+    pub fn kinds(&self) -> &LanguageKinds {
+        &self.kinds
+    }
+
+    ///
+    /// Add or replace the graph of the file at `path`.
+    ///
+    pub fn insert(&mut self, path: &str, graph: ASTGraph) -> Option<ASTGraph> {
+        self.files.insert(path.to_string(), graph)
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<ASTGraph> {
+        self.files.remove(path)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ASTGraph> {
+        self.files.get(path)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&str, &ASTGraph)> {
+        self.files.iter().map(|(path, graph)| (path.as_str(), graph))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    ///
+    /// Every function of every file, by path and then source order.
+    ///
+    pub fn functions(&self) -> Result<Vec<FunctionRef>, GraphError> {
+        let mut functions = Vec::new();
+        for (path, graph) in self.files.iter() {
+            let mut nodes: Vec<NodeIndex> = graph.graph.node_indices()
+                .filter(|&node| self.kinds.functions.contains(&graph.graph[node].kind_id))
+                .collect();
+            nodes.sort_by_key(|&node| graph.graph[node].range.start_byte);
+            for node in nodes {
+                if let Some(signature) = function_signature(graph, node, &self.kinds)? {
+                    functions.push(FunctionRef { file: path.clone(), name: signature.name, node: node });
+                }
+            }
+        }
+        Ok(functions)
+    }
+
+    ///
+    /// (caller, callee) names of all calls made from inside functions. Calls
+    /// are resolved by name only, so callees outside the project (library
+    /// functions) are included too.
+    ///
+    pub fn call_edges(&self) -> Result<BTreeSet<(String, String)>, GraphError> {
+        let mut edges = BTreeSet::new();
+        for graph in self.files.values() {
+            for call in graph.graph.node_indices().filter(|&node| self.kinds.calls.contains(&graph.graph[node].kind_id)) {
+                let caller = match enclosing_scope(graph, call, &self.kinds.functions) {
+                    Some(function) => function_signature(graph, function, &self.kinds)?,
+                    None => None,
+                };
+                if let (Some(caller), Some(callee)) = (caller, callee_name(graph, call, &self.kinds)) {
+                    edges.insert((caller.name, callee.to_string()));
+                }
+            }
+        }
+        Ok(edges)
+    }
+
+    ///
+    /// (including file, included file) for every include that names a file of
+    /// the project. An include matches a path equal to it or ending in
+    /// `/<include>`; includes of files outside the project are dropped.
+    ///
+    pub fn include_edges(&self) -> Result<BTreeSet<(String, String)>, GraphError> {
+        let mut edges = BTreeSet::new();
+        for (path, graph) in self.files.iter() {
+            for include in graph.graph.node_indices().filter(|&node| self.kinds.includes.contains(&graph.graph[node].kind_id)) {
+                let target = match graph.children(include).into_iter().skip(1).find(|&child| !self.kinds.comments.contains(&graph.graph[child].kind_id)) {
+                    Some(target) => graph.try_node_source(target)?.trim().trim_matches(&['"', '\'', '<', '>'][..]),
+                    None => continue,
+                };
+                let suffix = format!("/{}", target);
+                for included in self.files.keys().filter(|candidate| *candidate == target || candidate.ends_with(&suffix)) {
+                    edges.insert((path.clone(), included.clone()));
+                }
+            }
+        }
+        Ok(edges)
+    }
+
+    ///
+    /// Functions possibly affected by changes at the given (file, node)
+    /// locations: the functions containing the changed nodes and, for changes
+    /// outside any function (declarations, macros, types), every function of
+    /// the file and of the files including it directly or transitively; then
+    /// everything calling any of those, directly or transitively. Changed
+    /// functions are part of the result, which is in file and source order.
+    ///
+    pub fn impacted_functions(&self, changes: &[(&str, NodeIndex)]) -> Result<Vec<FunctionRef>, GraphError> {
+        let functions = self.functions()?;
+        let mut includers: HashMap<String, Vec<String>> = HashMap::new();
+        for (including, included) in self.include_edges()? {
+            includers.entry(included).or_default().push(including);
+        }
+        let mut callers: HashMap<String, Vec<String>> = HashMap::new();
+        for (caller, callee) in self.call_edges()? {
+            callers.entry(callee).or_default().push(caller);
+        }
+
+        let mut impacted_names: BTreeSet<String> = BTreeSet::new();
+        let mut impacted_files: BTreeSet<String> = BTreeSet::new();
+        for (path, node) in changes.iter() {
+            let graph = match self.files.get(*path) {
+                Some(graph) => graph,
+                None => continue,
+            };
+            let function = if self.kinds.functions.contains(&graph.graph[*node].kind_id) {
+                Some(*node)
+            } else {
+                enclosing_scope(graph, *node, &self.kinds.functions)
+            };
+            let signature = match function {
+                Some(function) => function_signature(graph, function, &self.kinds)?,
+                None => None,
+            };
+            match signature {
+                Some(signature) => { impacted_names.insert(signature.name); },
+                None => {
+                    let mut queue = VecDeque::from([path.to_string()]);
+                    while let Some(file) = queue.pop_front() {
+                        if impacted_files.insert(file.clone()) {
+                            queue.extend(includers.get(&file).into_iter().flatten().cloned());
+                        }
+                    }
+                },
+            }
+        }
+        impacted_names.extend(functions.iter().filter(|function| impacted_files.contains(&function.file)).map(|function| function.name.clone()));
+
+        // reverse call graph reachability
+        let mut queue: VecDeque<String> = impacted_names.iter().cloned().collect();
+        while let Some(name) = queue.pop_front() {
+            for caller in callers.get(&name).into_iter().flatten() {
+                if impacted_names.insert(caller.clone()) {
+                    queue.push_back(caller.clone());
+                }
+            }
+        }
+
+        Ok(functions.into_iter().filter(|function| impacted_names.contains(&function.name)).collect())
+    }
+
+    // name -> sorted (content hash, file) of every function with that name
+    fn function_contents(&self) -> Result<BTreeMap<String, Vec<(u64, String)>>, GraphError> {
+        let mut contents: BTreeMap<String, Vec<(u64, String)>> = BTreeMap::new();
+        for function in self.functions()? {
+            let hash = token_hash(&self.files[&function.file], function.node)?;
+            contents.entry(function.name).or_default().push((hash, function.file));
+        }
+        for entries in contents.values_mut() {
+            entries.sort();
+        }
+        Ok(contents)
+    }
+
+    ///
+    /// Compare this snapshot (the old one) with `other` (the new one).
+    ///
+    /// A function is modified when its code (tokens, not layout) changed and
+    /// moved when the code is identical but the files holding it differ.
+    ///
+    pub fn diff(&self, other: &ProjectGraph) -> Result<ProjectDiff, GraphError> {
+        let mut report = ProjectDiff::default();
+
+        for (path, graph) in self.files.iter() {
+            match other.files.get(path) {
+                None => report.removed_files.push(path.clone()),
+                Some(new_graph) if graph.source() != new_graph.source() || !graph.structurally_equal(new_graph) => {
+                    let patch = delta::diff(graph, new_graph);
+                    report.modified_files.push(FileDiff {
+                        path: path.clone(),
+                        added_nodes: patch.added_nodes(),
+                        reused_subtrees: patch.reused_subtrees(),
+                    });
+                },
+                Some(_) => {},
+            }
+        }
+        report.added_files = other.files.keys().filter(|path| !self.files.contains_key(*path)).cloned().collect();
+
+        let old_functions = self.function_contents()?;
+        let new_functions = other.function_contents()?;
+        for (name, old_entries) in old_functions.iter() {
+            let new_entries = match new_functions.get(name) {
+                Some(entries) => entries,
+                None => {
+                    report.removed_functions.push(name.clone());
+                    continue;
+                },
+            };
+            let old_hashes: Vec<u64> = old_entries.iter().map(|(hash, _)| *hash).collect();
+            let new_hashes: Vec<u64> = new_entries.iter().map(|(hash, _)| *hash).collect();
+            if old_hashes != new_hashes {
+                report.modified_functions.push(name.clone());
+            } else if old_entries != new_entries {
+                report.moved_functions.push(MovedFunction {
+                    name: name.clone(),
+                    from: old_entries.iter().map(|(_, file)| file.clone()).collect(),
+                    to: new_entries.iter().map(|(_, file)| file.clone()).collect(),
+                });
+            }
+        }
+        report.added_functions = new_functions.keys().filter(|name| !old_functions.contains_key(*name)).cloned().collect();
+
+        let old_calls = self.call_edges()?;
+        let new_calls = other.call_edges()?;
+        report.added_calls = new_calls.difference(&old_calls).cloned().collect();
+        report.removed_calls = old_calls.difference(&new_calls).cloned().collect();
+
+        Ok(report)
+    }
+}
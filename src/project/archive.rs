@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use tree_sitter::Language;
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::analysis::{KindLookup, LanguageKinds};
+use crate::error::GraphError;
+use crate::interner::Interner;
+use crate::project::ProjectGraph;
+use crate::source::SourceStart;
+
+///
+/// Names of the node kinds in an archive, each stored once in its interner.
+///
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct KindNames {
+    names: Interner,
+    ids: HashMap<u16, u32>,
+}
+
+impl KindNames {
+    ///
+    /// The names `language` gives the kinds used in `graphs`.
+    ///
+    pub fn for_graphs<'g, I: IntoIterator<Item = &'g ASTGraph>>(language: &Language, graphs: I) -> Self {
+        let mut kind_names = KindNames::default();
+        for graph in graphs {
+            for gnode in graph.graph.node_weights() {
+                if !kind_names.ids.contains_key(&gnode.kind_id) {
+                    if let Some(name) = language.node_kind_for_id(gnode.kind_id) {
+                        kind_names.insert(gnode.kind_id, name);
+                    }
+                }
+            }
+        }
+        kind_names
+    }
+
+    pub(crate) fn insert(&mut self, kind_id: u16, name: &str) {
+        let id = self.names.intern(name);
+        self.ids.insert(kind_id, id);
+    }
+
+    pub fn get(&self, kind_id: u16) -> Option<&str> {
+        self.ids.get(&kind_id).and_then(|&id| self.names.get(id))
+    }
+
+    ///
+    /// (kind id, name) pairs, by kind id.
+    ///
+    pub fn to_pairs(&self) -> Vec<(u16, String)> {
+        let mut pairs: Vec<(u16, String)> = self.ids.keys().map(|&kind_id| (kind_id, self.get(kind_id).unwrap().to_string())).collect();
+        pairs.sort();
+        pairs
+    }
+
+    pub fn from_pairs(pairs: &[(u16, String)]) -> Self {
+        let mut kind_names = KindNames::default();
+        for (kind_id, name) in pairs {
+            kind_names.insert(*kind_id, name);
+        }
+        kind_names
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.names
+    }
+}
+
+impl KindLookup for KindNames {
+    fn ids_named(&self, name: &str) -> HashSet<u16> {
+        match self.names.id_of(name) {
+            Some(id) => self.ids.iter().filter(|(_, &name_id)| name_id == id).map(|(&kind_id, _)| kind_id).collect(),
+            None => HashSet::new(),
+        }
+    }
+
+    fn name_of(&self, kind_id: u16) -> Option<&str> {
+        self.get(kind_id)
+    }
+}
+
+#[derive(Serialize,Deserialize)]
+struct ArchivedFile {
+    path: u32,
+    title: u32,
+    metadata: Vec<(u32, u32)>,
+    labels: Vec<u32>,
+    source: Option<String>,
+    source_start: SourceStart,
+    graph: SerializableGraph,
+}
+
+#[derive(Serialize,Deserialize)]
+struct ProjectArchive {
+    strings: Interner,
+    kind_names: Vec<(u16, u32)>,
+    files: Vec<ArchivedFile>,
+}
+
+impl ProjectGraph {
+    ///
+    /// Write all files of the project as one bincode archive. Paths, titles,
+    /// metadata and the names of the node kinds used (looked up in
+    /// `language`) go into a single interner stored once for the archive.
+    ///
+    pub fn write_archive<W: Write>(&self, writer: W, language: &Language) -> Result<(), GraphError> {
+        let mut strings = Interner::new();
+        let mut kind_names = BTreeMap::new();
+        let mut files = Vec::with_capacity(self.files.len());
+        for (path, graph) in self.files.iter() {
+            for node in graph.graph.node_indices() {
+                let kind_id = graph.graph[node].kind_id;
+                if !kind_names.contains_key(&kind_id) {
+                    if let Some(name) = language.node_kind_for_id(kind_id) {
+                        kind_names.insert(kind_id, strings.intern(name));
+                    }
+                }
+            }
+            // the title, metadata and labels are kept interned instead
+            let mut serializable_graph = graph.to_serializable();
+            serializable_graph.title.clear();
+            serializable_graph.metadata.clear();
+            serializable_graph.labels.clear();
+            files.push(ArchivedFile {
+                path: strings.intern(path),
+                title: strings.intern(&graph.title),
+                metadata: graph.metadata().iter().map(|(key, value)| (strings.intern(key), strings.intern(value))).collect(),
+                labels: graph.labels().iter().map(|label| strings.intern(label)).collect(),
+                source: graph.source().map(|source| source.to_string()),
+                source_start: graph.source_start(),
+                graph: serializable_graph,
+            });
+        }
+        let archive = ProjectArchive { strings, kind_names: kind_names.into_iter().collect(), files };
+        bincode::serialize_into(writer, &archive)?;
+        Ok(())
+    }
+
+    ///
+    /// Read an archive written by `write_archive`, with the kind names it
+    /// carries.
+    ///
+    pub fn read_archive<R: Read>(reader: R, kinds: LanguageKinds) -> Result<(ProjectGraph, KindNames), GraphError> {
+        let archive: ProjectArchive = bincode::deserialize_from(reader)?;
+        let string = |id: u32| -> Result<String, GraphError> {
+            archive.strings.get(id)
+                .map(|text| text.to_string())
+                .ok_or_else(|| GraphError::Encoding(format!("string {} missing from the archive", id)))
+        };
+
+        let mut project = ProjectGraph::new(kinds);
+        let mut names = Interner::new();
+        let mut ids = HashMap::with_capacity(archive.kind_names.len());
+        for &(kind_id, id) in archive.kind_names.iter() {
+            ids.insert(kind_id, names.intern(&string(id)?));
+        }
+        for file in archive.files {
+            let mut graph = ASTGraph::from_serializable(file.graph);
+            graph.set_title(string(file.title)?);
+            for (key, value) in file.metadata {
+                graph.set_metadata(&string(key)?, &string(value)?);
+            }
+            for label in file.labels {
+                graph.add_label(&string(label)?);
+            }
+            if let Some(source) = file.source {
+                graph.set_source_at(source, file.source_start);
+            }
+            project.insert(&string(file.path)?, graph);
+        }
+        Ok((project, KindNames { names, ids }))
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tree_sitter::{Language, Tree};
+
+use crate::ASTGraph;
+
+/// metadata key of the build time, in seconds since the Unix epoch
+pub const BUILT_AT: &str = "tree-graph.built-at";
+/// metadata key of the version of this crate that built the graph
+pub const CRATE_VERSION: &str = "tree-graph.version";
+/// metadata key of the grammar's ABI version
+pub const GRAMMAR_ABI: &str = "tree-graph.grammar-abi";
+/// metadata key of the grammar's number of node kinds, which changes with
+/// most grammar releases and so stands in for its version
+pub const GRAMMAR_KINDS: &str = "tree-graph.grammar-kinds";
+
+///
+/// When, by which crate version and from which grammar a graph was built.
+/// `build_from_tree` records it in the graph's metadata, which every format
+/// saves (in the versioned header of `save_to_file`), so it is available
+/// again on load; fields are `None` for graphs that were not built from a
+/// tree (or were stored before it was recorded).
+///
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct Provenance {
+    pub built_at: Option<u64>,
+    pub crate_version: Option<String>,
+    pub grammar_abi: Option<usize>,
+    pub grammar_kinds: Option<usize>,
+}
+
+impl Provenance {
+    ///
+    /// Time since the graph was built, `None` if unknown (or in the future).
+    ///
+    pub fn age(&self) -> Option<Duration> {
+        let built_at = UNIX_EPOCH + Duration::from_secs(self.built_at?);
+        SystemTime::now().duration_since(built_at).ok()
+    }
+
+    ///
+    /// Whether the graph was built at most `max_age` ago; unknown build times
+    /// are not fresh.
+    ///
+    pub fn is_fresh(&self, max_age: Duration) -> bool {
+        self.age().is_some_and(|age| age <= max_age)
+    }
+
+    ///
+    /// Whether the graph was built with this crate version and a grammar
+    /// matching `language`, so kind ids mean the same as when it was built.
+    ///
+    pub fn is_compatible_with(&self, language: &Language) -> bool {
+        self.crate_version.as_deref() == Some(env!("CARGO_PKG_VERSION"))
+            && self.grammar_abi == Some(language.version())
+            && self.grammar_kinds == Some(language.node_kind_count())
+    }
+}
+
+impl ASTGraph {
+    pub(crate) fn record_provenance(&mut self, tree: &Tree) {
+        let built_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let language = tree.language();
+        self.set_metadata(BUILT_AT, &built_at.to_string());
+        self.set_metadata(CRATE_VERSION, env!("CARGO_PKG_VERSION"));
+        self.set_metadata(GRAMMAR_ABI, &language.version().to_string());
+        self.set_metadata(GRAMMAR_KINDS, &language.node_kind_count().to_string());
+    }
+
+    pub fn provenance(&self) -> Provenance {
+        let number = |key: &str| self.metadata_value(key).and_then(|value| value.parse::<usize>().ok());
+        Provenance {
+            built_at: self.metadata_value(BUILT_AT).and_then(|value| value.parse().ok()),
+            crate_version: self.metadata_value(CRATE_VERSION).map(|value| value.to_string()),
+            grammar_abi: number(GRAMMAR_ABI),
+            grammar_kinds: number(GRAMMAR_KINDS),
+        }
+    }
+}
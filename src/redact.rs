@@ -0,0 +1,183 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tree_sitter::Language;
+
+use crate::ASTGraph;
+use crate::analysis::kind_ids;
+use crate::error::GraphError;
+use crate::hashing::Fnv64;
+use crate::store::{decode_graph, encode_graph};
+
+///
+/// How redacted text is replaced. Either way the replacement has the same
+/// length and keeps line breaks, so every node range stays valid.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Redaction {
+    /// every character becomes `x`
+    Strip,
+    /// letters derived from a hash of the text, so equal texts (the same
+    /// identifier in several places) stay equal after redaction
+    Hash,
+}
+
+///
+/// What `redacted_source` hides. String and character literal contents are
+/// always redacted; comments by default; identifiers only on request.
+///
+#[derive(Debug,Clone)]
+pub struct RedactOptions {
+    pub mode: Redaction,
+    pub literals: HashSet<u16>,
+    /// parts of literals to redact when the grammar splits them out (the
+    /// quotes and prefixes around them are kept)
+    pub literal_contents: HashSet<u16>,
+    pub comments: HashSet<u16>,
+    pub identifiers: HashSet<u16>,
+}
+
+impl RedactOptions {
+    pub fn new(language: &Language) -> Self {
+        let named = |names: &[&str]| -> HashSet<u16> { names.iter().flat_map(|name| kind_ids(language, name)).collect() };
+        RedactOptions {
+            mode: Redaction::Hash,
+            literals: named(&["string_literal", "raw_string_literal", "char_literal", "system_lib_string"]),
+            literal_contents: named(&["string_content", "escape_sequence", "character", "raw_string_content"]),
+            comments: named(&["comment"]),
+            identifiers: HashSet::new(),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Redaction) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_identifiers(mut self, language: &Language) -> Self {
+        for name in ["identifier", "field_identifier", "type_identifier", "namespace_identifier", "name"] {
+            self.identifiers.extend(kind_ids(language, name));
+        }
+        self
+    }
+
+    pub fn without_comments(mut self) -> Self {
+        self.comments.clear();
+        self
+    }
+
+    fn replacement(&self, text: &str) -> String {
+        let mut hasher = Fnv64::new();
+        hasher.write(text.as_bytes());
+        let mut state = hasher.finish() | 1;
+        text.bytes().map(|byte| {
+            if byte == b'\n' {
+                return '\n';
+            }
+            match self.mode {
+                Redaction::Strip => 'x',
+                Redaction::Hash => {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (b'a' + (state % 26) as u8) as char
+                },
+            }
+        }).collect()
+    }
+}
+
+impl ASTGraph {
+    // byte ranges to replace, sorted and non-overlapping
+    fn redacted_spans(&self, options: &RedactOptions) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        for node in self.graph.node_indices() {
+            let kind_id = self.graph[node].kind_id;
+            let range = self.graph[node].range;
+            if options.identifiers.contains(&kind_id) {
+                spans.push((range.start_byte, range.end_byte));
+            } else if options.comments.contains(&kind_id) {
+                let text = self.get_node_source(node);
+                let opening = text.len() - text.trim_start_matches(|c: char| c == '/' || c == '*' || c == '!' || c == '#').len();
+                let closing = if text.len() >= opening + 2 && text.ends_with("*/") { 2 } else { 0 };
+                spans.push((range.start_byte + opening, range.end_byte - closing));
+            } else if options.literals.contains(&kind_id) {
+                let contents: Vec<NodeIndex> = self.collect_subgraph_nodes(node).into_iter()
+                    .filter(|&n| options.literal_contents.contains(&self.graph[n].kind_id))
+                    .collect();
+                if contents.is_empty() {
+                    if range.end_byte - range.start_byte >= 2 {
+                        spans.push((range.start_byte + 1, range.end_byte - 1));
+                    }
+                } else {
+                    spans.extend(contents.iter().map(|&n| (self.graph[n].range.start_byte, self.graph[n].range.end_byte)));
+                }
+            }
+        }
+
+        spans.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start < last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    ///
+    /// The source with literal contents, comments and (optionally)
+    /// identifiers replaced, byte for byte the same length as the original.
+    /// Fails if the graph has no source.
+    ///
+    pub fn redacted_source(&self, options: &RedactOptions) -> Result<String, GraphError> {
+        let source = self.try_source()?;
+        let base = self.source_start.byte;
+        let mut redacted = String::with_capacity(source.len());
+        let mut position = 0;
+        for (start, end) in self.redacted_spans(options) {
+            let (start, end) = (start - base, end - base);
+            redacted.push_str(&source[position..start]);
+            redacted.push_str(&options.replacement(&source[start..end]));
+            position = end;
+        }
+        redacted.push_str(&source[position..]);
+        Ok(redacted)
+    }
+
+    ///
+    /// Copy of the graph carrying the redacted source. Stored leaf text is
+    /// not carried over, since it would hold the original text, and recorded
+    /// source hashes are recomputed from the redacted source, since they
+    /// would identify it.
+    ///
+    pub fn redacted(&self, options: &RedactOptions) -> Result<ASTGraph, GraphError> {
+        let mut graph = self.clone();
+        graph.set_source_at(self.redacted_source(options)?, self.source_start);
+        graph.leaf_text = None;
+        if graph.graph.node_weights().any(|gnode| gnode.source_hash != 0) {
+            graph.record_source_hashes();
+        }
+        Ok(graph)
+    }
+
+    ///
+    /// Save the graph with its title and redacted source, for sharing graphs
+    /// of proprietary code. Load with `load_from_file_with_source`.
+    ///
+    pub fn save_to_file_redacted<P: AsRef<Path>>(&self, path: P, options: &RedactOptions) -> Result<(), GraphError> {
+        let (_, bytes) = encode_graph(&self.redacted(options)?);
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    ///
+    /// Load a graph saved together with its source (by
+    /// `save_to_file_redacted`).
+    ///
+    pub fn load_from_file_with_source<P: AsRef<Path>>(path: P) -> Result<ASTGraph, GraphError> {
+        decode_graph(&fs::read(path)?)
+    }
+}
@@ -0,0 +1,121 @@
+use petgraph::graph::NodeIndex;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::ops::Range;
+
+use crate::ASTGraph;
+use crate::geometry::{GRange, LineIndex};
+
+///
+/// Outcome of `remap_ranges`. `changed_leaves` are the leaf nodes whose old
+/// text overlapped an edit; their ranges were remapped too, but the text
+/// under them is no longer what was parsed. Comment leaves showing up here
+/// are expected after comment edits, anything else calls for a reparse.
+///
+#[derive(Debug,Clone,Default)]
+pub struct RemapReport {
+    pub remapped: usize,
+    pub changed_leaves: Vec<NodeIndex>,
+}
+
+impl RemapReport {
+    pub fn is_clean(&self) -> bool {
+        self.changed_leaves.is_empty()
+    }
+}
+
+// (old range, new range, unchanged?) of every diff op covering old bytes
+struct OffsetMap {
+    spans: Vec<(Range<usize>, Range<usize>, bool)>,
+    edits: Vec<(Range<usize>, bool)>, // old ranges of every non-equal op, and whether it is a pure insertion
+    new_len: usize,
+}
+
+impl OffsetMap {
+    fn new(old_source: &str, new_source: &str) -> Self {
+        let ops = capture_diff_slices(Algorithm::Myers, old_source.as_bytes(), new_source.as_bytes());
+        let mut spans = Vec::new();
+        let mut edits = Vec::new();
+
+        for op in ops.iter() {
+            let tag = op.tag();
+            if tag != DiffTag::Equal {
+                edits.push((op.old_range(), tag == DiffTag::Insert));
+            }
+            if !op.old_range().is_empty() {
+                spans.push((op.old_range(), op.new_range(), tag == DiffTag::Equal));
+            }
+        }
+
+        OffsetMap { spans, edits, new_len: new_source.len() }
+    }
+
+    fn span_containing(&self, byte: usize) -> Option<&(Range<usize>, Range<usize>, bool)> {
+        let position = self.spans.partition_point(|(old, _, _)| old.end <= byte);
+        self.spans.get(position).filter(|(old, _, _)| old.start <= byte)
+    }
+
+    fn map_start(&self, byte: usize) -> usize {
+        match self.span_containing(byte) {
+            Some((old, new, true)) => new.start + (byte - old.start),
+            Some((_, new, false)) => new.start,
+            None => self.new_len,
+        }
+    }
+
+    fn map_end(&self, byte: usize) -> usize {
+        if byte == 0 {
+            return self.map_start(0);
+        }
+        match self.span_containing(byte - 1) {
+            Some((old, new, true)) => new.start + (byte - 1 - old.start) + 1,
+            Some((_, new, false)) => new.end,
+            None => self.new_len,
+        }
+    }
+
+    fn touches_edit(&self, range: &GRange) -> bool {
+        self.edits.iter().any(|(old, insertion)| {
+            if *insertion {
+                range.start_byte < old.start && old.start < range.end_byte
+            } else {
+                old.start < range.end_byte && range.start_byte < old.end
+            }
+        })
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Move every node range from `old_source` onto `new_source` by diffing the
+    /// two texts, and make `new_source` the graph's source. Meant for edits that
+    /// don't change the syntax tree (reformatting, comment edits); check the
+    /// report to see whether that held.
+    ///
+    pub fn remap_ranges(&mut self, old_source: &str, new_source: &str) -> RemapReport {
+        let offsets = OffsetMap::new(old_source, new_source);
+        let lines = LineIndex::new(new_source);
+        let mut report = RemapReport::default();
+
+        let nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        for node in nodes {
+            let old_range = self.graph[node].range;
+            let is_leaf = self.graph.neighbors(node).next().is_none();
+            if is_leaf && offsets.touches_edit(&old_range) {
+                report.changed_leaves.push(node);
+            }
+
+            let start_byte = offsets.map_start(old_range.start_byte);
+            let end_byte = offsets.map_end(old_range.end_byte).max(start_byte);
+            self.graph[node].range = GRange {
+                start_byte: start_byte,
+                end_byte: end_byte,
+                start_point: lines.point(start_byte),
+                end_point: lines.point(end_byte),
+            };
+            report.remapped += 1;
+        }
+
+        self.set_source(new_source.to_string());
+        report
+    }
+}
@@ -0,0 +1,169 @@
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Language, Parser, Query, QueryCursor, QueryMatch};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+
+///
+/// Result of a rewrite: the new source, its graph (freshly parsed) and how
+/// many matches were replaced.
+///
+#[derive(Debug,Clone)]
+pub struct RewriteResult {
+    pub source: String,
+    pub graph: ASTGraph,
+    pub replacements: usize,
+}
+
+///
+/// A compiled search-and-replace rule.
+///
+/// Each match of `query` replaces the node captured as `@target` (or the
+/// query's first capture if there is no `@target`) with `template`, in which
+/// `$name` is substituted by the source text of capture `@name` and `$$`
+/// stands for a literal `$`. Where matches overlap, the one starting first
+/// (the outer one, on ties) wins and the others are skipped.
+///
+pub struct Rewrite {
+    query: Query,
+    template: String,
+    target: u32,
+}
+
+fn query_error<E: std::fmt::Display>(err: E) -> GraphError {
+    GraphError::Query(err.to_string())
+}
+
+impl Rewrite {
+    pub fn new(language: &Language, query: &str, template: &str) -> Result<Self, GraphError> {
+        let query = Query::new(language, query).map_err(query_error)?;
+        if query.capture_names().is_empty() {
+            return Err(GraphError::Query("rewrite query needs at least one capture".to_string()));
+        }
+        let target = query.capture_index_for_name("target").unwrap_or(0);
+
+        let rewrite = Rewrite { query, template: template.to_string(), target };
+        rewrite.check_template()?;
+        Ok(rewrite)
+    }
+
+    // every `$name` must name a capture of the query
+    fn check_template(&self) -> Result<(), GraphError> {
+        for name in template_names(&self.template) {
+            if self.query.capture_index_for_name(name).is_none() {
+                return Err(GraphError::Query(format!("template uses unknown capture `{}`", name)));
+            }
+        }
+        Ok(())
+    }
+
+    fn interpolate(&self, query_match: &QueryMatch, source: &str) -> String {
+        expand_template(&self.template, |name| {
+            let index = self.query.capture_index_for_name(name);
+            query_match.captures.iter()
+                .find(|c| Some(c.index) == index)
+                .map(|capture| source[capture.node.start_byte()..capture.node.end_byte()].to_string())
+        })
+    }
+
+    ///
+    /// Apply the rule to the graph's source (an error if it has none) and
+    /// reparse the result with `language`.
+    ///
+    pub fn apply(&self, graph: &ASTGraph, language: &Language) -> Result<RewriteResult, GraphError> {
+        let mut parser = Parser::new();
+        parser.set_language(language).map_err(query_error)?;
+        let source = graph.try_source()?;
+        let tree = parser.parse(source, None).ok_or_else(|| GraphError::Query("parsing the source failed".to_string()))?;
+
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.query, tree.root_node(), source.as_bytes());
+        while let Some(query_match) = matches.next() {
+            if let Some(capture) = query_match.captures.iter().find(|c| c.index == self.target) {
+                edits.push((capture.node.start_byte(), capture.node.end_byte(), self.interpolate(query_match, source)));
+            }
+        }
+        edits.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+
+        let mut rewritten = String::with_capacity(source.len());
+        let mut position = 0;
+        let mut replacements = 0;
+        for (start, end, text) in edits {
+            if start < position {
+                continue;
+            }
+            rewritten.push_str(&source[position..start]);
+            rewritten.push_str(&text);
+            position = end;
+            replacements += 1;
+        }
+        rewritten.push_str(&source[position..]);
+
+        let new_tree = parser.parse(&rewritten, None).ok_or_else(|| GraphError::Query("parsing the rewritten source failed".to_string()))?;
+        let mut new_graph = ASTGraph::new(rewritten.clone());
+        new_graph.build_from_tree(&new_tree);
+        new_graph.set_title(graph.title());
+        new_graph.metadata = graph.metadata.clone();
+
+        Ok(RewriteResult { source: rewritten, graph: new_graph, replacements })
+    }
+}
+
+// length of the `$name` placeholder name at the start of `rest`
+fn name_length(rest: &str) -> usize {
+    rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len())
+}
+
+///
+/// Replace every `$name` in `template` with `lookup(name)` (nothing when it
+/// returns `None`); `$$` is a literal `$`.
+///
+pub fn expand_template<F: Fn(&str) -> Option<String>>(template: &str, lookup: F) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        if rest.starts_with('$') {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+        let length = name_length(rest);
+        if let Some(value) = lookup(&rest[..length]) {
+            out.push_str(&value);
+        }
+        rest = &rest[length..];
+    }
+    out.push_str(rest);
+
+    out
+}
+
+///
+/// Names of all `$name` placeholders in `template`.
+///
+pub fn template_names(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        rest = &rest[dollar + 1..];
+        if rest.starts_with('$') {
+            rest = &rest[1..];
+            continue;
+        }
+        let length = name_length(rest);
+        names.push(&rest[..length]);
+        rest = &rest[length..];
+    }
+    names
+}
+
+///
+/// One-shot form of `Rewrite::new(...)?.apply(...)`.
+///
+pub fn rewrite(graph: &ASTGraph, language: &Language, query: &str, template: &str) -> Result<RewriteResult, GraphError> {
+    Rewrite::new(language, query, template)?.apply(graph, language)
+}
@@ -0,0 +1,199 @@
+use petgraph::graph::NodeIndex;
+use std::fmt;
+use tree_sitter::{Language, Query};
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::geometry::GRange;
+use crate::rewrite::{expand_template, template_names};
+use crate::search::Reparsed;
+
+#[cfg(feature="sarif")]
+pub mod sarif;
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+///
+/// A rule finding at one node. `node` indexes the graph the rules ran on and
+/// `node_id` is that node's tree-sitter id.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct Diagnostic {
+    pub rule_id: String,
+    pub severity: Severity,
+    pub message: String,
+    pub range: GRange,
+    pub node: NodeIndex,
+    pub node_id: usize,
+}
+
+///
+/// What a rule looks for. Query matchers report the node captured as
+/// `@target` (or the query's first capture).
+///
+pub enum Matcher {
+    Kind(u16),
+    Predicate(Box<dyn Fn(&ASTGraph, NodeIndex) -> bool + Send + Sync>),
+    Query(Query),
+}
+
+///
+/// A lint rule. In `message`, `$text` expands to the first line of the
+/// matched node's source, `$kind` to its kind id, and for query rules `$name`
+/// to the text of capture `@name`.
+///
+pub struct Rule {
+    pub id: String,
+    pub severity: Severity,
+    pub message: String,
+    pub matcher: Matcher,
+}
+
+///
+/// Rules run together over a graph. Query rules need the graph's language
+/// (to compile the queries and reparse the graph's source); kind and
+/// predicate rules work on any graph, including deserialized ones.
+///
+pub struct RuleSet {
+    language: Option<Language>,
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        RuleSet { language: None, rules: Vec::new() }
+    }
+
+    pub fn with_language(language: Language) -> Self {
+        RuleSet { language: Some(language), rules: Vec::new() }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn add(&mut self, rule: Rule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn add_kind_rule(&mut self, id: &str, severity: Severity, kind_id: u16, message: &str) -> &mut Self {
+        self.add(Rule { id: id.to_string(), severity, message: message.to_string(), matcher: Matcher::Kind(kind_id) })
+    }
+
+    pub fn add_predicate_rule<F>(&mut self, id: &str, severity: Severity, predicate: F, message: &str) -> &mut Self
+    where
+        F: Fn(&ASTGraph, NodeIndex) -> bool + Send + Sync + 'static
+    {
+        self.add(Rule { id: id.to_string(), severity, message: message.to_string(), matcher: Matcher::Predicate(Box::new(predicate)) })
+    }
+
+    pub fn add_query_rule(&mut self, id: &str, severity: Severity, query: &str, message: &str) -> Result<&mut Self, GraphError> {
+        let language = self.language.as_ref()
+            .ok_or_else(|| GraphError::Query("query rules need a RuleSet created with_language".to_string()))?;
+        let query = Query::new(language, query).map_err(|err| GraphError::Query(err.to_string()))?;
+        for name in template_names(message) {
+            if name != "text" && name != "kind" && query.capture_index_for_name(name).is_none() {
+                return Err(GraphError::Query(format!("message uses unknown capture `{}`", name)));
+            }
+        }
+        Ok(self.add(Rule { id: id.to_string(), severity, message: message.to_string(), matcher: Matcher::Query(query) }))
+    }
+
+    pub fn run(&self, graph: &ASTGraph) -> Result<Vec<Diagnostic>, GraphError> {
+        let mut diagnostics = Vec::new();
+        // parsed on the first query rule and shared by the rest
+        let mut reparsed: Option<Reparsed> = None;
+
+        for rule in self.rules.iter() {
+            match &rule.matcher {
+                Matcher::Kind(kind_id) => {
+                    for node in graph.graph.node_indices().filter(|&n| graph.graph[n].kind_id == *kind_id) {
+                        diagnostics.push(diagnostic(graph, rule, node, |_| None));
+                    }
+                },
+                Matcher::Predicate(predicate) => {
+                    for node in graph.graph.node_indices().filter(|&n| predicate(graph, n)) {
+                        diagnostics.push(diagnostic(graph, rule, node, |_| None));
+                    }
+                },
+                Matcher::Query(query) => {
+                    if reparsed.is_none() {
+                        reparsed = Some(Reparsed::new(graph, self.language.as_ref().unwrap())?);
+                    }
+                    run_query(graph, reparsed.as_ref().unwrap(), rule, query, &mut diagnostics)?;
+                },
+            }
+        }
+
+        diagnostics.sort_by_key(|d| (d.range.start_byte, d.range.end_byte, std::cmp::Reverse(d.severity)));
+        Ok(diagnostics)
+    }
+}
+
+fn run_query(graph: &ASTGraph, reparsed: &Reparsed, rule: &Rule, query: &Query, diagnostics: &mut Vec<Diagnostic>) -> Result<(), GraphError> {
+    let target = query.capture_index_for_name("target").unwrap_or(0);
+
+    for captures in reparsed.matches(query)? {
+        let node = match captures.iter().find(|(index, _)| *index == target) {
+            Some((_, node)) => *node,
+            None => continue,
+        };
+        diagnostics.push(diagnostic(graph, rule, node, |name| {
+            let index = query.capture_index_for_name(name);
+            captures.iter()
+                .find(|(capture, _)| Some(*capture) == index)
+                .and_then(|(_, node)| graph.try_node_source(*node).ok())
+                .map(|text| text.to_string())
+        }));
+    }
+
+    Ok(())
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::new()
+    }
+}
+
+fn diagnostic<F: Fn(&str) -> Option<String>>(graph: &ASTGraph, rule: &Rule, node: NodeIndex, captures: F) -> Diagnostic {
+    let gnode = &graph.graph[node];
+    let message = expand_template(&rule.message, |name| match name {
+        "text" => graph.range_text(gnode.range.start_byte, gnode.range.end_byte)
+            .map(|text| text.lines().next().unwrap_or("").to_string()),
+        "kind" => Some(gnode.kind_id.to_string()),
+        _ => captures(name),
+    });
+
+    Diagnostic {
+        rule_id: rule.id.clone(),
+        severity: rule.severity,
+        message: message,
+        range: gnode.range,
+        node: node,
+        node_id: gnode.id,
+    }
+}
+
+///
+/// Run every rule of `rules` over `graph`, returning diagnostics in source order.
+///
+pub fn run_rules(graph: &ASTGraph, rules: &RuleSet) -> Result<Vec<Diagnostic>, GraphError> {
+    rules.run(graph)
+}
@@ -0,0 +1,114 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::error::GraphError;
+use crate::geometry::GPoint;
+use crate::rules::Diagnostic;
+
+pub const SARIF_VERSION: &str = "2.1.0";
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+///
+/// Diagnostics of one source file, identified in the log by `uri` (usually a
+/// path relative to the repository root, which is what code scanning expects).
+/// With the file's text in `source` regions get columns too; without it they
+/// only have lines and byte offsets.
+///
+pub struct SarifArtifact<'a> {
+    pub uri: &'a str,
+    pub source: Option<&'a str>,
+    pub diagnostics: &'a [Diagnostic],
+}
+
+// 1-based column of `point` in code points, counting the characters between
+// the start of its line and its byte
+fn code_point_column(source: &str, byte: usize, point: &GPoint) -> Option<usize> {
+    source.get(byte.checked_sub(point.column)?..byte).map(|prefix| prefix.chars().count() + 1)
+}
+
+fn result(artifact: &SarifArtifact, rule_index: usize, diagnostic: &Diagnostic) -> Value {
+    let range = &diagnostic.range;
+    let mut region = json!({
+        "startLine": range.start_point.row + 1,
+        "endLine": range.end_point.row + 1,
+        "byteOffset": range.start_byte,
+        "byteLength": range.end_byte - range.start_byte,
+    });
+    let columns = artifact.source.and_then(|source| Some((
+        code_point_column(source, range.start_byte, &range.start_point)?,
+        code_point_column(source, range.end_byte, &range.end_point)?,
+    )));
+    if let Some((start_column, end_column)) = columns {
+        region["startColumn"] = json!(start_column);
+        region["endColumn"] = json!(end_column);
+    }
+    json!({
+        "ruleId": diagnostic.rule_id,
+        "ruleIndex": rule_index,
+        "level": diagnostic.severity.to_string(),
+        "message": { "text": diagnostic.message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": artifact.uri },
+                "region": region,
+            }
+        }],
+        "properties": { "nodeId": diagnostic.node_id },
+    })
+}
+
+///
+/// Build a SARIF 2.1.0 log with a single run of `tool_name` over `artifacts`.
+/// The run's rule list holds every rule id that produced a result. Lines and
+/// columns are 1-based, columns counting code points (tree-sitter's count
+/// bytes) as the declared column kind says. `byteOffset` and `byteLength`
+/// locate each region exactly either way.
+///
+pub fn to_sarif(tool_name: &str, artifacts: &[SarifArtifact]) -> Value {
+    let mut rules: BTreeMap<&str, usize> = BTreeMap::new();
+    for artifact in artifacts.iter() {
+        for diagnostic in artifact.diagnostics.iter() {
+            rules.entry(diagnostic.rule_id.as_str()).or_insert(0);
+        }
+    }
+    for (index, (_, rule_index)) in rules.iter_mut().enumerate() {
+        *rule_index = index;
+    }
+
+    let descriptors: Vec<Value> = rules.keys().map(|id| json!({ "id": id })).collect();
+    let results: Vec<Value> = artifacts.iter()
+        .flat_map(|artifact| artifact.diagnostics.iter()
+            .map(|diagnostic| result(artifact, rules[diagnostic.rule_id.as_str()], diagnostic))
+            .collect::<Vec<Value>>())
+        .collect();
+    let locations: Vec<Value> = artifacts.iter()
+        .map(|artifact| json!({ "location": { "uri": artifact.uri } }))
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": tool_name,
+                    "informationUri": "https://github.com/jcarlson23/tree-graph",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": descriptors,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "artifacts": locations,
+            "results": results,
+        }]
+    })
+}
+
+///
+/// `to_sarif` written as pretty-printed JSON.
+///
+pub fn write_sarif<W: Write>(tool_name: &str, artifacts: &[SarifArtifact], writer: W) -> Result<(), GraphError> {
+    serde_json::to_writer_pretty(writer, &to_sarif(tool_name, artifacts))
+        .map_err(|err| GraphError::Encoding(err.to_string()))
+}
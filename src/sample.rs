@@ -0,0 +1,69 @@
+use petgraph::graph::NodeIndex;
+use std::collections::{HashSet, VecDeque};
+
+use crate::ASTGraph;
+
+///
+/// How `sample_subgraph` picks its nodes. Both keep the sample connected to
+/// the roots, so it is a forest of top parts of the original trees.
+///
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum SampleStrategy {
+    /// breadth-first from the roots: the top levels of the file
+    TopDown,
+    /// grows from the roots by adding random children of the nodes taken so
+    /// far; the same seed gives the same sample
+    RandomSubtree { seed: u64 },
+}
+
+// xorshift64*, enough to pick frontier nodes (and shuffle datasets)
+// reproducibly
+pub(crate) struct SampleRng(u64);
+
+impl SampleRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        SampleRng(seed.max(1))
+    }
+
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % bound as u64) as usize
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// A graph of at most `max_nodes` nodes taken from this one with
+    /// `strategy`, for docs, bug reports and visual checks of huge files.
+    /// Node data and source are those of this graph.
+    ///
+    pub fn sample_subgraph(&self, max_nodes: usize, strategy: SampleStrategy) -> ASTGraph {
+        let mut nodes: HashSet<NodeIndex> = HashSet::new();
+        match strategy {
+            SampleStrategy::TopDown => {
+                let mut queue: VecDeque<NodeIndex> = self.roots().into_iter().collect();
+                while let Some(node) = queue.pop_front() {
+                    if nodes.len() == max_nodes {
+                        break;
+                    }
+                    nodes.insert(node);
+                    queue.extend(self.children(node));
+                }
+            }
+            SampleStrategy::RandomSubtree { seed } => {
+                let mut rng = SampleRng::new(seed);
+                let mut frontier = self.roots();
+                while !frontier.is_empty() && nodes.len() < max_nodes {
+                    let node = frontier.swap_remove(rng.below(frontier.len()));
+                    nodes.insert(node);
+                    frontier.extend(self.graph.neighbors(node));
+                }
+            }
+        }
+        let mut sample = self.create_subgraph(&nodes);
+        sample.set_title(self.title.clone());
+        sample
+    }
+}
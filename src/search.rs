@@ -0,0 +1,243 @@
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Language, Parser, Query, QueryCursor, Tree};
+
+use crate::ASTGraph;
+use crate::analysis::KindLookup;
+use crate::error::GraphError;
+
+///
+/// A subtree found by `find_similar` and its edit distance to the pattern.
+///
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct SimilarMatch {
+    pub node: NodeIndex,
+    pub distance: usize,
+}
+
+///
+/// A fresh parse of a graph's source for running tree-sitter queries against
+/// the graph. Captured tree nodes are mapped back to graph nodes by kind and
+/// extent.
+///
+pub(crate) struct Reparsed<'g> {
+    graph: &'g ASTGraph,
+    tree: Tree,
+    by_extent: HashMap<(u16, usize, usize), NodeIndex>,
+}
+
+impl<'g> Reparsed<'g> {
+    pub(crate) fn new(graph: &'g ASTGraph, language: &Language) -> Result<Self, GraphError> {
+        let mut parser = Parser::new();
+        parser.set_language(language).map_err(|err| GraphError::Query(err.to_string()))?;
+        let tree = parser.parse(graph.try_source()?, None)
+            .ok_or_else(|| GraphError::Query("parsing the source failed".to_string()))?;
+
+        let mut by_extent = HashMap::with_capacity(graph.graph.node_count());
+        for node in graph.graph.node_indices() {
+            let gnode = &graph.graph[node];
+            by_extent.entry((gnode.kind_id, gnode.range.start_byte, gnode.range.end_byte)).or_insert(node);
+        }
+
+        Ok(Reparsed { graph, tree, by_extent })
+    }
+
+    ///
+    /// Captures of every match of `query`, as (capture index, graph node).
+    ///
+    pub(crate) fn matches(&self, query: &Query) -> Result<Vec<Vec<(u32, NodeIndex)>>, GraphError> {
+        let mut results = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(query, self.tree.root_node(), self.graph.source_text().as_bytes());
+        // the reparse covers the graph's source, which may start mid-file
+        let base = self.graph.source_start.byte;
+        while let Some(query_match) = matches.next() {
+            let mut captures = Vec::with_capacity(query_match.captures.len());
+            for capture in query_match.captures.iter() {
+                let key = (capture.node.kind_id(), base + capture.node.start_byte(), base + capture.node.end_byte());
+                let node = *self.by_extent.get(&key)
+                    .ok_or_else(|| GraphError::Query("graph does not match its source".to_string()))?;
+                captures.push((capture.index, node));
+            }
+            results.push(captures);
+        }
+        Ok(results)
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Preorder (kind, depth below `node`) sequence of the subtree at `node`;
+    /// two subtrees with equal sequences have the same shape.
+    ///
+    pub(crate) fn shape_sequence(&self, node: NodeIndex) -> Vec<(u16, usize)> {
+        let mut sequence = Vec::new();
+        let mut stack = vec![(node, 0)];
+        while let Some((current, depth)) = stack.pop() {
+            sequence.push((self.graph[current].kind_id, depth));
+            for child in self.children(current).into_iter().rev() {
+                stack.push((child, depth + 1));
+            }
+        }
+        sequence
+    }
+
+    ///
+    /// Find subtrees shaped like the code in `pattern_source` ("where else does
+    /// this occur"). The snippet is parsed with `language` (the graph's
+    /// language), its root wrapper (`translation_unit`, `program`, ...) is
+    /// dropped when it has a single child, and every subtree with the same
+    /// root kind whose preorder kind/depth sequence is within `tolerance`
+    /// edits of the pattern's is returned, closest first.
+    ///
+    /// Only kinds are compared, so identifiers and literal values are
+    /// normalized away: `foo(a, 1)` matches `bar(b, 2)` at distance 0.
+    ///
+    pub fn find_similar(&self, pattern_source: &str, language: &Language, tolerance: usize) -> Result<Vec<SimilarMatch>, GraphError> {
+        let mut parser = Parser::new();
+        parser.set_language(language).map_err(|err| GraphError::Query(err.to_string()))?;
+        let tree = parser.parse(pattern_source, None)
+            .ok_or_else(|| GraphError::Query("parsing the pattern failed".to_string()))?;
+
+        let mut pattern = ASTGraph::new(pattern_source.to_string());
+        pattern.build_from_tree(&tree);
+        let mut pattern_root = pattern.root().ok_or_else(|| GraphError::Query("empty pattern".to_string()))?;
+        let top_level = pattern.children(pattern_root);
+        if top_level.len() == 1 {
+            pattern_root = top_level[0];
+        }
+
+        let pattern_kind = pattern.graph[pattern_root].kind_id;
+        let pattern_sequence = pattern.shape_sequence(pattern_root);
+
+        let mut matches = Vec::new();
+        for node in self.graph.node_indices() {
+            if self.graph[node].kind_id != pattern_kind {
+                continue;
+            }
+            let size = self.subtree_size(node);
+            if size.abs_diff(pattern_sequence.len()) > tolerance {
+                continue;
+            }
+            let distance = edit_distance(&pattern_sequence, &self.shape_sequence(node));
+            if distance <= tolerance {
+                matches.push(SimilarMatch { node, distance });
+            }
+        }
+        matches.sort_by_key(|m| (m.distance, self.graph[m.node].range.start_byte));
+
+        Ok(matches)
+    }
+}
+
+// one element of a kind path pattern
+enum PathStep {
+    Kind(HashSet<u16>),
+    // `*`: exactly one node of any kind
+    Any,
+    // `**`: zero or more nodes of any kind
+    AnyPath,
+}
+
+fn compile_path_pattern<K: KindLookup + ?Sized>(kinds: &K, pattern: &[&str]) -> Result<Vec<PathStep>, GraphError> {
+    if pattern.is_empty() {
+        return Err(GraphError::Query("empty path pattern".to_string()));
+    }
+    if pattern.last() == Some(&"**") {
+        return Err(GraphError::Query("a path pattern cannot end with `**`".to_string()));
+    }
+    pattern.iter().map(|&step| match step {
+        "*" => Ok(PathStep::Any),
+        "**" => Ok(PathStep::AnyPath),
+        name => {
+            let ids = kinds.ids_named(name);
+            if ids.is_empty() {
+                Err(GraphError::Query(format!("unknown node kind `{}`", name)))
+            } else {
+                Ok(PathStep::Kind(ids))
+            }
+        },
+    }).collect()
+}
+
+impl ASTGraph {
+    fn match_path(&self, node: NodeIndex, steps: &[PathStep], path: &mut Vec<NodeIndex>, found: &mut Vec<Vec<NodeIndex>>) {
+        match &steps[0] {
+            PathStep::AnyPath => {
+                // skip it, or let it take `node` and stay on it for the children
+                self.match_path(node, &steps[1..], path, found);
+                path.push(node);
+                for child in self.children(node) {
+                    self.match_path(child, steps, path, found);
+                }
+                path.pop();
+            },
+            step => {
+                if let PathStep::Kind(ids) = step {
+                    if !ids.contains(&self.graph[node].kind_id) {
+                        return;
+                    }
+                }
+                path.push(node);
+                if steps.len() == 1 {
+                    found.push(path.clone());
+                } else {
+                    for child in self.children(node) {
+                        self.match_path(child, &steps[1..], path, found);
+                    }
+                }
+                path.pop();
+            },
+        }
+    }
+
+    fn paths_matching<K: KindLookup + ?Sized>(&self, starts: Vec<NodeIndex>, kinds: &K, pattern: &[&str]) -> Result<Vec<Vec<NodeIndex>>, GraphError> {
+        let steps = compile_path_pattern(kinds, pattern)?;
+        let mut found = Vec::new();
+        for start in starts {
+            self.match_path(start, &steps, &mut Vec::new(), &mut found);
+        }
+        found.sort_by_key(|path| path.iter().map(|&node| (self.graph[node].range.start_byte, node.index())).collect::<Vec<_>>());
+        found.dedup();
+        Ok(found)
+    }
+
+    ///
+    /// Downward paths (parent to child at every step) whose kind names match
+    /// `pattern` element by element, starting at any node. `*` stands for one
+    /// node of any kind, `**` for any number of nodes (including none), e.g.
+    /// `["function_definition", "**", "call_expression"]` finds every call
+    /// inside a function together with the nodes in between. Names are
+    /// looked up in `kinds`, the grammar or a saved kind table.
+    ///
+    pub fn find_paths_matching<K: KindLookup + ?Sized>(&self, kinds: &K, pattern: &[&str]) -> Result<Vec<Vec<NodeIndex>>, GraphError> {
+        self.paths_matching(self.graph.node_indices().collect(), kinds, pattern)
+    }
+
+    ///
+    /// Like `find_paths_matching`, but only paths starting at a root.
+    ///
+    pub fn find_root_paths_matching<K: KindLookup + ?Sized>(&self, kinds: &K, pattern: &[&str]) -> Result<Vec<Vec<NodeIndex>>, GraphError> {
+        self.paths_matching(self.roots(), kinds, pattern)
+    }
+}
+
+///
+/// Levenshtein distance between two sequences.
+///
+pub fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + if x == y { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
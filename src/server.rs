@@ -0,0 +1,189 @@
+//!
+//! A long-lived `GraphService` over JSON-RPC 2.0 on a byte stream (stdio,
+//! usually), framed like the Language Server Protocol: a `Content-Length`
+//! header, a blank line, then the JSON body. Editors and tools that are not
+//! written in Rust keep a warm index this way without FFI.
+//!
+//! Methods: `build` {uri, language, source}, `close` {uri}, `query` {uri,
+//! kind}, `extract` {uri, kind}, `source` {uri, node}, `shutdown`, and the
+//! `exit` notification.
+//!
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Read, Write};
+
+use crate::error::GraphError;
+use crate::service::GraphService;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Operations that reach the service and fail there (unknown uri, kind...).
+pub const SERVICE_ERROR: i64 = -32000;
+
+///
+/// Largest body `read_message` accepts; larger ones are skipped unread and
+/// answered with a parse error, so a bad header can't make the server
+/// allocate whatever it claims.
+///
+pub const MAX_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+
+///
+/// The next framed message, or `None` at the end of the stream.
+///
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>, GraphError> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return match length {
+                None => Ok(None),
+                Some(_) => Err(GraphError::Encoding("stream ended inside a message header".to_string())),
+            };
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>().map_err(|err| GraphError::Encoding(err.to_string()))?);
+            }
+        }
+    }
+
+    let length = length.unwrap_or(0);
+    if length > MAX_MESSAGE_BYTES {
+        io::copy(&mut reader.by_ref().take(length as u64), &mut io::sink())?;
+        return Err(GraphError::Encoding(format!("message of {} bytes is over the {}-byte limit", length, MAX_MESSAGE_BYTES)));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|err| GraphError::Encoding(err.to_string()))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<(), GraphError> {
+    let body = serde_json::to_vec(message).map_err(|err| GraphError::Encoding(err.to_string()))?;
+    write!(writer, "Content-Length: {}\r\n\r\n", body.len())?;
+    writer.write_all(&body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn string_param<'v>(params: &'v Value, name: &str) -> Result<&'v str, (i64, String)> {
+    params.get(name).and_then(Value::as_str).ok_or_else(|| (INVALID_PARAMS, format!("missing string parameter `{}`", name)))
+}
+
+fn to_value<T: Serialize>(result: Result<T, GraphError>) -> Result<Value, (i64, String)> {
+    match result {
+        Ok(value) => serde_json::to_value(value).map_err(|err| (SERVICE_ERROR, err.to_string())),
+        Err(err) => Err((SERVICE_ERROR, err.to_string())),
+    }
+}
+
+///
+/// JSON-RPC front end of a `GraphService`.
+///
+pub struct GraphServer {
+    service: GraphService,
+    shutdown: bool,
+}
+
+impl GraphServer {
+    pub fn new(service: GraphService) -> Self {
+        GraphServer { service: service, shutdown: false }
+    }
+
+    pub fn service(&self) -> &GraphService {
+        &self.service
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "build" => {
+                let uri = string_param(params, "uri")?;
+                let language = string_param(params, "language")?;
+                let source = string_param(params, "source")?.to_string();
+                to_value(self.service.build(uri, language, source))
+            },
+            "close" => Ok(Value::Bool(self.service.close(string_param(params, "uri")?))),
+            "query" => to_value(self.service.query(string_param(params, "uri")?, string_param(params, "kind")?)),
+            "extract" => to_value(self.service.extract(string_param(params, "uri")?, string_param(params, "kind")?)),
+            "source" => {
+                let uri = string_param(params, "uri")?;
+                let node = params.get("node").and_then(Value::as_u64)
+                    .ok_or_else(|| (INVALID_PARAMS, "missing integer parameter `node`".to_string()))?;
+                to_value(self.service.subtree_source(uri, node as usize))
+            },
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    ///
+    /// Answer one message. Notifications (no `id`) get no response.
+    ///
+    pub fn handle(&mut self, message: &Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let method = match message.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => return Some(error_response(id.unwrap_or(Value::Null), INVALID_REQUEST, "missing method".to_string())),
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.dispatch(method, &params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, message),
+        })
+    }
+
+    ///
+    /// Answer messages from `reader` on `writer` until the `exit`
+    /// notification or the end of the stream. Bodies that are not JSON get
+    /// a parse error response and the server keeps going.
+    ///
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut reader: R, mut writer: W) -> Result<(), GraphError> {
+        loop {
+            let message = match read_message(&mut reader) {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok(()),
+                Err(GraphError::Encoding(err)) => {
+                    write_message(&mut writer, &error_response(Value::Null, PARSE_ERROR, err))?;
+                    continue;
+                },
+                Err(err) => return Err(err),
+            };
+            if message.get("method").and_then(Value::as_str) == Some("exit") {
+                return Ok(());
+            }
+            if let Some(response) = self.handle(&message) {
+                write_message(&mut writer, &response)?;
+            }
+        }
+    }
+
+    ///
+    /// `serve` on the process's stdin and stdout.
+    ///
+    pub fn serve_stdio(&mut self) -> Result<(), GraphError> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        self.serve(stdin.lock(), stdout.lock())
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown
+    }
+}
@@ -0,0 +1,146 @@
+//!
+//! The operations the crate's service front ends (JSON-RPC, gRPC) offer,
+//! independent of the wire format: graphs built from sources sent by a
+//! client and kept warm under a client-chosen uri, then queried by kind name.
+//!
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tree_sitter::{Language, Parser};
+
+use crate::ASTGraph;
+use crate::analysis::kind_ids;
+use crate::error::GraphError;
+use crate::geometry::GRange;
+use crate::languages::LanguageRegistry;
+
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct BuildSummary {
+    pub nodes: usize,
+    pub edges: usize,
+}
+
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct NodeInfo {
+    pub index: usize,
+    pub kind: String,
+    pub kind_id: u16,
+    pub range: GRange,
+}
+
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct ExtractedSubgraph {
+    pub root: NodeInfo,
+    pub nodes: usize,
+    pub source: String,
+}
+
+struct LoadedGraph {
+    language: Language,
+    graph: ASTGraph,
+}
+
+///
+/// Graphs by uri, built with the grammars of a `LanguageRegistry`.
+///
+pub struct GraphService {
+    registry: LanguageRegistry,
+    graphs: BTreeMap<String, LoadedGraph>,
+}
+
+impl GraphService {
+    pub fn new(registry: LanguageRegistry) -> Self {
+        GraphService { registry: registry, graphs: BTreeMap::new() }
+    }
+
+    fn loaded(&self, uri: &str) -> Result<&LoadedGraph, GraphError> {
+        self.graphs.get(uri).ok_or_else(|| GraphError::Query(format!("no graph for {}", uri)))
+    }
+
+    fn node_info(loaded: &LoadedGraph, node: NodeIndex) -> NodeInfo {
+        let gnode = &loaded.graph[node];
+        NodeInfo {
+            index: node.index(),
+            kind: loaded.language.node_kind_for_id(gnode.kind_id).unwrap_or("").to_string(),
+            kind_id: gnode.kind_id,
+            range: gnode.range,
+        }
+    }
+
+    fn kinds_named(loaded: &LoadedGraph, kind: &str) -> Result<HashSet<u16>, GraphError> {
+        let ids = kind_ids(&loaded.language, kind);
+        if ids.is_empty() {
+            return Err(GraphError::Query(format!("unknown node kind `{}`", kind)));
+        }
+        Ok(ids)
+    }
+
+    ///
+    /// Parse `source` as `language` (a registry name) and keep its graph
+    /// under `uri`, replacing any earlier one.
+    ///
+    pub fn build(&mut self, uri: &str, language: &str, source: String) -> Result<BuildSummary, GraphError> {
+        let entry = self.registry.get(language).ok_or_else(|| GraphError::Query(format!("unknown language `{}`", language)))?;
+        let mut parser = Parser::new();
+        parser.set_language(&entry.language).map_err(|err| GraphError::Query(err.to_string()))?;
+        let tree = parser.parse(&source, None).ok_or_else(|| GraphError::Query(format!("parsing {} failed", uri)))?;
+        let mut graph = ASTGraph::new(source);
+        graph.build_from_tree(&tree);
+        graph.set_title(uri.to_string());
+
+        let summary = BuildSummary { nodes: graph.graph().node_count(), edges: graph.edge_count() };
+        self.graphs.insert(uri.to_string(), LoadedGraph { language: entry.language.clone(), graph: graph });
+        Ok(summary)
+    }
+
+    pub fn close(&mut self, uri: &str) -> bool {
+        self.graphs.remove(uri).is_some()
+    }
+
+    pub fn uris(&self) -> impl Iterator<Item = &str> {
+        self.graphs.keys().map(|uri| uri.as_str())
+    }
+
+    pub fn graph(&self, uri: &str) -> Option<&ASTGraph> {
+        self.graphs.get(uri).map(|loaded| &loaded.graph)
+    }
+
+    ///
+    /// Nodes of kind `kind` (by name), in index order.
+    ///
+    pub fn query(&self, uri: &str, kind: &str) -> Result<Vec<NodeInfo>, GraphError> {
+        let loaded = self.loaded(uri)?;
+        let kinds = GraphService::kinds_named(loaded, kind)?;
+        Ok(loaded.graph.nodes()
+            .filter(|(_, gnode)| kinds.contains(&gnode.kind_id))
+            .map(|(node, _)| GraphService::node_info(loaded, node))
+            .collect())
+    }
+
+    ///
+    /// The subtrees rooted at nodes of kind `kind`, with their sources.
+    ///
+    pub fn extract(&self, uri: &str, kind: &str) -> Result<Vec<ExtractedSubgraph>, GraphError> {
+        let loaded = self.loaded(uri)?;
+        let kinds = GraphService::kinds_named(loaded, kind)?;
+        let mut extracted = kinds.iter()
+            .flat_map(|&kind_id| loaded.graph.subtrees_of_kind(kind_id))
+            .map(|subtree| Ok(ExtractedSubgraph {
+                root: GraphService::node_info(loaded, subtree.root),
+                nodes: loaded.graph.subtree_size(subtree.root),
+                source: loaded.graph.try_node_source(subtree.root)?.to_string(),
+            }))
+            .collect::<Result<Vec<ExtractedSubgraph>, GraphError>>()?;
+        extracted.sort_by_key(|subgraph| subgraph.root.index);
+        Ok(extracted)
+    }
+
+    pub fn subtree_source(&self, uri: &str, index: usize) -> Result<String, GraphError> {
+        let loaded = self.loaded(uri)?;
+        let node = NodeIndex::new(index);
+        if loaded.graph.get(node).is_none() {
+            return Err(GraphError::InvalidNode(format!("no node {} in {}", index, uri)));
+        }
+        Ok(loaded.graph.try_node_source(node)?.to_string())
+    }
+}
@@ -0,0 +1,313 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature="mmap")]
+use memmap2::Mmap;
+#[cfg(feature="mmap")]
+use std::fs::File;
+#[cfg(feature="mmap")]
+use std::path::Path;
+
+use crate::ASTGraph;
+use crate::error::GraphError;
+use crate::geometry::{GPoint, GRange};
+
+///
+/// Where an external source comes from: a blob store, a content-addressed
+/// cache, a VCS object database. Called once per buffer, on first use.
+///
+pub trait SourceFetcher: Send + Sync {
+    fn fetch(&self, key: &str) -> Result<String, GraphError>;
+}
+
+impl<F: Fn(&str) -> Result<String, GraphError> + Send + Sync> SourceFetcher for F {
+    fn fetch(&self, key: &str) -> Result<String, GraphError> {
+        self(key)
+    }
+}
+
+///
+/// A source fetched on demand under `key`. Clones share the fetched text;
+/// a failed fetch is not remembered, so the next access tries again.
+///
+#[derive(Clone)]
+pub struct ExternalSource {
+    key: String,
+    fetcher: Arc<dyn SourceFetcher>,
+    text: Arc<OnceLock<String>>,
+}
+
+impl ExternalSource {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn is_fetched(&self) -> bool {
+        self.text.get().is_some()
+    }
+
+    fn text(&self) -> Result<&str, GraphError> {
+        if self.text.get().is_none() {
+            let fetched = self.fetcher.fetch(&self.key)
+                .map_err(|err| GraphError::SourceUnavailable(format!("fetching `{}` failed: {}", self.key, err)))?;
+            // another thread may have won the race; its text is as good
+            let _ = self.text.set(fetched);
+        }
+        Ok(self.text.get().map(|text| text.as_str()).unwrap_or(""))
+    }
+}
+
+///
+/// The source text a graph's ranges point into, wherever it lives: owned,
+/// shared between graphs (subgraphs of one file, say), memory-mapped, or
+/// held elsewhere and fetched the first time it is read.
+///
+#[derive(Clone)]
+pub enum SourceBuffer {
+    Owned(String),
+    Shared(Arc<str>),
+    #[cfg(feature="mmap")]
+    Mapped(Arc<Mmap>),
+    External(ExternalSource),
+}
+
+impl SourceBuffer {
+    pub fn external<F: SourceFetcher + 'static>(key: &str, fetcher: F) -> SourceBuffer {
+        SourceBuffer::from_fetcher(key, Arc::new(fetcher))
+    }
+
+    ///
+    /// Like `external`, for one fetcher serving many graphs.
+    ///
+    pub fn from_fetcher(key: &str, fetcher: Arc<dyn SourceFetcher>) -> SourceBuffer {
+        SourceBuffer::External(ExternalSource { key: key.to_string(), fetcher: fetcher, text: Arc::new(OnceLock::new()) })
+    }
+
+    ///
+    /// Map the file at `path`, which must be UTF-8 and stay unchanged while
+    /// mapped (see `MappedAstGraph`).
+    ///
+    #[cfg(feature="mmap")]
+    pub fn map_file<P: AsRef<Path>>(path: P) -> Result<SourceBuffer, GraphError> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read-only; the caller keeps the file unchanged
+        // while it is mapped
+        let map = unsafe { Mmap::map(&file)? };
+        std::str::from_utf8(&map).map_err(|err| GraphError::Encoding(format!("source is not UTF-8: {}", err)))?;
+        Ok(SourceBuffer::Mapped(Arc::new(map)))
+    }
+
+    ///
+    /// The text, fetching it first for an external source.
+    ///
+    pub fn text(&self) -> Result<&str, GraphError> {
+        match self {
+            SourceBuffer::Owned(text) => Ok(text),
+            SourceBuffer::Shared(text) => Ok(text),
+            // SAFETY: checked to be UTF-8 in `map_file`, and the map is read-only
+            #[cfg(feature="mmap")]
+            SourceBuffer::Mapped(map) => Ok(unsafe { std::str::from_utf8_unchecked(map) }),
+            SourceBuffer::External(external) => external.text(),
+        }
+    }
+
+    ///
+    /// Whether reading the text is free: false only for an external source
+    /// not fetched yet.
+    ///
+    pub fn is_loaded(&self) -> bool {
+        match self {
+            SourceBuffer::External(external) => external.is_fetched(),
+            _ => true,
+        }
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if let SourceBuffer::Owned(text) = self {
+            text.shrink_to_fit();
+        }
+    }
+}
+
+impl fmt::Debug for SourceBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceBuffer::Owned(text) => write!(f, "Owned({} bytes)", text.len()),
+            SourceBuffer::Shared(text) => write!(f, "Shared({} bytes)", text.len()),
+            #[cfg(feature="mmap")]
+            SourceBuffer::Mapped(map) => write!(f, "Mapped({} bytes)", map.len()),
+            SourceBuffer::External(external) => write!(f, "External({:?}, fetched: {})", external.key, external.is_fetched()),
+        }
+    }
+}
+
+///
+/// Buffers are equal when their texts are, whatever their origin (which
+/// fetches external sources); sources that can't be read equal nothing.
+///
+impl PartialEq for SourceBuffer {
+    fn eq(&self, other: &SourceBuffer) -> bool {
+        match (self.text(), other.text()) {
+            (Ok(text), Ok(other_text)) => text == other_text,
+            _ => false,
+        }
+    }
+}
+
+impl From<String> for SourceBuffer {
+    fn from(text: String) -> SourceBuffer {
+        SourceBuffer::Owned(text)
+    }
+}
+
+impl From<&str> for SourceBuffer {
+    fn from(text: &str) -> SourceBuffer {
+        SourceBuffer::Owned(text.to_string())
+    }
+}
+
+impl From<Arc<str>> for SourceBuffer {
+    fn from(text: Arc<str>) -> SourceBuffer {
+        SourceBuffer::Shared(text)
+    }
+}
+
+///
+/// Where a graph's source starts in the file its node ranges refer to: at
+/// the start for graphs built from a whole file, at the subtree's start for
+/// the subgraphs of `extract_subgraphs`, whose source is just their slice.
+///
+#[derive(Serialize,Deserialize,Debug,Copy,Clone,PartialEq)]
+pub struct SourceStart {
+    pub byte: usize,
+    pub point: GPoint,
+}
+
+impl Default for SourceStart {
+    fn default() -> Self {
+        SourceStart { byte: 0, point: GPoint { row: 0, column: 0 } }
+    }
+}
+
+impl SourceStart {
+    pub fn of(range: &GRange) -> Self {
+        SourceStart { byte: range.start_byte, point: range.start_point }
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// The source, if the graph has one. Graphs built from a tree have it;
+    /// deserialized graphs, imports and graphs after `drop_source` don't
+    /// until `set_source` attaches one. An external source is fetched here
+    /// on first use; `try_source` says why when that fails.
+    ///
+    pub fn source(&self) -> Option<&str> {
+        self.try_source().ok()
+    }
+
+    pub fn source_buffer(&self) -> Option<&SourceBuffer> {
+        self.source.as_ref()
+    }
+
+    ///
+    /// Whether a source is attached, without fetching it.
+    ///
+    pub fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    ///
+    /// Attach the source of the whole file the ranges refer to.
+    ///
+    pub fn set_source<S: Into<SourceBuffer>>(&mut self, source: S) {
+        self.set_source_at(source, SourceStart::default());
+    }
+
+    ///
+    /// Attach a source that is only part of the file, starting at `start`.
+    ///
+    pub fn set_source_at<S: Into<SourceBuffer>>(&mut self, source: S, start: SourceStart) {
+        self.source = Some(source.into());
+        self.source_start = start;
+    }
+
+    pub fn source_start(&self) -> SourceStart {
+        self.source_start
+    }
+
+    pub fn take_source(&mut self) -> Option<SourceBuffer> {
+        self.source.take()
+    }
+
+    ///
+    /// The source, or an error saying the graph has none or fetching it
+    /// failed.
+    ///
+    pub fn try_source(&self) -> Result<&str, GraphError> {
+        match self.source.as_ref() {
+            Some(source) => source.text(),
+            None => Err(GraphError::SourceUnavailable(format!("graph `{}` has no source attached", self.title))),
+        }
+    }
+
+    ///
+    /// Source text of `node`; unlike `get_node_source` an error, not a
+    /// panic, when the node, the source or its range within the source is
+    /// missing.
+    ///
+    pub fn try_node_source(&self, node: NodeIndex) -> Result<&str, GraphError> {
+        self.slice_source(self.try_source()?, node)
+    }
+
+    ///
+    /// Source text of each of `nodes`, looking the source up (and fetching
+    /// it, if external) once for all of them. Fails up front if the graph has
+    /// no source; a node that isn't in it yields an error in its place.
+    ///
+    pub fn node_sources<'a, I>(&'a self, nodes: I) -> Result<impl Iterator<Item = Result<&'a str, GraphError>> + 'a, GraphError>
+    where
+        I: IntoIterator<Item = NodeIndex>,
+        I::IntoIter: 'a,
+    {
+        let source = self.try_source()?;
+        Ok(nodes.into_iter().map(move |node| self.slice_source(source, node)))
+    }
+
+    ///
+    /// Write the source text of `node` to `writer` without copying it, for
+    /// concatenating many nodes into one export.
+    ///
+    pub fn write_node_source<W: Write>(&self, node: NodeIndex, writer: &mut W) -> Result<(), GraphError> {
+        writer.write_all(self.try_node_source(node)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn slice_source<'s>(&self, source: &'s str, node: NodeIndex) -> Result<&'s str, GraphError> {
+        let range = self.graph.node_weight(node)
+            .ok_or_else(|| GraphError::InvalidNode(format!("no node {}", node.index())))?
+            .range;
+        slice_at(source, self.source_start, range.start_byte, range.end_byte).ok_or_else(|| GraphError::SourceUnavailable(format!(
+            "range {}..{} of node {} is outside the {}-byte source starting at byte {}",
+            range.start_byte, range.end_byte, node.index(), source.len(), self.source_start.byte)))
+    }
+
+    ///
+    /// Text of the file bytes `start..end`, if the source holds them.
+    ///
+    pub(crate) fn range_text(&self, start: usize, end: usize) -> Option<&str> {
+        slice_at(self.source()?, self.source_start, start, end)
+    }
+
+    // the source, empty when there is none, for code that treats both alike
+    pub(crate) fn source_text(&self) -> &str {
+        self.source().unwrap_or("")
+    }
+}
+
+fn slice_at(source: &str, start: SourceStart, from: usize, to: usize) -> Option<&str> {
+    source.get(from.checked_sub(start.byte)?..to.checked_sub(start.byte)?)
+}
@@ -0,0 +1,131 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::ASTGraph;
+use crate::geometry::GRange;
+
+///
+/// A subtree and its node count (root included).
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct SubtreeSize {
+    pub node: NodeIndex,
+    pub kind_id: u16,
+    pub size: usize,
+    pub range: GRange,
+}
+
+///
+/// A root-to-leaf path; `depth` is the number of edges on it.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct DeepPath {
+    pub leaf: NodeIndex,
+    pub depth: usize,
+    pub path: Vec<NodeIndex>,
+    pub range: GRange,
+}
+
+impl ASTGraph {
+    // subtree sizes of all nodes, by node index, in one pass
+    fn subtree_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![1; self.graph.node_count()];
+        let mut order = Vec::with_capacity(self.graph.node_count());
+        let mut stack = self.roots();
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(self.graph.neighbors(node));
+        }
+        for &node in order.iter().rev() {
+            if let Some(parent) = self.parent(node) {
+                sizes[parent.index()] += sizes[node.index()];
+            }
+        }
+        sizes
+    }
+
+    ///
+    /// The `k` largest subtrees, biggest first (ties in node order). Useful
+    /// for spotting pathological files and sizing chunks before heavy work.
+    ///
+    pub fn largest_subtrees(&self, k: usize) -> Vec<SubtreeSize> {
+        let sizes = self.subtree_sizes();
+        let mut nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        nodes.sort_by_key(|node| (std::cmp::Reverse(sizes[node.index()]), node.index()));
+        nodes.into_iter().take(k).map(|node| SubtreeSize {
+            node: node,
+            kind_id: self.graph[node].kind_id,
+            size: sizes[node.index()],
+            range: self.graph[node].range,
+        }).collect()
+    }
+
+    ///
+    /// The `k` deepest root-to-leaf paths, deepest first (ties in source
+    /// order of the leaf).
+    ///
+    pub fn deepest_paths(&self, k: usize) -> Vec<DeepPath> {
+        let mut leaves: Vec<(usize, NodeIndex)> = Vec::new();
+        let mut stack: Vec<(NodeIndex, usize)> = self.roots().into_iter().map(|root| (root, 0)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            let mut children = self.graph.neighbors(node).peekable();
+            if children.peek().is_none() {
+                leaves.push((depth, node));
+            }
+            stack.extend(children.map(|child| (child, depth + 1)));
+        }
+        leaves.sort_by_key(|&(depth, leaf)| (std::cmp::Reverse(depth), self.graph[leaf].range.start_byte, leaf.index()));
+
+        leaves.into_iter().take(k).map(|(depth, leaf)| {
+            let mut path = vec![leaf];
+            while let Some(parent) = self.parent(*path.last().unwrap()) {
+                path.push(parent);
+            }
+            path.reverse();
+            DeepPath { leaf: leaf, depth: depth, path: path, range: self.graph[leaf].range }
+        }).collect()
+    }
+
+    ///
+    /// Edges on the longest root-to-leaf path (0 for a single node or an
+    /// empty graph).
+    ///
+    pub fn depth(&self) -> usize {
+        self.deepest_paths(1).first().map_or(0, |path| path.depth)
+    }
+
+    ///
+    /// Multi-line report for logs: size, shape and source of the graph.
+    ///
+    pub fn describe(&self) -> String {
+        let leaves = self.graph.node_indices().filter(|&node| self.graph.neighbors(node).next().is_none()).count();
+        let kinds: HashSet<u16> = self.graph.node_indices().map(|node| self.graph[node].kind_id).collect();
+        let largest = self.largest_subtrees(self.roots().len() + 1).into_iter()
+            .find(|subtree| self.parent(subtree.node).is_some());
+
+        let mut report = format!("graph {}\n", self);
+        report.push_str(&format!("  roots: {}\n", self.roots().len()));
+        report.push_str(&format!("  leaves: {}\n", leaves));
+        report.push_str(&format!("  distinct kinds: {}\n", kinds.len()));
+        if let Some(largest) = largest {
+            report.push_str(&format!("  largest subtree: {} nodes (kind {}, line {})\n",
+                                     largest.size, largest.kind_id, largest.range.start_point.row + 1));
+        }
+        match self.source() {
+            Some(source) => report.push_str(&format!("  source: {} bytes, {} lines", source.len(), source.lines().count())),
+            None => report.push_str("  source: none"),
+        }
+        report
+    }
+}
+
+///
+/// One-line summary: `title, N nodes, M edges, depth D`.
+///
+impl fmt::Display for ASTGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let title = if self.title.is_empty() { "untitled" } else { self.title.as_str() };
+        write!(f, "{}, {} nodes, {} edges, depth {}", title, self.graph.node_count(), self.graph.edge_count(), self.depth())
+    }
+}
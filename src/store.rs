@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+use crate::hashing::Fnv64;
+use crate::provenance;
+use crate::source::SourceStart;
+
+#[cfg(feature="s3")]
+pub mod object;
+
+///
+/// Persistent collection of named graphs.
+///
+/// Graphs are stored content-addressed: `put` serializes the graph (title,
+/// metadata, labels, source and structure), names the object after the hash
+/// of everything but the build time (`provenance::BUILT_AT`), and records
+/// `name -> key` in a manifest. Storing identical graphs under several names
+/// keeps a single object, even when they were built at different times: the
+/// object keeps the build time of the first one stored, and that is what
+/// `get` returns for every name. `gc` deletes objects no manifest entry
+/// points to any more.
+///
+pub trait GraphStore {
+    /// Store `graph` under `name` (replacing any previous entry) and return its key.
+    fn put(&mut self, name: &str, graph: &ASTGraph) -> Result<String, GraphError>;
+
+    fn get(&self, name: &str) -> Result<Option<ASTGraph>, GraphError>;
+
+    fn list(&self) -> Result<Vec<ManifestEntry>, GraphError>;
+
+    /// Drop `name` from the manifest; the object itself goes on the next `gc`.
+    fn remove(&mut self, name: &str) -> Result<bool, GraphError>;
+
+    /// Delete unreferenced objects, returning how many were removed.
+    fn gc(&mut self) -> Result<usize, GraphError>;
+}
+
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub key: String,
+    pub title: String,
+    pub node_count: usize,
+    pub size: u64,
+}
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize,Deserialize,Debug,Clone)]
+struct Manifest {
+    version: u32,
+    entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest { version: MANIFEST_VERSION, entries: BTreeMap::new() }
+    }
+}
+
+#[derive(Serialize,Deserialize)]
+struct StoredGraph {
+    source: Option<String>,
+    source_start: SourceStart,
+    graph: SerializableGraph,
+}
+
+pub(crate) fn encode_graph(graph: &ASTGraph) -> Result<(String, Vec<u8>), GraphError> {
+    let stored = StoredGraph {
+        source: graph.source().map(|source| source.to_string()),
+        source_start: graph.source_start(),
+        graph: graph.to_serializable(),
+    };
+    let bytes = bincode::serialize(&stored)?;
+
+    // the key leaves out the build time, so rebuilding an unchanged file
+    // still maps to the object already stored, which keeps its own
+    let mut keyed = stored;
+    keyed.graph.metadata.remove(provenance::BUILT_AT);
+    let mut hasher = Fnv64::new();
+    hasher.write(&bincode::serialize(&keyed)?);
+    Ok((format!("{:016x}", hasher.finish()), bytes))
+}
+
+pub(crate) fn decode_graph(bytes: &[u8]) -> Result<ASTGraph, GraphError> {
+    let stored: StoredGraph = bincode::deserialize(bytes)?;
+    let mut graph = ASTGraph::try_from_serializable(stored.graph).map_err(|err| GraphError::Storage(err.to_string()))?;
+    if let Some(source) = stored.source {
+        graph.set_source_at(source, stored.source_start);
+    }
+    Ok(graph)
+}
+
+fn decode_manifest(bytes: &[u8]) -> Result<Manifest, GraphError> {
+    let manifest: Manifest = bincode::deserialize(bytes)?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(GraphError::Encoding(format!("unsupported manifest version {}", manifest.version)));
+    }
+    Ok(manifest)
+}
+
+///
+/// Raw key/value storage underneath a `BackendStore`. Keys are `/`-separated
+/// relative paths such as `manifest.bin` or `objects/3f/3f0c...tgb`;
+/// implementations map them onto files, object keys, database rows, etc.
+/// Implement this to keep graphs in Redis, RocksDB or any other database.
+///
+pub trait GraphBackend {
+    /// Store `bytes` under `key`, replacing any existing value atomically.
+    fn put(&mut self, key: &str, bytes: &[u8]) -> Result<(), GraphError>;
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, GraphError>;
+
+    fn contains(&self, key: &str) -> Result<bool, GraphError> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// All keys starting with `prefix`.
+    fn list(&self, prefix: &str) -> Result<Vec<String>, GraphError>;
+
+    fn delete(&mut self, key: &str) -> Result<bool, GraphError>;
+}
+
+pub const MANIFEST_KEY: &str = "manifest.bin";
+
+///
+/// Key of the object with content hash `key`: `objects/<k0k1>/<key>.tgb`,
+/// where `<key>` is the 16 hex digit FNV-1a hash of the object bytes (less
+/// the build time, see `GraphStore`) and `<k0k1>` its first two digits.
+///
+pub fn object_key(key: &str) -> String {
+    format!("objects/{}/{}.tgb", &key[..2], key)
+}
+
+///
+/// `GraphStore` over any `GraphBackend`, with the layout
+///
+/// ```text
+/// manifest.bin              bincode manifest (name -> entry)
+/// objects/<k0k1>/<key>.tgb  one object per distinct graph
+/// ```
+///
+/// The manifest is a single value, so when several processes share a
+/// backend the last `put`/`remove` wins; call `refresh` to pick up changes
+/// made by others.
+///
+pub struct BackendStore<B: GraphBackend> {
+    backend: B,
+    manifest: Manifest,
+}
+
+impl<B: GraphBackend> BackendStore<B> {
+    pub fn new(backend: B) -> Result<Self, GraphError> {
+        let mut store = BackendStore { backend: backend, manifest: Manifest::default() };
+        store.refresh()?;
+        Ok(store)
+    }
+
+    ///
+    /// Re-read the manifest from the backend.
+    ///
+    pub fn refresh(&mut self) -> Result<(), GraphError> {
+        self.manifest = match self.backend.get(MANIFEST_KEY)? {
+            Some(bytes) => decode_manifest(&bytes)?,
+            None => Manifest::default(),
+        };
+        Ok(())
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    fn write_manifest(&mut self) -> Result<(), GraphError> {
+        let bytes = bincode::serialize(&self.manifest)?;
+        self.backend.put(MANIFEST_KEY, &bytes)
+    }
+}
+
+impl<B: GraphBackend> GraphStore for BackendStore<B> {
+    fn put(&mut self, name: &str, graph: &ASTGraph) -> Result<String, GraphError> {
+        let (key, bytes) = encode_graph(graph)?;
+        let path = object_key(&key);
+
+        if !self.backend.contains(&path)? {
+            self.backend.put(&path, &bytes)?;
+        }
+
+        self.manifest.entries.insert(name.to_string(), ManifestEntry {
+            name: name.to_string(),
+            key: key.clone(),
+            title: graph.title(),
+            node_count: graph.node_count(),
+            size: bytes.len() as u64,
+        });
+        self.write_manifest()?;
+
+        Ok(key)
+    }
+
+    fn get(&self, name: &str) -> Result<Option<ASTGraph>, GraphError> {
+        let entry = match self.manifest.entries.get(name) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        match self.backend.get(&object_key(&entry.key))? {
+            Some(bytes) => Ok(Some(decode_graph(&bytes)?)),
+            None => Err(GraphError::Storage(format!("object {} for {} is missing", entry.key, name))),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<ManifestEntry>, GraphError> {
+        Ok(self.manifest.entries.values().cloned().collect())
+    }
+
+    fn remove(&mut self, name: &str) -> Result<bool, GraphError> {
+        if self.manifest.entries.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.write_manifest()?;
+        Ok(true)
+    }
+
+    fn gc(&mut self) -> Result<usize, GraphError> {
+        let live: HashSet<String> = self.manifest.entries.values().map(|entry| object_key(&entry.key)).collect();
+        let mut removed = 0;
+
+        for key in self.backend.list("objects/")? {
+            if !live.contains(&key) && self.backend.delete(&key)? {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+///
+/// `GraphBackend` on the local filesystem: every key is a file below `root`.
+/// Values are written to a temporary file and renamed into place.
+///
+pub struct DirectoryBackend {
+    root: PathBuf,
+}
+
+impl DirectoryBackend {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self, GraphError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        Ok(DirectoryBackend { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    fn collect_keys(&self, directory: &Path, keys: &mut Vec<String>) -> Result<(), GraphError> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.collect_keys(&path, keys)?;
+            } else if path.extension().is_none_or(|extension| extension != "tmp") {
+                let relative = path.strip_prefix(&self.root).unwrap();
+                let key: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+                keys.push(key.join("/"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GraphBackend for DirectoryBackend {
+    fn put(&mut self, key: &str, bytes: &[u8]) -> Result<(), GraphError> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temporary = path.with_extension("tmp");
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, GraphError> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn contains(&self, key: &str) -> Result<bool, GraphError> {
+        Ok(self.path(key).exists())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, GraphError> {
+        let mut keys = Vec::new();
+        self.collect_keys(&self.root, &mut keys)?;
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, GraphError> {
+        match fs::remove_file(self.path(key)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+///
+/// The crate's default on-disk graph repository, see `BackendStore` for the
+/// layout.
+///
+pub type DirectoryStore = BackendStore<DirectoryBackend>;
+
+impl BackendStore<DirectoryBackend> {
+    ///
+    /// Open the store at `root`, creating the directory if needed.
+    ///
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, GraphError> {
+        BackendStore::new(DirectoryBackend::new(root)?)
+    }
+}
@@ -0,0 +1,93 @@
+use s3::bucket::Bucket;
+
+use crate::error::GraphError;
+use super::{BackendStore, GraphBackend};
+
+///
+/// `GraphBackend` on S3-compatible object storage; keys are stored below
+/// `prefix` in the bucket.
+///
+pub struct S3Backend {
+    bucket: Bucket,
+    prefix: String,
+}
+
+///
+/// Graph store shared through a bucket, with the same layout as
+/// `DirectoryStore`. Objects are immutable and safe to share between workers.
+///
+pub type S3Store = BackendStore<S3Backend>;
+
+fn storage_error<E: std::fmt::Display>(err: E) -> GraphError {
+    GraphError::Storage(err.to_string())
+}
+
+impl S3Backend {
+    ///
+    /// Use `prefix` in an already configured bucket (region, credentials,
+    /// path style, ...).
+    ///
+    pub fn new(bucket: Bucket, prefix: &str) -> Self {
+        S3Backend { bucket: bucket, prefix: prefix.trim_matches('/').to_string() }
+    }
+
+    fn path(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", self.prefix, key)
+        }
+    }
+}
+
+impl BackendStore<S3Backend> {
+    pub fn open(bucket: Bucket, prefix: &str) -> Result<Self, GraphError> {
+        BackendStore::new(S3Backend::new(bucket, prefix))
+    }
+}
+
+impl GraphBackend for S3Backend {
+    fn put(&mut self, key: &str, bytes: &[u8]) -> Result<(), GraphError> {
+        let path = self.path(key);
+        let response = self.bucket.put_object(&path, bytes).map_err(storage_error)?;
+        match response.status_code() {
+            200 => Ok(()),
+            code => Err(GraphError::Storage(format!("PUT {} returned {}", path, code))),
+        }
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, GraphError> {
+        let path = self.path(key);
+        let response = self.bucket.get_object(&path).map_err(storage_error)?;
+        match response.status_code() {
+            200 => Ok(Some(response.bytes().to_vec())),
+            404 => Ok(None),
+            code => Err(GraphError::Storage(format!("GET {} returned {}", path, code))),
+        }
+    }
+
+    fn contains(&self, key: &str) -> Result<bool, GraphError> {
+        let (_, status) = self.bucket.head_object(&self.path(key)).map_err(storage_error)?;
+        Ok(status == 200)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, GraphError> {
+        let full_prefix = self.path(prefix);
+        let strip = if self.prefix.is_empty() { 0 } else { self.prefix.len() + 1 };
+        let listing = self.bucket.list(full_prefix, None).map_err(storage_error)?;
+        Ok(listing.into_iter()
+            .flat_map(|page| page.contents.into_iter())
+            .map(|object| object.key[strip..].to_string())
+            .collect())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, GraphError> {
+        let path = self.path(key);
+        let response = self.bucket.delete_object(&path).map_err(storage_error)?;
+        match response.status_code() {
+            200 | 204 => Ok(true),
+            404 => Ok(false),
+            code => Err(GraphError::Storage(format!("DELETE {} returned {}", path, code))),
+        }
+    }
+}
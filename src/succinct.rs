@@ -0,0 +1,159 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::ASTGraph;
+
+///
+/// Packed bit vector, 64 bits per word, lowest bit first.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq,Eq)]
+pub struct Bits {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bits {
+    pub fn push(&mut self, bit: bool) {
+        if self.len % 64 == 0 {
+            self.words.push(0);
+        }
+        if bit {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len {
+            return None;
+        }
+        Some(self.words[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(move |index| self.words[index / 64] & (1 << (index % 64)) != 0)
+    }
+}
+
+///
+/// Balanced-parentheses encoding: an open bit (1) when entering a node and
+/// a close bit (0) when leaving it, in preorder; `kinds` holds the kind of
+/// each node in the same order. Two bits and a `u16` per node.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq,Eq)]
+pub struct BalancedParentheses {
+    pub bits: Bits,
+    pub kinds: Vec<u16>,
+}
+
+impl BalancedParentheses {
+    ///
+    /// Parent of every node (by preorder position), decoded in one scan.
+    ///
+    pub fn parents(&self) -> Vec<Option<u32>> {
+        let mut parents = Vec::with_capacity(self.kinds.len());
+        let mut open: Vec<u32> = Vec::new();
+        for bit in self.bits.iter() {
+            if bit {
+                parents.push(open.last().copied());
+                open.push(parents.len() as u32 - 1);
+            } else {
+                open.pop();
+            }
+        }
+        parents
+    }
+}
+
+///
+/// Level-order unary degree sequence (LOUDS): the degree of a virtual
+/// super-root whose children are the graph's roots, then that of every node
+/// in level order, each as one 1 per child followed by a 0; `kinds` holds
+/// the kinds in level order.
+///
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq,Eq)]
+pub struct Louds {
+    pub bits: Bits,
+    pub kinds: Vec<u16>,
+}
+
+impl Louds {
+    ///
+    /// Parent of every node (by level-order position), `None` for roots.
+    ///
+    pub fn parents(&self) -> Vec<Option<u32>> {
+        let mut parents = Vec::with_capacity(self.kinds.len());
+        // each 1 is a child of the node whose degree is being read; the
+        // first 0 ends the super-root's, the next ones those of nodes 0, 1, ...
+        let mut current: Option<u32> = None;
+        let mut closed = 0;
+        for bit in self.bits.iter() {
+            if bit {
+                parents.push(current);
+            } else {
+                current = Some(closed);
+                closed += 1;
+            }
+        }
+        parents
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Balanced-parentheses encoding of the graph, children in source order.
+    ///
+    pub fn to_balanced_parentheses(&self) -> BalancedParentheses {
+        let mut encoded = BalancedParentheses::default();
+        let mut stack: Vec<(NodeIndex, bool)> = self.roots().into_iter().rev().map(|root| (root, false)).collect();
+        while let Some((node, leaving)) = stack.pop() {
+            if leaving {
+                encoded.bits.push(false);
+                continue;
+            }
+            encoded.bits.push(true);
+            encoded.kinds.push(self.graph[node].kind_id);
+            stack.push((node, true));
+            for child in self.children(node).into_iter().rev() {
+                stack.push((child, false));
+            }
+        }
+        encoded
+    }
+
+    ///
+    /// LOUDS encoding of the graph, children in source order.
+    ///
+    pub fn to_louds(&self) -> Louds {
+        let mut encoded = Louds::default();
+        let roots = self.roots();
+        for _ in roots.iter() {
+            encoded.bits.push(true);
+        }
+        encoded.bits.push(false);
+
+        let mut queue: VecDeque<NodeIndex> = roots.into_iter().collect();
+        while let Some(node) = queue.pop_front() {
+            encoded.kinds.push(self.graph[node].kind_id);
+            for child in self.children(node) {
+                encoded.bits.push(true);
+                queue.push_back(child);
+            }
+            encoded.bits.push(false);
+        }
+        encoded
+    }
+}
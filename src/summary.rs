@@ -0,0 +1,155 @@
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+use tree_sitter::Language;
+
+use crate::ASTGraph;
+use crate::analysis::{callee_name, kind_ids, LanguageKinds};
+use crate::excerpt::ELLIPSIS;
+
+const INDENT: &str = "  ";
+const MAX_HEADER_BYTES: usize = 80;
+
+///
+/// What `summarize` includes and how much room it gets.
+///
+#[derive(Debug,Clone)]
+pub struct SummaryOptions {
+    pub max_bytes: usize,
+    /// list the functions each function calls
+    pub calls: bool,
+    /// nesting levels of the outline to show, unlimited if `None`
+    pub max_depth: Option<usize>,
+    kinds: LanguageKinds,
+    control: HashSet<u16>,
+}
+
+impl SummaryOptions {
+    pub fn new(language: &Language) -> Self {
+        let control = ["if_statement", "else_clause", "elseif_clause", "for_statement", "for_range_loop", "while_statement",
+                       "do_statement", "switch_statement", "case_statement", "try_statement", "catch_clause",
+                       "do_loop_statement", "select_case_statement", "where_statement"]
+            .iter()
+            .flat_map(|name| kind_ids(language, name))
+            .collect();
+        SummaryOptions {
+            max_bytes: 2048,
+            calls: true,
+            max_depth: None,
+            kinds: LanguageKinds::new(language),
+            control: control,
+        }
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn without_calls(mut self) -> Self {
+        self.calls = false;
+        self
+    }
+}
+
+// whitespace runs become single spaces, cut to `max_bytes` on a char boundary
+fn one_line(text: &str, max_bytes: usize) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if collapsed.len() <= max_bytes {
+        return collapsed;
+    }
+    let mut cut = max_bytes.saturating_sub(ELLIPSIS.len());
+    while !collapsed.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}{}", &collapsed[..cut], ELLIPSIS)
+}
+
+impl ASTGraph {
+    // source from the start of `node` to its first block child (the header of
+    // a function or control structure), or its first line if it has no block
+    fn header(&self, node: NodeIndex, kinds: &LanguageKinds) -> String {
+        let range = self.graph[node].range;
+        let text = match self.range_text(range.start_byte, range.end_byte) {
+            Some(text) => text,
+            None => return String::new(),
+        };
+        let end = self.children(node).into_iter()
+            .find(|&child| kinds.blocks.contains(&self.graph[child].kind_id))
+            .map(|child| self.graph[child].range.start_byte - range.start_byte)
+            .unwrap_or_else(|| text.find('\n').unwrap_or(text.len()));
+        one_line(&text[..end], MAX_HEADER_BYTES)
+    }
+
+    fn outline(&self, node: NodeIndex, depth: usize, options: &SummaryOptions, lines: &mut Vec<String>) {
+        if options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return;
+        }
+        let kind_id = self.graph[node].kind_id;
+        let mut child_depth = depth;
+
+        if options.kinds.functions.contains(&kind_id) {
+            lines.push(format!("{}{}", INDENT.repeat(depth), self.header(node, &options.kinds)));
+            if options.calls && self.has_source() {
+                let mut calls: Vec<NodeIndex> = self.collect_subgraph_nodes(node).into_iter()
+                    .filter(|&n| options.kinds.calls.contains(&self.graph[n].kind_id))
+                    .collect();
+                calls.sort_by_key(|&call| self.graph[call].range.start_byte);
+                let mut names: Vec<&str> = Vec::new();
+                for name in calls.into_iter().filter_map(|call| callee_name(self, call, &options.kinds)) {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+                if !names.is_empty() {
+                    lines.push(format!("{}calls: {}", INDENT.repeat(depth + 1), names.join(", ")));
+                }
+            }
+            child_depth += 1;
+        } else if options.control.contains(&kind_id) {
+            lines.push(format!("{}{}", INDENT.repeat(depth), self.header(node, &options.kinds)));
+            child_depth += 1;
+        }
+
+        for child in self.children(node) {
+            self.outline(child, child_depth, options, lines);
+        }
+    }
+
+    ///
+    /// Compact outline of the subtree at `node` for prompts and reports: one
+    /// line per function (its signature, then the functions it calls) and per
+    /// control structure (its header), indented by nesting. Lines that don't
+    /// fit into `options.max_bytes` are replaced by a final `…`.
+    ///
+    pub fn summarize(&self, node: NodeIndex, options: &SummaryOptions) -> String {
+        let mut lines = Vec::new();
+        self.outline(node, 0, options, &mut lines);
+
+        let mut summary = String::new();
+        for (position, line) in lines.iter().enumerate() {
+            let separator = if summary.is_empty() { 0 } else { 1 };
+            let is_last = position + 1 == lines.len();
+            // room for the line, and unless it is the last one, for a closing ellipsis
+            let reserve = if is_last { 0 } else { 1 + ELLIPSIS.len() };
+            if summary.len() + separator + line.len() + reserve > options.max_bytes {
+                if summary.len() + separator + ELLIPSIS.len() <= options.max_bytes {
+                    if separator == 1 {
+                        summary.push('\n');
+                    }
+                    summary.push_str(ELLIPSIS);
+                }
+                break;
+            }
+            if separator == 1 {
+                summary.push('\n');
+            }
+            summary.push_str(line);
+        }
+        summary
+    }
+}
@@ -7,6 +7,21 @@ mod tests {
     use tree_sitter_cpp;
     use tree_sitter_fortran;
     use crate::geometry::{GNode,GPoint,GRange};
+    use crate::export::{self,ExportOptions,RdfOptions};
+    use petgraph::graph::NodeIndex;
+    use crate::import;
+    use crate::chunked::{self,ChunkedReader};
+    use crate::delta;
+    use crate::rewrite;
+    use crate::rules::{self,RuleSet,Severity};
+    use crate::analysis::{self,LanguageKinds};
+    use crate::analysis::signature;
+    use crate::analysis::taint::{self,TaintSpec};
+    use crate::project::{MovedFunction,ProjectGraph};
+    use crate::owned::OwnedAstGraph;
+    use crate::store::{BackendStore,DirectoryStore,GraphBackend,GraphStore};
+    use crate::error::GraphError;
+    use std::collections::BTreeMap;
     use tree_sitter::{Parser,TreeCursor,Node};
     use std::fs::File;
     use std::collections::HashSet;
@@ -164,9 +179,9 @@ mod tests {
     {
         // Create a sample ASTGraph for testing
         let mut ast_graph = ASTGraph::new("testing".to_string());
-        let a = ast_graph.graph.add_node(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 2, column: 5 } } });
-        let b = ast_graph.graph.add_node(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 3, column: 5 } } });
-        ast_graph.graph.add_edge(a, b, ());
+        let a = ast_graph.add_gnode(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 2, column: 5 } }, is_named: true, source_hash: 0 });
+        let b = ast_graph.add_gnode(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 3, column: 5 } }, is_named: true, source_hash: 0 });
+        ast_graph.add_edge(a, b);
 
         // Serialize the ASTGraph to a file for testing
         let serializable_graph = ast_graph.to_serializable();
@@ -192,11 +207,11 @@ mod tests {
     {
         // Create a sample ASTGraph for testing
         let mut ast_graph = ASTGraph::new("testing".to_string());
-        let a = ast_graph.graph.add_node(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } } });
-        let b = ast_graph.graph.add_node(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } } });
-        let c = ast_graph.graph.add_node( GNode { id: 4, kind_id: 7, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } } });
-        ast_graph.graph.add_edge(a, b, ());
-        ast_graph.graph.add_edge(a, c, ());
+        let a = ast_graph.add_gnode(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } }, is_named: true, source_hash: 0 });
+        let b = ast_graph.add_gnode(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } }, is_named: true, source_hash: 0 });
+        let c = ast_graph.add_gnode( GNode { id: 4, kind_id: 7, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } }, is_named: true, source_hash: 0 });
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
         
         // Serialize the ASTGraph to a file for testing
         let serializable_graph = ast_graph.to_serializable();
@@ -242,12 +257,12 @@ mod tests {
         fortran_types_to_split.insert(fortran_subroutine_kind);
         fortran_types_to_split.insert(fortran_program_kind);
 
-        let total_source = ast_graph.source.len();
+        let total_source = ast_graph.source().unwrap().len();
         let mut subgraph_counts = Vec::new();
 
         let function_subgraphs = ast_graph.extract_subgraphs( fortran_types_to_split );
         for subgraph in function_subgraphs.iter() {
-            let count = subgraph.source.len();
+            let count = subgraph.source().unwrap().len();
             subgraph_counts.push(count);
         }
 
@@ -316,11 +331,11 @@ mod tests {
     #[test]
     fn test_bfs_iterator() {
         let mut ast_graph = ASTGraph::new("testing".to_string());
-        let a = ast_graph.graph.add_node(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } } });
-        let b = ast_graph.graph.add_node(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } } });
-        let c = ast_graph.graph.add_node( GNode { id: 4, kind_id: 7, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } } });
-        ast_graph.graph.add_edge(a, b, ());
-        ast_graph.graph.add_edge(a, c, ());
+        let a = ast_graph.add_gnode(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } }, is_named: true, source_hash: 0 });
+        let b = ast_graph.add_gnode(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } }, is_named: true, source_hash: 0 });
+        let c = ast_graph.add_gnode( GNode { id: 4, kind_id: 7, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } }, is_named: true, source_hash: 0 });
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
 
         let mut nodes_touched = 0;
 
@@ -337,11 +352,11 @@ mod tests {
     #[test]
     fn test_dfs_iterator() {
         let mut ast_graph = ASTGraph::new("testing".to_string());
-        let a = ast_graph.graph.add_node(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } } });
-        let b = ast_graph.graph.add_node(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } } });
-        let c = ast_graph.graph.add_node( GNode { id: 4, kind_id: 7, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } } });
-        ast_graph.graph.add_edge(a, b, ());
-        ast_graph.graph.add_edge(a, c, ());
+        let a = ast_graph.add_gnode(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } }, is_named: true, source_hash: 0 });
+        let b = ast_graph.add_gnode(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } }, is_named: true, source_hash: 0 });
+        let c = ast_graph.add_gnode( GNode { id: 4, kind_id: 7, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } }, is_named: true, source_hash: 0 });
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
 
         let mut nodes_touched = 0;
 
@@ -406,11 +421,11 @@ mod tests {
         assert_eq!(function_subgraphs.len(),2);
 
         // check the source lengths
-        let total_length = ast_graph.source.len();
+        let total_length = ast_graph.source().unwrap().len();
         let mut function_total_length = 0;
 
         for func in function_subgraphs {
-            function_total_length += func.source.len();
+            function_total_length += func.source().unwrap().len();
         }
 
         // 15 bytes extra, even in the trimmed case.
@@ -500,18 +515,18 @@ mod tests {
     fn test_simple_path() {
 
         let mut ast_graph = ASTGraph::new("testing".to_string());
-        let a = ast_graph.graph.add_node(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } } });
-        let b = ast_graph.graph.add_node(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } } });
-        let c = ast_graph.graph.add_node( GNode { id: 4, kind_id: 72, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } } });
-        let d = ast_graph.graph.add_node( GNode { id: 5, kind_id: 37, range:  GRange { start_byte: 16, end_byte: 20, start_point: GPoint { row: 4, column: 1 }, end_point: GPoint { row: 4, column: 5 } } });
-        let e = ast_graph.graph.add_node( GNode { id: 7, kind_id: 4, range:  GRange { start_byte: 21, end_byte: 25, start_point: GPoint { row: 5, column: 1 }, end_point: GPoint { row: 5, column: 5 } } });
-        let f = ast_graph.graph.add_node( GNode { id: 10, kind_id: 7, range:  GRange { start_byte: 26, end_byte: 30, start_point: GPoint { row: 6, column: 1 }, end_point: GPoint { row: 6, column: 5 } } });
-
-        ast_graph.graph.add_edge(a, b, ());
-        ast_graph.graph.add_edge(a, c, ());
-        ast_graph.graph.add_edge(c, d, ());
-        ast_graph.graph.add_edge(c, e, ());
-        ast_graph.graph.add_edge(d, f, ());
+        let a = ast_graph.add_gnode(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 5, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 1, column: 5 } }, is_named: true, source_hash: 0 });
+        let b = ast_graph.add_gnode(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } }, is_named: true, source_hash: 0 });
+        let c = ast_graph.add_gnode( GNode { id: 4, kind_id: 72, range:  GRange { start_byte: 11, end_byte: 15, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 3, column: 5 } }, is_named: true, source_hash: 0 });
+        let d = ast_graph.add_gnode( GNode { id: 5, kind_id: 37, range:  GRange { start_byte: 16, end_byte: 20, start_point: GPoint { row: 4, column: 1 }, end_point: GPoint { row: 4, column: 5 } }, is_named: true, source_hash: 0 });
+        let e = ast_graph.add_gnode( GNode { id: 7, kind_id: 4, range:  GRange { start_byte: 21, end_byte: 25, start_point: GPoint { row: 5, column: 1 }, end_point: GPoint { row: 5, column: 5 } }, is_named: true, source_hash: 0 });
+        let f = ast_graph.add_gnode( GNode { id: 10, kind_id: 7, range:  GRange { start_byte: 26, end_byte: 30, start_point: GPoint { row: 6, column: 1 }, end_point: GPoint { row: 6, column: 5 } }, is_named: true, source_hash: 0 });
+
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
+        ast_graph.add_edge(c, d);
+        ast_graph.add_edge(c, e);
+        ast_graph.add_edge(d, f);
 
         let optional_path = ast_graph.path_from_to(a, f);
         let true_path = vec![a,c,d,f];
@@ -526,4 +541,3185 @@ mod tests {
     
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn export_collapses_below_max_depth() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(GNode { id: 1, kind_id: 1, range: GRange { start_byte: 0, end_byte: 30, start_point: GPoint { row: 1, column: 1 }, end_point: GPoint { row: 6, column: 5 } }, is_named: true, source_hash: 0 });
+        let b = ast_graph.add_gnode(GNode { id: 2, kind_id: 3, range: GRange { start_byte: 6, end_byte: 10, start_point: GPoint { row: 2, column: 1 }, end_point: GPoint { row: 2, column: 5 } }, is_named: true, source_hash: 0 });
+        let c = ast_graph.add_gnode( GNode { id: 4, kind_id: 72, range:  GRange { start_byte: 11, end_byte: 30, start_point: GPoint { row: 3, column: 1 }, end_point: GPoint { row: 6, column: 5 } }, is_named: true, source_hash: 0 });
+        let d = ast_graph.add_gnode( GNode { id: 5, kind_id: 37, range:  GRange { start_byte: 16, end_byte: 30, start_point: GPoint { row: 4, column: 1 }, end_point: GPoint { row: 6, column: 5 } }, is_named: true, source_hash: 0 });
+        let f = ast_graph.add_gnode( GNode { id: 10, kind_id: 7, range:  GRange { start_byte: 26, end_byte: 30, start_point: GPoint { row: 6, column: 1 }, end_point: GPoint { row: 6, column: 5 } }, is_named: true, source_hash: 0 });
+
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
+        ast_graph.add_edge(c, d);
+        ast_graph.add_edge(d, f);
+
+        let full = export::to_dot(&ast_graph, &ExportOptions::default());
+        assert!(full.contains("n4 [label=\"7\", kind_id=7, ts_id=10, range=\"26,30,6,1,6,5\"];"));
+
+        let collapsed = export::to_dot(&ast_graph, &ExportOptions::with_max_depth(1));
+        assert!(collapsed.contains("p2 [label=\"… (2 nodes)\"];"));
+        assert!(!collapsed.contains("n3 ["));
+        assert!(!collapsed.contains("p1 ["));
+
+        let mermaid = export::to_mermaid(&ast_graph, &ExportOptions::with_max_depth(1));
+        assert!(mermaid.starts_with("graph TD"));
+        assert!(mermaid.contains("n2 --> p2"));
+
+        let plantuml = export::to_plantuml(&ast_graph, &ExportOptions::with_max_depth(1));
+        assert!(plantuml.starts_with("@startuml"));
+        assert!(plantuml.contains("rectangle \"… (2 nodes)\" as p2"));
+        assert!(plantuml.trim_end().ends_with("@enduml"));
+    }
+
+    // utility function to build a synthetic node on a single row
+    fn test_node(id: usize, kind_id: u16, start_byte: usize, end_byte: usize, row: usize) -> GNode {
+        GNode { id: id, kind_id: kind_id, range: GRange { start_byte: start_byte, end_byte: end_byte, start_point: GPoint { row: row, column: 1 }, end_point: GPoint { row: row, column: 5 } }, is_named: true, source_hash: 0 }
+    }
+
+    #[test]
+    fn diff_dot_colors_unmatched_nodes() {
+        let mut old_graph = ASTGraph::new("testing".to_string());
+        let a = old_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = old_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        let c = old_graph.add_gnode(test_node(3, 4, 6, 10, 1));
+        old_graph.add_edge(a, b);
+        old_graph.add_edge(a, c);
+
+        let mut new_graph = ASTGraph::new("testing".to_string());
+        let x = new_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let y = new_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        new_graph.add_edge(x, y);
+
+        let dot = export::diff_dot(&old_graph, &new_graph, &[(a, x), (b, y)]);
+        assert!(dot.contains("a_n2 [label=\"4\", style=filled"));
+        assert!(dot.contains("b_n1 [label=\"3\"];"));
+        assert!(dot.contains("a_n0 -> b_n0 [style=dashed"));
+    }
+
+    #[test]
+    fn dot_round_trip() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        ast_graph.set_title("round \"trip\"".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        let c = ast_graph.add_gnode(test_node(3, 4, 6, 10, 1));
+        let d = ast_graph.add_gnode(test_node(4, 9, 6, 8, 1));
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
+        ast_graph.add_edge(c, d);
+
+        let dot = export::to_dot(&ast_graph, &ExportOptions::default());
+        let imported = import::from_dot(&dot).expect("Failed to import DOT");
+        assert_eq!(imported.title(), "round \"trip\"");
+        assert_eq!(imported.node_count(), 4);
+        assert_eq!(imported.graph.edge_count(), 3);
+        let root = imported.root().unwrap();
+        assert_eq!(imported.graph[root].range, ast_graph.graph[a].range);
+        let children: Vec<u16> = imported.children(root).iter().map(|&n| imported.graph[n].kind_id).collect();
+        assert_eq!(children, vec![3, 4]);
+
+        // placeholders of a collapsed export are dropped
+        let collapsed = export::to_dot(&ast_graph, &ExportOptions::with_max_depth(1));
+        let imported = import::from_dot(&collapsed).expect("Failed to import DOT");
+        assert_eq!(imported.node_count(), 3);
+        assert_eq!(imported.graph.edge_count(), 2);
+
+        assert!(import::from_dot("digraph {\n    n0 [kind_id=x];\n}").is_err());
+    }
+
+    #[cfg(feature="msgpack")]
+    #[test]
+    fn msgpack_round_trip() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        ast_graph.add_edge(a, b);
+
+        ast_graph.set_metadata("commit", "0deac2e");
+
+        let bytes = ast_graph.to_msgpack().expect("Failed to encode msgpack");
+        let decoded = ASTGraph::from_msgpack(&bytes).expect("Failed to decode msgpack");
+        assert_eq!(decoded.node_count(), 2);
+        assert_eq!(decoded.metadata_value("commit"), Some("0deac2e"));
+        assert_eq!(decoded.labels(), ast_graph.labels());
+        assert_eq!(decoded.graph.edge_count(), 1);
+        assert_eq!(decoded.get_node(b), Some(2));
+
+        let dangling = SerializableGraph {
+            nodes: vec![test_node(1, 1, 0, 10, 1)],
+            edges: vec![crate::geometry::Edge { source: NodeIndex::new(0), target: NodeIndex::new(3) }],
+            ..Default::default()
+        };
+        let bytes = rmp_serde::to_vec_named(&dangling).unwrap();
+        assert!(matches!(ASTGraph::from_msgpack(&bytes), Err(GraphError::Encoding(_))));
+    }
+
+    #[cfg(feature="cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 6, 10, 2));
+        ast_graph.add_edge(a, b);
+
+        ast_graph.set_title("cbor".to_string());
+        ast_graph.set_metadata("commit", "0deac2e");
+
+        let bytes = ast_graph.to_cbor().expect("Failed to encode CBOR");
+        let decoded = ASTGraph::from_cbor(&bytes).expect("Failed to decode CBOR");
+        assert_eq!(decoded.node_count(), 2);
+        assert_eq!(decoded.title(), "cbor");
+        assert_eq!(decoded.metadata(), ast_graph.metadata());
+        assert_eq!(decoded.graph[b].range, ast_graph.graph[b].range);
+
+        // drop the edge's target from the encoded node list
+        let mut value: ciborium::Value = ciborium::de::from_reader(&bytes[..]).unwrap();
+        if let ciborium::Value::Map(fields) = &mut value {
+            for (key, field) in fields.iter_mut() {
+                if let (Some("nodes"), ciborium::Value::Array(nodes)) = (key.as_text(), field) {
+                    nodes.pop();
+                }
+            }
+        }
+        let mut dangling = Vec::new();
+        ciborium::ser::into_writer(&value, &mut dangling).unwrap();
+        assert!(matches!(ASTGraph::from_cbor(&dangling), Err(GraphError::Encoding(_))));
+    }
+
+    #[cfg(feature="protobuf")]
+    #[test]
+    fn protobuf_round_trip() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        ast_graph.set_title("proto".to_string());
+        ast_graph.set_metadata("commit", "0deac2e");
+        ast_graph.add_label("bug");
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 6, 10, 2));
+        ast_graph.add_edge(a, b);
+
+        let bytes = ast_graph.to_protobuf();
+        let decoded = ASTGraph::from_protobuf(&bytes).expect("Failed to decode protobuf");
+        assert_eq!(decoded.title(), "proto");
+        assert_eq!(decoded.metadata_value("commit"), Some("0deac2e"));
+        assert_eq!(decoded.labels(), ast_graph.labels());
+        assert_eq!(decoded.graph.edge_count(), 1);
+        assert_eq!(decoded.graph[b].range, ast_graph.graph[b].range);
+    }
+
+    #[cfg(feature="flatbuffers")]
+    #[test]
+    fn flatbuffer_has_identifier_and_node_array() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 6, 10, 2));
+        ast_graph.add_edge(a, b);
+
+        ast_graph.set_metadata("commit", "0deac2e");
+
+        let bytes = ast_graph.to_flatbuffer();
+        assert!(flatbuffers::buffer_has_identifier(&bytes, "TGFB", false));
+        assert!(bytes.windows(7).any(|window| window == b"0deac2e"));
+        assert!(bytes.len() >= 2 * 64 + 8);
+    }
+
+    #[test]
+    fn turtle_export_links_children() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        let c = ast_graph.add_gnode(test_node(3, 4, 6, 10, 1));
+        ast_graph.add_edge(a, c);
+        ast_graph.add_edge(a, b);
+
+        let options = RdfOptions { namespace: "https://example.org/main.f90#".to_string() };
+        let turtle = export::to_turtle(&ast_graph, &options);
+        assert!(turtle.contains("@prefix node: <https://example.org/main.f90#> ."));
+        assert!(turtle.contains("tg:child node:n1, node:n2 ."));
+        assert!(turtle.contains("tg:childIndex 1 ."));
+    }
+
+    #[cfg(feature="jsonl")]
+    #[test]
+    fn jsonl_stream_round_trip() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        ast_graph.set_title("lines".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        let c = ast_graph.add_gnode(test_node(3, 4, 6, 10, 1));
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
+
+        let mut buffer = Vec::new();
+        ast_graph.stream_jsonl(&mut buffer).expect("Failed to write JSON Lines");
+        assert_eq!(buffer.iter().filter(|&&byte| byte == b'\n').count(), 6);
+
+        let decoded = ASTGraph::read_jsonl(buffer.as_slice()).expect("Failed to read JSON Lines");
+        assert_eq!(decoded.title(), "lines");
+        assert_eq!(decoded.node_count(), 3);
+        assert_eq!(decoded.graph.edge_count(), 2);
+        assert_eq!(decoded.get_node(c), Some(3));
+
+        let broken = "{\"type\":\"edge\",\"source\":0,\"target\":1}\n";
+        assert!(ASTGraph::read_jsonl(broken.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn write_to_and_read_from_buffer() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 5, 1));
+        ast_graph.add_edge(a, b);
+
+        let mut buffer: Vec<u8> = Vec::new();
+        ast_graph.write_to(&mut buffer).expect("Failed to serialize into buffer");
+        let decoded = ASTGraph::read_from(buffer.as_slice()).expect("Failed to deserialize from buffer");
+        assert_eq!(decoded.node_count(), 2);
+        assert_eq!(decoded.get_node(b), Some(2));
+
+        assert!(ASTGraph::read_from(&buffer[..3]).is_err());
+
+        let dangling = SerializableGraph {
+            nodes: vec![test_node(1, 1, 0, 10, 1)],
+            edges: vec![crate::geometry::Edge { source: NodeIndex::new(0), target: NodeIndex::new(1) }],
+            ..Default::default()
+        };
+        let mut buffer: Vec<u8> = Vec::new();
+        serialize_into(&mut buffer, &crate::FORMAT_VERSION).unwrap();
+        serialize_into(&mut buffer, &dangling).unwrap();
+        assert!(matches!(ASTGraph::read_from(buffer.as_slice()), Err(GraphError::Encoding(_))));
+    }
+
+    #[test]
+    fn chunked_file_loads_single_functions() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let cpp_function_definition:u16 = 250;
+        let mut cpp_types_to_split = HashSet::new();
+        cpp_types_to_split.insert(cpp_function_definition);
+
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        let written = chunked::write_chunked(&ast_graph, &cpp_types_to_split, &mut buffer).expect("Failed to write chunks");
+        assert_eq!(written.len(), 2);
+
+        let mut reader = ChunkedReader::new(buffer).expect("Failed to read chunk index");
+        assert_eq!(reader.entries(), written.as_slice());
+
+        let main_function = reader.load(1).expect("Failed to load chunk");
+        assert!(main_function.source().unwrap().starts_with("int main()"));
+        let expected = ast_graph.extract_subgraph_from(ast_graph.graph.node_indices().find(|&n| ast_graph.graph[n].id == written[1].root_id).unwrap());
+        assert_eq!(main_function.node_count(), expected.node_count());
+
+        assert!(reader.load(2).is_err());
+    }
+
+    // utility function listing (kind, byte range, point range) of all nodes in a canonical order
+    fn node_signatures(ast_graph: &ASTGraph) -> Vec<(u16, usize, usize, usize, usize, usize, usize)> {
+        let mut signatures: Vec<_> = ast_graph.graph.node_weights()
+            .map(|n| (n.kind_id, n.range.start_byte, n.range.end_byte, n.range.start_point.row, n.range.start_point.column, n.range.end_point.row, n.range.end_point.column))
+            .collect();
+        signatures.sort();
+        signatures
+    }
+
+    #[test]
+    fn delta_patch_rebuilds_new_version() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let old_tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut old_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        old_graph.build_from_tree(&old_tree);
+
+        let new_source = format!("int counter = 0;\n{}", CPP_STRING_TRIMMED.replace("return 0;", "return counter;"));
+        let new_tree = parser.parse(&new_source, None).unwrap();
+        let mut new_graph = ASTGraph::new(new_source.clone());
+        new_graph.build_from_tree(&new_tree);
+
+        let patch = delta::diff(&old_graph, &new_graph);
+        assert!(patch.reused_subtrees() > 0);
+        assert!(patch.added_nodes() < new_graph.node_count() / 2);
+
+        let bytes = delta::diff_serialize(&old_graph, &new_graph).expect("Failed to serialize patch");
+        let rebuilt = delta::apply_patch(&old_graph, &bytes).expect("Failed to apply patch");
+        assert_eq!(node_signatures(&rebuilt), node_signatures(&new_graph));
+        assert_eq!(rebuilt.graph.edge_count(), new_graph.graph.edge_count());
+
+        // the patch only applies to the graph it was computed from
+        assert!(delta::apply_patch(&new_graph, &bytes).is_err());
+    }
+
+    #[test]
+    fn directory_store_put_get_gc() {
+        let root = std::env::temp_dir().join(format!("tree-graph-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let mut first = ASTGraph::new("first source".to_string());
+        first.set_title("first".to_string());
+        let a = first.add_gnode(test_node(1, 1, 0, 10, 1));
+        let b = first.add_gnode(test_node(2, 3, 0, 5, 1));
+        first.add_edge(a, b);
+
+        let mut second = ASTGraph::new("second source".to_string());
+        second.add_gnode(test_node(7, 4, 0, 6, 1));
+
+        let mut store = DirectoryStore::open(&root).expect("Failed to open store");
+        let first_key = store.put("first.f90", &first).expect("Failed to put graph");
+        let copy_key = store.put("copy.f90", &first).expect("Failed to put graph");
+        store.put("second.f90", &second).expect("Failed to put graph");
+        assert_eq!(first_key, copy_key);
+
+        // reopening reads the manifest back
+        let mut store = DirectoryStore::open(&root).expect("Failed to reopen store");
+        assert_eq!(store.list().unwrap().len(), 3);
+        let loaded = store.get("first.f90").unwrap().expect("Missing graph");
+        assert_eq!(loaded.title(), "first");
+        assert_eq!(loaded.source(), Some("first source"));
+        assert_eq!(loaded.node_count(), 2);
+        assert!(store.get("missing.f90").unwrap().is_none());
+
+        assert!(store.remove("second.f90").unwrap());
+        assert!(store.remove("first.f90").unwrap());
+        assert_eq!(store.gc().unwrap(), 1); // the first graph is still referenced by copy.f90
+        assert!(store.get("copy.f90").unwrap().is_some());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    // in-memory backend, standing in for a user-provided database
+    #[derive(Default)]
+    struct MemoryBackend {
+        values: BTreeMap<String, Vec<u8>>,
+    }
+
+    impl GraphBackend for MemoryBackend {
+        fn put(&mut self, key: &str, bytes: &[u8]) -> Result<(), GraphError> {
+            self.values.insert(key.to_string(), bytes.to_vec());
+            Ok(())
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, GraphError> {
+            Ok(self.values.get(key).cloned())
+        }
+
+        fn list(&self, prefix: &str) -> Result<Vec<String>, GraphError> {
+            Ok(self.values.keys().filter(|key| key.starts_with(prefix)).cloned().collect())
+        }
+
+        fn delete(&mut self, key: &str) -> Result<bool, GraphError> {
+            Ok(self.values.remove(key).is_some())
+        }
+    }
+
+    #[test]
+    fn custom_backend_store() {
+        let mut ast_graph = ASTGraph::new("source".to_string());
+        ast_graph.add_gnode(test_node(1, 1, 0, 6, 1));
+
+        let mut store = BackendStore::new(MemoryBackend::default()).expect("Failed to create store");
+        let key = store.put("main.f90", &ast_graph).expect("Failed to put graph");
+        assert!(store.backend().values.contains_key("manifest.bin"));
+        assert!(store.backend().values.contains_key(&format!("objects/{}/{}.tgb", &key[..2], key)));
+        assert_eq!(store.get("main.f90").unwrap().unwrap().node_count(), 1);
+
+        store.remove("main.f90").unwrap();
+        assert_eq!(store.gc().unwrap(), 1);
+        assert_eq!(store.backend().values.len(), 1);
+
+        // rebuilds differ only in build time: one object, the first build time
+        let mut first = ast_graph.clone();
+        first.set_metadata(crate::provenance::BUILT_AT, "2026-01-01T00:00:00Z");
+        let mut rebuilt = ast_graph.clone();
+        rebuilt.set_metadata(crate::provenance::BUILT_AT, "2026-02-01T00:00:00Z");
+        let key = store.put("first.f90", &first).unwrap();
+        assert_eq!(store.put("rebuilt.f90", &rebuilt).unwrap(), key);
+        assert_eq!(store.backend().values.len(), 2);
+        let stored = store.get("rebuilt.f90").unwrap().unwrap();
+        assert_eq!(stored.metadata_value(crate::provenance::BUILT_AT), Some("2026-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn node_ids_are_checked_against_their_graph() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 7, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 4, 1));
+        let c = ast_graph.add_gnode(test_node(3, 4, 5, 7, 1));
+        ast_graph.add_edge(a, b);
+        ast_graph.add_edge(a, c);
+
+        let c_id = ast_graph.id_of(c).unwrap();
+        assert_eq!(ast_graph.node(c_id).unwrap().kind_id, 4);
+        assert_eq!(ast_graph.node_source(c_id).unwrap(), "ng");
+        assert!(ast_graph.id_of(NodeIndex::new(3)).is_none());
+
+        let subgraph = ast_graph.subgraph_at(ast_graph.id_of(a).unwrap()).unwrap();
+        assert_ne!(subgraph.graph_id(), ast_graph.graph_id());
+        assert!(subgraph.node(c_id).is_err());
+        assert_eq!(subgraph.node_ids().count(), 3);
+    }
+
+    #[test]
+    fn stable_ids_survive_serialization() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut buffer: Vec<u8> = Vec::new();
+        ast_graph.write_to(&mut buffer).unwrap();
+        let reloaded = ASTGraph::read_from(buffer.as_slice()).unwrap();
+
+        let ids = ast_graph.stable_ids();
+        for node in ast_graph.graph.node_indices() {
+            let id = ast_graph.stable_id(node);
+            assert_eq!(ids[&node], id);
+
+            let parsed: crate::ids::StableId = id.to_string().parse().unwrap();
+            let resolved = reloaded.resolve_stable_id(&parsed).expect("Stable id did not resolve");
+            assert_eq!(reloaded.graph[resolved].range, ast_graph.graph[node].range);
+        }
+    }
+
+    #[test]
+    fn structural_equality_ignores_node_order() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        // create_subgraph inserts nodes in hash-set order
+        let root = ast_graph.root().unwrap();
+        let copy = ast_graph.extract_subgraph_from(root);
+        assert!(ast_graph.structurally_equal(&copy));
+        assert_eq!(ast_graph.structural_hash(), copy.structural_hash());
+
+        let function_definition = ast_graph.children(root)[0];
+        let function = ast_graph.extract_subgraph_from(function_definition);
+        assert!(!ast_graph.structurally_equal(&function));
+    }
+
+    #[test]
+    fn remap_ranges_after_reformatting() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        // reindent and reword the comment, then compare against a fresh parse
+        let new_source = CPP_STRING_TRIMMED.replace("\n    ", "\n  ").replace("Prompt the user", "Ask");
+        let report = ast_graph.remap_ranges(CPP_STRING_TRIMMED, &new_source);
+        assert_eq!(report.remapped, ast_graph.node_count());
+        assert_eq!(report.changed_leaves.len(), 1); // the comment
+
+        let new_tree = parser.parse(&new_source, None).unwrap();
+        let mut reparsed = ASTGraph::new(new_source.clone());
+        reparsed.build_from_tree(&new_tree);
+        assert!(ast_graph.structurally_equal(&reparsed));
+    }
+
+    #[test]
+    fn replace_node_source_produces_reparseable_edit() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let mut tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let zero = ast_graph.graph.node_indices()
+            .find(|&n| ast_graph.get_node_source(n) == "0" && ast_graph.graph.neighbors(n).next().is_none())
+            .expect("No literal 0 in the source");
+        let (patched, edit) = ast_graph.replace_node_source(zero, "EXIT_FAILURE\n        ").unwrap();
+        assert!(patched.contains("return EXIT_FAILURE\n        ;"));
+        assert_eq!(edit.new_end_position.row, edit.start_position.row + 1);
+        assert_eq!(edit.new_end_position.column, 8);
+
+        tree.edit(&edit);
+        let new_tree = parser.parse(&patched, Some(&tree)).unwrap();
+        let fresh_tree = parser.parse(&patched, None).unwrap();
+        assert_eq!(new_tree.root_node().to_sexp(), fresh_tree.root_node().to_sexp());
+    }
+
+    #[test]
+    fn rewrite_calls_with_template() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let query = r#"(call_expression function: (qualified_identifier name: (identifier) @name (#eq? @name "getline")) arguments: (argument_list) @args) @target"#;
+        let result = rewrite::rewrite(&ast_graph, &tree_sitter_cpp::language(), query, "safe_getline$args").expect("Rewrite failed");
+        assert_eq!(result.replacements, 2);
+        assert!(result.source.contains("safe_getline(file, line)"));
+        assert!(result.source.contains("safe_getline(std::cin, filePath)"));
+        assert!(!result.source.contains("std::getline"));
+        assert!(result.graph.node_count() < ast_graph.node_count());
+
+        assert!(rewrite::rewrite(&ast_graph, &tree_sitter_cpp::language(), query, "$missing").is_err());
+    }
+
+    #[test]
+    fn find_similar_ignores_identifiers() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        // same shape as `file.close();`
+        let exact = ast_graph.find_similar("x.run();", &tree_sitter_cpp::language(), 0).unwrap();
+        assert_eq!(exact.len(), 1);
+        assert_eq!(ast_graph.get_node_source(exact[0].node), "file.close();");
+
+        // an extra argument costs one edit
+        let loose = ast_graph.find_similar("x.run(y);", &tree_sitter_cpp::language(), 1).unwrap();
+        assert_eq!(loose.len(), 1);
+        assert_eq!(loose[0].distance, 1);
+    }
+
+    #[test]
+    fn rules_report_kind_and_query_matches() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut rule_set = RuleSet::with_language(tree_sitter_cpp::language());
+        rule_set.add_kind_rule("function", Severity::Note, 250, "function `$text`");
+        rule_set.add_query_rule("qualified-call", Severity::Warning,
+                                "(call_expression function: (qualified_identifier) @callee) @target",
+                                "call to $callee").unwrap();
+        assert!(rule_set.add_query_rule("bad", Severity::Error, "(call_expression) @target", "$callee").is_err());
+
+        let diagnostics = rules::run_rules(&ast_graph, &rule_set).unwrap();
+        let rule_ids: Vec<&str> = diagnostics.iter().map(|d| d.rule_id.as_str()).collect();
+        assert_eq!(rule_ids, vec!["function", "qualified-call", "function", "qualified-call"]);
+        assert_eq!(diagnostics[0].message, "function `void readFile(const std::string& filePath) {`");
+        assert_eq!(diagnostics[1].message, "call to std::getline");
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+
+        for diagnostic in diagnostics.iter() {
+            let gnode = &ast_graph.graph[diagnostic.node];
+            assert_eq!(diagnostic.range, gnode.range);
+            assert_eq!(diagnostic.node_id, gnode.id);
+        }
+    }
+
+    #[cfg(feature="sarif")]
+    #[test]
+    fn sarif_log_for_rule_diagnostics() {
+        use crate::rules::sarif::{self,SarifArtifact};
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut rule_set = RuleSet::new();
+        rule_set.add_kind_rule("function", Severity::Warning, 250, "function `$text`");
+        let diagnostics = rules::run_rules(&ast_graph, &rule_set).unwrap();
+
+        let log = sarif::to_sarif("tree-graph", &[SarifArtifact { uri: "src/read.cpp", source: ast_graph.source(), diagnostics: &diagnostics }]);
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "function");
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["level"], "warning");
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/read.cpp");
+        assert_eq!(location["region"]["startLine"], diagnostics[0].range.start_point.row + 1);
+        assert_eq!(location["region"]["byteOffset"], diagnostics[0].range.start_byte);
+        assert_eq!(location["region"]["startColumn"], diagnostics[0].range.start_point.column + 1);
+
+        // columns count code points, not the bytes tree-sitter counts
+        let source = "int \u{e9}t\u{e9}() { return 0; }\nint z\u{fc}r\u{fc}ck() { return 1; }\n";
+        let tree = parser.parse(source, None).unwrap();
+        let mut accented = ASTGraph::new(source.to_string());
+        accented.build_from_tree(&tree);
+        let diagnostics = rules::run_rules(&accented, &rule_set).unwrap();
+        let range = diagnostics[1].range;
+        let log = sarif::to_sarif("tree-graph", &[SarifArtifact { uri: "src/accents.cpp", source: accented.source(), diagnostics: &diagnostics }]);
+        let region = &log["runs"][0]["results"][1]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startColumn"], 1);
+        assert_eq!(region["endColumn"], range.end_point.column + 1 - 2);
+        let log = sarif::to_sarif("tree-graph", &[SarifArtifact { uri: "src/accents.cpp", source: None, diagnostics: &diagnostics }]);
+        assert!(log["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"].get("startColumn").is_none());
+
+        let mut buffer = Vec::new();
+        sarif::write_sarif("tree-graph", &[], &mut buffer).unwrap();
+        assert!(String::from_utf8(buffer).unwrap().contains("\"runs\""));
+    }
+
+    const CPP_TAINT:&str = r#"
+    void handle() {
+        std::string input = read_input();
+        std::string copy;
+        copy = input;
+        std::string fixed = "ls";
+        system(copy.c_str());
+        system(fixed.c_str());
+    }
+    "#;
+
+    #[test]
+    fn taint_flows_through_assignments() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_TAINT, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_TAINT.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut spec = TaintSpec::new(tree_sitter_cpp::language());
+        spec.add_scope_kind(250);
+        spec.add_source(r#"(call_expression function: (identifier) @name (#eq? @name "read_input")) @target"#).unwrap();
+        spec.add_sink(r#"(call_expression function: (identifier) @name (#eq? @name "system") arguments: (argument_list) @target)"#).unwrap();
+        spec.add_propagator("(init_declarator declarator: (identifier) @to value: (_) @from)").unwrap();
+        spec.add_propagator("(assignment_expression left: (identifier) @to right: (_) @from)").unwrap();
+        assert!(spec.add_propagator("(assignment_expression) @target").is_err());
+
+        let flows = taint::find_flows(&ast_graph, &spec).unwrap();
+        assert_eq!(flows.len(), 1);
+        let path: Vec<&str> = flows[0].path.iter().map(|node| ast_graph.get_node_source(*node)).collect();
+        assert_eq!(path, vec!["read_input()", "input", "input", "copy", "copy", "(copy.c_str())"]);
+        assert_eq!(flows[0].source, flows[0].path[0]);
+    }
+
+    const CPP_DEAD_CODE:&str = r#"
+    int compute(int limit) {
+        int unused = 3;
+        int total = 0;
+        for (int i = 0; i < limit; i++) {
+            total += i;
+        }
+        return total;
+        total = 1;
+        limit++;
+    }
+    "#;
+
+    #[test]
+    fn unused_variables_and_unreachable_statements() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_DEAD_CODE, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_DEAD_CODE.to_string());
+        ast_graph.build_from_tree(&tree);
+        let kinds = LanguageKinds::new(&tree_sitter_cpp::language());
+
+        let unused: Vec<&str> = analysis::unused_variables(&ast_graph, &kinds).iter()
+            .map(|node| ast_graph.get_node_source(*node))
+            .collect();
+        assert_eq!(unused, vec!["unused"]);
+
+        let unreachable: Vec<&str> = analysis::unreachable_statements(&ast_graph, &kinds).iter()
+            .map(|node| ast_graph.get_node_source(*node))
+            .collect();
+        assert_eq!(unreachable, vec!["total = 1;", "limit++;"]);
+    }
+
+    #[test]
+    fn signature_changes_between_snapshots() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let kinds = LanguageKinds::new(&tree_sitter_cpp::language());
+
+        let old_source = "int add(int a, int b) { return a + b; }\nvoid report(const char* m) {}\nint gone() { return 0; }\n";
+        let new_source = "int add(int x, int y) { return y + x; }\nvoid report(const std::string& m) {}\nint fresh() { return 1; }\n";
+        let mut snapshots = Vec::new();
+        for source in [old_source, new_source] {
+            let tree = parser.parse(source, None).unwrap();
+            let mut ast_graph = ASTGraph::new(source.to_string());
+            ast_graph.build_from_tree(&tree);
+            snapshots.push(ast_graph);
+        }
+
+        let signatures = signature::function_signatures(&snapshots[0], &kinds).unwrap();
+        let names: Vec<&str> = signatures.iter().map(|signature| signature.name.as_str()).collect();
+        assert_eq!(names, vec!["add", "report", "gone"]);
+        let subgraph = snapshots[0].extract_subgraph_from(signatures[0].node);
+        assert_eq!(signature::signature_hash(&subgraph, &kinds).unwrap(), Some(signatures[0].hash));
+
+        let changes = signature::compare_signatures(&snapshots[..1], &snapshots[1..], &kinds).unwrap();
+        assert_eq!(changes.added, vec!["fresh".to_string()]);
+        assert_eq!(changes.removed, vec!["gone".to_string()]);
+        assert_eq!(changes.changed, vec!["report".to_string()]);
+        assert!(signature::compare_signatures(&snapshots, &snapshots, &kinds).unwrap().is_empty());
+    }
+
+    fn cpp_project(files: &[(&str, &str)]) -> ProjectGraph {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let mut project = ProjectGraph::new(LanguageKinds::new(&tree_sitter_cpp::language()));
+        for (path, source) in files {
+            let tree = parser.parse(source, None).unwrap();
+            let mut ast_graph = ASTGraph::new(source.to_string());
+            ast_graph.build_from_tree(&tree);
+            project.insert(path, ast_graph);
+        }
+        project
+    }
+
+    #[test]
+    fn project_diff_reports_function_and_call_changes() {
+        let old = cpp_project(&[
+            ("a.cpp", "int helper() { return 1; }\nint main() { return helper(); }\n"),
+            ("b.cpp", "void util() {}\n"),
+        ]);
+        let new = cpp_project(&[
+            ("a.cpp", "int main() { return helper() + twice(); }\nint twice() { return 2; }\n"),
+            ("b.cpp", "void util() {}\nint helper() { return 1; }\n"),
+            ("c.cpp", "void extra() {}\n"),
+        ]);
+
+        let report = old.diff(&new).unwrap();
+        assert_eq!(report.added_files, vec!["c.cpp".to_string()]);
+        assert!(report.removed_files.is_empty());
+        let modified: Vec<&str> = report.modified_files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(modified, vec!["a.cpp", "b.cpp"]);
+        assert!(report.modified_files[1].reused_subtrees > 0);
+
+        assert_eq!(report.added_functions, vec!["extra".to_string(), "twice".to_string()]);
+        assert!(report.removed_functions.is_empty());
+        assert_eq!(report.modified_functions, vec!["main".to_string()]);
+        assert_eq!(report.moved_functions, vec![MovedFunction {
+            name: "helper".to_string(),
+            from: vec!["a.cpp".to_string()],
+            to: vec!["b.cpp".to_string()],
+        }]);
+        assert_eq!(report.added_calls, vec![("main".to_string(), "twice".to_string())]);
+        assert!(report.removed_calls.is_empty());
+
+        assert!(old.diff(&old).unwrap().is_empty());
+    }
+
+    #[test]
+    fn impacted_functions_follow_callers_and_includes() {
+        let project = cpp_project(&[
+            ("include/util.h", "int twice(int x);\nstruct Config { int level; };\n"),
+            ("src/util.cpp", "#include \"util.h\"\nint twice(int x) { return 2 * x; }\n"),
+            ("src/main.cpp", "#include <util.h>\nint run() { return twice(3); }\nint main() { return run(); }\nint unrelated() { return 0; }\n"),
+        ]);
+        assert_eq!(project.include_edges().unwrap().len(), 2);
+
+        let twice = project.functions().unwrap().into_iter().find(|function| function.name == "twice").unwrap();
+        let impacted: Vec<String> = project.impacted_functions(&[(twice.file.as_str(), twice.node)]).unwrap().into_iter()
+            .map(|function| function.name)
+            .collect();
+        assert_eq!(impacted, vec!["run", "main", "twice"]);
+
+        let header = project.get("include/util.h").unwrap();
+        let impacted = project.impacted_functions(&[("include/util.h", header.root().unwrap())]).unwrap();
+        assert_eq!(impacted.len(), 4);
+    }
+
+    #[test]
+    fn owned_graph_keeps_tree_in_step() {
+        let mut owned = OwnedAstGraph::parse(&tree_sitter_cpp::language(), CPP_STRING_TRIMMED.to_string()).unwrap();
+        assert_eq!(owned.node_count(), owned.tree().root_node().descendant_count());
+        assert_eq!(owned.walk().node().kind(), "translation_unit");
+
+        let function = owned.graph().graph.node_indices().find(|&node| owned.graph().graph[node].kind_id == 250).unwrap();
+        let (source, edit) = owned.replace_node_source(function, "void readFile() {}").unwrap();
+        owned.edit(&edit, source).unwrap();
+        assert!(owned.get_node_source(owned.root().unwrap()).contains("void readFile() {}"));
+        assert_eq!(owned.node_count(), owned.tree().root_node().descendant_count());
+        assert!(!owned.tree().root_node().has_error());
+    }
+
+    #[test]
+    fn graph_indices_and_tree_nodes_convert_both_ways() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let by_id = ast_graph.ts_id_index();
+        for index in ast_graph.graph.node_indices() {
+            let node = ast_graph.resolve_ts_node(index, &tree).expect("Node not found in its tree");
+            assert_eq!(node.id(), ast_graph.graph[index].id);
+            assert_eq!(by_id[&node.id()], index);
+        }
+
+        let identifier = tree.root_node().descendant_for_byte_range(10, 10).unwrap();
+        let index = ast_graph.graph_index_for(&identifier).unwrap();
+        assert_eq!(ast_graph.get_node_source(index), identifier.utf8_text(CPP_STRING_TRIMMED.as_bytes()).unwrap());
+
+        let other_tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        assert!(ast_graph.graph_index_for(&other_tree.root_node()).is_none());
+    }
+
+    #[cfg(feature="highlight")]
+    #[test]
+    fn highlight_classes_on_leaves() {
+        use crate::highlight::Highlighting;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let highlighting = Highlighting::new(tree_sitter_cpp::language(), "cpp", tree_sitter_cpp::HIGHLIGHT_QUERY).unwrap();
+        let classes = ast_graph.highlight_leaves(&highlighting).unwrap();
+        assert!(!classes.is_empty());
+        for (node, class) in classes.iter() {
+            assert_eq!(ast_graph.graph.neighbors(*node).count(), 0);
+            assert!(highlighting.names().iter().any(|name| name == class));
+        }
+        let getline = classes.iter()
+            .find(|(node, _)| ast_graph.get_node_source(**node) == "getline")
+            .map(|(_, class)| *class);
+        assert_eq!(getline, Some("function"));
+    }
+
+    #[test]
+    fn leaf_text_survives_without_source() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let identifiers = analysis::kind_ids(&tree_sitter_cpp::language(), "identifier");
+        ast_graph.store_leaf_text_for(&identifiers);
+        ast_graph.drop_source();
+        let stored = ast_graph.stored_leaf_text().unwrap();
+        assert!(stored.table().len() < stored.len());
+
+        let mut buffer = Vec::new();
+        ast_graph.write_with_text(&mut buffer).expect("Failed to write graph");
+        let decoded = ASTGraph::read_with_text(buffer.as_slice()).expect("Failed to read graph");
+
+        let mut names: Vec<&str> = decoded.graph.node_indices()
+            .filter(|node| identifiers.contains(&decoded.graph[*node].kind_id))
+            .filter_map(|node| decoded.leaf_text(node))
+            .collect();
+        names.sort();
+        names.dedup();
+        assert!(names.contains(&"readFile"));
+        assert!(names.contains(&"filePath"));
+
+        let root = decoded.root().unwrap();
+        assert!(decoded.leaf_text(root).is_none());
+    }
+
+    #[test]
+    fn redacted_file_hides_literals_and_identifiers() {
+        use crate::redact::{RedactOptions,Redaction};
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        ast_graph.record_source_hashes();
+
+        let literals_only = ast_graph.redacted_source(&RedactOptions::new(&tree_sitter_cpp::language()).with_mode(Redaction::Strip)).unwrap();
+        assert_eq!(literals_only.len(), CPP_STRING_TRIMMED.len());
+        assert!(!literals_only.contains("Could not open"));
+        assert!(!literals_only.contains("Prompt the user"));
+        assert!(literals_only.contains("\"xxxxxxxxxxxxxxxxxxxxx\""));
+        assert!(literals_only.contains("readFile(filePath)"));
+
+        let options = RedactOptions::new(&tree_sitter_cpp::language()).with_identifiers(&tree_sitter_cpp::language());
+        let path = std::env::temp_dir().join(format!("tree-graph-redacted-{}.tgb", std::process::id()));
+        ast_graph.save_to_file_redacted(&path, &options).expect("Failed to save redacted graph");
+        let loaded = ASTGraph::load_from_file_with_source(&path).expect("Failed to load redacted graph");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.node_count(), ast_graph.node_count());
+        let source = loaded.get_node_source(loaded.root().unwrap());
+        assert!(!source.contains("readFile"));
+        assert!(!source.contains("filePath"));
+        assert!(!source.contains("std::"));
+        assert_eq!(source.lines().count(), CPP_STRING_TRIMMED.lines().count());
+
+        // hashes of the original text would let it be confirmed by guessing
+        let root = loaded.root().unwrap();
+        assert_ne!(loaded.graph[root].source_hash, ast_graph.graph[ast_graph.root().unwrap()].source_hash);
+        assert_eq!(loaded.graph[root].source_hash, crate::checksum::source_hash(source));
+        let mut unhashed = ast_graph.clone();
+        unhashed.clear_source_hashes();
+        assert!(unhashed.redacted(&options).unwrap().graph.node_weights().all(|gnode| gnode.source_hash == 0));
+    }
+
+    #[test]
+    fn excerpts_fit_the_budget() {
+        use crate::excerpt::{Ellipsis,ELLIPSIS};
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let blocks = analysis::kind_ids(&tree_sitter_cpp::language(), "compound_statement");
+        let mut bodies: Vec<NodeIndex> = ast_graph.graph.node_indices().filter(|node| blocks.contains(&ast_graph.graph[*node].kind_id)).collect();
+        bodies.sort_by_key(|node| ast_graph.graph[*node].range.start_byte);
+        let body = bodies[0];
+
+        let tail = ast_graph.excerpt(body, 20, Ellipsis::Tail);
+        assert!(tail.len() <= 20 && tail.ends_with(ELLIPSIS));
+        let middle = ast_graph.excerpt(body, 40, Ellipsis::Middle);
+        assert!(middle.len() <= 40 && middle.contains(ELLIPSIS) && middle.ends_with('}'));
+        let statements = ast_graph.excerpt(body, 60, Ellipsis::Statements);
+        assert_eq!(statements, "{\n        std::ifstream file(filePath); … }");
+        assert_eq!(ast_graph.excerpt(body, 10_000, Ellipsis::Tail), ast_graph.get_node_source(body));
+
+        let source = "const char* s = \"ééééé\";";
+        let tree = parser.parse(source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        for max_bytes in 0..source.len() {
+            assert!(ast_graph.excerpt(root, max_bytes, Ellipsis::Middle).len() <= max_bytes);
+            assert!(ast_graph.excerpt(root, max_bytes, Ellipsis::Statements).len() <= max_bytes);
+        }
+    }
+
+    #[test]
+    fn summary_outlines_functions_within_budget() {
+        use crate::excerpt::ELLIPSIS;
+        use crate::summary::SummaryOptions;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+
+        let options = SummaryOptions::new(&tree_sitter_cpp::language());
+        let summary = ast_graph.summarize(root, &options);
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "void readFile(const std::string& filePath)");
+        assert!(lines.contains(&"  if (!file.is_open())"));
+        assert!(lines.contains(&"  while (std::getline(file, line))"));
+        assert!(lines.contains(&"int main()"));
+        assert!(lines.iter().any(|line| line.starts_with("  calls: ") && line.contains("readFile")));
+
+        let outline = ast_graph.summarize(root, &options.clone().without_calls().with_max_depth(1));
+        assert_eq!(outline, "void readFile(const std::string& filePath)\nint main()");
+
+        let short = ast_graph.summarize(root, &options.with_max_bytes(60));
+        assert!(short.len() <= 60 && short.ends_with(ELLIPSIS));
+    }
+
+    #[test]
+    fn feature_matrix_from_registered_extractors() {
+        use crate::features::{FeatureExtractor,FeatureMatrix,FeatureSet,KindOneHot,Shape};
+
+        struct IsLeaf;
+        impl FeatureExtractor for IsLeaf {
+            fn name(&self) -> &str { "leaf" }
+            fn width(&self) -> usize { 1 }
+            fn features(&self, graph: &ASTGraph, node: NodeIndex) -> Vec<f32> {
+                vec![if graph.graph.neighbors(node).next().is_none() { 1.0 } else { 0.0 }]
+            }
+        }
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+
+        let features = FeatureSet::new()
+            .add(KindOneHot { kinds: vec![ast_graph.graph[root].kind_id, 250] })
+            .add(Shape)
+            .add(IsLeaf);
+        let matrix = features.extract(&ast_graph).unwrap();
+        assert_eq!(matrix.width, 6);
+        assert_eq!(matrix.rows, ast_graph.graph.node_count());
+        let row = matrix.row(root).unwrap();
+        assert_eq!(&row[..4], &[1.0, 0.0, 0.0, 2.0][..]);
+        assert_eq!(row[5], 0.0);
+        assert!(matrix.values.chunks(6).all(|row| row[0] + row[1] <= 1.0));
+
+        let path = std::env::temp_dir().join("tree_graph_features.bin");
+        matrix.save_to_file(&path).unwrap();
+        assert_eq!(FeatureMatrix::load_from_file(&path).unwrap(), matrix);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn largest_subtrees_and_deepest_paths() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+
+        let largest = ast_graph.largest_subtrees(3);
+        assert_eq!(largest.len(), 3);
+        assert_eq!(largest[0].node, root);
+        assert_eq!(largest[0].size, ast_graph.graph.node_count());
+        assert_eq!(largest[1].kind_id, 250);
+        assert_eq!(largest[1].size, ast_graph.subtree_size(largest[1].node));
+        assert!(largest[1].size >= largest[2].size);
+
+        let deepest = ast_graph.deepest_paths(2);
+        assert_eq!(deepest.len(), 2);
+        assert!(deepest[0].depth >= deepest[1].depth);
+        assert_eq!(deepest[0].path.len(), deepest[0].depth + 1);
+        assert_eq!(deepest[0].path[0], root);
+        assert_eq!(*deepest[0].path.last().unwrap(), deepest[0].leaf);
+        assert_eq!(ast_graph.path_from_to(root, deepest[0].leaf).unwrap(), deepest[0].path);
+        assert!(ast_graph.graph.node_indices().all(|node| ast_graph.path_from_to(root, node).unwrap().len() <= deepest[0].path.len()));
+    }
+
+    #[test]
+    fn paths_matching_kind_sequences() {
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let direct = ast_graph.find_paths_matching(&language, &["compound_statement", "expression_statement", "call_expression"]).unwrap();
+        let calls: Vec<&str> = direct.iter().map(|path| ast_graph.get_node_source(path[2])).collect();
+        assert_eq!(calls, vec!["file.close()", "std::getline(std::cin, filePath)", "readFile(filePath)"]);
+
+        let nested = ast_graph.find_root_paths_matching(&language, &["translation_unit", "function_definition", "**", "call_expression"]).unwrap();
+        assert_eq!(nested.len(), 5);
+        assert!(nested.iter().all(|path| path[0] == ast_graph.root().unwrap() && ast_graph.graph[path[1]].kind_id == 250));
+
+        let wildcard = ast_graph.find_paths_matching(&language, &["function_definition", "*", "compound_statement"]).unwrap();
+        assert!(wildcard.is_empty());
+        assert_eq!(ast_graph.find_paths_matching(&language, &["function_definition", "compound_statement"]).unwrap().len(), 2);
+
+        assert!(ast_graph.find_paths_matching(&language, &["no_such_kind"]).is_err());
+        assert!(ast_graph.find_paths_matching(&language, &["function_definition", "**"]).is_err());
+    }
+
+    #[test]
+    fn regular_path_queries_over_edge_kinds() {
+        use crate::overlay::{EdgeKind,Overlay,PathExpr};
+
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let functions = ast_graph.children(root);
+        let (read_file, main) = (functions[0], functions[1]);
+
+        let mut overlay = Overlay::new(&ast_graph);
+        overlay.add_calls(&LanguageKinds::new(&language)).unwrap();
+
+        // main calls readFile, readFile calls nothing defined in the file
+        let called = PathExpr::parse("Child* Call").unwrap();
+        assert_eq!(overlay.query_from(main, &called), vec![read_file]);
+        assert!(overlay.query_from(read_file, &called).is_empty());
+
+        let pairs = overlay.query(&PathExpr::parse("Child+ Call").unwrap());
+        assert!(pairs.contains(&(root, read_file)) && pairs.contains(&(main, read_file)));
+        assert!(pairs.iter().all(|&(_, end)| end == read_file));
+
+        assert_eq!(overlay.query_from(read_file, &PathExpr::parse("Sibling").unwrap()), vec![main]);
+        assert_eq!(overlay.query_from(root, &PathExpr::parse("Child{2}").unwrap()).len(),
+                   functions.iter().map(|&function| ast_graph.children(function).len()).sum::<usize>());
+        assert_eq!(overlay.query_from(root, &PathExpr::parse("(Child | Flow){0,1}").unwrap()), vec![root, read_file, main]);
+
+        overlay.add_edge(main, root, EdgeKind::Flow);
+        assert_eq!(overlay.query_from(main, &PathExpr::parse("Flow Child?").unwrap()), vec![root, read_file, main]);
+
+        for invalid in ["", "Child Parent", "(Child", "Child{3,1}", "Child{x}", "Call**&"] {
+            assert!(PathExpr::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn union_intersection_and_induced_graphs() {
+        use crate::algebra::Identity;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let functions = ast_graph.children(root);
+
+        let read_file = ast_graph.extract_subgraph_from(functions[0]);
+        let main = ast_graph.extract_subgraph_from(functions[1]);
+
+        let both = read_file.union(&main, Identity::TreeSitterId);
+        assert_eq!(both.graph.node_count(), read_file.graph.node_count() + main.graph.node_count());
+        assert_eq!(both.roots().len(), 2);
+        assert_eq!(read_file.intersection(&main, Identity::TreeSitterId).graph.node_count(), 0);
+
+        // the whole graph already contains each function
+        let merged = ast_graph.union(&main, Identity::Extent);
+        assert_eq!(merged.graph.node_count(), ast_graph.graph.node_count());
+        assert_eq!(merged.graph.edge_count(), ast_graph.graph.edge_count());
+        let common = ast_graph.intersection(&read_file, Identity::Extent);
+        assert_eq!(common.graph.node_count(), read_file.graph.node_count());
+        assert_eq!(common.roots().len(), 1);
+
+        // reparsing gives new tree-sitter ids but the same positions
+        let reparsed_tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut reparsed = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        reparsed.build_from_tree(&reparsed_tree);
+        assert_eq!(ast_graph.intersection(&reparsed, Identity::Stable).graph.node_count(), ast_graph.graph.node_count());
+
+        let induced = ast_graph.induced(&[root, functions[0], functions[1]].into_iter().collect());
+        assert_eq!(induced.graph.node_count(), 3);
+        assert_eq!(induced.graph.edge_count(), 2);
+        let split = ast_graph.induced(&ast_graph.graph.node_indices().filter(|&node| node != root).collect());
+        assert_eq!(split.roots().len(), 2);
+    }
+
+    #[test]
+    fn lcrs_encoding_round_trips() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let functions = ast_graph.children(root);
+
+        let lcrs = ast_graph.to_lcrs();
+        assert_eq!(lcrs.len(), ast_graph.graph.node_count());
+        assert_eq!(lcrs.nodes[0].node, ast_graph.graph[root]);
+        assert_eq!(lcrs.nodes[0].right, None);
+        let first = lcrs.nodes[0].left.unwrap() as usize;
+        assert_eq!(lcrs.nodes[first].node, ast_graph.graph[functions[0]]);
+        let second = lcrs.nodes[first].right.unwrap() as usize;
+        assert_eq!(lcrs.nodes[second].node, ast_graph.graph[functions[1]]);
+        assert_eq!(lcrs.nodes[second].right, None);
+
+        let rebuilt = ASTGraph::from_lcrs(&lcrs, CPP_STRING_TRIMMED.to_string()).unwrap();
+        assert_eq!(rebuilt.to_lcrs(), lcrs);
+        assert_eq!(rebuilt.graph.edge_count(), ast_graph.graph.edge_count());
+        assert!(rebuilt.structurally_equal(&ast_graph));
+
+        // a forest keeps its roots as a sibling chain
+        let forest = ast_graph.induced(&ast_graph.graph.node_indices().filter(|&node| node != root).collect());
+        let forest_lcrs = forest.to_lcrs();
+        assert!(forest_lcrs.nodes[0].right.is_some());
+        assert_eq!(ASTGraph::from_lcrs(&forest_lcrs, String::new()).unwrap().roots().len(), 2);
+
+        let mut broken = lcrs.clone();
+        broken.nodes[1].right = Some(0);
+        assert!(ASTGraph::from_lcrs(&broken, String::new()).is_err());
+    }
+
+    #[test]
+    fn succinct_encodings_keep_the_shape() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let count = ast_graph.graph.node_count();
+
+        let mut preorder = Vec::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            preorder.push(node);
+            stack.extend(ast_graph.children(node).into_iter().rev());
+        }
+        let mut level_order = vec![root];
+        let mut position = 0;
+        while position < level_order.len() {
+            level_order.extend(ast_graph.children(level_order[position]));
+            position += 1;
+        }
+
+        let parens = ast_graph.to_balanced_parentheses();
+        assert_eq!(parens.bits.len(), 2 * count);
+        assert_eq!(parens.kinds, preorder.iter().map(|&node| ast_graph.graph[node].kind_id).collect::<Vec<u16>>());
+        let expected: Vec<Option<u32>> = preorder.iter()
+            .map(|&node| ast_graph.parent(node).map(|parent| preorder.iter().position(|&n| n == parent).unwrap() as u32))
+            .collect();
+        assert_eq!(parens.parents(), expected);
+
+        let louds = ast_graph.to_louds();
+        assert_eq!(louds.bits.len(), 2 * count + 1);
+        assert_eq!(louds.kinds, level_order.iter().map(|&node| ast_graph.graph[node].kind_id).collect::<Vec<u16>>());
+        let expected: Vec<Option<u32>> = level_order.iter()
+            .map(|&node| ast_graph.parent(node).map(|parent| level_order.iter().position(|&n| n == parent).unwrap() as u32))
+            .collect();
+        assert_eq!(louds.parents(), expected);
+
+        let bytes = bincode::serialize(&louds).unwrap();
+        assert_eq!(bincode::deserialize::<crate::succinct::Louds>(&bytes).unwrap(), louds);
+    }
+
+    #[test]
+    fn arena_build_matches_recursive_build() {
+        use crate::arena::TreeArena;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let mut arena = TreeArena::new();
+        for source in [CPP_STRING_TRIMMED, CPP_TAINT, ""] {
+            let tree = parser.parse(source, None).unwrap();
+            let mut expected = ASTGraph::new(source.to_string());
+            expected.build_from_tree(&tree);
+
+            let built = arena.build(&tree, source.to_string());
+            assert_eq!(built.node_count(), expected.node_count());
+            assert!(built.graph.node_indices().all(|node| built.graph[node] == expected.graph[node]));
+            let edges = |graph: &ASTGraph| graph.graph.edge_indices().map(|edge| graph.graph.edge_endpoints(edge).unwrap()).collect::<Vec<_>>();
+            assert_eq!(edges(&built), edges(&expected));
+            assert!(arena.capacity() >= built.graph.node_count());
+
+            let mut bulk = ASTGraph::new(source.to_string());
+            bulk.build_from_tree_in_bulk(&tree);
+            assert_eq!(edges(&bulk), edges(&expected));
+        }
+    }
+
+    #[test]
+    fn add_edges_in_bulk() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let edges: Vec<(NodeIndex, NodeIndex)> = ast_graph.graph.edge_indices().map(|edge| ast_graph.graph.edge_endpoints(edge).unwrap()).collect();
+        let mut copy = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        for gnode in ast_graph.graph().node_weights() {
+            copy.add_gnode(*gnode);
+        }
+        copy.add_edges(&edges);
+        assert!(copy.structurally_equal(&ast_graph));
+
+        let function = ast_graph.children(ast_graph.root().unwrap())[0];
+        let subgraph = ast_graph.extract_subgraph_from(function);
+        assert_eq!(subgraph.graph.edge_count(), subgraph.graph.node_count() - 1);
+        assert_eq!(subgraph.roots().len(), 1);
+    }
+
+    #[test]
+    fn nested_matches_extract_whole_subtrees() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        // function bodies contain the bodies of their if and while statements
+        let blocks = analysis::kind_ids(&tree_sitter_cpp::language(), "compound_statement");
+        let matches: Vec<NodeIndex> = ast_graph.graph.node_indices().filter(|node| blocks.contains(&ast_graph.graph[*node].kind_id)).collect();
+        assert_eq!(matches.len(), 4);
+
+        let subgraphs = ast_graph.extract_subgraphs(blocks);
+        assert_eq!(subgraphs.len(), matches.len());
+        for (&node, subgraph) in matches.iter().zip(subgraphs.iter()) {
+            assert_eq!(subgraph.graph.node_count(), ast_graph.subtree_size(node));
+            assert!(subgraph.structurally_equal(&ast_graph.extract_subgraph_from(node)));
+            assert_eq!(subgraph.source(), Some(ast_graph.get_node_source(node)));
+        }
+    }
+
+    #[test]
+    fn compact_and_reindex_keep_the_graph() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut compacted = ast_graph.clone();
+        let kept = compacted.id_of(NodeIndex::new(3)).unwrap();
+        compacted.compact();
+        assert_eq!(compacted.resolve(kept).unwrap(), NodeIndex::new(3));
+        assert!(compacted.structurally_equal(&ast_graph));
+        assert_eq!(compacted.node_count(), ast_graph.node_count());
+
+        // subgraphs are built from unordered node sets
+        let function = ast_graph.children(ast_graph.root().unwrap())[0];
+        let mut subgraph = ast_graph.extract_subgraph_from(function);
+        subgraph.store_leaf_text();
+        let before = subgraph.clone();
+        let stale = subgraph.id_of(NodeIndex::new(0)).unwrap();
+        let mapping = subgraph.reindex();
+        assert!(matches!(subgraph.resolve(stale), Err(GraphError::InvalidNode(_))));
+
+        assert!(subgraph.structurally_equal(&before));
+        assert_eq!(subgraph.root(), Some(NodeIndex::new(0)));
+        assert_eq!(subgraph.to_balanced_parentheses(), before.to_balanced_parentheses());
+        for (old, new) in mapping {
+            assert_eq!(subgraph.graph[new], before.graph[old]);
+            assert_eq!(subgraph.get_node(new), before.get_node(old));
+            assert_eq!(subgraph.stored_leaf_text().unwrap().get(new), before.stored_leaf_text().unwrap().get(old));
+        }
+        let mut preorder = Vec::new();
+        let mut stack = vec![NodeIndex::new(0)];
+        while let Some(node) = stack.pop() {
+            preorder.push(node.index());
+            stack.extend(subgraph.children(node).into_iter().rev());
+        }
+        assert_eq!(preorder, (0..subgraph.graph.node_count()).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn remove_subtree_and_reroot_keep_maps_consistent() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut original = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        original.build_from_tree(&tree);
+        original.store_leaf_text();
+        let functions = original.children(original.root().unwrap());
+
+        let mut pruned = original.clone();
+        // the freed index is still in bounds, now holding a moved node
+        let stale = pruned.id_of(functions[0]).unwrap();
+        let moved = pruned.remove_subtree(functions[0]).unwrap();
+        assert!(functions[0].index() < pruned.graph.node_count());
+        assert!(matches!(pruned.resolve(stale), Err(GraphError::InvalidNode(_))));
+        assert!(pruned.resolve(pruned.id_of(NodeIndex::new(0)).unwrap()).is_ok());
+        let removed = original.collect_subgraph_nodes(functions[0]);
+        assert_eq!(pruned.graph.node_count(), original.graph.node_count() - removed.len());
+        assert_eq!(pruned.node_count(), pruned.graph.node_count());
+        assert!(pruned.structurally_equal(&original.induced(&original.graph.node_indices().filter(|node| !removed.contains(node)).collect())));
+        for node in pruned.graph.node_indices() {
+            assert_eq!(pruned.get_node(node), Some(pruned.graph[node].id));
+        }
+        for (old, new) in moved.iter() {
+            assert!(!removed.contains(old));
+            assert_eq!(pruned.graph[*new], original.graph[*old]);
+            assert_eq!(pruned.leaf_text(*new), original.leaf_text(*old));
+        }
+        // the root was never moved
+        assert_eq!(pruned.root(), original.root());
+
+        let main = functions[1];
+        let mut rerooted = original.clone();
+        let stale = rerooted.id_of(main).unwrap();
+        let moved = rerooted.reroot(main).unwrap();
+        assert!(matches!(rerooted.resolve(stale), Err(GraphError::InvalidNode(_))));
+        let new_root = rerooted.root().unwrap();
+        assert_eq!(moved.get(&main).copied().unwrap_or(main), new_root);
+        assert!(rerooted.structurally_equal(&original.extract_subgraph_from(main)));
+        assert_eq!(rerooted.get_node_source(new_root), original.get_node_source(main));
+        assert!(rerooted.graph.node_indices().all(|node| rerooted.get_node(node) == Some(rerooted.graph[node].id)));
+
+        assert!(rerooted.remove_subtree(NodeIndex::new(10_000)).is_err());
+    }
+
+    #[test]
+    fn facade_reads_and_builds_graphs() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        assert_eq!(ast_graph.nodes().count(), ast_graph.node_count());
+        assert_eq!(ast_graph.edges().count(), ast_graph.edge_count());
+        assert_eq!(ast_graph.graph().node_count(), ast_graph.node_count());
+
+        // rebuild the graph through the facade alone
+        let mut copy = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        for (node, gnode) in ast_graph.nodes() {
+            assert_eq!(copy.add_gnode(*gnode), node);
+        }
+        copy.add_edges(&ast_graph.edges().collect::<Vec<_>>());
+        assert!(copy.structurally_equal(&ast_graph));
+        assert!(copy.nodes().all(|(node, gnode)| copy.get_node(node) == Some(gnode.id)));
+        assert_eq!(copy.edges().collect::<Vec<_>>(), ast_graph.edges().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iterate_nodes_and_edges() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut count = 0;
+        for (node, gnode) in &ast_graph {
+            assert_eq!(ast_graph.get_node(node), Some(gnode.id));
+            count += 1;
+        }
+        assert_eq!(count, ast_graph.node_count());
+        assert_eq!(ast_graph.nodes().len(), ast_graph.node_count());
+        assert_eq!(ast_graph.edges().len(), ast_graph.edge_count());
+
+        let functions = (&ast_graph).into_iter().filter(|(_, gnode)| gnode.kind_id == 250).count();
+        assert_eq!(functions, 2);
+        assert!(ast_graph.edges().all(|(parent, child)| ast_graph.parent(child) == Some(parent)));
+    }
+
+    #[test]
+    fn index_graph_by_node() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+
+        assert_eq!(ast_graph[root], ast_graph.graph[root]);
+        assert_eq!(ast_graph.get(root), Some(&ast_graph[root]));
+        assert!(ast_graph.children(root).iter().all(|&function| ast_graph[function].kind_id == 250));
+        assert_eq!(ast_graph.get(NodeIndex::new(ast_graph.node_count())), None);
+    }
+
+    #[test]
+    fn display_and_describe_graphs() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let depth = ast_graph.depth();
+        assert_eq!(depth, ast_graph.deepest_paths(1)[0].depth);
+        assert_eq!(ast_graph.to_string(), format!("untitled, {} nodes, {} edges, depth {}", ast_graph.node_count(), ast_graph.edge_count(), depth));
+
+        ast_graph.set_title("read_file.cpp".to_string());
+        let report = ast_graph.describe();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], format!("graph {}", ast_graph));
+        assert!(lines[0].starts_with("graph read_file.cpp, "));
+        assert_eq!(lines[1], "  roots: 1");
+        assert!(lines.iter().any(|line| line.starts_with("  largest subtree: ") && line.contains("kind 250, line 2")));
+        assert_eq!(*lines.last().unwrap(), format!("  source: {} bytes, {} lines", CPP_STRING_TRIMMED.len(), CPP_STRING_TRIMMED.lines().count()));
+
+        assert_eq!(ASTGraph::new(String::new()).to_string(), "untitled, 0 nodes, 0 edges, depth 0");
+    }
+
+    #[test]
+    fn prelude_covers_basic_use() {
+        use crate::prelude::*;
+
+        fn build(source: &str, language: &Language) -> Result<(ASTGraph, NodeIndex), GraphError> {
+            let mut parser = Parser::new();
+            parser.set_language(language).map_err(|err| GraphError::Query(err.to_string()))?;
+            let tree: Tree = parser.parse(source, None).unwrap();
+            let graph = TreeArena::new().build(&tree, source.to_string());
+            let root: NodeIndex = graph.root().unwrap();
+            let _: &GNode = &graph[root];
+            let _: GRange = graph[root].range;
+            let _: StableId = graph.stable_id(root);
+            Ok((graph, root))
+        }
+
+        let (graph, root) = build(CPP_STRING_TRIMMED, &tree_sitter_cpp::language()).unwrap();
+        assert_eq!(graph.children(root).len(), 2);
+    }
+
+    #[test]
+    fn graphs_carry_custom_payloads() {
+        use crate::payload::ASTGraphWith;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+
+        let graph = ASTGraphWith::build(&tree, CPP_STRING_TRIMMED.to_string(), |node, source| {
+            (node.kind().to_string(), source[node.byte_range()].split_whitespace().collect::<Vec<_>>().join(" "))
+        });
+        assert_eq!(graph.payloads().count(), graph.node_count());
+        for (node, (kind, text)) in graph.payloads() {
+            assert_eq!(Some(kind.as_str()), tree_sitter_cpp::language().node_kind_for_id(graph.graph()[node].kind_id));
+            assert_eq!(*text, graph.get_node_source(node).split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+
+        let function = graph.children(graph.root().unwrap())[1];
+        assert_eq!(graph.payload(function).unwrap().1, "int main() { std::string filePath; // Prompt the user for the file path std::cout << \"Enter the file path: \"; std::getline(std::cin, filePath); readFile(filePath); return 0; }");
+        let main = graph.extract_subgraph_from(function);
+        assert_eq!(main.node_count(), graph.subtree_size(function));
+        assert!(main.payloads().all(|(node, (_, text))| *text == main.get_node_source(node).split_whitespace().collect::<Vec<_>>().join(" ")));
+        assert_eq!(main.payload(main.root().unwrap()).unwrap().0, "function_definition");
+    }
+
+    #[test]
+    fn weighted_and_undirected_shortest_paths() {
+        use crate::paths::{EdgeCost,PathOptions};
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let functions = ast_graph.children(root);
+        let leaf = ast_graph.deepest_paths(1)[0].leaf;
+
+        let (cost, path) = ast_graph.shortest_path(root, leaf, &PathOptions::default()).unwrap();
+        assert_eq!(path, ast_graph.path_from_to(root, leaf).unwrap());
+        assert_eq!(cost as usize, path.len() - 1);
+
+        let by_bytes = PathOptions::default().with_cost(EdgeCost::ByteDistance);
+        let (cost, path) = ast_graph.shortest_path(root, leaf, &by_bytes).unwrap();
+        assert_eq!(cost as usize, ast_graph[leaf].range.start_byte - ast_graph[root].range.start_byte);
+        assert_eq!(*path.last().unwrap(), leaf);
+
+        // siblings are only connected when edges can be walked upwards
+        assert!(ast_graph.shortest_path(functions[0], functions[1], &PathOptions::default()).is_none());
+        let (cost, path) = ast_graph.shortest_path(functions[0], functions[1], &PathOptions::default().undirected()).unwrap();
+        assert_eq!((cost, path), (2, vec![functions[0], root, functions[1]]));
+
+        fn kind_change(parent: &GNode, child: &GNode) -> u64 {
+            if parent.kind_id == child.kind_id { 0 } else { 1 }
+        }
+        let custom = PathOptions::default().with_cost(EdgeCost::Custom(kind_change));
+        assert_eq!(ast_graph.shortest_path(root, root, &custom), Some((0, vec![root])));
+    }
+
+    #[test]
+    fn convert_to_other_petgraph_types() {
+        use petgraph::adj::List;
+        use petgraph::graphmap::DiGraphMap;
+        use petgraph::stable_graph::StableDiGraph;
+        use petgraph::visit::IntoNeighbors;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+
+        let map: DiGraphMap<NodeIndex, ()> = (&ast_graph).into();
+        assert_eq!(map.node_count(), ast_graph.node_count());
+        assert_eq!(map.edge_count(), ast_graph.edge_count());
+        let mut children: Vec<NodeIndex> = map.neighbors(root).collect();
+        children.sort_by_key(|&node| ast_graph[node].range.start_byte);
+        assert_eq!(children, ast_graph.children(root));
+
+        let list: List<()> = (&ast_graph).into();
+        assert_eq!(list.node_count(), ast_graph.node_count());
+        assert_eq!(list.edge_count(), ast_graph.edge_count());
+        assert_eq!((&list).neighbors(root.index() as u32).count(), ast_graph.children(root).len());
+
+        let stable: StableDiGraph<GNode, ()> = (&ast_graph).into();
+        assert!(ast_graph.nodes().all(|(node, gnode)| stable[node] == *gnode));
+        assert_eq!(stable.edge_count(), ast_graph.edge_count());
+    }
+
+    #[test]
+    fn petgraph_algorithms_run_on_ast_graph() {
+        use petgraph::algo::{dijkstra, is_isomorphic, tarjan_scc, toposort};
+        use petgraph::visit::Dfs;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+
+        assert_eq!(tarjan_scc(&ast_graph).len(), ast_graph.node_count());
+        assert_eq!(toposort(&ast_graph, None).unwrap()[0], root);
+
+        let mut dfs = Dfs::new(&ast_graph, root);
+        let mut visited = 0;
+        while dfs.next(&ast_graph).is_some() {
+            visited += 1;
+        }
+        assert_eq!(visited, ast_graph.node_count());
+
+        let depths = dijkstra(&ast_graph, root, None, |_| 1);
+        let leaf = ast_graph.deepest_paths(1)[0].leaf;
+        assert_eq!(depths[&leaf], ast_graph.depth());
+
+        let reparsed = crate::arena::TreeArena::new().build(&parser.parse(CPP_STRING_TRIMMED, None).unwrap(), CPP_STRING_TRIMMED.to_string());
+        assert!(is_isomorphic(&ast_graph, &reparsed));
+        let function = ast_graph.extract_subgraph_from(ast_graph.children(root)[0]);
+        assert!(!is_isomorphic(&ast_graph, &function));
+    }
+
+    #[test]
+    fn project_archive_interns_paths_and_kinds() {
+        let mut interner = crate::interner::Interner::new();
+        let first = interner.intern("src/a.cpp");
+        assert_eq!(interner.intern("src/a.cpp"), first);
+        assert_eq!(interner.get(first), Some("src/a.cpp"));
+        assert_eq!(interner.id_of("src/b.cpp"), None);
+
+        let project = cpp_project(&[
+            ("src/a.cpp", "int helper() { return 1; }\n"),
+            ("src/b.cpp", "int main() { return helper(); }\n"),
+        ]);
+        let mut buffer = Vec::new();
+        project.write_archive(&mut buffer, &tree_sitter_cpp::language()).unwrap();
+        let (restored, names) = ProjectGraph::read_archive(&buffer[..], LanguageKinds::new(&tree_sitter_cpp::language())).unwrap();
+
+        assert_eq!(restored.len(), 2);
+        for (path, graph) in project.files() {
+            let copy = restored.get(path).unwrap();
+            assert_eq!(copy.source, graph.source);
+            assert_eq!(copy.nodes().collect::<Vec<_>>(), graph.nodes().collect::<Vec<_>>());
+            assert_eq!(copy.edge_count(), graph.edge_count());
+        }
+        let root = NodeIndex::new(0);
+        let kind_id = restored.get("src/a.cpp").unwrap()[root].kind_id;
+        assert_eq!(names.get(kind_id), Some("translation_unit"));
+        assert_eq!(names.get(250), Some("function_definition"));
+
+        assert!(ProjectGraph::read_archive(&buffer[..4], LanguageKinds::new(&tree_sitter_cpp::language())).is_err());
+    }
+
+    #[test]
+    fn byte_distance_heuristic_finds_the_same_paths() {
+        use crate::paths::Heuristic;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let root = ast_graph.root().unwrap();
+        let heuristic = Heuristic::byte_distance(&ast_graph);
+        assert!(matches!(heuristic, Heuristic::ByteDistance { bytes_per_edge } if bytes_per_edge >= 1));
+        for node in ast_graph.graph.node_indices() {
+            let path = ast_graph.path_from_to(root, node).unwrap();
+            assert_eq!(ast_graph.path_from_to_with(root, node, &heuristic).unwrap(), path);
+            // admissible: never more than the edges actually left
+            assert!(heuristic.estimate(&ast_graph[root], &ast_graph[node]) <= (path.len() - 1) as u64);
+        }
+        let functions = ast_graph.children(root);
+        assert!(ast_graph.path_from_to_with(functions[1], functions[0], &heuristic).is_none());
+        assert_eq!(Heuristic::Zero.estimate(&ast_graph[root], &ast_graph[functions[1]]), 0);
+    }
+
+    #[test]
+    fn batch_paths_match_single_queries() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let root = ast_graph.root().unwrap();
+        let goals: Vec<NodeIndex> = ast_graph.graph.node_indices().rev().collect();
+        let paths = ast_graph.paths_from_to_many(root, &goals);
+        assert_eq!(paths.len(), goals.len());
+        for (goal, path) in goals.iter().zip(paths) {
+            assert_eq!(path, ast_graph.path_from_to(root, *goal));
+        }
+
+        let function = ast_graph.children(root)[0];
+        let outside = ast_graph.children(root)[1];
+        let paths = ast_graph.paths_from_to_many(function, &[outside, function]);
+        assert_eq!(paths, vec![None, Some(vec![function])]);
+        assert!(ast_graph.paths_from_to_many(root, &[]).is_empty());
+    }
+
+    #[test]
+    fn pairwise_distances_match_undirected_paths() {
+        use crate::paths::PathOptions;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let function = ast_graph.children(ast_graph.root().unwrap())[0];
+        let mut nodes: Vec<NodeIndex> = ast_graph.collect_subgraph_nodes(function).into_iter().collect();
+        nodes.sort();
+        let matrix = ast_graph.pairwise_distances(&nodes);
+        assert_eq!(matrix.nodes(), &nodes[..]);
+        let undirected = PathOptions::default().undirected();
+        for (i, &a) in nodes.iter().enumerate() {
+            for (j, &b) in nodes.iter().enumerate() {
+                let (cost, _) = ast_graph.shortest_path(a, b, &undirected).unwrap();
+                assert_eq!(matrix.get(i, j), Some(cost as usize));
+            }
+        }
+        let leaf = *nodes.iter().find(|&&node| ast_graph.children(node).is_empty()).unwrap();
+        assert_eq!(matrix.between(function, leaf), ast_graph.path_from_to(function, leaf).map(|path| path.len() - 1));
+        assert!(ast_graph.pairwise_distances(&[]).nodes().is_empty());
+    }
+
+    #[test]
+    fn sampled_graphs_stay_small_and_connected() {
+        use crate::sample::SampleStrategy;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let top = ast_graph.sample_subgraph(10, SampleStrategy::TopDown);
+        assert_eq!(top.graph().node_count(), 10);
+        assert_eq!(top.roots().len(), 1);
+        assert_eq!(top.graph().edge_count(), 9);
+        let root = top.roots()[0];
+        assert_eq!(top[root], ast_graph[ast_graph.root().unwrap()]);
+        assert_eq!(top.children(root).len(), ast_graph.children(ast_graph.root().unwrap()).len());
+
+        let random = ast_graph.sample_subgraph(15, SampleStrategy::RandomSubtree { seed: 7 });
+        assert_eq!(random.graph().node_count(), 15);
+        assert_eq!(random.roots().len(), 1);
+        let again = ast_graph.sample_subgraph(15, SampleStrategy::RandomSubtree { seed: 7 });
+        let ids = |graph: &ASTGraph| graph.nodes().map(|(_, gnode)| gnode.id).collect::<HashSet<usize>>();
+        assert_eq!(ids(&random), ids(&again));
+
+        let everything = ast_graph.sample_subgraph(usize::MAX, SampleStrategy::TopDown);
+        assert_eq!(everything.graph().node_count(), ast_graph.graph().node_count());
+        assert_eq!(ast_graph.sample_subgraph(0, SampleStrategy::RandomSubtree { seed: 1 }).graph().node_count(), 0);
+    }
+
+    #[test]
+    fn minimize_keeps_the_failing_property() {
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        ast_graph.set_title("repro".to_string());
+
+        let call = language.id_for_node_kind("call_expression", true);
+        let calls = |graph: &ASTGraph| graph.nodes().filter(|(_, gnode)| gnode.kind_id == call).count();
+        let mut runs = 0;
+        let minimized = ast_graph.minimize_for_repro(|graph| { runs += 1; calls(graph) > 0 });
+
+        assert!(runs > 1);
+        assert_eq!(calls(&minimized), 1);
+        assert_eq!(minimized.title, "repro");
+        // one call left, reached by a single chain from the root
+        assert_eq!(minimized.roots().len(), 1);
+        assert_eq!(minimized.graph().edge_count(), minimized.graph().node_count() - 1);
+        assert!(minimized.nodes().all(|(node, _)| minimized.children(node).len() <= 1));
+        let (leaf, _) = minimized.nodes().find(|(node, _)| minimized.children(*node).is_empty()).unwrap();
+        assert_eq!(minimized[leaf].kind_id, call);
+
+        let unchanged = ast_graph.minimize_for_repro(|_| false);
+        assert_eq!(unchanged.graph().node_count(), ast_graph.graph().node_count());
+    }
+
+    #[cfg(feature="test-corpus")]
+    #[test]
+    fn fixtures_match_their_stats() {
+        use crate::fixtures::{self,FixtureLanguage};
+
+        assert_eq!(fixtures::fixtures().len(), 4);
+        for fixture in fixtures::fixtures() {
+            assert_eq!(fixture.source.lines().count(), fixture.stats.lines);
+            match fixture.measure() {
+                Some(measured) => assert_eq!(measured, fixture.stats, "{}", fixture.name),
+                None => assert_eq!(fixture.language, FixtureLanguage::Python),
+            }
+        }
+        let graph = fixtures::fixture("cpp/file_reader").unwrap().graph().unwrap();
+        assert_eq!(graph.title, "cpp/file_reader");
+        assert_eq!(fixtures::fixtures_for(FixtureLanguage::Cpp).count(), 2);
+        assert!(fixtures::fixture("cpp/missing").is_none());
+    }
+
+    #[test]
+    fn registry_profiles_split_functions_classes_and_methods() {
+        use crate::languages::LanguageRegistry;
+
+        let registry = LanguageRegistry::with_builtin();
+        assert!(registry.names().any(|name| name == "fortran"));
+        let entry = registry.for_path("src/shapes.HPP").unwrap();
+        assert_eq!(entry.name, "cpp");
+        assert!(registry.for_path("README").is_none());
+
+        let source = "class Circle {\npublic:\n    double area() const { return 3.0 * r * r; }\n    double r;\n};\n\
+                      double total(Circle c) { return c.area(); }\n";
+        let mut parser = Parser::new();
+        parser.set_language(&entry.language).expect("Error loading CPP grammar");
+        let tree = parser.parse(source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let extraction = entry.profile.extract(&ast_graph);
+        assert_eq!(extraction.classes.len(), 1);
+        assert_eq!(extraction.methods.len(), 1);
+        assert_eq!(extraction.functions.len(), 1);
+        assert!(ast_graph.get_node_source(extraction.methods[0]).starts_with("double area()"));
+        assert!(ast_graph.get_node_source(extraction.functions[0]).starts_with("double total("));
+    }
+
+    #[cfg(feature="lang-python")]
+    #[test]
+    fn python_profile_finds_methods_inside_classes() {
+        use crate::languages::LanguageRegistry;
+
+        let registry = LanguageRegistry::with_builtin();
+        let entry = registry.get("python").unwrap();
+        let source = "class Reader:\n    def read(self):\n        return 1\n\ndef main():\n    Reader().read()\n";
+        let mut parser = Parser::new();
+        parser.set_language(&entry.language).expect("Error loading Python grammar");
+        let tree = parser.parse(source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let extraction = entry.profile.extract(&ast_graph);
+        assert_eq!((extraction.classes.len(), extraction.methods.len(), extraction.functions.len()), (1, 1, 1));
+    }
+
+    #[test]
+    fn metadata_is_stored_and_carried_to_subgraphs() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let recorded = ast_graph.metadata().len();
+        assert_eq!(ast_graph.set_metadata("commit", "0f529d7"), None);
+        ast_graph.set_metadata("path", "src/reader.cpp");
+        assert_eq!(ast_graph.set_metadata("commit", "2f6f08c"), Some("0f529d7".to_string()));
+        assert_eq!(ast_graph.metadata_value("commit"), Some("2f6f08c"));
+
+        let function = ast_graph.children(ast_graph.root().unwrap())[0];
+        assert_eq!(ast_graph.extract_subgraph_from(function).metadata(), ast_graph.metadata());
+
+        let path = std::env::temp_dir().join("tree_graph_metadata_test.tgb");
+        ast_graph.save_to_file_redacted(&path, &crate::redact::RedactOptions::new(&tree_sitter_cpp::language())).unwrap();
+        let loaded = ASTGraph::load_from_file_with_source(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.metadata_value("path"), Some("src/reader.cpp"));
+        assert_eq!(loaded.metadata().len(), recorded + 2);
+
+        assert_eq!(ast_graph.remove_metadata("path"), Some("src/reader.cpp".to_string()));
+        assert_eq!(ast_graph.metadata_value("path"), None);
+    }
+
+    #[test]
+    fn provenance_is_recorded_and_survives_storage() {
+        use crate::provenance::Provenance;
+        use std::time::Duration;
+
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let provenance = ast_graph.provenance();
+        assert!(provenance.built_at.is_some());
+        assert_eq!(provenance.crate_version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(provenance.grammar_kinds, Some(language.node_kind_count()));
+        assert!(provenance.is_fresh(Duration::from_secs(3600)));
+        assert!(provenance.is_compatible_with(&language));
+        assert!(!provenance.is_compatible_with(&tree_sitter_fortran::language()));
+
+        let mut bulk = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        bulk.build_from_tree_in_bulk(&tree);
+        assert!(bulk.provenance().is_compatible_with(&language));
+
+        let path = std::env::temp_dir().join("tree_graph_provenance_test.tgb");
+        ast_graph.save_to_file_redacted(&path, &crate::redact::RedactOptions::new(&language)).unwrap();
+        let loaded = ASTGraph::load_from_file_with_source(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.provenance(), provenance);
+
+        let unbuilt = ASTGraph::new(String::new()).provenance();
+        assert_eq!(unbuilt, Provenance::default());
+        assert!(!unbuilt.is_fresh(Duration::from_secs(3600)));
+        assert!(!unbuilt.is_compatible_with(&language));
+    }
+
+    #[test]
+    fn subtrees_of_kind_are_lazy_handles() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let functions: Vec<_> = ast_graph.subtrees_of_kind(250).collect();
+        assert_eq!(functions.len(), 2);
+        assert!(functions[0].source.unwrap().starts_with("void readFile("));
+        assert!(functions[1].source.unwrap().starts_with("int main()"));
+        assert_eq!(functions[1].range, ast_graph[functions[1].root].range);
+        assert_eq!(functions[0].children(), ast_graph.children(functions[0].root));
+
+        let copy = functions[1].to_graph();
+        assert_eq!(copy.graph().node_count(), ast_graph.collect_subgraph_nodes(functions[1].root).len());
+        assert_eq!(ast_graph.subtrees_of_kind(u16::MAX).count(), 0);
+    }
+
+    #[test]
+    fn line_windows_select_ast_regions() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        // row 0 is empty; `readFile` spans rows 1-15 and `main` rows 16-26
+        let functions = ast_graph.children(ast_graph.root().unwrap());
+        assert_eq!(ast_graph[functions[1]].range.start_point.row, 16);
+        let in_lines = ast_graph.nodes_in_lines(2, 2);
+        assert!(in_lines.contains(&functions[0]));
+        assert!(!in_lines.contains(&functions[1]));
+        assert!(in_lines.iter().any(|&node| ast_graph.get_node_source(node) == "std::ifstream file(filePath);"));
+
+        // `return 0;` on row 25
+        let covering = ast_graph.covering_node_for_lines(25, 25).unwrap();
+        assert_eq!(ast_graph.get_node_source(covering), "return 0;");
+        assert_eq!(ast_graph.covering_node_for_lines(3, 20), ast_graph.root());
+        // the body of `readFile`
+        assert_eq!(ast_graph.parent(ast_graph.covering_node_for_lines(2, 14).unwrap()), Some(functions[0]));
+        let region = ast_graph.subgraph_for_lines(16, 22).unwrap();
+        assert_eq!(region.graph().node_count(), ast_graph.collect_subgraph_nodes(ast_graph.covering_node_for_lines(16, 22).unwrap()).len());
+        assert!(ast_graph.subgraph_for_lines(500, 510).is_none());
+    }
+
+    #[test]
+    fn diff_hunks_map_to_nodes_and_functions() {
+        use crate::hunks;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let kinds = LanguageKinds::new(&tree_sitter_cpp::language());
+        let functions = ast_graph.children(ast_graph.root().unwrap());
+
+        let diff = "diff --git a/reader.cpp b/reader.cpp\n--- a/reader.cpp\n+++ b/reader.cpp\n\
+                    @@ -24,4 +24,4 @@ int main() {\n         readFile(filePath);\n \n-        return 1;\n+        return 0;\n     }\n";
+        let parsed = hunks::parse_hunks(diff).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].path.as_deref(), Some("reader.cpp"));
+        assert_eq!(parsed[0].changed_rows, vec![25]);
+
+        let affected = ast_graph.affected_nodes_from_diff(diff).unwrap();
+        assert_eq!(affected.len(), 1);
+        assert_eq!(ast_graph.get_node_source(affected[0]), "return 0;");
+        assert_eq!(ast_graph.affected_functions_from_diff(diff, &kinds).unwrap(), vec![functions[1]]);
+
+        // a deleted line in `readFile` and an added one in `main`
+        let diff = "--- a/reader.cpp\n+++ b/reader.cpp\n@@ -10,3 +10,2 @@\n         std::string line;\n-        std::string unused;\n         while (std::getline(file, line)) {\n\
+                    @@ -23,2 +22,3 @@\n         std::getline(std::cin, filePath);\n+\n         readFile(filePath);\n";
+        let parsed = hunks::parse_hunks(diff).unwrap();
+        assert_eq!(parsed[0].changed_rows, vec![9, 10]);
+        assert_eq!(parsed[1].changed_rows, vec![22]);
+        assert_eq!(ast_graph.affected_functions_from_diff(diff, &kinds).unwrap(), functions);
+        assert_eq!(ast_graph.affected_nodes_from_diff(diff).unwrap().len(), 2);
+
+        assert!(hunks::parse_hunks("@@ -1,2 +1,2 @@\n context\n").is_err());
+        assert!(matches!(hunks::parse_hunks("@@ -x +1 @@\n"), Err(GraphError::Parse { line: 1, .. })));
+        assert!(ast_graph.affected_nodes_from_diff("").unwrap().is_empty());
+    }
+
+    #[cfg(feature="mmap")]
+    #[test]
+    fn mapped_sources_are_read_in_place() {
+        use crate::mapped::MappedAstGraph;
+
+        let path = std::env::temp_dir().join(format!("tree-graph-mapped-{}.cpp", std::process::id()));
+        fs::write(&path, CPP_STRING_TRIMMED).unwrap();
+        let mapped = MappedAstGraph::open(&path, &tree_sitter_cpp::language()).unwrap();
+
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        ast_graph.build_from_tree(&parser.parse(CPP_STRING_TRIMMED, None).unwrap());
+
+        assert_eq!(mapped.source(), CPP_STRING_TRIMMED);
+        assert_eq!(mapped.graph().graph().node_count(), ast_graph.graph().node_count());
+        for (node, _) in ast_graph.nodes() {
+            assert_eq!(mapped.get_node_source(node), ast_graph.get_node_source(node));
+        }
+        let owned = mapped.into_owned();
+        assert_eq!(owned.get_node_source(owned.root().unwrap()), ast_graph.get_node_source(ast_graph.root().unwrap()));
+
+        fs::write(&path, [0xff, 0xfe, 0x00]).unwrap();
+        assert!(matches!(MappedAstGraph::open(&path, &tree_sitter_cpp::language()), Err(GraphError::Encoding(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn giant_literals_are_elided_in_leaf_text_and_exports() {
+        let blob = "QUJD".repeat(5000);
+        let source = format!("const char* data = \"{}\";\nint x = 1;\n", blob);
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(&source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source.clone());
+        ast_graph.build_from_tree(&tree);
+
+        ast_graph.store_leaf_text_within(64);
+        let leaf_text = ast_graph.stored_leaf_text().unwrap();
+        assert!(leaf_text.table().strings().iter().all(|text| text.len() <= 64));
+        assert!(leaf_text.table().strings().iter().any(|text| text.contains(crate::excerpt::ELLIPSIS)));
+        assert!(leaf_text.table().strings().iter().any(|text| text == "x"));
+
+        let dot = export::to_dot(&ast_graph, &ExportOptions::default().with_leaf_text(32));
+        assert!(dot.len() < blob.len());
+        assert!(dot.contains(": x\""));
+        assert!(dot.lines().all(|line| line.len() < 200));
+        assert!(!export::to_dot(&ast_graph, &ExportOptions::default()).contains(": x\""));
+        let reimported = import::from_dot(&dot).unwrap();
+        assert_eq!(reimported.graph().node_count(), ast_graph.graph().node_count());
+    }
+
+    #[test]
+    fn build_limits_bail_out_with_typed_errors() {
+        use crate::limits::{BuildLimits,Limit};
+        use std::time::Duration;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let count = ast_graph.graph().node_count();
+
+        let limited = ASTGraph::build_with_limits(&tree, CPP_STRING_TRIMMED.to_string(), &BuildLimits::default().with_max_nodes(count)).unwrap();
+        assert_eq!(limited.nodes().collect::<Vec<_>>(), ast_graph.nodes().collect::<Vec<_>>());
+        assert_eq!(limited.edges().collect::<Vec<_>>(), ast_graph.edges().collect::<Vec<_>>());
+
+        let result = ASTGraph::build_with_limits(&tree, CPP_STRING_TRIMMED.to_string(), &BuildLimits::default().with_max_nodes(count - 1));
+        assert!(matches!(result, Err(GraphError::LimitExceeded(Limit::Nodes(max))) if max == count - 1));
+        let bytes = BuildLimits::default().with_max_bytes(10);
+        assert!(matches!(bytes.check_source(CPP_STRING_TRIMMED), Err(GraphError::LimitExceeded(Limit::Bytes(10)))));
+        assert!(ASTGraph::build_with_limits(&tree, CPP_STRING_TRIMMED.to_string(), &bytes).is_err());
+        let timed = BuildLimits::default().with_timeout(Duration::ZERO);
+        let error = ASTGraph::build_with_limits(&tree, CPP_STRING_TRIMMED.to_string(), &timed).unwrap_err();
+        assert_eq!(error.to_string(), "limit exceeded: build reached its 0ns timeout");
+    }
+
+    #[test]
+    fn cancelled_work_stops_with_an_error() {
+        use crate::cancel::CancelToken;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+
+        let cancel = CancelToken::new();
+        let built = ASTGraph::build_cancellable(&tree, CPP_STRING_TRIMMED.to_string(), &cancel).unwrap();
+        let kinds: HashSet<u16> = [250].into_iter().collect();
+        assert_eq!(built.extract_subgraphs_cancellable(kinds.clone(), &cancel).unwrap().len(), 2);
+
+        cancel.clone().cancel();
+        assert!(cancel.is_cancelled());
+        assert!(matches!(ASTGraph::build_cancellable(&tree, CPP_STRING_TRIMMED.to_string(), &cancel), Err(GraphError::Cancelled)));
+        assert!(matches!(built.extract_subgraphs_cancellable(kinds.clone(), &cancel), Err(GraphError::Cancelled)));
+        assert_eq!(built.extract_subgraphs(kinds).len(), 2);
+
+        // the host's own flag, flipped after the first file
+        let flag = Arc::new(AtomicBool::new(false));
+        let files = vec![("a.cpp".to_string(), "int a() { return 1; }".to_string()),
+                         ("b.cpp".to_string(), "int b() { return 2; }".to_string())];
+        let mut project = ProjectGraph::new(LanguageKinds::new(&tree_sitter_cpp::language()));
+        let token = CancelToken::from_flag(flag.clone());
+        let mut remaining = files.into_iter();
+        let first = remaining.next().unwrap();
+        assert_eq!(project.ingest(vec![first], &tree_sitter_cpp::language(), &token).unwrap(), 1);
+        flag.store(true, Ordering::Relaxed);
+        assert!(matches!(project.ingest(remaining, &tree_sitter_cpp::language(), &token), Err(GraphError::Cancelled)));
+        assert_eq!(project.len(), 1);
+        assert_eq!(project.get("a.cpp").unwrap().title(), "a.cpp");
+
+        let calls = CancelToken::from_fn(|| true);
+        assert!(calls.is_cancelled());
+    }
+
+    #[cfg(feature="parallel")]
+    #[test]
+    fn parallel_work_runs_in_the_configured_pool() {
+        use crate::parallel::Parallelism;
+        use std::sync::Arc;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+        assert_eq!(Parallelism::with_pool(pool).install(rayon::current_num_threads).unwrap(), 2);
+        assert_eq!(Parallelism::with_threads(3).install(rayon::current_num_threads).unwrap(), 3);
+
+        let kinds: HashSet<u16> = [250].into_iter().collect();
+        let parallel = ast_graph.extract_subgraphs_parallel(&kinds, &Parallelism::with_threads(2)).unwrap();
+        let sequential = ast_graph.extract_subgraphs(kinds);
+        assert_eq!(parallel.len(), sequential.len());
+        for (a, b) in parallel.iter().zip(sequential.iter()) {
+            assert_eq!(a.source, b.source);
+            assert_eq!(a.graph().node_count(), b.graph().node_count());
+        }
+
+        let files: Vec<(String, String)> = (0..8).map(|i| (format!("f{}.cpp", i), format!("int f{}() {{ return {}; }}", i, i))).collect();
+        let mut project = ProjectGraph::new(LanguageKinds::new(&tree_sitter_cpp::language()));
+        assert_eq!(project.ingest_parallel(files, &tree_sitter_cpp::language(), &Parallelism::new()).unwrap(), 8);
+        assert_eq!(project.len(), 8);
+        assert_eq!(project.get("f3.cpp").unwrap().title(), "f3.cpp");
+    }
+
+    #[cfg(feature="server")]
+    #[test]
+    fn server_answers_framed_requests() {
+        use crate::languages::LanguageRegistry;
+        use crate::server::{self,GraphServer,METHOD_NOT_FOUND,SERVICE_ERROR};
+        use crate::service::GraphService;
+        use serde_json::json;
+
+        let requests = [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "build", "params": { "uri": "file:///read.cpp", "language": "cpp", "source": CPP_STRING_TRIMMED } }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "query", "params": { "uri": "file:///read.cpp", "kind": "function_definition" } }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "extract", "params": { "uri": "file:///read.cpp", "kind": "function_definition" } }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "query", "params": { "uri": "file:///missing.cpp", "kind": "function_definition" } }),
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "frobnicate" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+            json!({ "jsonrpc": "2.0", "id": 6, "method": "shutdown" }),
+        ];
+        let mut input = Vec::new();
+        for request in requests.iter() {
+            server::write_message(&mut input, request).unwrap();
+        }
+
+        let mut server = GraphServer::new(GraphService::new(LanguageRegistry::with_builtin()));
+        let mut output = Vec::new();
+        server.serve(input.as_slice(), &mut output).unwrap();
+        assert!(!server.is_shut_down(), "nothing after `exit` is read");
+
+        let mut reader = output.as_slice();
+        let mut responses = Vec::new();
+        while let Some(response) = server::read_message(&mut reader).unwrap() {
+            responses.push(response);
+        }
+        assert_eq!(responses.len(), 5);
+
+        let graph = server.service().graph("file:///read.cpp").unwrap();
+        assert_eq!(responses[0]["result"]["nodes"], graph.graph().node_count());
+        let functions = responses[1]["result"].as_array().unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0]["kind"], "function_definition");
+        let extracted = responses[2]["result"].as_array().unwrap();
+        assert!(extracted[1]["source"].as_str().unwrap().contains("return 0;"));
+        assert_eq!(responses[3]["error"]["code"], SERVICE_ERROR);
+        assert_eq!(responses[4]["id"], 5);
+        assert_eq!(responses[4]["error"]["code"], METHOD_NOT_FOUND);
+
+        let oversized = format!("Content-Length: {}\r\n\r\n{{}}", server::MAX_MESSAGE_BYTES + 1);
+        assert!(matches!(server::read_message(&mut oversized.as_bytes()), Err(GraphError::Encoding(_))));
+    }
+
+    #[cfg(feature="grpc")]
+    #[test]
+    fn grpc_handlers_serve_built_graphs() {
+        use crate::grpc::{BuildGraphRequest,ExtractSubgraphsRequest,GetSubtreeSourceRequest,GraphGrpcServer,QueryRequest};
+        use crate::languages::LanguageRegistry;
+        use crate::service::GraphService;
+
+        let server = GraphGrpcServer::new(GraphService::new(LanguageRegistry::with_builtin()));
+        let built = server.build_graph(BuildGraphRequest {
+            uri: "read.cpp".to_string(),
+            language: "cpp".to_string(),
+            source: CPP_STRING_TRIMMED.to_string(),
+        }).unwrap();
+        assert_eq!(built.edges + 1, built.nodes);
+
+        let query = QueryRequest { uri: "read.cpp".to_string(), kind: "function_definition".to_string() };
+        let functions = server.query(query).unwrap().nodes;
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[1].range.as_ref().unwrap().start_point.as_ref().unwrap().row, 16);
+
+        let extract = ExtractSubgraphsRequest { uri: "read.cpp".to_string(), kind: "function_definition".to_string() };
+        let subgraphs = server.extract_subgraphs(extract).unwrap().subgraphs;
+        let main = server.get_subtree_source(GetSubtreeSourceRequest { uri: "read.cpp".to_string(), node: functions[1].index }).unwrap();
+        assert_eq!(subgraphs[1].source, main.source);
+
+        let missing = server.query(QueryRequest { uri: "missing.cpp".to_string(), kind: "function_definition".to_string() });
+        assert_eq!(missing.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[cfg(feature="http")]
+    #[test]
+    fn http_routes_serve_project_files() {
+        use crate::http::respond;
+
+        let project = cpp_project(&[("src/read file.cpp", CPP_STRING_TRIMMED)]);
+        let graph = project.get("src/read file.cpp").unwrap();
+
+        let files: serde_json::Value = serde_json::from_str(&respond(&project, "GET", "/files").body).unwrap();
+        assert_eq!(files[0]["path"], "src/read file.cpp");
+        assert_eq!(files[0]["nodes"], graph.graph().node_count());
+
+        let nodes = respond(&project, "GET", "/files/src/read%20file.cpp/nodes");
+        assert_eq!(nodes.content_type, "application/json");
+        let nodes: serde_json::Value = serde_json::from_str(&nodes.body).unwrap();
+        assert_eq!(nodes.as_array().unwrap().len(), graph.graph().node_count());
+        let edges: serde_json::Value = serde_json::from_str(&respond(&project, "GET", "/files/src/read%20file.cpp/edges").body).unwrap();
+        assert_eq!(edges.as_array().unwrap().len(), graph.edge_count());
+
+        let dot = respond(&project, "GET", "/files/src/read%20file.cpp/graph.dot?max_depth=2");
+        assert_eq!(dot.body, export::to_dot(graph, &ExportOptions::with_max_depth(2)));
+        let mermaid = respond(&project, "GET", "/files/src/read%20file.cpp/graph.mmd");
+        assert!(mermaid.body.starts_with("graph TD"));
+
+        assert_eq!(respond(&project, "GET", "/files/src/missing.cpp/nodes").status, 404);
+        assert_eq!(respond(&project, "GET", "/files/src/read%20file.cpp/graph.dot?max_depth=two").status, 400);
+        assert_eq!(respond(&project, "POST", "/files").status, 405);
+    }
+
+    #[test]
+    fn pages_cover_nodes_and_children_in_order() {
+        use crate::page::NodeOrder;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let total = ast_graph.graph().node_count();
+
+        for order in [NodeOrder::Index, NodeOrder::Position, NodeOrder::Kind] {
+            let whole = ast_graph.nodes_page(0, usize::MAX, order);
+            assert_eq!(whole.items.len(), total);
+            assert!(!whole.has_more());
+
+            let mut paged = Vec::new();
+            let mut offset = Some(0);
+            while let Some(next) = offset {
+                let page = ast_graph.nodes_page(next, 7, order);
+                assert_eq!(page.total, total);
+                paged.extend(page.items.iter().copied());
+                offset = page.next_offset();
+            }
+            assert_eq!(paged, whole.items, "{:?}", order);
+        }
+        let by_kind = ast_graph.nodes_page(0, usize::MAX, NodeOrder::Kind).items;
+        assert!(by_kind.windows(2).all(|pair| ast_graph.graph()[pair[0]].kind_id <= ast_graph.graph()[pair[1]].kind_id));
+        assert!(ast_graph.nodes_page(total, 10, NodeOrder::Position).items.is_empty());
+
+        let root = ast_graph.roots()[0];
+        let children = ast_graph.children(root);
+        let page = ast_graph.children_page(root, 1, 1).unwrap();
+        assert_eq!(page.items, vec![children[1]]);
+        assert_eq!(page.total, children.len());
+        assert!(ast_graph.children_page(NodeIndex::new(total), 0, 1).is_err());
+    }
+
+    #[test]
+    fn cached_graphs_answer_kind_queries_without_the_grammar() {
+        use crate::cached::CachedGraph;
+
+        let mut parser = Parser::new();
+        let language = tree_sitter_cpp::language();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        ast_graph.set_title("read.cpp".to_string());
+        let pattern = ["function_definition", "**", "call_expression"];
+        let expected = ast_graph.find_paths_matching(&language, &pattern).unwrap();
+
+        let mut buffer = Vec::new();
+        CachedGraph::new(ast_graph, &language).write_to(&mut buffer).unwrap();
+        // from here on only the saved table names kinds
+        let cached = CachedGraph::read_from(buffer.as_slice()).unwrap();
+        assert_eq!(cached.graph().title(), "read.cpp");
+        assert_eq!(cached.find_paths_matching(&pattern).unwrap(), expected);
+
+        let functions = cached.nodes_of_kind("function_definition").unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(cached.kind_name(functions[0]), Some("function_definition"));
+        assert!(cached.graph().get_node_source(functions[1]).contains("return 0;"));
+        assert!(cached.nodes_of_kind("lambda_expression").is_err());
+    }
+
+    #[test]
+    fn kind_tables_name_kinds_without_the_grammar() {
+        use crate::kinds::KindTable;
+        use std::sync::Arc;
+
+        let language = tree_sitter_cpp::language();
+        let table = KindTable::from_language(&language);
+        assert_eq!(table.len(), language.node_kind_count());
+
+        let mut buffer = Vec::new();
+        table.write_to(&mut buffer).unwrap();
+        let table = KindTable::read_from(buffer.as_slice()).unwrap();
+        for (kind_id, kind) in table.iter() {
+            assert_eq!(Some(kind.name.as_str()), language.node_kind_for_id(kind_id));
+            assert_eq!(kind.named, language.node_kind_is_named(kind_id));
+        }
+        assert_eq!(table.is_named(250), Some(true));
+        assert_eq!(table.name(250), Some("function_definition"));
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let pattern = ["function_definition", "*", "identifier"];
+        assert_eq!(ast_graph.find_paths_matching(&table, &pattern).unwrap(), ast_graph.find_paths_matching(&language, &pattern).unwrap());
+
+        let dot = export::to_dot(&ast_graph, &ExportOptions::default().with_kind_names(Arc::new(table)));
+        assert!(dot.contains("[label=\"function_definition\", kind_id=250"));
+        assert_eq!(import::from_dot(&dot).unwrap().graph().node_count(), ast_graph.graph().node_count());
+    }
+
+    #[test]
+    fn named_flags_survive_serialization() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let named = ast_graph.nodes().filter(|(_, gnode)| gnode.is_named).count();
+        assert!(named > 0 && named < ast_graph.graph().node_count());
+
+        let root = ast_graph.roots()[0];
+        let main = ast_graph.nodes().filter(|(_, gnode)| gnode.kind_id == 250).map(|(node, _)| node).nth(1).unwrap();
+        let body = ast_graph.children(main).into_iter().last().unwrap();
+        assert!(ast_graph.children(body).iter().any(|&child| ast_graph.get_node_source(child) == "{"));
+        assert!(ast_graph.named_children(body).all(|child| ast_graph.graph()[child].is_named));
+        let descendants: Vec<NodeIndex> = ast_graph.named_descendants(root).collect();
+        assert_eq!(descendants.len(), named - 1);
+        assert_eq!(descendants[0], ast_graph.named_children(root).next().unwrap());
+
+        let mut buffer = Vec::new();
+        serialize_into(&mut buffer, &ast_graph.to_serializable()).unwrap();
+        let decoded = ASTGraph::from_serializable(deserialize_from(buffer.as_slice()).unwrap());
+        assert_eq!(decoded.named_descendants(root).collect::<Vec<_>>(), descendants);
+
+        let imported = import::from_dot(&export::to_dot(&ast_graph, &ExportOptions::default())).unwrap();
+        assert_eq!(imported.nodes().filter(|(_, gnode)| gnode.is_named).count(), named);
+    }
+
+    #[test]
+    fn overlay_layers_keep_tree_walks_pure() {
+        use crate::overlay::{EdgeKind,Overlay};
+
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let functions = ast_graph.children(root);
+        let (read_file, main) = (functions[0], functions[1]);
+
+        let mut overlay = Overlay::new(&ast_graph);
+        let tree_walk = overlay.walk_layer(EdgeKind::Child);
+        assert_eq!(tree_walk.len(), ast_graph.graph().node_count());
+        assert_eq!(overlay.walk_layer_from(main, EdgeKind::Child).len(), ast_graph.subtree_size(main));
+
+        overlay.add_calls(&LanguageKinds::new(&language)).unwrap();
+        // parallel edges of different kinds between the same pair
+        overlay.add_edge(main, read_file, EdgeKind::Call);
+        overlay.add_edge(main, read_file, EdgeKind::Flow);
+        overlay.add_edge(main, read_file, EdgeKind::Flow);
+        let kinds: Vec<EdgeKind> = overlay.edges(main).iter().filter(|&&(_, to)| to == read_file).map(|&(kind, _)| kind).collect();
+        assert_eq!(kinds, vec![EdgeKind::Call, EdgeKind::Flow]);
+
+        assert_eq!(overlay.walk_layer(EdgeKind::Child), tree_walk);
+        assert_eq!(overlay.walk_layer_from(main, EdgeKind::Flow), vec![main, read_file]);
+        let calls = overlay.layer(EdgeKind::Call);
+        assert!(calls.iter().all(|&(_, to)| to == read_file));
+        let call_walk = overlay.walk_layer(EdgeKind::Call);
+        assert_eq!(call_walk[..2], [main, read_file]);
+        assert_eq!(call_walk.len(), calls.len() + 1);
+
+        overlay.remove_layer(EdgeKind::Call);
+        assert!(overlay.layer(EdgeKind::Call).is_empty());
+        assert_eq!(overlay.layer(EdgeKind::Flow), vec![(main, read_file)]);
+        assert_eq!(overlay.walk_layer(EdgeKind::Child), tree_walk);
+    }
+
+    #[test]
+    fn detached_overlays_attach_to_rebuilt_graphs() {
+        use crate::overlay::{DetachedOverlay,EdgeKind,Overlay};
+
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let build = |parser: &mut Parser| {
+            let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+            let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+            ast_graph.build_from_tree(&tree);
+            ast_graph
+        };
+        let ast_graph = build(&mut parser);
+        let mut overlay = Overlay::new(&ast_graph);
+        overlay.add_calls(&LanguageKinds::new(&language)).unwrap();
+        let calls = overlay.layer(EdgeKind::Call);
+        assert!(!calls.is_empty());
+
+        // shipped without the tree layers, read back elsewhere
+        let detached = overlay.detach(&[EdgeKind::Call, EdgeKind::Flow]);
+        assert_eq!(detached.len(), calls.len());
+        let mut buffer = Vec::new();
+        detached.write_to(&mut buffer).unwrap();
+        let shipped = DetachedOverlay::read_from(buffer.as_slice()).unwrap();
+        assert_eq!(shipped, detached);
+
+        let rebuilt = build(&mut parser);
+        let view = shipped.view(&rebuilt).unwrap();
+        assert_eq!(view.layer(EdgeKind::Call), calls);
+        assert_eq!(view.layer(EdgeKind::Child).len(), rebuilt.edge_count());
+
+        let root = rebuilt.root().unwrap();
+        let mut flows = DetachedOverlay::for_graph(&rebuilt);
+        flows.add_edge(rebuilt.stable_id(root), rebuilt.stable_id(rebuilt.children(root)[1]), EdgeKind::Flow);
+        let mut combined = shipped.clone();
+        combined.combine(&flows).unwrap();
+        assert_eq!(combined.len(), calls.len() + 1);
+        assert_eq!(combined.view(&rebuilt).unwrap().layer(EdgeKind::Flow).len(), 1);
+
+        let other = cpp_project(&[("other.cpp", "int f() { return 1; }")]);
+        assert!(shipped.view(other.get("other.cpp").unwrap()).is_err());
+        let mut unbound = DetachedOverlay::new();
+        unbound.add_edge("0/7".parse().unwrap(), "0".parse().unwrap(), EdgeKind::Flow);
+        assert!(Overlay::new(&rebuilt).combine(&unbound).is_err());
+    }
+
+    #[test]
+    fn forests_share_identical_subtrees() {
+        use crate::forest::Forest;
+
+        let helper = "int twice(int x) {\n    return 2 * x;\n}\n";
+        let first = format!("{}int main() {{ return twice(1); }}\n", helper);
+        let second = format!("#include <cstdio>\n\n{}\nint other() {{ return twice(2); }}\n", helper);
+        let project = cpp_project(&[("first.cpp", first.as_str()), ("second.cpp", second.as_str()), ("read.cpp", CPP_STRING_TRIMMED)]);
+
+        let mut forest = Forest::new();
+        let mut added = BTreeMap::new();
+        for path in ["first.cpp", "second.cpp", "read.cpp"] {
+            added.insert(path, forest.insert(path, project.get(path).unwrap()));
+        }
+        assert_eq!(forest.len(), 3);
+        assert_eq!(forest.expanded_node_count(), project.files().map(|(_, graph)| graph.graph().node_count()).sum::<usize>());
+        assert!(forest.node_count() < forest.expanded_node_count());
+        // the second file reuses the whole helper function
+        let helper_size = project.get("first.cpp").map(|graph| graph.subtree_size(graph.children(graph.root().unwrap())[0])).unwrap();
+        assert!(added["second.cpp"] + helper_size <= project.get("second.cpp").unwrap().graph().node_count());
+
+        for (path, graph) in project.files() {
+            let rebuilt = forest.get(path).unwrap();
+            assert!(rebuilt.structurally_equal(graph), "{}", path);
+            let rebuilt_functions: Vec<&str> = rebuilt.nodes().filter(|(_, gnode)| gnode.kind_id == 250).map(|(node, _)| rebuilt.get_node_source(node)).collect();
+            let functions: Vec<&str> = graph.nodes().filter(|(_, gnode)| gnode.kind_id == 250).map(|(node, _)| graph.get_node_source(node)).collect();
+            assert_eq!(rebuilt_functions, functions);
+        }
+        assert!(forest.get("missing.cpp").is_none());
+    }
+
+    #[test]
+    fn duplicated_subtrees_are_reported_by_byte_cost() {
+        use crate::analysis::duplicates::duplicated_subtrees;
+        use crate::forest::Forest;
+
+        let helper = "int twice(int x) {\n    return 2 * x;\n}\n";
+        let files = [
+            ("a.cpp", format!("{}int main() {{ return twice(1); }}\n", helper)),
+            ("b.cpp", format!("#include <cstdio>\n{}", helper)),
+            ("c.cpp", format!("{}\n{}", helper, CPP_STRING_TRIMMED)),
+        ];
+        let sources: Vec<(&str, &str)> = files.iter().map(|(path, source)| (*path, source.as_str())).collect();
+        let project = cpp_project(&sources);
+        let mut forest = Forest::new();
+        for (path, graph) in project.files() {
+            forest.insert(path, graph);
+        }
+
+        let duplicates = duplicated_subtrees(&forest, 5);
+        let top = &duplicates[0];
+        assert_eq!(top.kind_id, 250);
+        assert_eq!(top.text, helper.trim_end());
+        assert_eq!(top.occurrences, 3);
+        assert_eq!(top.files, vec!["a.cpp", "b.cpp", "c.cpp"]);
+        assert_eq!(top.redundant_bytes(), 2 * helper.trim_end().len());
+        // parts of the helper are only repeated along with it
+        assert!(duplicates.iter().all(|duplicate| !top.text.contains(&duplicate.text) || duplicate.forest_node == top.forest_node || duplicate.occurrences > 3));
+        assert!(duplicates.windows(2).all(|pair| pair[0].redundant_bytes() >= pair[1].redundant_bytes()));
+        assert!(duplicates.iter().all(|duplicate| duplicate.nodes >= 5 && duplicate.occurrences > 1));
+    }
+
+    #[test]
+    fn source_hashes_detect_stale_sources() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        assert!(!ast_graph.has_source_hashes());
+        ast_graph.record_source_hashes();
+        assert!(ast_graph.nodes().all(|(_, gnode)| gnode.source_hash != 0));
+
+        // hashes travel with the serialized graph, which has no source
+        let mut buffer = Vec::new();
+        serialize_into(&mut buffer, &ast_graph.to_serializable()).unwrap();
+        let decoded = ASTGraph::from_serializable(deserialize_from(buffer.as_slice()).unwrap());
+        assert!(decoded.check_source(CPP_STRING_TRIMMED).is_ok());
+        let imported = import::from_dot(&export::to_dot(&ast_graph, &ExportOptions::default())).unwrap();
+        assert!(imported.stale_nodes(CPP_STRING_TRIMMED).is_empty());
+
+        let edited = CPP_STRING_TRIMMED.replace("return 0;", "return 1;");
+        let stale = decoded.stale_nodes(&edited);
+        assert!(!stale.is_empty());
+        let literal = stale.iter().copied().find(|&node| decoded.graph().neighbors(node).next().is_none()).unwrap();
+        assert_eq!(ast_graph.get_node_source(literal), "0");
+        assert!(stale.contains(&decoded.root().unwrap()));
+        assert!(decoded.check_source(&edited).is_err());
+        assert!(!decoded.stale_nodes(&CPP_STRING_TRIMMED[..100]).is_empty());
+
+        ast_graph.clear_source_hashes();
+        assert!(ast_graph.stale_nodes(&edited).is_empty());
+    }
+
+    #[test]
+    fn missing_sources_are_reported_not_sliced() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let main = ast_graph.children(ast_graph.root().unwrap())[1];
+        assert!(ast_graph.try_node_source(main).unwrap().starts_with("int main()"));
+
+        let mut buffer = Vec::new();
+        serialize_into(&mut buffer, &ast_graph.to_serializable()).unwrap();
+        let mut decoded = ASTGraph::from_serializable(deserialize_from(buffer.as_slice()).unwrap());
+        assert!(!decoded.has_source());
+        assert!(matches!(decoded.try_node_source(main), Err(GraphError::SourceUnavailable(_))));
+        assert!(matches!(decoded.replace_node_source(main, "int main() {}"), Err(GraphError::SourceUnavailable(_))));
+        assert!(decoded.leaf_text(main).is_none());
+        assert!(decoded.describe().contains("source: none"));
+
+        // a truncated source is caught too, and a full one makes the graph usable again
+        decoded.set_source(&CPP_STRING_TRIMMED[..20]);
+        assert!(matches!(decoded.try_node_source(main), Err(GraphError::SourceUnavailable(_))));
+        decoded.set_source(CPP_STRING_TRIMMED.to_string());
+        assert_eq!(decoded.try_node_source(main).unwrap(), ast_graph.get_node_source(main));
+        assert!(matches!(decoded.try_node_source(NodeIndex::new(100_000)), Err(GraphError::InvalidNode(_))));
+
+        ast_graph.drop_source();
+        assert!(ast_graph.try_source().is_err());
+        let functions = ast_graph.extract_subgraphs([250].into_iter().collect());
+        assert!(functions.iter().all(|function| !function.has_source()));
+    }
+
+    #[test]
+    fn external_sources_are_fetched_once_on_demand() {
+        use crate::source::SourceBuffer;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize,Ordering};
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let main = ast_graph.children(ast_graph.root().unwrap())[1];
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let mut lazy = ast_graph.clone();
+        lazy.set_source(SourceBuffer::external("blobs/read.cpp", move |key: &str| -> Result<String, GraphError> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(key, "blobs/read.cpp");
+            Ok(CPP_STRING_TRIMMED.to_string())
+        }));
+        assert!(lazy.has_source());
+        assert!(!lazy.source_buffer().unwrap().is_loaded());
+        assert_eq!(fetches.load(Ordering::SeqCst), 0);
+
+        assert_eq!(lazy.get_node_source(main), ast_graph.get_node_source(main));
+        assert!(lazy.source_buffer().unwrap().is_loaded());
+        let copy = lazy.clone();
+        assert_eq!(copy.source(), Some(CPP_STRING_TRIMMED));
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // shared and owned buffers holding the same text are equal
+        let shared: Arc<str> = Arc::from(CPP_STRING_TRIMMED);
+        lazy.set_source(shared);
+        assert_eq!(lazy.source_buffer(), ast_graph.source_buffer());
+
+        let mut missing = ast_graph.clone();
+        missing.set_source(SourceBuffer::external("blobs/gone.cpp", |key: &str| -> Result<String, GraphError> { Err(GraphError::Storage(format!("no blob {}", key))) }));
+        match missing.try_node_source(main) {
+            Err(GraphError::SourceUnavailable(message)) => assert!(message.contains("no blob blobs/gone.cpp")),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(missing.source().is_none());
+    }
+
+    #[test]
+    fn node_sources_are_read_in_batches() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let functions = ast_graph.children(ast_graph.root().unwrap());
+
+        let texts: Vec<&str> = ast_graph.node_sources(functions.iter().copied()).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(texts.len(), 2);
+        assert!(texts[0].starts_with("void readFile("));
+        assert!(texts[1].starts_with("int main()"));
+
+        let mut output = Vec::new();
+        for &function in functions.iter() {
+            ast_graph.write_node_source(function, &mut output).unwrap();
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), texts.concat());
+
+        ast_graph.drop_source();
+        assert!(matches!(ast_graph.node_sources(functions.iter().copied()), Err(GraphError::SourceUnavailable(_))));
+        assert!(ast_graph.write_node_source(functions[0], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn subgraph_batches_are_written_as_dot_files() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let mut subgraphs = ast_graph.extract_subgraphs([250].into_iter().collect());
+        subgraphs[0].set_title("../src/read file.cpp".to_string());
+        subgraphs.push(subgraphs[0].clone());
+        subgraphs.push(ast_graph.clone());
+
+        let dir = std::env::temp_dir().join(format!("tree-graph-dot-batch-{}", std::process::id()));
+        let manifest = export::write_all_dot(&subgraphs, &dir, &ExportOptions::default()).expect("Failed to write dot files");
+        let files: Vec<&str> = manifest.entries.iter().map(|entry| entry.file.as_str()).collect();
+        assert_eq!(files, vec!["_src_read_file.cpp.dot", "graph_1.dot", "_src_read_file.cpp_2.dot", "graph_3.dot"]);
+        assert_eq!(manifest.entries[0].title, "../src/read file.cpp");
+        assert_eq!(manifest.entries[3].node_count, ast_graph.graph().node_count());
+
+        for (entry, graph) in manifest.entries.iter().zip(subgraphs.iter()) {
+            let text = fs::read_to_string(dir.join(&entry.file)).unwrap();
+            let imported = import::from_dot(&text).unwrap();
+            assert_eq!(imported.graph().node_count(), graph.graph().node_count());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch_manifests_describe_and_validate_artifacts() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let mut functions = ast_graph.extract_subgraphs([250].into_iter().collect());
+        functions[0].set_title("readFile".to_string());
+        functions[1].set_title("main".to_string());
+
+        let dir = std::env::temp_dir().join(format!("tree-graph-manifest-{}", std::process::id()));
+        let manifest = export::write_all_dot(&functions, &dir, &ExportOptions::default()).unwrap();
+        let entry = manifest.get("main.dot").unwrap();
+        assert_eq!(entry.title, "main");
+        assert_eq!(entry.node_count, functions[1].graph().node_count());
+        assert_eq!(entry.range, Some(functions[1][functions[1].root().unwrap()].range));
+        assert_eq!(entry.hash, crate::manifest::content_hash(fs::read(dir.join("main.dot")).unwrap().as_slice()));
+        assert!(manifest.verify(&dir).is_empty());
+
+        #[cfg(feature="manifest")]
+        assert_eq!(crate::manifest::Manifest::load(&dir).unwrap(), manifest);
+
+        fs::write(dir.join("main.dot"), "digraph \"main\" {}").unwrap();
+        fs::remove_file(dir.join("readFile.dot")).unwrap();
+        let broken: Vec<&str> = manifest.verify(&dir).iter().map(|entry| entry.file.as_str()).collect();
+        assert_eq!(broken, vec!["readFile.dot", "main.dot"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn labels_travel_with_graphs_and_datasets() {
+        use crate::dataset;
+        use crate::labels::LabelMap;
+
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        assert!(ast_graph.add_label("lang:cpp"));
+        assert!(!ast_graph.add_label("lang:cpp"));
+
+        let mut functions = ast_graph.extract_subgraphs([250].into_iter().collect());
+        assert!(functions.iter().all(|function| function.labels() == ["lang:cpp"]));
+        functions[0].add_label("bug");
+        functions[1].add_label("clean");
+
+        #[cfg(feature="jsonl")]
+        {
+            let mut jsonl = Vec::new();
+            functions[0].stream_jsonl(&mut jsonl).unwrap();
+            assert_eq!(ASTGraph::read_jsonl(jsonl.as_slice()).unwrap().labels(), functions[0].labels());
+        }
+
+        let map = LabelMap::from_graphs(&functions);
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![("bug", 0), ("clean", 1), ("lang:cpp", 2)]);
+        assert_eq!(map.ids_of(&functions[0]), vec![2, 0]);
+        assert_eq!(map.name(1), Some("clean"));
+
+        let dir = std::env::temp_dir().join(format!("tree-graph-dataset-{}", std::process::id()));
+        let manifest = dataset::write_dataset(&functions, &dir).unwrap();
+        assert_eq!(manifest.label_map, map);
+        assert_eq!(manifest.entries[1].labels, vec!["lang:cpp".to_string(), "clean".to_string()]);
+        let loaded = dataset::read_dataset(&dir, &manifest).unwrap();
+        assert_eq!(loaded[0].labels(), functions[0].labels());
+        assert_eq!(loaded[1].source(), functions[1].source());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dataset_splits_are_seeded_and_stratified() {
+        use crate::dataset::{self,Stratify};
+
+        let mut graphs = Vec::new();
+        for index in 0..40 {
+            let mut graph = ASTGraph::new(String::new());
+            graph.set_title(format!("graph {}", index));
+            graph.add_label(if index % 4 == 0 { "bug" } else { "clean" });
+            graph.add_label(if index < 30 { "lang:cpp" } else { "lang:fortran" });
+            graphs.push(graph);
+        }
+
+        let parts = dataset::split(&graphs, &[0.8, 0.1, 0.1], &Stratify::None, 7);
+        assert_eq!(parts.iter().map(|part| part.len()).collect::<Vec<_>>(), vec![32, 4, 4]);
+        let mut all: Vec<usize> = parts.concat();
+        all.sort_unstable();
+        assert_eq!(all, (0..40).collect::<Vec<_>>());
+        assert_eq!(dataset::split(&graphs, &[8.0, 1.0, 1.0], &Stratify::None, 7), parts);
+        assert_ne!(dataset::split(&graphs, &[0.8, 0.1, 0.1], &Stratify::None, 8), parts);
+
+        // every part gets its share of bugs and of Fortran
+        let halves = dataset::split(&graphs, &[0.5, 0.5], &Stratify::Labels, 3);
+        for half in halves.iter() {
+            assert_eq!(half.len(), 20);
+            assert_eq!(half.iter().filter(|&&index| graphs[index].has_label("bug")).count(), 5);
+        }
+        let by_language = dataset::split(&graphs, &[0.8, 0.2], &Stratify::LabelPrefix("lang:".to_string()), 3);
+        assert_eq!(by_language[1].iter().filter(|&&index| graphs[index].has_label("lang:fortran")).count(), 2);
+    }
+
+    #[test]
+    fn similarity_pairs_use_clones_as_positives() {
+        use crate::dataset;
+
+        let sources = [
+            "int f(int a) { return a + 1; }",
+            "int g(int b) { return b + 2; }",
+            "int h(int c) { return c + 3; }",
+            "void p() { while (true) {} }",
+            "void q() { while (true) { } }",
+            "int x = 1;",
+            "double y = 2.0;",
+        ];
+        let graphs: Vec<ASTGraph> = sources.iter()
+            .map(|source| {
+                let mut parser = Parser::new();
+                parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+                let tree = parser.parse(source, None).unwrap();
+                let mut graph = ASTGraph::new(source.to_string());
+                graph.build_from_tree(&tree);
+                graph
+            })
+            .collect();
+
+        let pairs = dataset::pairs(&graphs, ASTGraph::structural_hash, 2.0, 11);
+        let positives: Vec<(usize, usize)> = pairs.iter().filter(|pair| pair.similar).map(|pair| (pair.first, pair.second)).collect();
+        assert_eq!(positives, vec![(0, 1), (0, 2), (1, 2), (3, 4), (5, 6)]);
+        let negatives: Vec<&dataset::GraphPair> = pairs.iter().filter(|pair| !pair.similar).collect();
+        assert_eq!(negatives.len(), 10);
+        assert!(negatives.iter().all(|pair| pair.first < pair.second && graphs[pair.first].structural_hash() != graphs[pair.second].structural_hash()));
+        assert_eq!(dataset::pairs(&graphs, ASTGraph::structural_hash, 2.0, 11), pairs);
+
+        // asking for more negatives than exist gives all of them
+        assert_eq!(dataset::pairs(&graphs, ASTGraph::structural_hash, 100.0, 11).len(), 21);
+    }
+
+    #[test]
+    fn struct_tokens_linearize_trees_within_a_budget() {
+        use crate::tokens::{StructToken,StructTokenOptions};
+
+        let language = tree_sitter_cpp::language();
+        let source = "int x = 1;";
+        let mut parser = Parser::new();
+        parser.set_language(&language).expect("Error loading CPP grammar");
+        let tree = parser.parse(source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let full = ast_graph.to_struct_string(&language, &StructTokenOptions::default());
+        assert_eq!(full, "(translation_unit (declaration (primitive_type \"int\") (init_declarator (identifier \"x\") (number_literal \"1\"))))");
+        let tokens = ast_graph.to_struct_tokens(&language, &StructTokenOptions::default());
+        assert_eq!(tokens[2], StructToken::Open("primitive_type".to_string()));
+        assert_eq!(tokens[3], StructToken::Text("int".to_string()));
+
+        let everything = StructTokenOptions { named_only: false, ..StructTokenOptions::default() };
+        assert!(ast_graph.to_struct_string(&language, &everything).contains("(= \"=\")"));
+
+        assert_eq!(ast_graph.to_struct_string(&language, &StructTokenOptions::with_max_bytes(full.len())), full);
+        for max_bytes in [0, 17, 40, 60, full.len() - 1] {
+            let truncated = ast_graph.to_struct_string(&language, &StructTokenOptions::with_max_bytes(max_bytes));
+            assert!(truncated.len() <= max_bytes);
+            assert_eq!(truncated.matches('(').count(), truncated.matches(')').count());
+            assert!(full.starts_with(truncated.trim_end_matches(')')));
+        }
+
+        // without source or stored text, only the structure remains
+        ast_graph.drop_source();
+        assert_eq!(ast_graph.to_struct_string(&language, &StructTokenOptions::default()),
+                   "(translation_unit (declaration (primitive_type) (init_declarator (identifier) (number_literal))))");
+    }
+
+    #[test]
+    fn ast_cursors_move_like_tree_sitter_cursors() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        // the same moves in lockstep land on the same nodes, in a full preorder walk
+        let same = |ts: &TreeCursor, ours: &crate::cursor::AstCursor| {
+            ts.node().kind_id() == ours.gnode().kind_id && ts.node().start_byte() == ours.gnode().range.start_byte
+                && ts.node().end_byte() == ours.gnode().range.end_byte && ts.depth() == ours.depth()
+        };
+        let mut ts = tree.walk();
+        let mut ours = ast_graph.walk().unwrap();
+        let mut visited = 0;
+        'walk: loop {
+            assert!(same(&ts, &ours));
+            visited += 1;
+            if ts.goto_first_child() {
+                assert!(ours.goto_first_child());
+                continue;
+            }
+            assert!(!ours.goto_first_child());
+            loop {
+                let moved = ts.goto_next_sibling();
+                assert_eq!(ours.goto_next_sibling(), moved);
+                if moved {
+                    continue 'walk;
+                }
+                let up = ts.goto_parent();
+                assert_eq!(ours.goto_parent(), up);
+                if !up {
+                    break 'walk;
+                }
+            }
+        }
+        assert_eq!(visited, ast_graph.graph().node_count());
+
+        // a cursor started on a node stays inside its subtree
+        let main = ast_graph.children(ast_graph.root().unwrap())[1];
+        let mut cursor = ast_graph.walk_from(main);
+        assert!(!cursor.goto_next_sibling());
+        assert!(!cursor.goto_parent());
+        assert!(cursor.goto_last_child());
+        assert!(cursor.goto_previous_sibling());
+        assert!(cursor.goto_parent());
+        assert_eq!(cursor.node(), main);
+        let body_start = ast_graph[ast_graph.children(main)[2]].range.start_byte;
+        assert_eq!(cursor.goto_first_child_for_byte(body_start), Some(2));
+        cursor.reset(ast_graph.root().unwrap());
+        assert_eq!(cursor.depth(), 0);
+        assert!(ASTGraph::new(String::new()).walk().is_none());
+    }
+
+    #[test]
+    fn limited_traversals_bound_depth_and_breadth() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+        let root = ast_graph.root().unwrap();
+        let functions = ast_graph.children(root);
+
+        assert_eq!(ast_graph.descendants_within(root, 0).count(), 0);
+        assert_eq!(ast_graph.descendants_within(root, 1).collect::<Vec<_>>(), functions);
+        let two_levels: Vec<NodeIndex> = ast_graph.descendants_within(root, 2).collect();
+        let mut expected = Vec::new();
+        for &function in functions.iter() {
+            expected.push(function);
+            expected.extend(ast_graph.children(function));
+        }
+        assert_eq!(two_levels, expected);
+        assert_eq!(ast_graph.descendants_within(root, usize::MAX).count(), ast_graph.graph().node_count() - 1);
+
+        let first: Vec<NodeIndex> = ast_graph.bfs_limited(root, 1 + functions.len()).collect();
+        assert_eq!(first[0], root);
+        assert_eq!(&first[1..], functions.as_slice());
+        let next: Vec<NodeIndex> = ast_graph.bfs_limited(root, 3 + functions.len()).skip(1 + functions.len()).collect();
+        assert_eq!(next, ast_graph.children(functions[0])[..2].to_vec());
+        assert_eq!(ast_graph.bfs_limited(root, usize::MAX).count(), ast_graph.graph().node_count());
+        assert_eq!(ast_graph.bfs_limited(root, 0).count(), 0);
+    }
+
+    #[test]
+    fn extracted_subgraphs_slice_their_own_source() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).unwrap();
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut built = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        built.build_from_tree(&tree);
+        built.record_source_hashes();
+
+        let mut subgraphs = built.extract_subgraphs(HashSet::from([250u16]));
+        let mut main = subgraphs.remove(1);
+        let root = main.roots()[0];
+        assert!(main.try_node_source(root).unwrap().starts_with("int main()"));
+        assert_eq!(main.source_start().byte, main.graph[root].range.start_byte);
+        let id = main.id_of(root).unwrap();
+        assert!(main.node_source(id).unwrap().ends_with('}'));
+
+        let hashes: Vec<u32> = main.graph.node_weights().map(|gnode| gnode.source_hash).collect();
+        main.record_source_hashes();
+        assert!(hashes.iter().all(|&hash| hash != 0));
+        assert_eq!(main.graph.node_weights().map(|gnode| gnode.source_hash).collect::<Vec<_>>(), hashes);
+
+        // a node of the other function is outside this slice: an error, not a panic
+        let first = subgraphs[0].roots()[0];
+        let mut mixed = main.clone();
+        let foreign = mixed.add_gnode(subgraphs[0].graph[first]);
+        assert!(matches!(mixed.try_node_source(foreign), Err(GraphError::SourceUnavailable(_))));
+        let sources: Vec<_> = mixed.node_sources(vec![root, foreign]).unwrap().collect();
+        assert!(sources[0].is_ok());
+        assert!(matches!(sources[1], Err(GraphError::SourceUnavailable(_))));
+        assert_eq!(mixed.node_sources(vec![root]).unwrap().count(), 1);
+
+        let decoded = crate::store::decode_graph(&crate::store::encode_graph(&main).unwrap().1).unwrap();
+        assert_eq!(decoded.source_start(), main.source_start());
+        assert_eq!(decoded.try_node_source(decoded.roots()[0]).unwrap(), main.try_node_source(root).unwrap());
+    }
+
+    #[test]
+    fn analyses_on_extracted_subgraphs_read_their_slice() {
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let kinds = LanguageKinds::new(&language);
+        let functions = analysis::kind_ids(&language, "function_definition");
+
+        let tree = parser.parse(CPP_DEAD_CODE, None).unwrap();
+        let mut built = ASTGraph::new(CPP_DEAD_CODE.to_string());
+        built.build_from_tree(&tree);
+        let mut compute = built.extract_subgraphs(functions.clone()).remove(0);
+        let unused: Vec<&str> = analysis::unused_variables(&compute, &kinds).iter()
+            .map(|node| compute.try_node_source(*node).unwrap())
+            .collect();
+        assert_eq!(unused, vec!["unused"]);
+
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut built = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        built.build_from_tree(&tree);
+        let mut main = built.extract_subgraphs(functions).remove(1);
+        let calls: Vec<NodeIndex> = main.graph.node_indices()
+            .filter(|&node| analysis::kind_ids(&language, "call_expression").contains(&main.graph[node].kind_id))
+            .collect();
+        let names: Vec<&str> = calls.iter().filter_map(|&call| analysis::callee_name(&main, call, &kinds)).collect();
+        assert!(names.contains(&"readFile"));
+
+        // without a source there is nothing to name, rather than a panic
+        compute.drop_source();
+        assert!(analysis::unused_variables(&compute, &kinds).is_empty());
+        main.drop_source();
+        assert!(calls.iter().all(|&call| analysis::callee_name(&main, call, &kinds).is_none()));
+    }
+
+    #[test]
+    fn title_and_metadata_survive_save_and_load() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        let a = ast_graph.add_gnode(test_node(1, 1, 0, 7, 1));
+        let b = ast_graph.add_gnode(test_node(2, 3, 0, 4, 1));
+        ast_graph.add_edge(a, b);
+        ast_graph.set_title("main.f90".to_string());
+        ast_graph.set_metadata("commit", "0deac2e");
+        ast_graph.set_metadata("path", "src/main.f90");
+
+        let path = std::env::temp_dir().join(format!("tree-graph-metadata-{}.tgb", std::process::id()));
+        ast_graph.save_to_file(&path).unwrap();
+        let loaded = ASTGraph::load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.title(), "main.f90");
+        assert_eq!(loaded.metadata(), ast_graph.metadata());
+        assert_eq!(loaded.node_count(), 2);
+    }
+
+    #[test]
+    fn provenance_survives_save_and_load() {
+        let language = tree_sitter_cpp::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(CPP_STRING_TRIMMED, None).unwrap();
+        let mut ast_graph = ASTGraph::new(CPP_STRING_TRIMMED.to_string());
+        ast_graph.build_from_tree(&tree);
+
+        let path = std::env::temp_dir().join(format!("tree-graph-provenance-{}.tgb", std::process::id()));
+        ast_graph.save_to_file(&path).unwrap();
+        let loaded = ASTGraph::load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.provenance(), ast_graph.provenance());
+        assert!(loaded.provenance().is_fresh(std::time::Duration::from_secs(3600)));
+        assert!(loaded.provenance().is_compatible_with(&language));
+
+        let mut buffer: Vec<u8> = Vec::new();
+        ast_graph.write_to(&mut buffer).unwrap();
+        buffer[..4].copy_from_slice(&(crate::FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(ASTGraph::read_from(buffer.as_slice()), Err(GraphError::Encoding(_))));
+    }
+
+    #[test]
+    fn labels_survive_save_and_load() {
+        let mut ast_graph = ASTGraph::new("testing".to_string());
+        ast_graph.add_gnode(test_node(1, 1, 0, 7, 1));
+        ast_graph.add_label("bug");
+        ast_graph.add_label("lang:fortran");
+
+        let path = std::env::temp_dir().join(format!("tree-graph-labels-{}.tgb", std::process::id()));
+        ast_graph.save_to_file(&path).unwrap();
+        let loaded = ASTGraph::load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.labels(), ast_graph.labels());
+        assert!(loaded.has_label("lang:fortran"));
+    }
+
+    #[test]
+    fn analyses_fail_without_a_source() {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_cpp::language()).expect("Error loading CPP grammar");
+        let source = "#include \"util.h\"\nint helper() { return 1; }\nint main() { return helper(); }\n";
+        let tree = parser.parse(source, None).unwrap();
+        let mut ast_graph = ASTGraph::new(source.to_string());
+        ast_graph.build_from_tree(&tree);
+        ast_graph.drop_source();
+        let kinds = LanguageKinds::new(&tree_sitter_cpp::language());
+        fn unavailable<T>(result: Result<T, GraphError>) -> bool {
+            matches!(result, Err(GraphError::SourceUnavailable(_)))
+        }
+
+        let functions: Vec<_> = ast_graph.subtrees_of_kind(250).collect();
+        assert_eq!(functions.len(), 2);
+        assert!(functions.iter().all(|function| function.source.is_none()));
+
+        assert!(unavailable(signature::function_signatures(&ast_graph, &kinds)));
+        assert!(unavailable(signature::compare_signatures(&[ast_graph.clone()], &[], &kinds)));
+
+        assert!(unavailable(crate::overlay::Overlay::new(&ast_graph).add_calls(&kinds)));
+
+        let mut project = ProjectGraph::new(kinds);
+        project.insert("main.cpp", ast_graph.clone());
+        assert!(unavailable(project.functions()));
+        assert!(unavailable(project.call_edges()));
+        assert!(unavailable(project.include_edges()));
+        assert!(unavailable(project.impacted_functions(&[("main.cpp", ast_graph.root().unwrap())])));
+        assert!(unavailable(project.diff(&ProjectGraph::new(LanguageKinds::new(&tree_sitter_cpp::language())))));
+
+        let mut spec = TaintSpec::new(tree_sitter_cpp::language());
+        spec.add_source("(call_expression) @target").unwrap();
+        assert!(unavailable(taint::find_flows(&ast_graph, &spec)));
+
+        let mut rule_set = RuleSet::with_language(tree_sitter_cpp::language());
+        rule_set.add_query_rule("call", Severity::Note, "(call_expression function: (identifier) @callee) @target", "call to $callee").unwrap();
+        assert!(unavailable(rules::run_rules(&ast_graph, &rule_set)));
+    }
+
+    #[test]
+    fn stored_graph_with_a_dangling_edge_is_a_storage_error() {
+        let dangling = SerializableGraph {
+            nodes: vec![test_node(1, 1, 0, 10, 1)],
+            edges: vec![crate::geometry::Edge { source: NodeIndex::new(0), target: NodeIndex::new(3) }],
+            ..Default::default()
+        };
+        // laid out as a stored graph: no source, default start, the graph
+        let bytes = bincode::serialize(&(None::<String>, crate::source::SourceStart::default(), dangling)).unwrap();
+        assert!(matches!(crate::store::decode_graph(&bytes), Err(GraphError::Storage(_))));
+    }
+
+    #[test]
+    fn chunk_with_a_dangling_edge_fails_to_load() {
+        let node = test_node(1, 1, 0, 10, 1);
+        let dangling = SerializableGraph {
+            nodes: vec![node],
+            edges: vec![crate::geometry::Edge { source: NodeIndex::new(0), target: NodeIndex::new(3) }],
+            ..Default::default()
+        };
+        // laid out as a chunk: no source, default start, the graph
+        let chunk = bincode::serialize(&(None::<String>, crate::source::SourceStart::default(), dangling)).unwrap();
+        let entries = vec![chunked::ChunkEntry {
+            root_id: node.id,
+            kind_id: node.kind_id,
+            range: node.range,
+            offset: chunked::MAGIC.len() as u64,
+            length: chunk.len() as u64,
+        }];
+
+        let mut file = chunked::MAGIC.to_vec();
+        file.extend(&chunk);
+        let index_offset = file.len() as u64;
+        file.extend(bincode::serialize(&entries).unwrap());
+        file.extend(index_offset.to_le_bytes());
+        file.extend(chunked::MAGIC);
+
+        let mut reader = ChunkedReader::new(std::io::Cursor::new(file)).unwrap();
+        assert!(matches!(reader.load(0), Err(GraphError::Encoding(_))));
+    }
+}
@@ -0,0 +1,190 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::{ASTGraph, SerializableGraph};
+use crate::error::GraphError;
+use crate::excerpt::Ellipsis;
+use crate::interner::Interner;
+
+///
+/// Interned text of (some of) a graph's leaves, kept by the graph so leaf
+/// values survive without the source.
+///
+#[derive(Debug,Clone,Default)]
+pub struct LeafText {
+    table: Interner,
+    leaves: HashMap<NodeIndex, u32>,
+}
+
+impl LeafText {
+    pub fn table(&self) -> &Interner {
+        &self.table
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn get(&self, node: NodeIndex) -> Option<&str> {
+        self.leaves.get(&node).and_then(|&id| self.table.get(id))
+    }
+
+    ///
+    /// The same texts for the nodes of a subgraph, given the mapping from
+    /// this graph's indices to the subgraph's.
+    ///
+    pub(crate) fn remove(&mut self, node: NodeIndex) {
+        self.leaves.remove(&node);
+    }
+
+    // the leaf at `from` now lives at `to`
+    pub(crate) fn move_node(&mut self, from: NodeIndex, to: NodeIndex) {
+        if let Some(id) = self.leaves.remove(&from) {
+            self.leaves.insert(to, id);
+        }
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.table.shrink_to_fit();
+        self.leaves.shrink_to_fit();
+    }
+
+    pub(crate) fn remap(&self, mapping: &HashMap<NodeIndex, NodeIndex>) -> LeafText {
+        LeafText {
+            table: self.table.clone(),
+            leaves: self.leaves.iter()
+                .filter_map(|(node, &id)| mapping.get(node).map(|&new_node| (new_node, id)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize,Deserialize)]
+struct GraphWithText {
+    graph: SerializableGraph,
+    strings: Vec<String>,
+    leaves: Vec<(u32, u32)>,
+}
+
+impl ASTGraph {
+    ///
+    /// Intern the source text of every leaf (identifiers, literals, keywords,
+    /// punctuation) into the graph.
+    ///
+    pub fn store_leaf_text(&mut self) {
+        self.store_leaf_text_where(|_| true, None)
+    }
+
+    ///
+    /// `store_leaf_text`, eliding the middle of leaves longer than
+    /// `max_bytes` (embedded base64 blobs, generated tables) so no stored
+    /// text exceeds it.
+    ///
+    pub fn store_leaf_text_within(&mut self, max_bytes: usize) {
+        self.store_leaf_text_where(|_| true, Some(max_bytes))
+    }
+
+    ///
+    /// Intern the source text of the leaves whose kind is in `kinds`, e.g.
+    /// just identifiers and literals.
+    ///
+    pub fn store_leaf_text_for(&mut self, kinds: &HashSet<u16>) {
+        self.store_leaf_text_where(|kind_id| kinds.contains(&kind_id), None)
+    }
+
+    fn store_leaf_text_where<F: Fn(u16) -> bool>(&mut self, keep: F, max_bytes: Option<usize>) {
+        let mut leaf_text = self.leaf_text.take().unwrap_or_default();
+        for node in self.graph.node_indices() {
+            let gnode = &self.graph[node];
+            if self.graph.neighbors(node).next().is_some() || !keep(gnode.kind_id) {
+                continue;
+            }
+            if let Some(text) = self.range_text(gnode.range.start_byte, gnode.range.end_byte) {
+                let id = match max_bytes {
+                    Some(max_bytes) if text.len() > max_bytes => leaf_text.table.intern(&self.excerpt(node, max_bytes, Ellipsis::Middle)),
+                    _ => leaf_text.table.intern(text),
+                };
+                leaf_text.leaves.insert(node, id);
+            }
+        }
+        self.leaf_text = Some(leaf_text);
+    }
+
+    pub fn stored_leaf_text(&self) -> Option<&LeafText> {
+        self.leaf_text.as_ref()
+    }
+
+    ///
+    /// Text of a leaf: the stored copy if there is one, otherwise the slice of
+    /// the source (if the graph still has it).
+    ///
+    pub fn leaf_text(&self, node: NodeIndex) -> Option<&str> {
+        if let Some(text) = self.leaf_text.as_ref().and_then(|leaf_text| leaf_text.get(node)) {
+            return Some(text);
+        }
+        let range = &self.graph.node_weight(node)?.range;
+        self.range_text(range.start_byte, range.end_byte)
+    }
+
+    ///
+    /// Forget the source, keeping stored leaf text. Use after
+    /// `store_leaf_text_for` to share graphs without the code they came from.
+    ///
+    pub fn drop_source(&mut self) {
+        self.source = None;
+    }
+
+    ///
+    /// The graph as `write_to` encodes it plus the stored leaf text, in one
+    /// bincode record; read it back with `read_with_text`.
+    ///
+    pub fn write_with_text<W: Write>(&self, writer: W) -> Result<(), GraphError> {
+        let (strings, leaves) = match &self.leaf_text {
+            Some(leaf_text) => {
+                let mut leaves: Vec<(u32, u32)> = leaf_text.leaves.iter().map(|(node, &id)| (node.index() as u32, id)).collect();
+                leaves.sort();
+                (leaf_text.table.strings().to_vec(), leaves)
+            },
+            None => (Vec::new(), Vec::new()),
+        };
+        let stored = GraphWithText { graph: self.to_serializable(), strings, leaves };
+        bincode::serialize_into(writer, &stored)?;
+        Ok(())
+    }
+
+    pub fn read_with_text<R: Read>(reader: R) -> Result<ASTGraph, GraphError> {
+        let stored: GraphWithText = bincode::deserialize_from(reader)?;
+        let mut graph = ASTGraph::from_serializable(stored.graph);
+        let table = Interner::from(stored.strings);
+        let mut leaves = HashMap::with_capacity(stored.leaves.len());
+        for (node, id) in stored.leaves {
+            if node as usize >= graph.graph.node_count() || table.get(id).is_none() {
+                return Err(GraphError::Encoding(format!("leaf text entry ({}, {}) out of range", node, id)));
+            }
+            leaves.insert(NodeIndex::new(node as usize), id);
+        }
+        if !leaves.is_empty() {
+            graph.leaf_text = Some(LeafText { table, leaves });
+        }
+        Ok(graph)
+    }
+
+    pub fn save_to_file_with_text<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_with_text(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load_from_file_with_text<P: AsRef<Path>>(path: P) -> Result<ASTGraph, GraphError> {
+        ASTGraph::read_with_text(BufReader::new(File::open(path)?))
+    }
+}
@@ -0,0 +1,172 @@
+use petgraph::graph::NodeIndex;
+use std::fmt;
+
+use crate::ASTGraph;
+use crate::analysis::KindLookup;
+
+///
+/// One token of the linearized tree: a node opening (`(identifier`), the
+/// text of a leaf (`"readFile"`, quoted and escaped when displayed) or a
+/// node closing (`)`).
+///
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum StructToken {
+    Open(String),
+    Text(String),
+    Close,
+}
+
+impl StructToken {
+    // bytes the token takes in `to_struct_string`, with its separator
+    fn rendered_len(&self, first: bool) -> usize {
+        match self {
+            StructToken::Open(kind) => kind.len() + if first { 1 } else { 2 },
+            StructToken::Text(_) => self.to_string().len() + 1,
+            StructToken::Close => 1,
+        }
+    }
+}
+
+impl fmt::Display for StructToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StructToken::Open(kind) => write!(f, "({}", kind),
+            StructToken::Text(text) => {
+                write!(f, "\"")?;
+                for c in text.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\r' => write!(f, "\\r")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            },
+            StructToken::Close => write!(f, ")"),
+        }
+    }
+}
+
+///
+/// What `to_struct_tokens` emits. By default, like tree-sitter's S-expressions,
+/// only named nodes appear, with the text of named leaves and no limit.
+///
+/// `max_bytes` bounds the length of `to_struct_string`: the walk stops at
+/// the first node that no longer fits, and the nodes still open are closed,
+/// so a truncated sequence is still balanced.
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct StructTokenOptions {
+    pub named_only: bool,
+    pub leaf_text: bool,
+    pub max_bytes: Option<usize>,
+}
+
+impl Default for StructTokenOptions {
+    fn default() -> Self {
+        StructTokenOptions { named_only: true, leaf_text: true, max_bytes: None }
+    }
+}
+
+impl StructTokenOptions {
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        StructTokenOptions { max_bytes: Some(max_bytes), ..StructTokenOptions::default() }
+    }
+}
+
+// bytes left for the rest of the string; a node is only opened if its
+// tokens and the closes of it and of every open ancestor still fit
+struct Budget {
+    left: usize,
+    first: bool,
+}
+
+impl Budget {
+    fn take(&mut self, tokens: &[StructToken], open: usize) -> bool {
+        let mut cost = open + 1;
+        let mut first = self.first;
+        for token in tokens {
+            cost += token.rendered_len(first);
+            first = false;
+        }
+        if cost > self.left {
+            return false;
+        }
+        self.left -= cost - open - 1;
+        self.first = false;
+        true
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// The trees as a sequence of tokens, `(function_definition (identifier
+    /// "readFile") ...)`, for sequence models over ASTs. Kinds are named
+    /// with `kinds` (a grammar, a `KindTable`), falling back to the kind id.
+    /// Leaf text comes from stored leaf text or the source, when there is
+    /// either.
+    ///
+    pub fn to_struct_tokens<K: KindLookup + ?Sized>(&self, kinds: &K, options: &StructTokenOptions) -> Vec<StructToken> {
+        let mut tokens = Vec::new();
+        let mut budget = Budget { left: options.max_bytes.unwrap_or(usize::MAX), first: true };
+        // (node, closing): nodes left to open, and pending closes of open ones
+        let mut stack: Vec<(NodeIndex, bool)> = self.roots().into_iter().rev().map(|root| (root, false)).collect();
+        let mut open = 0;
+        let mut truncated = false;
+
+        while let Some((node, closing)) = stack.pop() {
+            if closing {
+                tokens.push(StructToken::Close);
+                budget.left -= 1;
+                open -= 1;
+                continue;
+            }
+            if truncated {
+                continue;
+            }
+            let gnode = &self.graph[node];
+            let children = self.children(node);
+            if options.named_only && !gnode.is_named {
+                stack.extend(children.into_iter().rev().map(|child| (child, false)));
+                continue;
+            }
+
+            let name = kinds.name_of(gnode.kind_id).map(|name| name.to_string()).unwrap_or_else(|| gnode.kind_id.to_string());
+            let mut node_tokens = vec![StructToken::Open(name)];
+            let is_leaf = children.iter().all(|&child| options.named_only && !self.graph[child].is_named);
+            if options.leaf_text && is_leaf {
+                if let Some(text) = self.leaf_text(node) {
+                    node_tokens.push(StructToken::Text(text.to_string()));
+                }
+            }
+            if !budget.take(&node_tokens, open) {
+                truncated = true;
+                continue;
+            }
+            tokens.extend(node_tokens);
+            open += 1;
+            stack.push((node, true));
+            stack.extend(children.into_iter().rev().map(|child| (child, false)));
+        }
+
+        tokens
+    }
+
+    ///
+    /// `to_struct_tokens` rendered as one string, tokens separated by spaces
+    /// except before a `)`.
+    ///
+    pub fn to_struct_string<K: KindLookup + ?Sized>(&self, kinds: &K, options: &StructTokenOptions) -> String {
+        let mut out = String::new();
+        for token in self.to_struct_tokens(kinds, options) {
+            if !out.is_empty() && token != StructToken::Close {
+                out.push(' ');
+            }
+            out.push_str(&token.to_string());
+        }
+        out
+    }
+}
@@ -0,0 +1,129 @@
+//!
+//! petgraph's graph traits for `ASTGraph`, delegating to the inner graph, so
+//! petgraph's algorithms and traversals take `&ast_graph` directly.
+//!
+use fixedbitset::FixedBitSet;
+use petgraph::graph::{EdgeIndex, EdgeReference, EdgeReferences, Edges, Neighbors, NodeIndex, NodeIndices, NodeReferences};
+use petgraph::visit::{Data, EdgeCount, GetAdjacencyMatrix, GraphBase, GraphProp, IntoEdgeReferences, IntoEdges,
+                      IntoNeighbors, IntoNeighborsDirected, IntoNodeIdentifiers, IntoNodeReferences,
+                      NodeCompactIndexable, NodeCount, NodeIndexable, Visitable};
+use petgraph::{Directed, Direction};
+
+use crate::ASTGraph;
+use crate::geometry::GNode;
+
+impl GraphBase for ASTGraph {
+    type NodeId = NodeIndex;
+    type EdgeId = EdgeIndex;
+}
+
+impl Data for ASTGraph {
+    type NodeWeight = GNode;
+    type EdgeWeight = ();
+}
+
+impl GraphProp for ASTGraph {
+    type EdgeType = Directed;
+}
+
+impl NodeCount for ASTGraph {
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+}
+
+impl EdgeCount for ASTGraph {
+    fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+}
+
+impl NodeIndexable for ASTGraph {
+    fn node_bound(&self) -> usize {
+        self.graph.node_bound()
+    }
+
+    fn to_index(&self, node: NodeIndex) -> usize {
+        node.index()
+    }
+
+    fn from_index(&self, index: usize) -> NodeIndex {
+        NodeIndex::new(index)
+    }
+}
+
+impl NodeCompactIndexable for ASTGraph {}
+
+impl Visitable for ASTGraph {
+    type Map = FixedBitSet;
+
+    fn visit_map(&self) -> FixedBitSet {
+        self.graph.visit_map()
+    }
+
+    fn reset_map(&self, map: &mut FixedBitSet) {
+        self.graph.reset_map(map)
+    }
+}
+
+impl GetAdjacencyMatrix for ASTGraph {
+    type AdjMatrix = FixedBitSet;
+
+    fn adjacency_matrix(&self) -> FixedBitSet {
+        self.graph.adjacency_matrix()
+    }
+
+    fn is_adjacent(&self, matrix: &FixedBitSet, a: NodeIndex, b: NodeIndex) -> bool {
+        self.graph.is_adjacent(matrix, a, b)
+    }
+}
+
+impl<'a> IntoNeighbors for &'a ASTGraph {
+    type Neighbors = Neighbors<'a, ()>;
+
+    fn neighbors(self, node: NodeIndex) -> Neighbors<'a, ()> {
+        self.graph.neighbors(node)
+    }
+}
+
+impl<'a> IntoNeighborsDirected for &'a ASTGraph {
+    type NeighborsDirected = Neighbors<'a, ()>;
+
+    fn neighbors_directed(self, node: NodeIndex, direction: Direction) -> Neighbors<'a, ()> {
+        self.graph.neighbors_directed(node, direction)
+    }
+}
+
+impl<'a> IntoNodeIdentifiers for &'a ASTGraph {
+    type NodeIdentifiers = NodeIndices;
+
+    fn node_identifiers(self) -> NodeIndices {
+        self.graph.node_indices()
+    }
+}
+
+impl<'a> IntoNodeReferences for &'a ASTGraph {
+    type NodeRef = (NodeIndex, &'a GNode);
+    type NodeReferences = NodeReferences<'a, GNode>;
+
+    fn node_references(self) -> NodeReferences<'a, GNode> {
+        self.graph.node_references()
+    }
+}
+
+impl<'a> IntoEdgeReferences for &'a ASTGraph {
+    type EdgeRef = EdgeReference<'a, ()>;
+    type EdgeReferences = EdgeReferences<'a, ()>;
+
+    fn edge_references(self) -> EdgeReferences<'a, ()> {
+        self.graph.edge_references()
+    }
+}
+
+impl<'a> IntoEdges for &'a ASTGraph {
+    type Edges = Edges<'a, (), Directed>;
+
+    fn edges(self, node: NodeIndex) -> Edges<'a, (), Directed> {
+        self.graph.edges(node)
+    }
+}
@@ -0,0 +1,81 @@
+use petgraph::graph::NodeIndex;
+
+use crate::ASTGraph;
+use crate::geometry::GRange;
+
+// last row holding text of `range`: a range ending at column 0 stops at the
+// end of the previous row
+pub(crate) fn last_row(range: &GRange) -> usize {
+    if range.end_point.column == 0 && range.end_point.row > range.start_point.row {
+        range.end_point.row - 1
+    } else {
+        range.end_point.row
+    }
+}
+
+impl ASTGraph {
+    ///
+    /// Nodes with text on any of the rows `start_row..=end_row` (0-based, as
+    /// in `GPoint`), in index order. For editor selections and diff hunks.
+    ///
+    pub fn nodes_in_lines(&self, start_row: usize, end_row: usize) -> Vec<NodeIndex> {
+        self.graph.node_indices()
+            .filter(|&node| {
+                let range = &self.graph[node].range;
+                range.start_point.row <= end_row && last_row(range) >= start_row
+            })
+            .collect()
+    }
+
+    // bytes from the first to the last non-blank character on the rows
+    // `start_row..=end_row`, `None` if they are blank or past the end
+    fn text_span(&self, start_row: usize, end_row: usize) -> Option<(usize, usize)> {
+        let mut span: Option<(usize, usize)> = None;
+        let mut offset = self.source_start.byte;
+        let first_row = self.source_start.point.row;
+        for (row, line) in self.source_text().split_inclusive('\n').enumerate().map(|(row, line)| (first_row + row, line)) {
+            if row > end_row {
+                break;
+            }
+            if row >= start_row {
+                let trimmed = line.trim_start();
+                if !trimmed.trim_end().is_empty() {
+                    let start = offset + line.len() - trimmed.len();
+                    let end = start + trimmed.trim_end().len();
+                    span = Some((span.map_or(start, |(first, _)| first), end));
+                }
+            }
+            offset += line.len();
+        }
+        span
+    }
+
+    ///
+    /// Deepest node holding all the text on the rows `start_row..=end_row`
+    /// (for blank rows, the deepest spanning them), `None` if no node does
+    /// (rows past the end of the source).
+    ///
+    pub fn covering_node_for_lines(&self, start_row: usize, end_row: usize) -> Option<NodeIndex> {
+        let span = self.text_span(start_row, end_row);
+        let covers = |node: NodeIndex| {
+            let range = &self.graph[node].range;
+            match span {
+                Some((start, end)) => range.start_byte <= start && range.end_byte >= end,
+                None => range.start_point.row <= start_row && last_row(range) >= end_row,
+            }
+        };
+        let mut current = self.roots().into_iter().find(|&root| covers(root))?;
+        while let Some(child) = self.graph.neighbors(current).find(|&child| covers(child)) {
+            current = child;
+        }
+        Some(current)
+    }
+
+    ///
+    /// The subtree of `covering_node_for_lines`: the AST region of a line
+    /// range.
+    ///
+    pub fn subgraph_for_lines(&self, start_row: usize, end_row: usize) -> Option<ASTGraph> {
+        self.covering_node_for_lines(start_row, end_row).map(|node| self.extract_subgraph_from(node))
+    }
+}